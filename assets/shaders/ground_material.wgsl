@@ -22,6 +22,7 @@ struct MyExtendedMaterial {
     // filler: vec3<f32>,
     noise_scale: f32,
     // filler2: vec3<f32>,
+    sand_color: vec4<f32>,
 }
 
 @group(1) @binding(100)
@@ -29,6 +30,8 @@ var<uniform> my_extended_material: MyExtendedMaterial;
 
 @group(1) @binding(110) var material_color_texture: texture_2d<f32>;
 @group(1) @binding(111) var material_color_sampler: sampler;
+@group(1) @binding(112) var splat_texture: texture_2d<f32>;
+@group(1) @binding(113) var splat_sampler: sampler;
 
 @fragment
 fn fragment(
@@ -49,9 +52,14 @@ fn fragment(
     // pbr_input.material.base_color = smoothstep(grass, ground, noise);
 
     // pbr_input.material.base_color = grass * noise + ground * (1.0 - noise);
-    pbr_input.material.base_color = ground* noise + grass * (1.0 - noise);
+    let meadow = ground* noise + grass * (1.0 - noise);
+    let sand = mix(ground, my_extended_material.sand_color, 0.7);
     // pbr_input.material.base_color = ground;
 
+    // the splat map covers the whole plane, so it uses the unscaled uv
+    let splat = textureSample(splat_texture, splat_sampler, in.uv);
+    pbr_input.material.base_color = (meadow * splat.r + ground * splat.g + sand * splat.b) / (splat.r + splat.g + splat.b);
+
     // alpha discard
     // pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
 
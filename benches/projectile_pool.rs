@@ -0,0 +1,124 @@
+//! Towers firing every frame, projectiles recycled through the pool versus spawned and despawned.
+//! Run with `cargo bench --bench projectile_pool`.
+//!
+//! Headless, so a projectile's scene is stood in for by a few child entities,
+//! the part of a spawned scene the pool gets to skip.
+
+use bevy::prelude::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use no_communication_0::projectile::{Projectile, ProjectilePool};
+
+const MODEL: &str = "models/projectiles/arrow.gltf#Scene0";
+// entities a projectile's scene spawns under it
+const SCENE_NODES: usize = 4;
+// frames a projectile flies before it stops
+const FLIGHT_FRAMES: f32 = 60.0;
+
+#[derive(Resource)]
+struct Towers(usize);
+
+fn projectile() -> Projectile {
+    Projectile {
+        hits: 0,
+        caster_entity: Entity::PLACEHOLDER,
+        target_entity: None,
+        vel: Vec3::X,
+        asset_handle: Handle::default(),
+        additional_damage: 0,
+        damage_mul: 1.0,
+        is_crit: false,
+        traveled: 0.0,
+        bounces: 0,
+        hit_entities: Vec::new(),
+        spares_structures: true,
+    }
+}
+
+fn spawn_scene(commands: &mut Commands) {
+    commands
+        .spawn((SpatialBundle::default(), projectile()))
+        .with_children(|parent| {
+            for _ in 0..SCENE_NODES {
+                parent.spawn(SpatialBundle::default());
+            }
+        });
+}
+
+// what spawn_projectile did before the pool
+fn fire_spawning(mut commands: Commands, towers: Res<Towers>) {
+    for _ in 0..towers.0 {
+        spawn_scene(&mut commands);
+    }
+}
+
+fn fire_pooled(mut commands: Commands, towers: Res<Towers>, mut pool: ResMut<ProjectilePool>) {
+    for _ in 0..towers.0 {
+        match pool.take(MODEL) {
+            Some(entity) => {
+                commands.entity(entity).insert((
+                    Transform::default(),
+                    Visibility::Inherited,
+                    projectile(),
+                ));
+            }
+            None => spawn_scene(&mut commands),
+        }
+    }
+}
+
+fn land_despawning(mut commands: Commands, mut query: Query<(Entity, &mut Projectile)>) {
+    for (entity, mut projectile) in query.iter_mut() {
+        projectile.traveled += 1.0;
+        if projectile.traveled >= FLIGHT_FRAMES {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn land_pooled(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Projectile)>,
+    mut pool: ResMut<ProjectilePool>,
+) {
+    for (entity, mut projectile) in query.iter_mut() {
+        projectile.traveled += 1.0;
+        if projectile.traveled >= FLIGHT_FRAMES {
+            pool.recycle(&mut commands, entity, MODEL);
+        }
+    }
+}
+
+fn app(towers: usize, pooled: bool) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(Towers(towers))
+        .init_resource::<ProjectilePool>();
+    match pooled {
+        true => app.add_systems(Update, (land_pooled, fire_pooled).chain()),
+        false => app.add_systems(Update, (land_despawning, fire_spawning).chain()),
+    };
+    // fill the sky first, so every measured frame both fires and lands
+    for _ in 0..FLIGHT_FRAMES as usize * 2 {
+        app.update();
+    }
+    app
+}
+
+fn tower_volleys(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tower_volleys");
+    for towers in [10, 20, 40] {
+        let label = format!("{towers}_towers");
+        group.bench_function(BenchmarkId::new("spawn_despawn", &label), |b| {
+            let mut app = app(towers, false);
+            b.iter(|| app.update())
+        });
+        group.bench_function(BenchmarkId::new("pool", &label), |b| {
+            let mut app = app(towers, true);
+            b.iter(|| app.update())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tower_volleys);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+//! Robots looking for their closest tree, every robot against every tree versus the spatial grid.
+//! Run with `cargo bench --bench spatial_grid`.
+
+use bevy::prelude::*;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use no_communication_0::spatial_grid::SpatialHash;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const MAP_SIZE_HALF: f32 = 40.0;
+
+fn random_points(rng: &mut StdRng, count: usize) -> Vec<(Entity, Vec3)> {
+    (0..count)
+        .map(|i| {
+            let pos = Vec3::new(
+                rng.gen_range(-MAP_SIZE_HALF..MAP_SIZE_HALF),
+                0.0,
+                rng.gen_range(-MAP_SIZE_HALF..MAP_SIZE_HALF),
+            );
+            (Entity::from_raw(i as u32), pos)
+        })
+        .collect()
+}
+
+// what robot_ai did before the grid
+fn brute_force_nearest(trees: &[(Entity, Vec3)], pos: Vec3) -> Option<(f32, Entity)> {
+    trees
+        .iter()
+        .map(|(e, p)| (p.xz().distance_squared(pos.xz()), *e))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+fn nearest_tree(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut group = c.benchmark_group("nearest_tree");
+    // the last one is late in a run, with most of the forest chopped down
+    for (robot_count, tree_count) in [(100, 400), (200, 800), (200, 20)] {
+        let robots = random_points(&mut rng, robot_count);
+        let trees = random_points(&mut rng, tree_count);
+        let mut grid = SpatialHash::default();
+        let label = format!("{robot_count}_robots_{tree_count}_trees");
+
+        group.bench_function(BenchmarkId::new("brute_force", &label), |b| {
+            b.iter(|| {
+                for (_, pos) in robots.iter() {
+                    black_box(brute_force_nearest(&trees, *pos));
+                }
+            })
+        });
+        // includes refilling the grid, like update_spatial_grids does every frame
+        group.bench_function(BenchmarkId::new("spatial_grid", &label), |b| {
+            b.iter(|| {
+                grid.clear();
+                for (entity, pos) in trees.iter() {
+                    grid.insert(*entity, *pos);
+                }
+                for (_, pos) in robots.iter() {
+                    black_box(grid.nearest(*pos));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, nearest_tree);
+criterion_main!(benches);
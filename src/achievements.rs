@@ -0,0 +1,275 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::{
+    health::DeathEvent,
+    notification::NotificationEvent,
+    persistence,
+    player::Body,
+    state::{AppState, GameResult, RestartRunEvent, Wave},
+    tree::{SpawnTreeEvent, TreeTrunkTag},
+    ui_util::UiAssets,
+    weapon::{Weapon, WeaponAsset},
+};
+
+const ACHIEVEMENTS_FILE: &str = "achievements.ron";
+
+pub struct AchievementsPlugin;
+
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(
+            persistence::load::<Achievements>(ACHIEVEMENTS_FILE).unwrap_or_default(),
+        )
+        .add_systems(Startup, setup_achievements_ui)
+        .add_systems(
+            Update,
+            ((track_planted_trees, track_deaths), unlock_achievements).chain(),
+        )
+        // progress changes with every kill and planted tree, too often to save each time
+        .add_systems(OnExit(AppState::Playing), save_achievements)
+        .add_systems(Last, save_achievements.run_if(resource_changed::<Wave>()))
+        .add_systems(Update, (toggle_achievements_ui, update_achievements_ui))
+        .add_systems(OnEnter(AppState::Playing), reset_run_progress)
+        .add_systems(
+            PreUpdate,
+            reset_run_progress.run_if(on_event::<RestartRunEvent>()),
+        )
+        .add_systems(OnEnter(AppState::GameOver), track_win);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumIter, Serialize, Deserialize)]
+pub enum Achievement {
+    Gardener,
+    UntouchedForest,
+    Lumberjack,
+}
+
+impl Achievement {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Achievement::Gardener => "Gardener",
+            Achievement::UntouchedForest => "Untouched Forest",
+            Achievement::Lumberjack => "Lumberjack",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Achievement::Gardener => "Plant 50 trees",
+            Achievement::UntouchedForest => "Win without losing a tree",
+            Achievement::Lumberjack => "Kill the boss with an axe",
+        }
+    }
+
+    /// progress needed to unlock
+    pub fn target(&self) -> u32 {
+        match self {
+            Achievement::Gardener => 50,
+            Achievement::UntouchedForest => 1,
+            Achievement::Lumberjack => 1,
+        }
+    }
+}
+
+// Progress is kept between runs, saved next to the other save files.
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct Achievements {
+    progress: HashMap<Achievement, u32>,
+    unlocked: HashSet<Achievement>,
+    #[serde(skip)]
+    trees_lost_this_run: u32,
+}
+
+impl Achievements {
+    pub fn progress(&self, achievement: Achievement) -> u32 {
+        self.progress.get(&achievement).copied().unwrap_or(0)
+    }
+
+    pub fn is_unlocked(&self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+
+    pub fn add_progress(&mut self, achievement: Achievement, amount: u32) {
+        if self.is_unlocked(achievement) {
+            return;
+        }
+        let progress = self.progress.entry(achievement).or_insert(0);
+        *progress = (*progress + amount).min(achievement.target());
+    }
+}
+
+// only count trees planted during play, not the ones the map starts with
+fn track_planted_trees(
+    mut events: EventReader<SpawnTreeEvent>,
+    mut achievements: ResMut<Achievements>,
+) {
+    let planted = events.read().filter(|e| e.play_sound).count() as u32;
+    if planted > 0 {
+        achievements.add_progress(Achievement::Gardener, planted);
+    }
+}
+
+fn track_deaths(
+    mut events: EventReader<DeathEvent>,
+    mut achievements: ResMut<Achievements>,
+    trees: Query<(), With<TreeTrunkTag>>,
+    bodies: Query<&Body>,
+    weapons: Query<&Weapon>,
+    weapon_assets: Res<Assets<WeaponAsset>>,
+) {
+    for event in events.read() {
+        if trees.contains(event.entity) {
+            achievements.trees_lost_this_run += 1;
+        }
+        let killed_boss = matches!(bodies.get(event.entity), Ok(Body::Boss));
+        let with_axe = weapons
+            .get(event.killer_entity)
+            .ok()
+            .and_then(|w| weapon_assets.get(&w.0))
+            .is_some_and(|w| w.axe);
+        if killed_boss && with_axe {
+            achievements.add_progress(Achievement::Lumberjack, 1);
+        }
+    }
+}
+
+fn reset_run_progress(mut achievements: ResMut<Achievements>) {
+    achievements.trees_lost_this_run = 0;
+}
+
+fn track_win(result: Res<GameResult>, mut achievements: ResMut<Achievements>) {
+    if *result != GameResult::Won {
+        return;
+    }
+    if achievements.trees_lost_this_run == 0 {
+        achievements.add_progress(Achievement::UntouchedForest, 1);
+    }
+}
+
+fn unlock_achievements(
+    mut achievements: ResMut<Achievements>,
+    mut notification_event: EventWriter<NotificationEvent>,
+) {
+    if !achievements.is_changed() {
+        return;
+    }
+    let mut unlocked = false;
+    for achievement in Achievement::iter() {
+        if achievements.is_unlocked(achievement)
+            || achievements.progress(achievement) < achievement.target()
+        {
+            continue;
+        }
+        achievements.unlocked.insert(achievement);
+        unlocked = true;
+        notification_event.send(NotificationEvent {
+            text: format!("Achievement unlocked: {}", achievement.name()),
+            show_for: 4.0,
+            color: Color::GOLD,
+        });
+    }
+    // unlocks are rare, and often happen after the run has ended
+    if unlocked {
+        persistence::save(ACHIEVEMENTS_FILE, &*achievements);
+    }
+}
+
+fn save_achievements(achievements: Res<Achievements>) {
+    persistence::save(ACHIEVEMENTS_FILE, &*achievements);
+}
+
+#[derive(Component)]
+struct AchievementsUiTag;
+
+#[derive(Component)]
+struct AchievementText(Achievement);
+
+fn setup_achievements_ui(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    commands
+        .spawn((
+            AchievementsUiTag,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(30.0),
+                    top: Val::Percent(20.0),
+                    width: Val::Percent(40.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    row_gap: Val::Px(5.0),
+                    flex_direction: FlexDirection::Column,
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::BLACK.with_a(0.7).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Achievements",
+                TextStyle {
+                    font: ui_assets.font.clone(),
+                    font_size: 40.0,
+                    color: Color::GOLD,
+                },
+            ));
+            for achievement in Achievement::iter() {
+                parent.spawn((
+                    AchievementText(achievement),
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font: ui_assets.font.clone(),
+                            font_size: 24.0,
+                            color: Color::WHITE,
+                        },
+                    ),
+                ));
+            }
+        });
+}
+
+fn toggle_achievements_ui(
+    keyboard: Res<Input<KeyCode>>,
+    mut panel: Query<&mut Style, With<AchievementsUiTag>>,
+) {
+    if !keyboard.just_pressed(KeyCode::Tab) {
+        return;
+    }
+    for mut style in panel.iter_mut() {
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+fn update_achievements_ui(
+    achievements: Res<Achievements>,
+    mut texts: Query<(&mut Text, &AchievementText)>,
+) {
+    if !achievements.is_changed() {
+        return;
+    }
+    for (mut text, achievement) in texts.iter_mut() {
+        let a = achievement.0;
+        let unlocked = achievements.is_unlocked(a);
+        text.sections[0].value = format!(
+            "{} {} - {} ({}/{})",
+            if unlocked { "[x]" } else { "[ ]" },
+            a.name(),
+            a.description(),
+            achievements.progress(a),
+            a.target(),
+        );
+        text.sections[0].style.color = match unlocked {
+            true => Color::GOLD,
+            false => Color::WHITE,
+        };
+    }
+}
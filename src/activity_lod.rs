@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    camera::MainCameraTag, health::ApplyHealthEvent, pathfinding::PathFollow, pause::not_paused,
+    player::PlayerControllerTag, robot_ai::RobotController, spatial_grid::SpatialGrids,
+    tree::TreeTrunkTag,
+};
+
+// meters on the ground from the player or camera, further than this goes dormant
+const SLEEP_DISTANCE: f32 = 35.0;
+// a bit closer than SLEEP_DISTANCE, so things on the edge don't flip every check
+const WAKE_DISTANCE: f32 = 30.0;
+// seconds between checks, nothing out there moves fast enough to notice
+const CHECK_INTERVAL: f32 = 0.5;
+// seconds a hit keeps something awake, however far away it is
+const HIT_AWAKE_TIME: f32 = 5.0;
+
+pub struct ActivityLodPlugin;
+
+impl Plugin for ActivityLodPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (wake_on_hit, update_activity_lod)
+                .chain()
+                .run_if(not_paused),
+        );
+    }
+}
+
+/// Far away from the player and camera, nothing is simulated for it.
+///
+/// Jointed tree trunks turn into fixed bodies so their joint is left alone,
+/// robots with nothing to walk to are skipped by `robot_ai` and movement.
+#[derive(Component)]
+pub struct Dormant;
+
+// recently hit, stays awake until the timer runs out
+#[derive(Component)]
+struct StayAwake(Timer);
+
+fn wake_on_hit(
+    mut commands: Commands,
+    mut events: EventReader<ApplyHealthEvent>,
+    targets: Query<
+        (Has<Dormant>, Has<ImpulseJoint>),
+        Or<(With<TreeTrunkTag>, With<RobotController>)>,
+    >,
+) {
+    for event in events.read() {
+        if event.amount >= 0 {
+            continue;
+        }
+        let Ok((dormant, jointed)) = targets.get(event.target_entity) else {
+            continue;
+        };
+        commands
+            .entity(event.target_entity)
+            .insert(StayAwake(Timer::from_seconds(
+                HIT_AWAKE_TIME,
+                TimerMode::Once,
+            )));
+        if dormant {
+            set_dormant(&mut commands, event.target_entity, jointed, false);
+        }
+    }
+}
+
+fn update_activity_lod(
+    mut commands: Commands,
+    focus: Query<&GlobalTransform, Or<(With<PlayerControllerTag>, With<MainCameraTag>)>>,
+    trees: Query<
+        (Entity, &GlobalTransform, Has<Dormant>),
+        (With<TreeTrunkTag>, With<ImpulseJoint>, Without<StayAwake>),
+    >,
+    robots: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &RobotController,
+            &PathFollow,
+            Has<Dormant>,
+        ),
+        Without<StayAwake>,
+    >,
+    grids: Res<SpatialGrids>,
+    mut awake: Query<(Entity, &mut StayAwake)>,
+    time: Res<Time>,
+    mut since_check: Local<f32>,
+) {
+    for (entity, mut stay_awake) in awake.iter_mut() {
+        if stay_awake.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<StayAwake>();
+        }
+    }
+    *since_check += time.delta_seconds();
+    if *since_check < CHECK_INTERVAL {
+        return;
+    }
+    *since_check = 0.0;
+
+    let focus_points = focus
+        .iter()
+        .map(|t| t.translation().xz())
+        .collect::<Vec<_>>();
+    // what a dormant or awake entity at pos should be now, None keeps it as it is
+    let should_sleep = |pos: Vec3, dormant: bool| {
+        let distance = focus_points
+            .iter()
+            .map(|p| p.distance(pos.xz()))
+            .fold(f32::MAX, f32::min);
+        match dormant {
+            true => (distance < WAKE_DISTANCE).then_some(false),
+            false => (distance > SLEEP_DISTANCE).then_some(true),
+        }
+    };
+
+    for (entity, transform, dormant) in trees.iter() {
+        if let Some(sleep) = should_sleep(transform.translation(), dormant) {
+            set_dormant(&mut commands, entity, true, sleep);
+        }
+    }
+
+    // dormant robots never pick a goal themselves, so they wake up once there is one
+    let has_goals = !grids.trees.is_empty() || !grids.tree_spawners.is_empty();
+    for (entity, transform, controller, path, dormant) in robots.iter() {
+        let sleep = match dormant {
+            true if has_goals => Some(false),
+            _ if !controller.is_idle() || !path.is_empty() => dormant.then_some(false),
+            _ => should_sleep(transform.translation(), dormant),
+        };
+        if let Some(sleep) = sleep {
+            set_dormant(&mut commands, entity, false, sleep);
+        }
+    }
+}
+
+// a jointed trunk is fixed while dormant, a joint between two fixed bodies does nothing
+fn set_dormant(commands: &mut Commands, entity: Entity, jointed: bool, sleep: bool) {
+    if jointed {
+        commands.entity(entity).insert(match sleep {
+            true => RigidBody::Fixed,
+            false => RigidBody::Dynamic,
+        });
+    }
+    match sleep {
+        true => commands.entity(entity).insert(Dormant),
+        false => commands.entity(entity).remove::<Dormant>(),
+    };
+}
@@ -0,0 +1,178 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use serde::Deserialize;
+
+use crate::{
+    inventory::{Inventory, Item},
+    player::PlayerControllerTag,
+    ui_util::UiAssets,
+    weapon::{Weapon, WeaponAsset},
+};
+
+// arrows in a picked up quiver
+const QUIVER_ARROWS: u32 = 6;
+// where the ammo text sits relative to the cursor
+const CURSOR_OFFSET: Vec2 = Vec2::new(18.0, 18.0);
+
+pub struct AmmoPlugin;
+
+impl Plugin for AmmoPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_ammo_ui).add_systems(
+            Update,
+            (give_ammo, refill_from_quivers, tick_reload, update_ammo_ui),
+        );
+    }
+}
+
+/// Magazine settings for weapons that need ammo, see the `ammo` field of the weapon asset.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct AmmoConfig {
+    pub magazine: u32,
+    // seconds to refill the magazine
+    pub reload_time: f32,
+    // spare ammo the player starts with
+    pub starting_reserve: u32,
+}
+
+/// Shots left for the player's weapon, weapons without this never run out.
+#[derive(Component, Clone)]
+pub struct Ammo {
+    pub loaded: u32,
+    pub reserve: u32,
+    config: AmmoConfig,
+    // seconds left until the reload is done
+    reloading: Option<f32>,
+}
+
+impl Ammo {
+    pub fn new(config: AmmoConfig) -> Self {
+        Self {
+            loaded: config.magazine,
+            reserve: config.starting_reserve,
+            config,
+            reloading: None,
+        }
+    }
+
+    /// Uses up a shot, returning false if there was none loaded.
+    /// Starts reloading once the magazine is empty.
+    pub fn try_use(&mut self) -> bool {
+        if self.reloading.is_some() || self.loaded == 0 {
+            self.start_reload();
+            return false;
+        }
+        self.loaded -= 1;
+        if self.loaded == 0 {
+            self.start_reload();
+        }
+        true
+    }
+
+    fn start_reload(&mut self) {
+        if self.reloading.is_none() && self.reserve > 0 && self.loaded < self.config.magazine {
+            self.reloading = Some(self.config.reload_time);
+        }
+    }
+
+    // 0.0 just started reloading, 1.0 done
+    pub fn reload_progress(&self) -> Option<f32> {
+        self.reloading
+            .map(|left| 1.0 - left / self.config.reload_time.max(f32::EPSILON))
+    }
+}
+
+// the player picks up ammo as soon as their weapon turns out to need it
+fn give_ammo(
+    mut commands: Commands,
+    players: Query<(Entity, Ref<Weapon>), (With<PlayerControllerTag>, Without<Ammo>)>,
+    weapon_assets: Res<Assets<WeaponAsset>>,
+) {
+    for (entity, weapon) in players.iter() {
+        // just switched weapons, the hotbar might be handing back its ammo
+        if weapon.is_changed() {
+            continue;
+        }
+        let Some(config) = weapon_assets.get(&weapon.0).and_then(|w| w.ammo) else {
+            continue;
+        };
+        commands.entity(entity).insert(Ammo::new(config));
+    }
+}
+
+fn refill_from_quivers(mut query: Query<(&mut Inventory, &mut Ammo), Changed<Inventory>>) {
+    for (mut inventory, mut ammo) in query.iter_mut() {
+        // spending goes through Mut, checking first keeps the inventory from looking changed forever
+        if inventory.get_item_count(Item::Quiver) == 0 {
+            continue;
+        }
+        while inventory.spend_item(Item::Quiver, 1) {
+            ammo.reserve += QUIVER_ARROWS;
+        }
+        // picked up ammo while empty
+        if ammo.loaded == 0 {
+            ammo.start_reload();
+        }
+    }
+}
+
+fn tick_reload(mut query: Query<&mut Ammo>, time: Res<Time>) {
+    for mut ammo in query.iter_mut() {
+        let Some(left) = ammo.reloading else {
+            continue;
+        };
+        if left > time.delta_seconds() {
+            ammo.reloading = Some(left - time.delta_seconds());
+            continue;
+        }
+        let amount = (ammo.config.magazine - ammo.loaded).min(ammo.reserve);
+        ammo.loaded += amount;
+        ammo.reserve -= amount;
+        ammo.reloading = None;
+    }
+}
+
+#[derive(Component)]
+struct AmmoText;
+
+fn setup_ammo_ui(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    commands.spawn((
+        AmmoText,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: ui_assets.font.clone(),
+                font_size: 22.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            display: Display::None,
+            ..default()
+        }),
+    ));
+}
+
+// follows the cursor, since that's where the player is aiming
+fn update_ammo_ui(
+    player: Query<&Ammo, With<PlayerControllerTag>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut text: Query<(&mut Text, &mut Style), With<AmmoText>>,
+) {
+    let Ok((mut text, mut style)) = text.get_single_mut() else {
+        return;
+    };
+    let cursor = window.get_single().ok().and_then(|w| w.cursor_position());
+    let (Ok(ammo), Some(cursor)) = (player.get_single(), cursor) else {
+        style.display = Display::None;
+        return;
+    };
+    style.display = Display::Flex;
+    style.left = Val::Px(cursor.x + CURSOR_OFFSET.x);
+    style.top = Val::Px(cursor.y + CURSOR_OFFSET.y);
+    text.sections[0].value = match ammo.reload_progress() {
+        Some(progress) => format!("reloading {:.0}%", progress * 100.0),
+        None if ammo.loaded == 0 => "out of arrows".to_string(),
+        None => format!("{} / {}", ammo.loaded, ammo.reserve),
+    };
+}
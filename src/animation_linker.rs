@@ -1,4 +1,4 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
 
 pub struct AnimationEntityLinkPlugin;
 
@@ -8,50 +8,119 @@ impl Plugin for AnimationEntityLinkPlugin {
     }
 }
 
+/// All animation players found under a root entity.
+/// Composite characters (body + weapon + cape) can have several players,
+/// each one is reachable by its path from the root, like "body/weapon".
+/// Unnamed nodes in the path are written as their index among their siblings, like "#0".
 #[derive(Component)]
-pub struct AnimationEntityLink(pub Entity);
+pub struct AnimationEntityLink {
+    /// the first animation player that was linked
+    pub main: Entity,
+    pub players: HashMap<String, Entity>,
+}
+
+impl AnimationEntityLink {
+    /// animation player linked at `path`
+    pub fn get(&self, path: &str) -> Option<Entity> {
+        self.players.get(path).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Entity)> {
+        self.players.iter().map(|(path, e)| (path.as_str(), *e))
+    }
+}
 
-///! add this component to stop root parent search, and return this "trap lol"
+/// add this component to stop root parent search, and return this "trap lol"
 #[derive(Component)]
 pub struct AnimationEntityLinkTrap;
 
 /// OOOOMG lol, sorry :D
-///! finds the root top parent entity, and return the first child
+/// finds the root top parent entity, and return the first child
 fn get_top_parent(
     mut curr_entity: Entity,
     parent_query: &Query<(&Parent, Option<&AnimationEntityLinkTrap>)>,
 ) -> Entity {
     //Loop up all the way to the top parent
-    loop {
-        if let Ok((parent, trap)) = parent_query.get(curr_entity) {
-            match trap {
-                Some(_) => break,
-                None => curr_entity = parent.get(),
-            }
-        } else {
-            break;
+    while let Ok((parent, trap)) = parent_query.get(curr_entity) {
+        match trap {
+            Some(_) => break,
+            None => curr_entity = parent.get(),
         }
     }
     curr_entity
 }
 
+/// path from below the root down to the animation player node, names joined by '/'
+fn player_path(
+    entity: Entity,
+    top_entity: Entity,
+    parent_query: &Query<(&Parent, Option<&AnimationEntityLinkTrap>)>,
+    name_query: &Query<&Name>,
+    children_query: &Query<&Children>,
+) -> String {
+    let mut parts = Vec::new();
+    let mut curr_entity = entity;
+    while curr_entity != top_entity {
+        let Ok((parent, _)) = parent_query.get(curr_entity) else {
+            break;
+        };
+        let part = match name_query.get(curr_entity) {
+            Ok(name) => name.as_str().to_string(),
+            // scenes spawn their children in the same order every time
+            Err(_) => {
+                let index = children_query
+                    .get(parent.get())
+                    .ok()
+                    .and_then(|c| c.iter().position(|e| *e == curr_entity))
+                    .unwrap_or_default();
+                format!("#{index}")
+            }
+        };
+        parts.push(part);
+        curr_entity = parent.get();
+    }
+    parts.reverse();
+    parts.join("/")
+}
+
 pub fn link_animations(
     player_query: Query<Entity, Added<AnimationPlayer>>,
     parent_query: Query<(&Parent, Option<&AnimationEntityLinkTrap>)>,
-    animations_entity_link_query: Query<&AnimationEntityLink>,
+    name_query: Query<&Name>,
+    children_query: Query<&Children>,
+    mut animations_entity_link_query: Query<&mut AnimationEntityLink>,
     mut commands: Commands,
 ) {
+    // several players can show up under the same root in the same frame,
+    // group them before inserting since commands are deferred
+    let mut new_links: HashMap<Entity, Vec<(String, Entity)>> = HashMap::new();
+
     // Get all the Animation players which can be deep and hidden in the heirachy
     for entity in player_query.iter() {
         let top_entity = get_top_parent(entity, &parent_query);
+        let path = player_path(
+            entity,
+            top_entity,
+            &parent_query,
+            &name_query,
+            &children_query,
+        );
+        new_links
+            .entry(top_entity)
+            .or_default()
+            .push((path, entity));
+    }
 
-        // If the top parent has an animation config ref then link the player to the config
-        if animations_entity_link_query.get(top_entity).is_ok() {
-            warn!("Problem with multiple animationsplayers for the same top parent");
-        } else {
-            commands
-                .entity(top_entity)
-                .insert(AnimationEntityLink(entity.clone()));
+    for (top_entity, players) in new_links {
+        // If the top parent already has a link, add the new players to it
+        if let Ok(mut link) = animations_entity_link_query.get_mut(top_entity) {
+            link.players.extend(players);
+            continue;
         }
+        let main = players[0].1;
+        commands.entity(top_entity).insert(AnimationEntityLink {
+            main,
+            players: HashMap::from_iter(players),
+        });
     }
 }
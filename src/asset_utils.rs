@@ -35,8 +35,8 @@ impl From<SpannedError> for CustomAssetLoaderError {
 /// if the string file name is present, load the asset into field
 pub fn maybe_load_asset<'a, T, N>(
     name: N,
-    field: &'a mut Option<Handle<T>>,
-    load_context: &'a mut LoadContext,
+    field: &mut Option<Handle<T>>,
+    load_context: &mut LoadContext,
 ) where
     T: Asset,
     N: Into<AssetPath<'a>>,
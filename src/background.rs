@@ -1,6 +1,6 @@
 use bevy::prelude::*;
-use bevy::reflect::{TypePath, TypeUuid};
-use bevy::render::render_resource::{AsBindGroup, ShaderRef, Texture};
+use bevy::reflect::TypePath;
+use bevy::render::render_resource::{AsBindGroup, ShaderRef};
 
 #[derive(AsBindGroup, Clone, TypePath, Asset)]
 pub struct SpaceMaterial {
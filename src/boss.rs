@@ -0,0 +1,319 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_vector_shapes::{prelude::ShapePainter, shapes::DiscPainter};
+
+use crate::{
+    camera_shake::CameraShakeEvent,
+    cutscene::{CutsceneStep, PlayCutsceneEvent},
+    health::{ApplyHealthEvent, Health},
+    knockback::ApplyKnockbackEvent,
+    player::{Body, BodyGraphics, Controller, MonkeyTag, Player, PlayerInput, SpawnPlayerEvent},
+    settings::Settings,
+    state::Wave,
+    waves::{WaveDescriptors, WaveDescriptorsAsset},
+};
+
+pub const BOSS_NAME: &str = "MEGA ROBOT";
+
+// health fractions where the boss moves on to its next phase
+const PHASE_THRESHOLDS: [f32; 2] = [0.66, 0.33];
+// seconds between attacks
+const ATTACK_COOLDOWN: f32 = 4.0;
+const CHARGE_TIME: f32 = 0.8;
+const CHARGE_SPEED_MUL: f32 = 3.5;
+const CHARGE_HIT_RADIUS: f32 = 2.0;
+const CHARGE_DAMAGE: i32 = 3;
+// the boss stands still this long before slamming, while the area is shown
+const SLAM_WINDUP: f32 = 0.8;
+const SLAM_RADIUS: f32 = 6.0;
+const SLAM_DAMAGE: i32 = 4;
+const SLAM_KNOCKBACK: f32 = 30.0;
+// robots summoned for every phase the boss moves on to
+const MINIONS_PER_SUMMON: usize = 3;
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (boss_intro, add_boss_behavior, draw_slam_telegraph));
+    }
+}
+
+// when the boss shows up, look at it, roar and show its name
+fn boss_intro(
+    bosses: Query<(Entity, &Body, &Children), Added<Body>>,
+    graphics: Query<Entity, With<BodyGraphics>>,
+    mut cutscene_events: EventWriter<PlayCutsceneEvent>,
+) {
+    for (boss_entity, body, children) in bosses.iter() {
+        if *body != Body::Boss {
+            continue;
+        }
+        let roar_entity = children
+            .iter()
+            .find_map(|c| graphics.get(*c).ok())
+            .unwrap_or(boss_entity);
+
+        cutscene_events.send(PlayCutsceneEvent {
+            lock_input: true,
+            steps: vec![
+                CutsceneStep::FocusCamera(boss_entity),
+                CutsceneStep::Wait(1.0),
+                CutsceneStep::PlaySound {
+                    path: "sounds/robot-death.ogg".into(),
+                    volume: 1.0,
+                    speed: 0.4,
+                },
+                CutsceneStep::PunchScale {
+                    entity: roar_entity,
+                    amount: 0.25,
+                    duration: 1.2,
+                },
+                CutsceneStep::Banner {
+                    text: BOSS_NAME.into(),
+                    color: Color::RED,
+                    show_for: 2.5,
+                },
+                CutsceneStep::Wait(2.0),
+                CutsceneStep::ReleaseCamera,
+            ],
+        });
+    }
+}
+
+#[derive(Clone, Copy)]
+enum BossAttack {
+    Charge,
+    Slam,
+}
+
+enum BossAction {
+    Idle,
+    Charging {
+        dir: Vec3,
+        time_left: f32,
+        // every monkey is only hit once per charge
+        hit: Vec<Entity>,
+    },
+    SlamWindup {
+        time_left: f32,
+    },
+}
+
+/// Phased attacks of the boss, phases are picked from how much health is left.
+#[derive(Component)]
+pub struct BossBehavior {
+    // 0 until the first threshold is passed
+    phase: usize,
+    cooldown: f32,
+    action: BossAction,
+    // steps through the attacks of the current phase
+    attack_count: usize,
+    base_speed: f32,
+}
+
+impl BossBehavior {
+    fn attacks(&self) -> &'static [BossAttack] {
+        match self.phase {
+            0 => &[BossAttack::Charge],
+            1 => &[BossAttack::Charge, BossAttack::Slam],
+            _ => &[BossAttack::Slam, BossAttack::Charge, BossAttack::Slam],
+        }
+    }
+}
+
+fn add_boss_behavior(mut commands: Commands, bosses: Query<(Entity, &Body, &Player), Added<Body>>) {
+    for (entity, body, player) in bosses.iter() {
+        if *body != Body::Boss {
+            continue;
+        }
+        commands.entity(entity).insert(BossBehavior {
+            phase: 0,
+            cooldown: ATTACK_COOLDOWN,
+            action: BossAction::Idle,
+            attack_count: 0,
+            base_speed: player.movement_speed,
+        });
+    }
+}
+
+// runs after robot_ai, overriding its input while an attack is going on
+pub fn boss_behavior(
+    mut bosses: Query<(
+        Entity,
+        &mut BossBehavior,
+        &mut PlayerInput,
+        &mut Player,
+        &Health,
+        &GlobalTransform,
+    )>,
+    monkeys: Query<(Entity, &GlobalTransform), With<MonkeyTag>>,
+    mut spawn_player_event: EventWriter<SpawnPlayerEvent>,
+    mut apply_health_events: EventWriter<ApplyHealthEvent>,
+    mut knockback_events: EventWriter<ApplyKnockbackEvent>,
+    mut shake_events: EventWriter<CameraShakeEvent>,
+    asset_server: Res<AssetServer>,
+    wave: Res<Wave>,
+    wave_descriptors: Res<WaveDescriptors>,
+    wave_descriptor_assets: Res<Assets<WaveDescriptorsAsset>>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    for (boss, mut behavior, mut input, mut player, health, transform) in bosses.iter_mut() {
+        let pos = transform.translation();
+        let monkeys_within = |radius: f32| {
+            monkeys
+                .iter()
+                .filter(move |(_, t)| t.translation().distance(pos) < radius)
+                .map(|(e, _)| e)
+        };
+
+        let phase = PHASE_THRESHOLDS
+            .iter()
+            .filter(|t| health.percent() <= **t)
+            .count();
+        if phase > behavior.phase {
+            // one big hit can skip a phase, each one still brings its minions
+            let minions = (phase - behavior.phase) * MINIONS_PER_SUMMON;
+            behavior.phase = phase;
+            // as tough as the rest of the wave
+            let scaling = wave_descriptor_assets
+                .get(&wave_descriptors.0)
+                .and_then(|waves| waves.wave(wave.0))
+                .map(|(_, scaling)| scaling)
+                .unwrap_or_default();
+            for i in 0..minions {
+                let angle = TAU * i as f32 / minions as f32;
+                spawn_player_event.send(SpawnPlayerEvent {
+                    pos: pos + Quat::from_rotation_y(angle) * Vec3::Z * 3.0 + Vec3::Y,
+                    controller: Controller::Robot,
+                    body: Body::Robot,
+                    weapon: asset_server.load("weapons/axe.weapon.ron"),
+                    scaling,
+                });
+            }
+        }
+
+        let action = std::mem::replace(&mut behavior.action, BossAction::Idle);
+        behavior.action = match action {
+            BossAction::Idle => {
+                behavior.cooldown -= dt;
+                if behavior.cooldown > 0.0 {
+                    continue;
+                }
+                behavior.cooldown = ATTACK_COOLDOWN;
+                let attacks = behavior.attacks();
+                let attack = attacks[behavior.attack_count % attacks.len()];
+                behavior.attack_count += 1;
+                match attack {
+                    BossAttack::Charge => {
+                        let closest = monkeys.iter().min_by(|(_, a), (_, b)| {
+                            a.translation()
+                                .distance_squared(pos)
+                                .total_cmp(&b.translation().distance_squared(pos))
+                        });
+                        let dir = closest
+                            .map(|(_, t)| (t.translation() - pos) * Vec3::new(1.0, 0.0, 1.0))
+                            .unwrap_or_default()
+                            .normalize_or_zero();
+                        match dir == Vec3::ZERO {
+                            true => BossAction::Idle,
+                            false => {
+                                player.movement_speed = behavior.base_speed * CHARGE_SPEED_MUL;
+                                BossAction::Charging {
+                                    dir,
+                                    time_left: CHARGE_TIME,
+                                    hit: Vec::new(),
+                                }
+                            }
+                        }
+                    }
+                    BossAttack::Slam => BossAction::SlamWindup {
+                        time_left: SLAM_WINDUP,
+                    },
+                }
+            }
+            BossAction::Charging {
+                dir,
+                time_left,
+                mut hit,
+            } => {
+                input.movement = dir;
+                input.attack = None;
+                for monkey in monkeys_within(CHARGE_HIT_RADIUS) {
+                    if hit.contains(&monkey) {
+                        continue;
+                    }
+                    hit.push(monkey);
+                    apply_health_events.send(ApplyHealthEvent {
+                        amount: -CHARGE_DAMAGE,
+                        target_entity: monkey,
+                        caster_entity: boss,
+                        is_crit: false,
+                        is_finisher: false,
+                    });
+                }
+                match time_left - dt {
+                    t if t > 0.0 => BossAction::Charging {
+                        dir,
+                        time_left: t,
+                        hit,
+                    },
+                    _ => {
+                        player.movement_speed = behavior.base_speed;
+                        BossAction::Idle
+                    }
+                }
+            }
+            BossAction::SlamWindup { time_left } => {
+                input.movement = Vec3::ZERO;
+                input.attack = None;
+                match time_left - dt {
+                    t if t > 0.0 => BossAction::SlamWindup { time_left: t },
+                    _ => {
+                        knockback_events.send(ApplyKnockbackEvent {
+                            origin: pos,
+                            strength: SLAM_KNOCKBACK,
+                            radius: SLAM_RADIUS,
+                            falloff: 0.5,
+                        });
+                        shake_events.send(CameraShakeEvent::BOSS_SLAM);
+                        for monkey in monkeys_within(SLAM_RADIUS) {
+                            apply_health_events.send(ApplyHealthEvent {
+                                amount: -SLAM_DAMAGE,
+                                target_entity: monkey,
+                                caster_entity: boss,
+                                is_crit: false,
+                                is_finisher: false,
+                            });
+                        }
+                        BossAction::Idle
+                    }
+                }
+            }
+        };
+    }
+}
+
+// the slam area fills up while the boss winds up
+fn draw_slam_telegraph(
+    mut painter: ShapePainter,
+    bosses: Query<(&BossBehavior, &GlobalTransform)>,
+    settings: Res<Settings>,
+) {
+    for (behavior, transform) in bosses.iter() {
+        let BossAction::SlamWindup { time_left } = behavior.action else {
+            continue;
+        };
+        let progress = 1.0 - time_left / SLAM_WINDUP;
+        painter.color = settings.accessibility.palette.remap(Color::RED);
+        painter.thickness = 0.1;
+        painter.hollow = true;
+        painter.set_rotation(Quat::from_rotation_x(TAU / 4.0));
+        painter
+            .set_translation(transform.translation() * Vec3::new(1.0, 0.0, 1.0) + Vec3::Y * 0.05);
+        painter.circle(SLAM_RADIUS);
+        painter.circle(SLAM_RADIUS * progress);
+    }
+}
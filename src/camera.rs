@@ -1,7 +1,31 @@
-use bevy::{input::mouse::MouseMotion, math::vec3, prelude::*};
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    math::vec3,
+    prelude::*,
+};
 use dolly::prelude::*;
 
-use crate::{player::PlayerControllerTag, utils::movement_axis};
+use crate::{
+    input_map::{Action, InputBindings, InputMap},
+    placement::Placement,
+    player::PlayerControllerTag,
+    tree::TreeTrunkTag,
+};
+
+// the wheel zooms while this is held, otherwise it switches weapons
+const ZOOM_MODIFIERS: [KeyCode; 2] = [KeyCode::ControlLeft, KeyCode::ControlRight];
+const ZOOM_STEP: f32 = 0.1;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 1.6;
+// degrees per second while an orbit key is held
+const ORBIT_KEY_SPEED: f32 = 90.0;
+// degrees per pixel dragged with the middle mouse button
+const ORBIT_DRAG_SPEED: f32 = 0.3;
+// eases zoom and orbit back to default
+const RESET_KEY: KeyCode = KeyCode::Home;
+// with the player dead the camera circles the remaining trees this fast, in degrees per second
+const SPECTATE_ORBIT_SPEED: f32 = 10.0;
+const SPECTATE_ZOOM: f32 = 1.5;
 
 #[derive(Component)]
 pub struct MainCameraTag;
@@ -21,10 +45,29 @@ pub struct DollyCamera {
     pub rotation_speed: f32,
 }
 
+/// when set, the follow camera looks at this instead of the player
+#[derive(Resource, Default)]
+pub struct CameraFocus(pub Option<FocusTarget>);
+
+#[derive(Clone, Copy)]
+pub enum FocusTarget {
+    Entity(Entity),
+    Point(Vec3),
+}
+
 #[derive(Resource, Reflect)]
 pub struct FollowCameraSettings {
     pub offset: Vec3,
     pub yaw: f32,
+    // offset multiplier, smaller is closer
+    pub zoom: f32,
+    // degrees around the player, 0 looks towards -z
+    pub orbit: f32,
+}
+
+/// Whether the mouse wheel is currently zooming the camera instead of switching weapons.
+pub fn zoom_modifier_held(keyboard: &Input<KeyCode>) -> bool {
+    keyboard.any_pressed(ZOOM_MODIFIERS)
 }
 
 pub struct CameraPlugin;
@@ -32,16 +75,24 @@ pub struct CameraPlugin;
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<FollowCameraSettings>()
-            .add_systems(Update, ((free_fly_input, follow_player), update).chain());
+            .init_resource::<CameraFocus>()
+            .add_systems(
+                Update,
+                (
+                    (free_fly_input, (follow_camera_input, follow_player).chain()),
+                    update,
+                )
+                    .chain(),
+            );
     }
 }
 impl DollyCamera {
     pub fn new(pos: Vec3, rotation: Quat, speed: f32) -> Self {
         let mut yaw = YawPitch::new();
-        yaw.set_rotation_quat(rotation.into());
+        yaw.set_rotation_quat(rotation);
         Self {
             rig: CameraRig::builder()
-                .with(Position::new(pos.into()))
+                .with(Position::new(pos))
                 .with(yaw)
                 .with(Smooth::new_position_rotation(1.0, 1.0))
                 .build(),
@@ -56,6 +107,8 @@ impl Default for FollowCameraSettings {
         Self {
             offset: vec3(0.0, 15.0, 12.0),
             yaw: -50f32,
+            zoom: 1.0,
+            orbit: 0.0,
         }
     }
 }
@@ -64,22 +117,95 @@ pub fn follow_player(
     players: Query<&GlobalTransform, With<PlayerControllerTag>>,
     mut cameras: Query<&mut DollyCamera, With<FollowPlayerCamera>>,
     camera_settings: Res<FollowCameraSettings>,
+    camera_focus: Res<CameraFocus>,
+    transforms: Query<&GlobalTransform>,
+    trees: Query<&GlobalTransform, With<TreeTrunkTag>>,
+    time: Res<Time>,
+    // orbit while spectating, kept apart so the player's own orbit is back after respawning
+    mut spectate_orbit: Local<f32>,
 ) {
     let mut dolly_cam = cameras.single_mut();
-    let Ok(player) = players.get_single() else {
-        return;
+    let focus = camera_focus.0.and_then(|focus| match focus {
+        FocusTarget::Entity(entity) => transforms.get(entity).ok().map(|t| t.translation()),
+        FocusTarget::Point(pos) => Some(pos),
+    });
+    let player = players.get_single().ok().map(|t| t.translation());
+    let (target, orbit, zoom) = match focus.or(player) {
+        Some(target) => (target, camera_settings.orbit, camera_settings.zoom),
+        // spectate: watch the robots go after whatever trees are left
+        None => {
+            let Some(center) = centroid(trees.iter().map(|t| t.translation())) else {
+                return;
+            };
+            *spectate_orbit += SPECTATE_ORBIT_SPEED * time.delta_seconds();
+            *spectate_orbit %= 360.0;
+            (
+                center,
+                *spectate_orbit,
+                camera_settings.zoom * SPECTATE_ZOOM,
+            )
+        }
     };
 
+    // the rig smooths the jump when zoom or orbit change
+    let rotation = Quat::from_rotation_y(orbit.to_radians());
     let pos_driver = dolly_cam.rig.driver_mut::<Position>();
-    pos_driver.position = player.translation() + camera_settings.offset;
+    pos_driver.position = target + rotation * camera_settings.offset * zoom;
 
     let yaw_pitch = dolly_cam.rig.driver_mut::<YawPitch>();
     yaw_pitch.pitch_degrees = camera_settings.yaw;
-    yaw_pitch.yaw_degrees = 0.0;
+    yaw_pitch.yaw_degrees = orbit;
+}
+
+fn centroid(points: impl Iterator<Item = Vec3>) -> Option<Vec3> {
+    let (sum, count) = points.fold((Vec3::ZERO, 0), |(sum, count), p| (sum + p, count + 1));
+    (count > 0).then(|| sum / count as f32)
+}
+
+fn follow_camera_input(
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    bindings: Res<InputBindings>,
+    placement: Res<Placement>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut camera_settings: ResMut<FollowCameraSettings>,
+    time: Res<Time<Real>>,
+) {
+    let scroll: f32 = mouse_wheel.read().map(|e| e.y).sum();
+    let drag: f32 = mouse_motion.read().map(|e| e.delta.x).sum();
+    if keyboard.just_pressed(RESET_KEY) {
+        camera_settings.zoom = 1.0;
+        camera_settings.orbit = 0.0;
+        return;
+    }
+    if zoom_modifier_held(&keyboard) && scroll != 0.0 {
+        let zoom = camera_settings.zoom - scroll.signum() * ZOOM_STEP;
+        camera_settings.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+    // the rotate key is shared with placement
+    let mut orbit = match placement.is_active() {
+        true => 0.0,
+        false => {
+            bindings.axis(Action::OrbitLeft, Action::OrbitRight, &keyboard, &mouse)
+                * ORBIT_KEY_SPEED
+                * time.delta_seconds()
+        }
+    };
+    if mouse.pressed(MouseButton::Middle) && !drag.is_nan() {
+        orbit -= drag * ORBIT_DRAG_SPEED;
+    }
+    if orbit != 0.0 {
+        camera_settings.orbit = (camera_settings.orbit + orbit) % 360.0;
+    }
 }
 
 pub fn free_fly_input(
     keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    input_map: Res<InputMap>,
+    bindings: Res<InputBindings>,
+    axes: Res<Axis<GamepadAxis>>,
     mut mouse_motion: EventReader<MouseMotion>,
     mut query: Query<&mut DollyCamera, With<FreeFlyCamera>>,
     time: Res<Time>,
@@ -93,11 +219,10 @@ pub fn free_fly_input(
     }
     mouse_delta *= time.delta_seconds();
     for mut cam in query.iter_mut() {
-        let forward = movement_axis(&keyboard, KeyCode::S, KeyCode::W);
-        let side = movement_axis(&keyboard, KeyCode::D, KeyCode::A);
-        let y = movement_axis(&keyboard, KeyCode::Space, KeyCode::ShiftLeft);
+        let movement = input_map.movement(&bindings, &keyboard, &mouse, &axes);
+        let y = bindings.axis(Action::FlyUp, Action::FlyDown, &keyboard, &mouse);
 
-        let mut translation = vec3(side, 0.0, forward).normalize_or_zero();
+        let mut translation = vec3(movement.x, 0.0, movement.y).normalize_or_zero();
 
         // rotate translation so it's looking where camera is
         let rotation = cam.rig.final_transform.rotation;
@@ -0,0 +1,144 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{
+    camera::{self, DollyCamera},
+    health::{ApplyHealthEvent, DeathEvent},
+    player::PlayerControllerTag,
+    settings::Settings,
+    tower::TowerTag,
+};
+
+pub struct CameraShakePlugin;
+
+impl Plugin for CameraShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CameraShakeEvent>().add_systems(
+            Update,
+            (
+                shake_on_player_hit,
+                shake_on_tower_destroyed,
+                add_camera_shake,
+                apply_camera_shake.after(camera::update),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Shakes every dolly camera, a weaker shake doesn't cut a stronger one short.
+#[derive(Event, Clone, Copy)]
+pub struct CameraShakeEvent {
+    // meters at the start of the shake
+    pub amplitude: f32,
+    // wobbles per second
+    pub frequency: f32,
+    // amplitude lost per second
+    pub decay: f32,
+}
+
+impl CameraShakeEvent {
+    pub const PLAYER_HIT: Self = Self {
+        amplitude: 0.15,
+        frequency: 18.0,
+        decay: 0.6,
+    };
+    pub const BOSS_SLAM: Self = Self {
+        amplitude: 0.6,
+        frequency: 12.0,
+        decay: 0.8,
+    };
+    pub const TOWER_DESTROYED: Self = Self {
+        amplitude: 0.35,
+        frequency: 14.0,
+        decay: 0.7,
+    };
+}
+
+/// Offsets a [`DollyCamera`] on top of where its rig puts it.
+#[derive(Component, Default)]
+pub struct CameraShake {
+    amplitude: f32,
+    frequency: f32,
+    decay: f32,
+    time: f32,
+}
+
+fn shake_on_player_hit(
+    mut events: EventReader<ApplyHealthEvent>,
+    mut shake_events: EventWriter<CameraShakeEvent>,
+    players: Query<(), With<PlayerControllerTag>>,
+) {
+    if events
+        .read()
+        .any(|e| e.amount < 0 && players.contains(e.target_entity))
+    {
+        shake_events.send(CameraShakeEvent::PLAYER_HIT);
+    }
+}
+
+fn shake_on_tower_destroyed(
+    mut events: EventReader<DeathEvent>,
+    mut shake_events: EventWriter<CameraShakeEvent>,
+    towers: Query<(), With<TowerTag>>,
+) {
+    if events.read().any(|e| towers.contains(e.entity)) {
+        shake_events.send(CameraShakeEvent::TOWER_DESTROYED);
+    }
+}
+
+fn add_camera_shake(
+    mut commands: Commands,
+    mut events: EventReader<CameraShakeEvent>,
+    mut cameras: Query<(Entity, Option<&mut CameraShake>), With<DollyCamera>>,
+    settings: Res<Settings>,
+) {
+    let strength = settings.accessibility.screen_shake.strength();
+    for event in events.read() {
+        let amplitude = event.amplitude * strength;
+        if amplitude <= 0.0 {
+            continue;
+        }
+        for (entity, shake) in cameras.iter_mut() {
+            match shake {
+                Some(shake) if shake.amplitude >= amplitude => {}
+                Some(mut shake) => {
+                    shake.amplitude = amplitude;
+                    shake.frequency = event.frequency;
+                    shake.decay = event.decay;
+                }
+                None => {
+                    commands.entity(entity).insert(CameraShake {
+                        amplitude,
+                        frequency: event.frequency,
+                        decay: event.decay,
+                        time: 0.0,
+                    });
+                }
+            }
+        }
+    }
+}
+
+// runs after the rig has placed the camera for this frame
+fn apply_camera_shake(
+    mut commands: Commands,
+    mut cameras: Query<(Entity, &mut Transform, &mut CameraShake)>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, mut shake) in cameras.iter_mut() {
+        shake.time += time.delta_seconds();
+        shake.amplitude -= shake.decay * time.delta_seconds();
+        if shake.amplitude <= 0.0 {
+            commands.entity(entity).remove::<CameraShake>();
+            continue;
+        }
+        // two out of sync waves so it doesn't just slide back and forth
+        let phase = shake.time * shake.frequency * TAU;
+        let offset = transform.rotation
+            * Vec3::new(phase.sin(), (phase * 1.3 + 1.7).sin(), 0.0)
+            * shake.amplitude;
+        transform.translation += offset;
+    }
+}
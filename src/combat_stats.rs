@@ -0,0 +1,180 @@
+use bevy::prelude::*;
+
+use crate::{
+    health::{ApplyHealthEvent, DeathEvent},
+    inventory::Item,
+    item_pickups::ItemPickup,
+    pickup::OnPickedUpEvent,
+    player::{PlayerControllerTag, RobotTag},
+    state::{AppState, GameResult, RestartRunEvent},
+    tower::TowerTag,
+    tree::TreeTrunkTag,
+    ui_util::UiAssets,
+};
+
+pub struct CombatStatsPlugin;
+
+impl Plugin for CombatStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CombatStats>()
+            .add_systems(Update, (track_damage, track_deaths, track_fruit))
+            .add_systems(OnEnter(AppState::Playing), reset_combat_stats)
+            .add_systems(
+                PreUpdate,
+                reset_combat_stats.run_if(on_event::<RestartRunEvent>()),
+            )
+            .add_systems(OnEnter(AppState::GameOver), setup_summary_ui)
+            .add_systems(OnExit(AppState::GameOver), despawn_summary_ui);
+    }
+}
+
+/// Numbers for the end of run summary, reset when a run starts.
+#[derive(Resource, Default, Debug)]
+pub struct CombatStats {
+    // damage the player dealt to robots, before armor
+    pub damage_dealt: u32,
+    pub player_kills: u32,
+    // kills per tower, in the order the towers got their first kill
+    pub tower_kills: Vec<(Entity, u32)>,
+    pub trees_lost: u32,
+    pub fruit_collected: u32,
+}
+
+impl CombatStats {
+    fn add_tower_kill(&mut self, tower: Entity) {
+        match self.tower_kills.iter_mut().find(|(e, _)| *e == tower) {
+            Some((_, kills)) => *kills += 1,
+            None => self.tower_kills.push((tower, 1)),
+        }
+    }
+}
+
+fn reset_combat_stats(mut stats: ResMut<CombatStats>) {
+    *stats = CombatStats::default();
+}
+
+fn track_damage(
+    mut events: EventReader<ApplyHealthEvent>,
+    mut stats: ResMut<CombatStats>,
+    players: Query<(), With<PlayerControllerTag>>,
+    robots: Query<(), With<RobotTag>>,
+) {
+    for event in events.read() {
+        if event.amount < 0
+            && players.contains(event.caster_entity)
+            && robots.contains(event.target_entity)
+        {
+            stats.damage_dealt += event.amount.unsigned_abs();
+        }
+    }
+}
+
+fn track_deaths(
+    mut events: EventReader<DeathEvent>,
+    mut stats: ResMut<CombatStats>,
+    players: Query<(), With<PlayerControllerTag>>,
+    towers: Query<(), With<TowerTag>>,
+    robots: Query<(), With<RobotTag>>,
+    trees: Query<(), With<TreeTrunkTag>>,
+) {
+    for event in events.read() {
+        if trees.contains(event.entity) {
+            stats.trees_lost += 1;
+        }
+        if !robots.contains(event.entity) {
+            continue;
+        }
+        if players.contains(event.killer_entity) {
+            stats.player_kills += 1;
+        } else if towers.contains(event.killer_entity) {
+            stats.add_tower_kill(event.killer_entity);
+        }
+    }
+}
+
+fn track_fruit(
+    mut events: EventReader<OnPickedUpEvent>,
+    mut stats: ResMut<CombatStats>,
+    players: Query<(), With<PlayerControllerTag>>,
+    pickups: Query<&ItemPickup>,
+) {
+    for event in events.read() {
+        if !players.contains(event.receiver_entity) {
+            continue;
+        }
+        if let Ok(ItemPickup(Item::Banana | Item::Apple, count)) = pickups.get(event.pickup_entity)
+        {
+            stats.fruit_collected += count;
+        }
+    }
+}
+
+#[derive(Component)]
+struct SummaryUiTag;
+
+// sits to the left of the game over menu
+fn setup_summary_ui(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    stats: Res<CombatStats>,
+    result: Option<Res<GameResult>>,
+) {
+    let title_color = match result.as_deref() {
+        Some(GameResult::Won) => Color::GREEN,
+        _ => Color::RED,
+    };
+    let mut lines = vec![
+        format!("Damage dealt: {}", stats.damage_dealt),
+        format!("Your kills: {}", stats.player_kills),
+    ];
+    for (i, (_, kills)) in stats.tower_kills.iter().enumerate() {
+        lines.push(format!("Tower {} kills: {}", i + 1, kills));
+    }
+    lines.push(format!("Trees lost: {}", stats.trees_lost));
+    lines.push(format!("Fruit collected: {}", stats.fruit_collected));
+
+    commands
+        .spawn((
+            SummaryUiTag,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(5.0),
+                    top: Val::Percent(25.0),
+                    width: Val::Percent(25.0),
+                    padding: UiRect::all(Val::Px(20.0)),
+                    row_gap: Val::Px(6.0),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                background_color: Color::BLACK.with_a(0.7).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Run summary",
+                TextStyle {
+                    font: ui_assets.font.clone(),
+                    font_size: 36.0,
+                    color: title_color,
+                },
+            ));
+            for line in lines {
+                parent.spawn(TextBundle::from_section(
+                    line,
+                    TextStyle {
+                        font: ui_assets.font.clone(),
+                        font_size: 22.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            }
+        });
+}
+
+fn despawn_summary_ui(mut commands: Commands, panels: Query<Entity, With<SummaryUiTag>>) {
+    for entity in panels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
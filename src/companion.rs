@@ -0,0 +1,198 @@
+use std::cmp::Ordering;
+
+use bevy::prelude::*;
+use bevy_vector_shapes::{prelude::ShapePainter, shapes::DiscPainter};
+
+use crate::{
+    health::Health,
+    inventory::Inventory,
+    pathfinding::{PathFollow, PathRequest},
+    pickup::PickupTag,
+    placement::not_placing,
+    player::{PlayerControllerTag, PlayerInput, RobotTag},
+    pointer::PointerPos,
+    robot_ai::go_to,
+    spatial_grid::SpatialGrids,
+    tree::TreeTrunkTag,
+};
+
+// how far from the player/guarded tree the companion looks for robots
+const GUARD_RADIUS: f32 = 10.0;
+const SHOOT_RANGE: f32 = 8.0;
+const PICKUP_SEARCH_RADIUS: f32 = 15.0;
+const FOLLOW_DISTANCE: f32 = 4.0;
+// close enough to grab a pickup, the magnet does the rest
+const PICKUP_REACH: f32 = 1.0;
+// right clicking this close to a tree orders the companion to guard it
+const GUARD_CLICK_RADIUS: f32 = 3.0;
+
+pub struct CompanionPlugin;
+
+impl Plugin for CompanionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                guard_command.run_if(not_placing),
+                hand_over_items,
+                display_guard,
+            ),
+        );
+    }
+}
+
+/// A monkey fighting on the player's side.
+#[derive(Component, Default)]
+pub struct CompanionController {
+    /// tree to stay close to, follows the player when None
+    pub guard: Option<Entity>,
+}
+
+fn by_distance<T>(a: &(f32, T), b: &(f32, T)) -> Ordering {
+    a.0.partial_cmp(&b.0).unwrap_or(Ordering::Greater)
+}
+
+// walks the same planned paths as robot_ai, but hunting robots instead of trees
+pub fn companion_ai(
+    mut companions: Query<(
+        &mut PlayerInput,
+        &mut CompanionController,
+        &GlobalTransform,
+        &mut PathRequest,
+        &mut PathFollow,
+    )>,
+    player: Query<&GlobalTransform, With<PlayerControllerTag>>,
+    robots: Query<&GlobalTransform, (With<RobotTag>, With<Health>)>,
+    pickups: Query<&GlobalTransform, With<PickupTag>>,
+    trees: Query<&GlobalTransform, With<TreeTrunkTag>>,
+    grids: Res<SpatialGrids>,
+) {
+    for (mut player_input, mut controller, transform, mut path_request, mut path) in
+        companions.iter_mut()
+    {
+        let pos = transform.translation();
+        player_input.attack = None;
+        player_input.movement = Vec3::ZERO;
+
+        // guarded tree was cut down, go back to following
+        let guard_pos = controller.guard.and_then(|e| trees.get(e).ok());
+        if guard_pos.is_none() {
+            controller.guard = None;
+        }
+        let Some(anchor) = guard_pos.or(player.get_single().ok()) else {
+            continue;
+        };
+        let anchor = anchor.translation();
+
+        let closest_robot = grids
+            .robots
+            .nearest(anchor)
+            .filter(|(d, _)| *d < GUARD_RADIUS.powi(2))
+            .and_then(|(_, e)| Some((e, robots.get(e).ok()?.translation())));
+        if let Some((robot, robot_pos)) = closest_robot {
+            let reached = go_to(
+                &mut player_input,
+                &mut path_request,
+                &mut path,
+                pos,
+                robot_pos,
+                SHOOT_RANGE,
+                0.0,
+            );
+            if let Some(dir) = reached {
+                player_input.attack = Some((dir, Some(robot)));
+            }
+            continue;
+        }
+
+        // collect stray items while following, a guard stays put
+        if controller.guard.is_none() {
+            let closest_pickup = pickups
+                .iter()
+                .map(|t| (t.translation().distance_squared(pos), t.translation()))
+                .filter(|(d, _)| *d < PICKUP_SEARCH_RADIUS.powi(2))
+                .min_by(by_distance);
+            if let Some((_, pickup_pos)) = closest_pickup {
+                go_to(
+                    &mut player_input,
+                    &mut path_request,
+                    &mut path,
+                    pos,
+                    pickup_pos,
+                    PICKUP_REACH,
+                    0.0,
+                );
+                continue;
+            }
+        }
+
+        go_to(
+            &mut player_input,
+            &mut path_request,
+            &mut path,
+            pos,
+            anchor,
+            FOLLOW_DISTANCE,
+            0.0,
+        );
+    }
+}
+
+fn guard_command(
+    mouse: Res<Input<MouseButton>>,
+    pointer: Res<PointerPos>,
+    trees: Query<(Entity, &GlobalTransform), With<TreeTrunkTag>>,
+    mut companions: Query<&mut CompanionController>,
+) {
+    if !mouse.just_pressed(MouseButton::Right) || companions.is_empty() {
+        return;
+    }
+    // trees aren't pointer targets, so use the ground position under the cursor
+    let Some(ground_pos) = pointer.ground_pos else {
+        return;
+    };
+
+    // clicking away from trees calls the companions back
+    let tree = trees
+        .iter()
+        .map(|(e, t)| (t.translation().distance_squared(ground_pos), e))
+        .filter(|(d, _)| *d < GUARD_CLICK_RADIUS.powi(2))
+        .min_by(by_distance)
+        .map(|(_, e)| e);
+    for mut companion in companions.iter_mut() {
+        companion.guard = tree;
+    }
+}
+
+// whatever the companion picks up goes to the player
+fn hand_over_items(
+    mut companions: Query<&mut Inventory, (With<CompanionController>, Changed<Inventory>)>,
+    mut player: Query<&mut Inventory, (With<PlayerControllerTag>, Without<CompanionController>)>,
+) {
+    let Ok(mut player_inventory) = player.get_single_mut() else {
+        return;
+    };
+    for mut inventory in companions.iter_mut() {
+        for (item, count) in inventory.drain() {
+            player_inventory.add_item(item, count);
+        }
+    }
+}
+
+fn display_guard(
+    mut painter: ShapePainter,
+    companions: Query<&CompanionController>,
+    trees: Query<&GlobalTransform, With<TreeTrunkTag>>,
+) {
+    for companion in companions.iter() {
+        let Some(tree) = companion.guard.and_then(|e| trees.get(e).ok()) else {
+            continue;
+        };
+        painter.color = Color::CYAN;
+        painter.thickness = 0.05;
+        painter.hollow = true;
+        painter.set_rotation(Quat::from_rotation_x(std::f32::consts::TAU / 4.0));
+        painter.set_translation(tree.translation() * Vec3::new(1.0, 0.0, 1.0));
+        painter.circle(GUARD_RADIUS);
+    }
+}
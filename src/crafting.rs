@@ -0,0 +1,242 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+};
+use serde::Deserialize;
+
+use crate::{
+    asset_utils::CustomAssetLoaderError,
+    inventory::{Inventory, Item},
+    notification::NotificationEvent,
+    player::PlayerControllerTag,
+    ui_util::{JustClicked, UiAssets, DEFAULT_BUTTON_COLOR},
+};
+
+pub struct CraftingPlugin;
+
+impl Plugin for CraftingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<RecipesAsset>()
+            .init_asset_loader::<RecipesAssetLoader>()
+            .add_systems(Startup, (setup_recipes, setup_crafting_ui))
+            .add_systems(
+                Update,
+                (
+                    toggle_crafting_ui,
+                    spawn_recipe_buttons,
+                    craft_clicked_recipe,
+                    update_recipe_buttons,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Turns the inputs into the output, see recipes.recipes.ron.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Recipe {
+    pub id: String,
+    pub inputs: Vec<(Item, u32)>,
+    pub output: (Item, u32),
+}
+
+impl Recipe {
+    pub fn name(&self) -> String {
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|(item, count)| format!("{count}x {item}"))
+            .collect::<Vec<_>>()
+            .join(" + ");
+        format!("{inputs} -> {}x {}", self.output.1, self.output.0)
+    }
+}
+
+#[derive(Debug, Deserialize, Asset, TypePath)]
+pub struct RecipesAsset(pub Vec<Recipe>);
+
+impl RecipesAsset {
+    pub fn get(&self, id: &str) -> Option<&Recipe> {
+        self.0.iter().find(|r| r.id == id)
+    }
+}
+
+#[derive(Resource)]
+pub struct Recipes(pub Handle<RecipesAsset>);
+
+fn setup_recipes(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Recipes(asset_server.load("recipes.recipes.ron")));
+}
+
+#[derive(Component)]
+struct CraftingUiTag;
+
+#[derive(Component)]
+struct RecipeButton(String);
+
+fn setup_crafting_ui(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    commands
+        .spawn((
+            CraftingUiTag,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(10.0),
+                    top: Val::Percent(30.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    row_gap: Val::Px(5.0),
+                    flex_direction: FlexDirection::Column,
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::BLACK.with_a(0.7).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Crafting",
+                TextStyle {
+                    font: ui_assets.font.clone(),
+                    font_size: 32.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
+fn toggle_crafting_ui(
+    keyboard: Res<Input<KeyCode>>,
+    mut panel: Query<&mut Style, With<CraftingUiTag>>,
+) {
+    if !keyboard.just_pressed(KeyCode::C) {
+        return;
+    }
+    for mut style in panel.iter_mut() {
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+// the recipes are loaded after the panel is spawned
+fn spawn_recipe_buttons(
+    mut commands: Commands,
+    panel: Query<Entity, With<CraftingUiTag>>,
+    buttons: Query<(), With<RecipeButton>>,
+    recipes: Res<Recipes>,
+    recipe_assets: Res<Assets<RecipesAsset>>,
+    ui_assets: Res<UiAssets>,
+) {
+    if !buttons.is_empty() {
+        return;
+    }
+    let (Ok(panel), Some(recipes)) = (panel.get_single(), recipe_assets.get(&recipes.0)) else {
+        return;
+    };
+    commands.entity(panel).with_children(|parent| {
+        for recipe in recipes.0.iter() {
+            parent
+                .spawn((
+                    RecipeButton(recipe.id.clone()),
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(5.0)),
+                            ..default()
+                        },
+                        background_color: DEFAULT_BUTTON_COLOR.into(),
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        recipe.name(),
+                        TextStyle {
+                            font: ui_assets.font.clone(),
+                            font_size: 21.0,
+                            color: Color::BLACK,
+                        },
+                    ));
+                });
+        }
+    });
+}
+
+fn craft_clicked_recipe(
+    buttons: Query<&RecipeButton, With<JustClicked>>,
+    mut player: Query<&mut Inventory, With<PlayerControllerTag>>,
+    recipes: Res<Recipes>,
+    recipe_assets: Res<Assets<RecipesAsset>>,
+    mut notification_event: EventWriter<NotificationEvent>,
+) {
+    let (Ok(mut inventory), Some(recipes)) =
+        (player.get_single_mut(), recipe_assets.get(&recipes.0))
+    else {
+        return;
+    };
+    for button in buttons.iter() {
+        if !inventory.craft(&button.0, recipes) {
+            notification_event.send(NotificationEvent {
+                text: "Not enough items to craft that".into(),
+                show_for: 2.0,
+                color: Color::RED,
+            });
+        }
+    }
+}
+
+// recipes the player can't afford are grayed out
+fn update_recipe_buttons(
+    player: Query<&Inventory, (With<PlayerControllerTag>, Changed<Inventory>)>,
+    buttons: Query<(&RecipeButton, &Children)>,
+    mut texts: Query<&mut Text>,
+    recipes: Res<Recipes>,
+    recipe_assets: Res<Assets<RecipesAsset>>,
+) {
+    let (Ok(inventory), Some(recipes)) = (player.get_single(), recipe_assets.get(&recipes.0))
+    else {
+        return;
+    };
+    for (button, children) in buttons.iter() {
+        let Some(recipe) = recipes.get(&button.0) else {
+            continue;
+        };
+        let color = match inventory.has_items(recipe.inputs.iter().copied()) {
+            true => Color::BLACK,
+            false => Color::GRAY,
+        };
+        let mut iter = texts.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            text.sections[0].style.color = color;
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RecipesAssetLoader;
+
+impl AssetLoader for RecipesAssetLoader {
+    type Asset = RecipesAsset;
+    type Settings = ();
+    type Error = CustomAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let asset = ron::de::from_bytes::<RecipesAsset>(&bytes)?;
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["recipes.ron"]
+    }
+}
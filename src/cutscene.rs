@@ -0,0 +1,283 @@
+use std::{collections::VecDeque, f32::consts::PI};
+
+use bevy::prelude::*;
+
+use crate::{
+    camera::{CameraFocus, FocusTarget},
+    mixer::PlaySfxEvent,
+    ui_util::UiAssets,
+};
+
+// ends a cutscene that locks the input right away
+const SKIP_KEY: KeyCode = KeyCode::Space;
+
+pub struct CutscenePlugin;
+
+impl Plugin for CutscenePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayCutsceneEvent>()
+            .init_resource::<Cutscene>()
+            .add_systems(Startup, setup_skip_hint)
+            .add_systems(
+                Update,
+                (
+                    (skip_cutscene, run_cutscene, update_skip_hint).chain(),
+                    punch_scale,
+                    despawn_banners,
+                ),
+            );
+    }
+}
+
+/// One thing that happens during a cutscene, steps are executed in order.
+/// Every step except `Wait` finishes instantly.
+#[derive(Clone)]
+pub enum CutsceneStep {
+    /// pause the sequence for some seconds
+    Wait(f32),
+    /// make the follow camera look at an entity instead of the player
+    FocusCamera(Entity),
+    /// make the follow camera look at a spot, like where the next wave comes from
+    FocusPoint(Vec3),
+    /// give the follow camera back to the player
+    ReleaseCamera,
+    PlaySound {
+        path: String,
+        volume: f32,
+        speed: f32,
+    },
+    /// quickly wobble the scale of an entity, a cheap replacement for animation clips
+    PunchScale {
+        entity: Entity,
+        amount: f32,
+        duration: f32,
+    },
+    /// big text across the screen, like a boss name
+    Banner {
+        text: String,
+        color: Color,
+        show_for: f32,
+    },
+}
+
+/// Play a sequence of steps, if a cutscene is already playing the steps are queued after it.
+#[derive(Event)]
+pub struct PlayCutsceneEvent {
+    pub steps: Vec<CutsceneStep>,
+    /// ignore player input while the cutscene is playing
+    pub lock_input: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct Cutscene {
+    steps: VecDeque<CutsceneStep>,
+    wait: f32,
+    lock_input: bool,
+}
+
+impl Cutscene {
+    pub fn is_playing(&self) -> bool {
+        !self.steps.is_empty() || self.wait > 0.0
+    }
+}
+
+/// run condition, false while a cutscene is locking the input
+pub fn input_unlocked(cutscene: Res<Cutscene>) -> bool {
+    !(cutscene.lock_input && cutscene.is_playing())
+}
+
+#[derive(Component)]
+struct ScalePunch {
+    base_scale: Vec3,
+    amount: f32,
+    timer: Timer,
+}
+
+#[derive(Component)]
+struct Banner {
+    time_left: f32,
+}
+
+#[derive(Component)]
+struct SkipHint;
+
+fn setup_skip_hint(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    commands.spawn((
+        SkipHint,
+        TextBundle::from_section(
+            format!("{:?} to skip", SKIP_KEY),
+            TextStyle {
+                font: ui_assets.font.clone(),
+                font_size: 22.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(20.0),
+            bottom: Val::Px(200.0),
+            ..default()
+        }),
+        Visibility::Hidden,
+    ));
+}
+
+// only cutscenes that take the controls away can be skipped
+fn skip_cutscene(
+    keyboard: Res<Input<KeyCode>>,
+    mut cutscene: ResMut<Cutscene>,
+    mut camera_focus: ResMut<CameraFocus>,
+) {
+    if !cutscene.lock_input || !cutscene.is_playing() || !keyboard.just_pressed(SKIP_KEY) {
+        return;
+    }
+    cutscene.steps.clear();
+    cutscene.wait = 0.0;
+    camera_focus.0 = None;
+}
+
+fn update_skip_hint(cutscene: Res<Cutscene>, mut hints: Query<&mut Visibility, With<SkipHint>>) {
+    for mut visibility in hints.iter_mut() {
+        *visibility = match cutscene.lock_input && cutscene.is_playing() {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+    }
+}
+
+fn run_cutscene(
+    mut commands: Commands,
+    mut events: EventReader<PlayCutsceneEvent>,
+    mut cutscene: ResMut<Cutscene>,
+    mut camera_focus: ResMut<CameraFocus>,
+    transforms: Query<&Transform>,
+    asset_server: Res<AssetServer>,
+    ui_assets: Res<UiAssets>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
+    time: Res<Time>,
+) {
+    for event in events.read() {
+        if !cutscene.is_playing() {
+            cutscene.lock_input = false;
+        }
+        cutscene.lock_input |= event.lock_input;
+        cutscene.steps.extend(event.steps.iter().cloned());
+    }
+
+    if !cutscene.is_playing() {
+        return;
+    }
+
+    cutscene.wait -= time.delta_seconds();
+    while cutscene.wait <= 0.0 {
+        let Some(step) = cutscene.steps.pop_front() else {
+            // sequence is done, make sure the camera doesn't get stuck
+            cutscene.wait = 0.0;
+            camera_focus.0 = None;
+            break;
+        };
+        match step {
+            CutsceneStep::Wait(seconds) => cutscene.wait += seconds,
+            CutsceneStep::FocusCamera(entity) => camera_focus.0 = Some(FocusTarget::Entity(entity)),
+            CutsceneStep::FocusPoint(pos) => camera_focus.0 = Some(FocusTarget::Point(pos)),
+            CutsceneStep::ReleaseCamera => camera_focus.0 = None,
+            CutsceneStep::PlaySound {
+                path,
+                volume,
+                speed,
+            } => {
+                sfx_events.send(
+                    PlaySfxEvent::new(asset_server.load(path))
+                        .with_volume(volume)
+                        .with_speed(speed),
+                );
+            }
+            CutsceneStep::PunchScale {
+                entity,
+                amount,
+                duration,
+            } => {
+                let Ok(transform) = transforms.get(entity) else {
+                    continue;
+                };
+                let Some(mut entity_commands) = commands.get_entity(entity) else {
+                    continue;
+                };
+                entity_commands.insert(ScalePunch {
+                    base_scale: transform.scale,
+                    amount,
+                    timer: Timer::from_seconds(duration, TimerMode::Once),
+                });
+            }
+            CutsceneStep::Banner {
+                text,
+                color,
+                show_for,
+            } => {
+                commands
+                    .spawn((
+                        Banner {
+                            time_left: show_for,
+                        },
+                        NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                width: Val::Percent(100.0),
+                                top: Val::Percent(20.0),
+                                padding: UiRect::all(Val::Px(10.0)),
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            background_color: Color::BLACK.with_a(0.7).into(),
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            text,
+                            TextStyle {
+                                font: ui_assets.font.clone(),
+                                font_size: 80.0,
+                                color,
+                            },
+                        ));
+                    });
+            }
+        }
+    }
+}
+
+fn punch_scale(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Transform, &mut ScalePunch)>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, mut punch) in query.iter_mut() {
+        punch.timer.tick(time.delta());
+        let t = punch.timer.percent();
+        // a few wobbles that die out
+        let wobble = (t * PI * 4.0).sin().abs() * (1.0 - t);
+        transform.scale = punch.base_scale * (1.0 + wobble * punch.amount);
+        if punch.timer.finished() {
+            transform.scale = punch.base_scale;
+            commands.entity(entity).remove::<ScalePunch>();
+        }
+    }
+}
+
+fn despawn_banners(
+    mut commands: Commands,
+    mut banners: Query<(Entity, &mut Banner, &mut BackgroundColor)>,
+    time: Res<Time>,
+) {
+    const FADE_AT: f32 = 0.4;
+    for (entity, mut banner, mut background) in banners.iter_mut() {
+        banner.time_left -= time.delta_seconds();
+        if banner.time_left <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+        } else if banner.time_left <= FADE_AT {
+            let t = banner.time_left / FADE_AT;
+            background.0 = background.0.with_a(0.7 * t);
+        }
+    }
+}
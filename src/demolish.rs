@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+
+use crate::{
+    inventory::{Inventory, Item},
+    mixer::PlaySfxEvent,
+    placement::not_placing,
+    player::PlayerControllerTag,
+    pointer::PointerPos,
+    sapper::StructureTag,
+    selection::{Selection, SelectionActionsTag},
+    ui_util::{JustClicked, UiAssets},
+};
+
+// hold and click a building to tear it down
+const DEMOLISH_KEY: KeyCode = KeyCode::X;
+
+pub struct DemolishPlugin;
+
+impl Plugin for DemolishPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DemolishEvent>()
+            .init_resource::<DemolishRefund>()
+            // the selection panel it goes in is spawned on startup
+            .add_systems(PostStartup, setup_demolish_button)
+            .add_systems(
+                Update,
+                (
+                    demolish_on_click.run_if(not_placing),
+                    handle_demolish_button,
+                    demolish,
+                    update_demolish_button,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Everything spent on a building, including its upgrades.
+#[derive(Component, Default)]
+pub struct BuildCost(pub Vec<(Item, u32)>);
+
+impl BuildCost {
+    pub fn add(&mut self, cost: &[(Item, u32)]) {
+        for (item, count) in cost.iter() {
+            match self.0.iter_mut().find(|(i, _)| i == item) {
+                Some((_, c)) => *c += count,
+                None => self.0.push((*item, *count)),
+            }
+        }
+    }
+
+    // rounded down, so cheap buildings might give nothing back
+    fn refund(&self, fraction: f32) -> impl Iterator<Item = (Item, u32)> + '_ {
+        self.0
+            .iter()
+            .map(move |(item, count)| (*item, (*count as f32 * fraction) as u32))
+            .filter(|(_, count)| *count > 0)
+    }
+}
+
+/// Part of the build cost handed back when a building is torn down.
+#[derive(Resource)]
+pub struct DemolishRefund(pub f32);
+
+impl Default for DemolishRefund {
+    fn default() -> Self {
+        Self(0.5)
+    }
+}
+
+#[derive(Event)]
+pub struct DemolishEvent {
+    pub building: Entity,
+    // gets the refund
+    pub demolisher: Entity,
+}
+
+#[derive(Component)]
+struct DemolishButton;
+
+fn setup_demolish_button(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    actions: Query<Entity, With<SelectionActionsTag>>,
+) {
+    commands
+        .spawn((
+            DemolishButton,
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::all(Val::Px(5.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            // filled in by update_demolish_button
+            parent.spawn(TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: ui_assets.font.clone(),
+                    font_size: 21.0,
+                    color: Color::BLACK,
+                },
+            ));
+        })
+        .set_parent(actions.single());
+}
+
+fn demolish_on_click(
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    pointer: Res<PointerPos>,
+    buildings: Query<(), (With<StructureTag>, With<BuildCost>)>,
+    player: Query<Entity, With<PlayerControllerTag>>,
+    mut demolish_event: EventWriter<DemolishEvent>,
+) {
+    if !keyboard.pressed(DEMOLISH_KEY) || !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (Some(target), Ok(player)) = (pointer.pointer_on, player.get_single()) else {
+        return;
+    };
+    if buildings.contains(target.entity) {
+        demolish_event.send(DemolishEvent {
+            building: target.entity,
+            demolisher: player,
+        });
+    }
+}
+
+fn handle_demolish_button(
+    buttons: Query<(), (With<DemolishButton>, With<JustClicked>)>,
+    selection: Res<Selection>,
+    player: Query<Entity, With<PlayerControllerTag>>,
+    mut demolish_event: EventWriter<DemolishEvent>,
+) {
+    if buttons.is_empty() {
+        return;
+    }
+    if let (Some(building), Ok(player)) = (selection.0, player.get_single()) {
+        demolish_event.send(DemolishEvent {
+            building,
+            demolisher: player,
+        });
+    }
+}
+
+fn demolish(
+    mut commands: Commands,
+    mut events: EventReader<DemolishEvent>,
+    buildings: Query<(&BuildCost, &GlobalTransform), With<StructureTag>>,
+    mut inventories: Query<&mut Inventory>,
+    refund: Res<DemolishRefund>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
+) {
+    for event in events.read() {
+        let Ok((cost, transform)) = buildings.get(event.building) else {
+            continue;
+        };
+        if let Ok(mut inventory) = inventories.get_mut(event.demolisher) {
+            for (item, count) in cost.refund(refund.0) {
+                inventory.add_item(item, count);
+            }
+        }
+        // buildings storing items hand all of them over
+        if let Ok([mut stored, mut inventory]) =
+            inventories.get_many_mut([event.building, event.demolisher])
+        {
+            for (item, count) in stored.drain() {
+                inventory.add_item(item, count);
+            }
+        }
+        // the build sound slowed down
+        sfx_events.send(
+            PlaySfxEvent::new("build")
+                .with_speed(0.6)
+                .at(transform.translation()),
+        );
+        commands.entity(event.building).despawn_recursive();
+    }
+}
+
+fn update_demolish_button(
+    selection: Res<Selection>,
+    buildings: Query<&BuildCost, With<StructureTag>>,
+    refund: Res<DemolishRefund>,
+    mut button: Query<(&mut Style, &Children), With<DemolishButton>>,
+    mut texts: Query<&mut Text>,
+) {
+    let Ok((mut style, children)) = button.get_single_mut() else {
+        return;
+    };
+    let Some(cost) = selection.0.and_then(|e| buildings.get(e).ok()) else {
+        if style.display != Display::None {
+            style.display = Display::None;
+        }
+        return;
+    };
+    style.display = Display::Flex;
+    let refund = cost
+        .refund(refund.0)
+        .map(|(item, count)| format!("{count} {item}"))
+        .collect::<Vec<_>>();
+    let label = match refund.is_empty() {
+        true => String::from("Demolish"),
+        false => format!("Demolish (get back {})", refund.join(", ")),
+    };
+    let mut iter = texts.iter_many_mut(children);
+    while let Some(mut text) = iter.fetch_next() {
+        text.sections[0].value = label.clone();
+    }
+}
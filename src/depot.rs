@@ -0,0 +1,368 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_rapier3d::prelude::{Collider, CollisionGroups, Group, RigidBody};
+
+use crate::{
+    collision_groups::{COLLISION_CHARACTER, COLLISION_POINTER, COLLISION_WORLD},
+    demolish::BuildCost,
+    health::{Health, ShowHealthBar},
+    inventory::{Inventory, Item, ItemMetadata, ItemMetadataAsset},
+    knockback::KnockbackResistance,
+    mixer::PlaySfxEvent,
+    pathfinding::NavObstacle,
+    player::PlayerControllerTag,
+    sapper::StructureTag,
+    settings::Settings,
+    ui_util::{JustClicked, UiAssets},
+};
+
+pub const DEPOT_SIZE: Vec3 = Vec3::new(2.0, 1.2, 1.4);
+const DEPOT_HEALTH: i32 = 15;
+// the player can deposit and withdraw this close to a depot
+const DEPOT_RANGE: f32 = 3.5;
+
+pub struct DepotPlugin;
+
+impl Plugin for DepotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpawnDepotEvent>()
+            .init_resource::<NearbyDepot>()
+            .add_systems(Startup, (setup_depot_assets, setup_depot_panel))
+            .add_systems(
+                Update,
+                (
+                    spawn_depots,
+                    (find_nearby_depot, depot_buttons, update_depot_panel).chain(),
+                ),
+            );
+    }
+}
+
+/// Stores items apart from the player, see [`DepotStock`].
+#[derive(Component)]
+pub struct Depot;
+
+#[derive(Resource)]
+pub struct DepotAssets {
+    pub mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+#[derive(Event)]
+pub struct SpawnDepotEvent {
+    pub pos: Vec3,
+    // refunded in part when it's demolished
+    pub cost: Vec<(Item, u32)>,
+    // what it holds from the start, used when loading a save
+    pub items: Vec<(Item, u32)>,
+}
+
+// the depot the player is standing next to
+#[derive(Resource, Default)]
+struct NearbyDepot(Option<Entity>);
+
+#[derive(Component)]
+struct DepotPanelTag;
+
+#[derive(Component)]
+struct DepotPanelText;
+
+#[derive(Component, Clone, Copy)]
+enum DepotButton {
+    Deposit,
+    Withdraw,
+}
+
+/// Depot stock that purchases can draw from after the buyer's own items.
+#[derive(SystemParam)]
+pub struct DepotStock<'w, 's> {
+    depots: Query<'w, 's, Ref<'static, Inventory>, (With<Depot>, Without<PlayerControllerTag>)>,
+    settings: Res<'w, Settings>,
+}
+
+impl DepotStock<'_, '_> {
+    /// The inventory with the stock of every depot added, if depots may be drawn from.
+    pub fn combined(&self, inventory: &Inventory) -> Inventory {
+        match self.settings.general.draw_from_depots {
+            true => with_stock(inventory, self.depots.iter().map(|d| d.into_inner())),
+            false => inventory.clone(),
+        }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.settings.is_changed() || self.depots.iter().any(|d| d.is_changed())
+    }
+}
+
+/// Like [`DepotStock`], but can spend from the depots.
+#[derive(SystemParam)]
+pub struct DepotStockMut<'w, 's> {
+    depots: Query<'w, 's, &'static mut Inventory, (With<Depot>, Without<PlayerControllerTag>)>,
+    settings: Res<'w, Settings>,
+}
+
+impl DepotStockMut<'_, '_> {
+    /// See [`DepotStock::combined`].
+    pub fn combined(&self, inventory: &Inventory) -> Inventory {
+        match self.settings.general.draw_from_depots {
+            true => with_stock(inventory, self.depots.iter()),
+            false => inventory.clone(),
+        }
+    }
+
+    /// Spends from the inventory first and takes the rest from depots,
+    /// returning whether there was enough of everything.
+    pub fn spend_items(
+        &mut self,
+        inventory: &mut Inventory,
+        items: impl Iterator<Item = (Item, u32)> + Clone,
+    ) -> bool {
+        if !self.settings.general.draw_from_depots {
+            return inventory.spend_items(items);
+        }
+        if !self.combined(inventory).has_items(items.clone()) {
+            return false;
+        }
+        for (item, mut count) in items {
+            let own = count.min(inventory.get_item_count(item));
+            if own > 0 {
+                inventory.spend_item(item, own);
+                count -= own;
+            }
+            for mut depot in self.depots.iter_mut() {
+                let take = count.min(depot.get_item_count(item));
+                if take > 0 {
+                    depot.spend_item(item, take);
+                    count -= take;
+                }
+            }
+        }
+        true
+    }
+}
+
+fn with_stock<'a>(inventory: &Inventory, depots: impl Iterator<Item = &'a Inventory>) -> Inventory {
+    let mut combined = inventory.clone();
+    for depot in depots {
+        for (item, count) in depot.iter() {
+            combined.add_item(item, count);
+        }
+    }
+    combined
+}
+
+fn setup_depot_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(DepotAssets {
+        mesh: meshes.add(Mesh::from(shape::Box::new(
+            DEPOT_SIZE.x,
+            DEPOT_SIZE.y,
+            DEPOT_SIZE.z,
+        ))),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.55, 0.35, 0.2),
+            perceptual_roughness: 0.9,
+            ..default()
+        }),
+    });
+}
+
+fn setup_depot_panel(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    let text_style = |color: Color| TextStyle {
+        font: ui_assets.font.clone(),
+        font_size: 21.0,
+        color,
+    };
+    commands
+        .spawn((
+            DepotPanelTag,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(110.0),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(8.0),
+                    display: Display::None,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            // filled in by update_depot_panel
+            parent.spawn((
+                DepotPanelText,
+                TextBundle::from_section("", text_style(Color::WHITE)),
+            ));
+            for (button, label) in [
+                (DepotButton::Deposit, "Deposit all"),
+                (DepotButton::Withdraw, "Withdraw all"),
+            ] {
+                parent
+                    .spawn((
+                        button,
+                        ButtonBundle {
+                            style: Style {
+                                padding: UiRect::all(Val::Px(5.0)),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(label, text_style(Color::BLACK)));
+                    });
+            }
+        });
+}
+
+fn spawn_depots(
+    mut commands: Commands,
+    mut events: EventReader<SpawnDepotEvent>,
+    depot_assets: Res<DepotAssets>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
+) {
+    for ev in events.read() {
+        sfx_events.send(PlaySfxEvent::new("build").at(ev.pos));
+        let mut inventory = Inventory::default();
+        for (item, count) in ev.items.iter() {
+            inventory.add_item(*item, *count);
+        }
+        let half_size = DEPOT_SIZE / 2.0;
+        commands
+            .spawn((
+                Name::new("Depot"),
+                Depot,
+                inventory,
+                StructureTag,
+                BuildCost(ev.cost.clone()),
+                Health::new(DEPOT_HEALTH),
+                ShowHealthBar,
+                KnockbackResistance(1.0),
+                NavObstacle {
+                    radius: half_size.x,
+                },
+                PbrBundle {
+                    mesh: depot_assets.mesh.clone(),
+                    material: depot_assets.material.clone(),
+                    transform: Transform::from_translation(ev.pos + Vec3::Y * half_size.y),
+                    ..default()
+                },
+                RigidBody::Fixed,
+                Collider::cuboid(half_size.x, half_size.y, half_size.z),
+                // EXPLANATION: see docs/physics.txt
+                CollisionGroups::new(
+                    Group::from_bits(COLLISION_WORLD).unwrap(),
+                    Group::from_bits(COLLISION_CHARACTER | COLLISION_WORLD).unwrap(),
+                ),
+            ))
+            .with_children(|parent| {
+                // lets the depot be clicked to select it
+                parent.spawn((
+                    TransformBundle::default(),
+                    Collider::cuboid(half_size.x, half_size.y, half_size.z),
+                    CollisionGroups::new(
+                        Group::from_bits(COLLISION_POINTER).unwrap(),
+                        Group::from_bits(COLLISION_POINTER).unwrap(),
+                    ),
+                ));
+            });
+    }
+}
+
+fn find_nearby_depot(
+    player: Query<&GlobalTransform, With<PlayerControllerTag>>,
+    depots: Query<(Entity, &GlobalTransform), With<Depot>>,
+    mut nearby: ResMut<NearbyDepot>,
+) {
+    let flat = |t: &GlobalTransform| t.translation() * Vec3::new(1.0, 0.0, 1.0);
+    let found = player.get_single().ok().and_then(|player| {
+        depots
+            .iter()
+            .map(|(entity, t)| (entity, flat(t).distance_squared(flat(player))))
+            .filter(|(_, distance)| *distance < DEPOT_RANGE.powi(2))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(entity, _)| entity)
+    });
+    if nearby.0 != found {
+        nearby.0 = found;
+    }
+}
+
+fn depot_buttons(
+    buttons: Query<&DepotButton, With<JustClicked>>,
+    nearby: Res<NearbyDepot>,
+    mut player: Query<&mut Inventory, (With<PlayerControllerTag>, Without<Depot>)>,
+    mut depots: Query<&mut Inventory, With<Depot>>,
+    item_metadata: Res<ItemMetadata>,
+    metadata_assets: Res<Assets<ItemMetadataAsset>>,
+) {
+    let (Some(depot_entity), Ok(mut inventory)) = (nearby.0, player.get_single_mut()) else {
+        return;
+    };
+    let Ok(mut depot) = depots.get_mut(depot_entity) else {
+        return;
+    };
+    let metadata = metadata_assets.get(&item_metadata.0);
+    for button in buttons.iter() {
+        match button {
+            DepotButton::Deposit => {
+                for (item, count) in inventory.drain() {
+                    depot.add_item(item, count);
+                }
+            }
+            DepotButton::Withdraw => {
+                let stock: Vec<_> = depot.iter().collect();
+                for (item, count) in stock {
+                    // whatever is over the max stack stays in the depot
+                    let room = match metadata.and_then(|m| m.max_stack(item)) {
+                        Some(max) => max.saturating_sub(inventory.get_item_count(item)),
+                        None => count,
+                    };
+                    let take = count.min(room);
+                    if take > 0 && depot.spend_item(item, take) {
+                        inventory.add_item(item, take);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn update_depot_panel(
+    nearby: Res<NearbyDepot>,
+    depots: Query<&Inventory, With<Depot>>,
+    mut panel: Query<&mut Style, With<DepotPanelTag>>,
+    mut text: Query<&mut Text, With<DepotPanelText>>,
+) {
+    let Ok(mut style) = panel.get_single_mut() else {
+        return;
+    };
+    let Some(depot) = nearby.0.and_then(|e| depots.get(e).ok()) else {
+        if style.display != Display::None {
+            style.display = Display::None;
+        }
+        return;
+    };
+    style.display = Display::Flex;
+    let mut stock: Vec<_> = depot.iter().collect();
+    stock.sort_by_key(|(item, _)| *item as u32);
+    let stock = match stock.is_empty() {
+        true => String::from("empty"),
+        false => stock
+            .iter()
+            .map(|(item, count)| format!("{count} {item}"))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    let value = format!("Depot: {stock}");
+    if let Ok(mut text) = text.get_single_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Burns a mesh away from random spots, used for bodies that are dying.
+
+use bevy::{
+    asset::Asset,
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+};
+
+use crate::health::{Dying, FlashedMaterial};
+
+pub type DissolveStandardMaterial = ExtendedMaterial<StandardMaterial, DissolveMaterial>;
+
+pub struct DissolveMaterialPlugin;
+
+impl Plugin for DissolveMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<DissolveStandardMaterial>::default())
+            .add_systems(Update, (start_dissolve, update_dissolve).chain());
+    }
+}
+
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct DissolveMaterial {
+    // 0.0 untouched, 1.0 fully gone
+    #[uniform(100)]
+    pub progress: f32,
+    // glow along the edge that's burning away
+    #[uniform(101)]
+    pub edge_color: Color,
+}
+
+impl MaterialExtension for DissolveMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/dissolve_material.wgsl".into()
+    }
+
+    fn deferred_fragment_shader() -> ShaderRef {
+        "shaders/dissolve_material.wgsl".into()
+    }
+}
+
+// every mesh below a dying entity gets its own dissolving copy of its material,
+// the one from before a hit flash if it's still flashing
+fn start_dissolve(
+    mut commands: Commands,
+    dying: Query<Entity, Added<Dying>>,
+    children: Query<&Children>,
+    standard_handles: Query<(&Handle<StandardMaterial>, Option<&FlashedMaterial>)>,
+    standard_materials: Res<Assets<StandardMaterial>>,
+    mut dissolve_materials: ResMut<Assets<DissolveStandardMaterial>>,
+) {
+    for entity in dying.iter() {
+        for child in children.iter_descendants(entity) {
+            let Some(base) = standard_handles
+                .get(child)
+                .ok()
+                .map(|(handle, flashed)| flashed.map_or(handle, |f| &f.0))
+                .and_then(|h| standard_materials.get(h))
+                .cloned()
+            else {
+                continue;
+            };
+            let material = dissolve_materials.add(ExtendedMaterial {
+                base,
+                extension: DissolveMaterial {
+                    progress: 0.0,
+                    edge_color: Color::ORANGE,
+                },
+            });
+            commands
+                .entity(child)
+                .remove::<Handle<StandardMaterial>>()
+                .insert(material);
+        }
+    }
+}
+
+fn update_dissolve(
+    dying: Query<(Entity, &Dying)>,
+    children: Query<&Children>,
+    dissolve_handles: Query<&Handle<DissolveStandardMaterial>>,
+    mut dissolve_materials: ResMut<Assets<DissolveStandardMaterial>>,
+) {
+    for (entity, dying) in dying.iter() {
+        for child in children.iter_descendants(entity) {
+            let Ok(handle) = dissolve_handles.get(child) else {
+                continue;
+            };
+            if let Some(material) = dissolve_materials.get_mut(handle) {
+                material.extension.progress = dying.progress();
+            }
+        }
+    }
+}
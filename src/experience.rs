@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    health::{DeathEvent, Health},
+    menu::spawn_menu,
+    pause::{Pause, PauseReason},
+    player::{Body, Player, PlayerControllerTag, RobotTag},
+    state::{AppState, RestartRunEvent},
+    ui_util::{JustClicked, UiAssets},
+    weapon::WeaponStats,
+};
+
+// xp needed for the first level up, every level after needs XP_GROWTH times more
+const XP_BASE: f32 = 5.0;
+const XP_GROWTH: f32 = 1.4;
+const SPEED_BOOST: f32 = 1.1;
+const DAMAGE_BOOST: i32 = 1;
+const HEALTH_BOOST: i32 = 2;
+
+pub struct ExperiencePlugin;
+
+impl Plugin for ExperiencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_xp_ui)
+            .add_systems(
+                Update,
+                (
+                    gain_xp,
+                    (open_level_up, level_up_clicked).chain(),
+                    update_xp_ui,
+                )
+                    .run_if(in_state(AppState::Playing)),
+            )
+            .add_systems(OnExit(AppState::Playing), close_level_up)
+            .add_systems(
+                PreUpdate,
+                close_level_up.run_if(on_event::<RestartRunEvent>()),
+            );
+    }
+}
+
+/// Earned by killing robots, each level lets the player pick a [`LevelUpChoice`].
+#[derive(Component, Default, Clone, Serialize, Deserialize)]
+pub struct Experience {
+    pub level: u32,
+    pub xp: u32,
+    // level ups the player hasn't picked a boost for yet
+    unspent: u32,
+}
+
+impl Experience {
+    pub fn xp_to_next(&self) -> u32 {
+        (XP_BASE * XP_GROWTH.powi(self.level as i32)).round() as u32
+    }
+
+    fn add_xp(&mut self, xp: u32) {
+        self.xp += xp;
+        while self.xp >= self.xp_to_next() {
+            self.xp -= self.xp_to_next();
+            self.level += 1;
+            self.unspent += 1;
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum LevelUpChoice {
+    MovementSpeed,
+    Damage,
+    MaxHealth,
+}
+
+#[derive(Component)]
+struct LevelUpUiTag;
+
+#[derive(Component)]
+struct XpText;
+
+fn setup_xp_ui(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    commands.spawn((
+        XpText,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: ui_assets.font.clone(),
+                font_size: 24.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            ..default()
+        }),
+    ));
+}
+
+// the last hit on a robot earns the player its xp
+fn gain_xp(
+    mut events: EventReader<DeathEvent>,
+    robots: Query<&Body, With<RobotTag>>,
+    mut players: Query<&mut Experience, With<PlayerControllerTag>>,
+) {
+    for event in events.read() {
+        let (Ok(body), Ok(mut experience)) = (
+            robots.get(event.entity),
+            players.get_mut(event.killer_entity),
+        ) else {
+            continue;
+        };
+        experience.add_xp(body.xp_reward());
+    }
+}
+
+fn open_level_up(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    players: Query<&Experience, (With<PlayerControllerTag>, Changed<Experience>)>,
+    menus: Query<(), With<LevelUpUiTag>>,
+    mut pause: ResMut<Pause>,
+) {
+    let Ok(experience) = players.get_single() else {
+        return;
+    };
+    if experience.unspent == 0 || !menus.is_empty() {
+        return;
+    }
+    pause.pause(PauseReason::LevelUp);
+    spawn_menu(
+        &mut commands,
+        LevelUpUiTag,
+        &ui_assets,
+        &format!("Level {}!", experience.level + 1),
+        Color::GOLD,
+        Some("Pick a boost".to_string()),
+        &[
+            (LevelUpChoice::MovementSpeed, "+10% movement speed"),
+            (LevelUpChoice::Damage, "+1 damage"),
+            (LevelUpChoice::MaxHealth, "+2 max health"),
+        ],
+    );
+}
+
+fn level_up_clicked(
+    mut commands: Commands,
+    buttons: Query<&LevelUpChoice, With<JustClicked>>,
+    menus: Query<Entity, With<LevelUpUiTag>>,
+    mut players: Query<
+        (&mut Experience, &mut Player, &mut WeaponStats, &mut Health),
+        With<PlayerControllerTag>,
+    >,
+    mut pause: ResMut<Pause>,
+) {
+    let Some(choice) = buttons.iter().next() else {
+        return;
+    };
+    let Ok((mut experience, mut player, mut stats, mut health)) = players.get_single_mut() else {
+        return;
+    };
+    match choice {
+        LevelUpChoice::MovementSpeed => player.movement_speed *= SPEED_BOOST,
+        LevelUpChoice::Damage => stats.damage_add += DAMAGE_BOOST,
+        LevelUpChoice::MaxHealth => health.raise_max(HEALTH_BOOST),
+    }
+    // open_level_up shows the menu again for the next unspent level
+    experience.unspent = experience.unspent.saturating_sub(1);
+    for entity in menus.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    pause.resume(PauseReason::LevelUp);
+}
+
+fn close_level_up(mut commands: Commands, menus: Query<Entity, With<LevelUpUiTag>>) {
+    for entity in menus.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn update_xp_ui(
+    players: Query<&Experience, (With<PlayerControllerTag>, Changed<Experience>)>,
+    mut texts: Query<&mut Text, With<XpText>>,
+) {
+    let Ok(experience) = players.get_single() else {
+        return;
+    };
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!(
+            "Lv {}  {}/{} xp",
+            experience.level + 1,
+            experience.xp,
+            experience.xp_to_next()
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Gameplay and physics step at a fixed rate, so they behave the same at any frame rate.
+//! Whatever moves during those steps is drawn in between its last two steps.
+
+use bevy::{prelude::*, transform::TransformSystem};
+use bevy_rapier3d::prelude::*;
+
+/// seconds per gameplay and physics step
+pub const FIXED_TIMESTEP: f64 = 1.0 / 64.0;
+
+pub struct FixedStepPlugin;
+
+impl Plugin for FixedStepPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Time::<Fixed>::from_seconds(FIXED_TIMESTEP))
+            .configure_sets(
+                FixedUpdate,
+                (FixedStepSet::Restore, FixedStepSet::Simulate)
+                    .chain()
+                    .before(PhysicsSet::SyncBackend),
+            )
+            .configure_sets(
+                FixedUpdate,
+                FixedStepSet::Record.after(PhysicsSet::Writeback),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    restore_transforms.in_set(FixedStepSet::Restore),
+                    record_transforms.in_set(FixedStepSet::Record),
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                interpolate_transforms.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Gameplay systems that used to run every frame go in Simulate, physics steps right after.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FixedStepSet {
+    // puts interpolated transforms back where the last step left them
+    Restore,
+    Simulate,
+    // remembers where the step left everything, after physics wrote back
+    Record,
+}
+
+/// Drawn between where the last two fixed steps put it, instead of jumping once per step.
+#[derive(Component)]
+pub struct InterpolatedTransform {
+    previous: Transform,
+    current: Transform,
+    // what Transform was last set to here, anything else means it was moved outside the steps
+    last: Transform,
+}
+
+impl InterpolatedTransform {
+    pub fn new(transform: Transform) -> Self {
+        Self {
+            previous: transform,
+            current: transform,
+            last: transform,
+        }
+    }
+
+    // a teleport, nothing to interpolate from
+    fn snap_to(&mut self, transform: Transform) {
+        *self = Self::new(transform);
+    }
+}
+
+fn restore_transforms(mut query: Query<(&mut Transform, &mut InterpolatedTransform)>) {
+    for (mut transform, mut interpolated) in query.iter_mut() {
+        if *transform != interpolated.last {
+            interpolated.snap_to(*transform);
+        } else if *transform != interpolated.current {
+            *transform = interpolated.current;
+        }
+        interpolated.previous = interpolated.current;
+    }
+}
+
+fn record_transforms(mut query: Query<(&Transform, &mut InterpolatedTransform)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.current = *transform;
+        interpolated.last = *transform;
+    }
+}
+
+fn interpolate_transforms(
+    mut query: Query<(&mut Transform, &mut InterpolatedTransform)>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    let t = fixed_time.overstep_percentage();
+    for (mut transform, mut interpolated) in query.iter_mut() {
+        if *transform != interpolated.last {
+            interpolated.snap_to(*transform);
+            continue;
+        }
+        let (previous, current) = (interpolated.previous, interpolated.current);
+        *transform = Transform {
+            translation: previous.translation.lerp(current.translation, t),
+            rotation: previous.rotation.slerp(current.rotation, t),
+            scale: previous.scale.lerp(current.scale, t),
+        };
+        interpolated.last = *transform;
+    }
+}
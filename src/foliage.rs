@@ -1,9 +1,29 @@
-use bevy::{math::vec3, prelude::*};
+use bevy::{
+    gltf::{Gltf, GltfMesh, GltfNode},
+    math::vec3,
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_resource::PrimitiveTopology,
+    },
+    utils::HashMap,
+};
 use bevy_rapier3d::prelude::*;
 use bracket_noise::prelude::{FastNoise, NoiseType};
 use rand::Rng;
 
-use crate::map::MAP_SIZE_HALF;
+use crate::{
+    map::{CurrentMap, MapTerrain},
+    map_gen::{self, Biome, MapLayout},
+    player::PlayerControllerTag,
+};
+
+// meters per side of the square areas whose foliage is merged into one mesh per model
+const CHUNK_SIZE: f32 = 12.0;
+// chunks closer to the player than this are spawned as separate foliage instead
+const DYNAMIC_RADIUS: f32 = 4.0;
+// and merged back once the player is this much further away
+const MERGE_MARGIN: f32 = 2.0;
 
 #[derive(Event)]
 pub struct SpawnFoliageEvent {
@@ -20,19 +40,64 @@ pub struct TreeTrunkTag;
 #[derive(Resource)]
 pub struct TreeModels(Vec<Handle<Scene>>);
 
+// the same models as TreeModels, their meshes are what chunks are merged from
+#[derive(Resource)]
+struct FoliageGltfs(Vec<Handle<Gltf>>);
+
+#[derive(Clone, Copy)]
+struct FoliageInstance {
+    // index into TreeModels and FoliageGltfs
+    model: usize,
+    transform: Transform,
+}
+
+/// All foliage in one square of the map, drawn as a merged mesh per model while the player is away.
+#[derive(Component)]
+pub struct FoliageChunk {
+    coord: IVec2,
+    instances: Vec<FoliageInstance>,
+    // the merged meshes are up to date with instances
+    batched: bool,
+    // the player is close, the instances are separate entities
+    broken_up: bool,
+}
+
+impl FoliageChunk {
+    fn distance_to(&self, pos: Vec2) -> f32 {
+        let min = self.coord.as_vec2() * CHUNK_SIZE;
+        pos.distance(pos.clamp(min, min + CHUNK_SIZE))
+    }
+}
+
+// one merged mesh of a chunk
+#[derive(Component)]
+struct FoliageBatch;
+
 pub struct FoliagePlugin;
 
 impl Plugin for FoliagePlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SpawnFoliageEvent>()
             .add_systems(Startup, setup_tree_resources)
-            .add_systems(Startup, setup_foliage)
-            .add_systems(Update, (spawn_foliage,));
+            .add_systems(
+                Update,
+                setup_foliage
+                    .after(map_gen::generate_layout)
+                    .run_if(resource_changed::<CurrentMap>()),
+            )
+            .add_systems(
+                Update,
+                (spawn_foliage, break_up_near_chunks, batch_foliage_chunks).chain(),
+            );
     }
 }
 
-fn setup_foliage(mut foliage_events: EventWriter<SpawnFoliageEvent>) {
-    let map_size_i = MAP_SIZE_HALF as i32;
+fn setup_foliage(
+    mut foliage_events: EventWriter<SpawnFoliageEvent>,
+    layout: Res<MapLayout>,
+    map: Res<CurrentMap>,
+) {
+    let map_size_i = map.0.size_half.as_ivec2();
 
     let mut noise = FastNoise::seeded(1);
     noise.set_noise_type(NoiseType::Simplex);
@@ -40,46 +105,255 @@ fn setup_foliage(mut foliage_events: EventWriter<SpawnFoliageEvent>) {
 
     let mut rng = rand::thread_rng();
 
-    for z in (-map_size_i + 1)..(map_size_i - 1) {
-        for x in (-map_size_i + 1)..(map_size_i - 1) {
+    for z in (-map_size_i.y + 1)..(map_size_i.y - 1) {
+        for x in (-map_size_i.x + 1)..(map_size_i.x - 1) {
             let noise = noise.get_noise(z as f32, x as f32);
-            // 70% chance to discard randomly
-            let random_discard = rng.gen_range(0.0..1.0) < 0.7;
+            let pos = vec3(x as f32, 0.0, z as f32);
+            // 70% chance to discard randomly, bare dirt is sparser and sand has none
+            let discard_chance = match layout.biome_at(pos) {
+                Biome::Grass => 0.7,
+                Biome::Dirt => 0.85,
+                Biome::Sand => 1.0,
+            };
+            let random_discard = rng.gen_range(0.0..1.0) < discard_chance;
 
-            if noise > 0.4 && !random_discard {
-                foliage_events.send(SpawnFoliageEvent {
-                    pos: vec3(x as f32, 0.0, z as f32),
-                });
+            if noise > 0.4 && !random_discard && !layout.is_blocked(pos) {
+                foliage_events.send(SpawnFoliageEvent { pos });
             }
         }
     }
 }
 
+// foliage only picks its look here, the chunk it lands in decides how it's drawn
 fn spawn_foliage(
     mut events: EventReader<SpawnFoliageEvent>,
     mut commands: Commands,
+    mut chunks: Query<&mut FoliageChunk>,
     tree_models: Res<TreeModels>,
 ) {
+    let mut existing = chunks
+        .iter_mut()
+        .map(|chunk| (chunk.coord, chunk))
+        .collect::<HashMap<_, _>>();
+    let mut new_chunks = HashMap::<IVec2, Vec<FoliageInstance>>::default();
     for event in events.read() {
         let mut rng = rand::thread_rng();
-        let model_handle = tree_models.0[rng.gen_range(0..tree_models.0.len())].clone();
-        let scale = rng.gen_range(2.5..=3.5);
-
+        let instance = FoliageInstance {
+            model: rng.gen_range(0..tree_models.0.len()),
+            transform: Transform::from_translation(event.pos)
+                .with_scale(Vec3::splat(rng.gen_range(2.5..=3.5))),
+        };
+        let coord = (event.pos.xz() / CHUNK_SIZE).floor().as_ivec2();
+        match existing.get_mut(&coord) {
+            Some(chunk) => {
+                chunk.instances.push(instance);
+                chunk.batched = false;
+            }
+            None => new_chunks.entry(coord).or_default().push(instance),
+        }
+    }
+    for (coord, instances) in new_chunks {
         commands.spawn((
-            Name::new("foliage"),
-            TreeRootTag,
-            RigidBody::Fixed,
-            SceneBundle {
-                scene: model_handle,
-                transform: Transform::from_translation(event.pos).with_scale(Vec3::splat(scale)),
-                ..default()
+            Name::new("foliage chunk"),
+            FoliageChunk {
+                coord,
+                instances,
+                batched: false,
+                broken_up: false,
             },
+            // goes away with the map it was spawned for
+            MapTerrain,
+            SpatialBundle::default(),
+        ));
+    }
+}
+
+// (re)builds the merged meshes of chunks that changed, once the models are loaded
+fn batch_foliage_chunks(
+    mut commands: Commands,
+    mut chunks: Query<(Entity, &mut FoliageChunk, Option<&Children>)>,
+    batches: Query<(), With<FoliageBatch>>,
+    foliage_gltfs: Res<FoliageGltfs>,
+    gltfs: Res<Assets<Gltf>>,
+    gltf_nodes: Res<Assets<GltfNode>>,
+    gltf_meshes: Res<Assets<GltfMesh>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if chunks.iter().all(|(_, chunk, _)| chunk.batched) {
+        return;
+    }
+    let mut parts = Vec::with_capacity(foliage_gltfs.0.len());
+    for handle in foliage_gltfs.0.iter() {
+        let Some(node) = gltfs
+            .get(handle)
+            .and_then(|gltf| gltf.nodes.first())
+            .and_then(|node| gltf_nodes.get(node))
+        else {
+            return;
+        };
+        let Some(primitive) = node
+            .mesh
+            .as_ref()
+            .and_then(|mesh| gltf_meshes.get(mesh))
+            .and_then(|mesh| mesh.primitives.first())
+        else {
+            return;
+        };
+        if !meshes.contains(&primitive.mesh) {
+            return;
+        }
+        parts.push((
+            node.transform,
+            primitive.mesh.clone(),
+            primitive.material.clone().unwrap_or_default(),
         ));
     }
+
+    for (entity, mut chunk, children) in chunks.iter_mut().filter(|(_, c, _)| !c.batched) {
+        for child in children.iter().flat_map(|c| c.iter()) {
+            if batches.contains(*child) {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+        let visibility = match chunk.broken_up {
+            true => Visibility::Hidden,
+            false => Visibility::Inherited,
+        };
+        for (model, (node_transform, mesh, material)) in parts.iter().enumerate() {
+            let Some(mesh) = meshes.get(mesh) else {
+                continue;
+            };
+            let transforms = chunk
+                .instances
+                .iter()
+                .filter(|i| i.model == model)
+                .map(|i| i.transform.mul_transform(*node_transform))
+                .collect::<Vec<_>>();
+            if transforms.is_empty() {
+                continue;
+            }
+            let Some(merged) = merge_instances(mesh, &transforms) else {
+                continue;
+            };
+            let batch = commands
+                .spawn((
+                    FoliageBatch,
+                    PbrBundle {
+                        mesh: meshes.add(merged),
+                        material: material.clone(),
+                        visibility,
+                        ..default()
+                    },
+                ))
+                .id();
+            commands.entity(entity).add_child(batch);
+        }
+        chunk.batched = true;
+    }
+}
+
+// copies of the mesh placed at each transform, as a single mesh
+fn merge_instances(mesh: &Mesh, transforms: &[Transform]) -> Option<Mesh> {
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return None;
+    };
+    let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+    else {
+        return None;
+    };
+    let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_0) else {
+        return None;
+    };
+    let indices = match mesh.indices() {
+        Some(indices) => indices.iter().map(|i| i as u32).collect::<Vec<_>>(),
+        None => (0..positions.len() as u32).collect(),
+    };
+
+    let vertex_count = positions.len() * transforms.len();
+    let mut merged_positions = Vec::with_capacity(vertex_count);
+    let mut merged_normals = Vec::with_capacity(vertex_count);
+    let mut merged_uvs = Vec::with_capacity(vertex_count);
+    let mut merged_indices = Vec::with_capacity(indices.len() * transforms.len());
+    for transform in transforms {
+        let offset = merged_positions.len() as u32;
+        let matrix = transform.compute_matrix();
+        merged_positions.extend(
+            positions
+                .iter()
+                .map(|p| matrix.transform_point3(Vec3::from(*p)).to_array()),
+        );
+        // foliage is scaled evenly, so rotating the normals is enough
+        merged_normals.extend(
+            normals
+                .iter()
+                .map(|n| (transform.rotation * Vec3::from(*n)).to_array()),
+        );
+        merged_uvs.extend_from_slice(uvs);
+        merged_indices.extend(indices.iter().map(|i| i + offset));
+    }
+
+    let mut merged = Mesh::new(PrimitiveTopology::TriangleList);
+    merged.insert_attribute(Mesh::ATTRIBUTE_POSITION, merged_positions);
+    merged.insert_attribute(Mesh::ATTRIBUTE_NORMAL, merged_normals);
+    merged.insert_attribute(Mesh::ATTRIBUTE_UV_0, merged_uvs);
+    merged.set_indices(Some(Indices::U32(merged_indices)));
+    Some(merged)
+}
+
+// swaps the merged meshes around the player for separate foliage, and back once they leave
+fn break_up_near_chunks(
+    mut commands: Commands,
+    mut chunks: Query<(Entity, &mut FoliageChunk, Option<&Children>)>,
+    mut batches: Query<&mut Visibility, With<FoliageBatch>>,
+    separate: Query<(), With<TreeRootTag>>,
+    player: Query<&GlobalTransform, With<PlayerControllerTag>>,
+    tree_models: Res<TreeModels>,
+) {
+    let player_pos = player.get_single().ok().map(|t| t.translation().xz());
+    for (entity, mut chunk, children) in chunks.iter_mut() {
+        let distance = player_pos.map_or(f32::MAX, |p| chunk.distance_to(p));
+        let break_up = match chunk.broken_up {
+            true => distance < DYNAMIC_RADIUS + MERGE_MARGIN,
+            false => distance < DYNAMIC_RADIUS,
+        };
+        if break_up == chunk.broken_up {
+            continue;
+        }
+        chunk.broken_up = break_up;
+        for child in children.iter().flat_map(|c| c.iter()) {
+            if let Ok(mut visibility) = batches.get_mut(*child) {
+                *visibility = match break_up {
+                    true => Visibility::Hidden,
+                    false => Visibility::Inherited,
+                };
+            } else if !break_up && separate.contains(*child) {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+        if !break_up {
+            continue;
+        }
+        commands.entity(entity).with_children(|parent| {
+            for instance in chunk.instances.iter() {
+                parent.spawn((
+                    Name::new("foliage"),
+                    TreeRootTag,
+                    RigidBody::Fixed,
+                    SceneBundle {
+                        scene: tree_models.0[instance.model].clone(),
+                        transform: instance.transform,
+                        ..default()
+                    },
+                ));
+            }
+        });
+    }
 }
 
 fn setup_tree_resources(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let models = vec![
+    let names = [
         "foliage_0",
         "foliage_1",
         "foliage_2",
@@ -87,9 +361,15 @@ fn setup_tree_resources(mut commands: Commands, asset_server: Res<AssetServer>)
         "foliage_4",
         "foliage_5",
         "foliage_6",
-    ]
-    .iter()
-    .map(|name| asset_server.load(format!("models/foliage/{}.gltf#Scene0", name)))
-    .collect::<Vec<_>>();
+    ];
+    let models = names
+        .iter()
+        .map(|name| asset_server.load(format!("models/foliage/{}.gltf#Scene0", name)))
+        .collect::<Vec<_>>();
     commands.insert_resource(TreeModels(models));
+    let gltfs = names
+        .iter()
+        .map(|name| asset_server.load(format!("models/foliage/{}.gltf", name)))
+        .collect::<Vec<_>>();
+    commands.insert_resource(FoliageGltfs(gltfs));
 }
@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+
+use crate::{health::Health, settings::Settings, tree::TreeTrunkTag, ui_util::UiAssets};
+
+// the run is lost once every tree is gone, warn when this few are left
+const WARNING_TREES: usize = 5;
+const BAR_WIDTH: f32 = 200.0;
+
+pub struct ForestHudPlugin;
+
+impl Plugin for ForestHudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_forest_hud)
+            .add_systems(Update, update_forest_hud);
+    }
+}
+
+#[derive(Component)]
+struct ForestHudTag;
+
+#[derive(Component)]
+struct TreeCountText;
+
+// the filled part of the forest health bar
+#[derive(Component)]
+struct ForestHealthFill;
+
+fn setup_forest_hud(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    commands
+        .spawn((
+            ForestHudTag,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0),
+                    width: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(4.0),
+                    display: Display::None,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TreeCountText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: ui_assets.font.clone(),
+                        font_size: 26.0,
+                        color: Color::WHITE,
+                    },
+                ),
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(BAR_WIDTH),
+                        height: Val::Px(10.0),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        ForestHealthFill,
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(100.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::GREEN.into(),
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+fn update_forest_hud(
+    trees: Query<&Health, With<TreeTrunkTag>>,
+    mut hud: Query<&mut Style, (With<ForestHudTag>, Without<ForestHealthFill>)>,
+    mut count_text: Query<&mut Text, With<TreeCountText>>,
+    mut fill: Query<(&mut Style, &mut BackgroundColor), With<ForestHealthFill>>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let Ok(mut hud_style) = hud.get_single_mut() else {
+        return;
+    };
+    let count = trees.iter().count();
+    // nothing to protect outside of a run
+    let display = match count {
+        0 => Display::None,
+        _ => Display::Flex,
+    };
+    if hud_style.display != display {
+        hud_style.display = display;
+    }
+    if count == 0 {
+        return;
+    }
+    let (current, max) = trees
+        .iter()
+        .fold((0, 0), |(c, m), h| (c + h.current.max(0), m + h.max));
+    let palette = &settings.accessibility.palette;
+    let warning = count <= WARNING_TREES;
+    if let Ok(mut text) = count_text.get_single_mut() {
+        text.sections[0].value = match warning {
+            true => format!("Trees: {count} - protect them!"),
+            false => format!("Trees: {count}"),
+        };
+        text.sections[0].style.color = match warning {
+            true => {
+                let pulse = (time.elapsed_seconds() * 6.0).sin() * 0.5 + 0.5;
+                let red = palette.remap(Color::RED);
+                Color::rgb(
+                    1.0 + (red.r() - 1.0) * pulse,
+                    1.0 + (red.g() - 1.0) * pulse,
+                    1.0 + (red.b() - 1.0) * pulse,
+                )
+            }
+            false => Color::WHITE,
+        };
+    }
+    if let Ok((mut style, mut color)) = fill.get_single_mut() {
+        style.width = Val::Percent(current as f32 / max.max(1) as f32 * 100.0);
+        *color = match warning {
+            true => palette.remap(Color::RED),
+            false => palette.remap(Color::GREEN),
+        }
+        .into();
+    }
+}
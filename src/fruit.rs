@@ -0,0 +1,224 @@
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+
+use crate::{
+    health::FlashedMaterial,
+    item_pickups::{DropSource, SpawnDropsEvent},
+    weather::CurrentWeather,
+};
+
+// a tree holds at most this many fruits at once
+const MAX_FRUITS: usize = 3;
+// seconds between new fruits showing up on a tree
+const FRUIT_INTERVAL: std::ops::Range<f32> = 5.0..20.0;
+// fruits grow, then hang ripe for a while before they fall off
+const FRUIT_GROW_SECONDS: f32 = 12.0;
+const FRUIT_RIPE_SECONDS: f32 = 8.0;
+const FRUIT_RADIUS: f32 = 0.15;
+
+pub struct FruitPlugin;
+
+impl Plugin for FruitPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ShakeTreeEvent>()
+            .add_systems(Startup, setup_fruit_assets)
+            .add_systems(
+                Update,
+                (grow_new_fruits, ripen_fruits, shake_trees, drop_fruits).chain(),
+            );
+    }
+}
+
+/// Grows fruits on its branches, which drop from the source's table once ripe.
+#[derive(Component)]
+pub struct FruitTree {
+    source: DropSource,
+    timer: Timer,
+}
+
+impl FruitTree {
+    pub fn new(source: DropSource, first_in: f32) -> Self {
+        Self {
+            source,
+            timer: Timer::from_seconds(first_in, TimerMode::Once),
+        }
+    }
+
+    // seconds until the next fruit shows up
+    pub fn time_left(&self) -> f32 {
+        self.timer.remaining_secs()
+    }
+}
+
+/// Hanging from a [`FruitTree`], drops off once it has been ripe for a while.
+#[derive(Component)]
+pub struct Fruit {
+    source: DropSource,
+    grow: Timer,
+    ripe: Timer,
+    // undoes the tree's scale, so every fruit is a round ball
+    scale: Vec3,
+}
+
+impl Fruit {
+    pub fn is_ripe(&self) -> bool {
+        self.grow.finished()
+    }
+}
+
+/// Knocks a tree's ripe fruits down early, sent instead of damage when a monkey hits a tree
+/// that has some, see [`has_ripe_fruit`].
+#[derive(Event)]
+pub struct ShakeTreeEvent {
+    pub tree_entity: Entity,
+}
+
+pub fn has_ripe_fruit(children: &Children, fruits: &Query<&Fruit>) -> bool {
+    fruits.iter_many(children).any(Fruit::is_ripe)
+}
+
+#[derive(Resource)]
+struct FruitAssets {
+    mesh: Handle<Mesh>,
+    unripe: Handle<StandardMaterial>,
+    banana: Handle<StandardMaterial>,
+    apple: Handle<StandardMaterial>,
+}
+
+impl FruitAssets {
+    fn ripe(&self, source: DropSource) -> Handle<StandardMaterial> {
+        match source {
+            DropSource::AppleTree => self.apple.clone(),
+            _ => self.banana.clone(),
+        }
+    }
+}
+
+fn setup_fruit_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(FruitAssets {
+        mesh: meshes.add(
+            Mesh::try_from(shape::Icosphere {
+                radius: FRUIT_RADIUS,
+                subdivisions: 2,
+            })
+            .unwrap(),
+        ),
+        unripe: materials.add(Color::rgb(0.4, 0.7, 0.2).into()),
+        banana: materials.add(Color::rgb(1.0, 0.85, 0.1).into()),
+        apple: materials.add(Color::rgb(0.85, 0.1, 0.1).into()),
+    });
+}
+
+fn grow_new_fruits(
+    mut commands: Commands,
+    mut trees: Query<(Entity, &mut FruitTree, &Transform, Option<&Children>)>,
+    fruits: Query<(), With<Fruit>>,
+    fruit_assets: Res<FruitAssets>,
+    time: Res<Time>,
+    weather: Res<CurrentWeather>,
+) {
+    let mut rng = thread_rng();
+    let delta = time.delta().mul_f32(weather.weather.fruit_speed_mul());
+    for (entity, mut tree, transform, children) in trees.iter_mut() {
+        if !tree.timer.tick(delta).just_finished() {
+            continue;
+        }
+        tree.timer = Timer::from_seconds(rng.gen_range(FRUIT_INTERVAL), TimerMode::Once);
+        let count = children.map_or(0, |c| c.iter().filter(|e| fruits.contains(**e)).count());
+        if count >= MAX_FRUITS {
+            continue;
+        }
+        // somewhere in the crown, picked in world units then moved into the scaled tree's space
+        let offset = Vec3::new(
+            rng.gen_range(-0.6..0.6),
+            rng.gen_range(1.8..2.8),
+            rng.gen_range(-0.6..0.6),
+        );
+        let scale = transform.scale.recip();
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                Fruit {
+                    source: tree.source,
+                    grow: Timer::from_seconds(FRUIT_GROW_SECONDS, TimerMode::Once),
+                    ripe: Timer::from_seconds(FRUIT_RIPE_SECONDS, TimerMode::Once),
+                    scale,
+                },
+                PbrBundle {
+                    mesh: fruit_assets.mesh.clone(),
+                    material: fruit_assets.unripe.clone(),
+                    transform: Transform::from_translation(offset * scale).with_scale(Vec3::ZERO),
+                    ..default()
+                },
+            ));
+        });
+    }
+}
+
+// fruits swell up while growing, then change color once ripe.
+// a fruit ripening while its tree flashes gets the ripe color once the flash ends
+fn ripen_fruits(
+    mut fruits: Query<(
+        &mut Fruit,
+        &mut Transform,
+        &mut Handle<StandardMaterial>,
+        Option<&mut FlashedMaterial>,
+    )>,
+    fruit_assets: Res<FruitAssets>,
+    time: Res<Time>,
+) {
+    for (mut fruit, mut transform, mut material, flashed) in fruits.iter_mut() {
+        if !fruit.grow.finished() {
+            fruit.grow.tick(time.delta());
+            transform.scale = fruit.scale * fruit.grow.percent();
+            if fruit.grow.just_finished() {
+                let ripe = fruit_assets.ripe(fruit.source);
+                match flashed {
+                    Some(mut flashed) => flashed.0 = ripe,
+                    None => *material = ripe,
+                }
+            }
+            continue;
+        }
+        fruit.ripe.tick(time.delta());
+    }
+}
+
+fn shake_trees(
+    mut events: EventReader<ShakeTreeEvent>,
+    trees: Query<&Children, With<FruitTree>>,
+    mut fruits: Query<&mut Fruit>,
+) {
+    for event in events.read() {
+        let Ok(children) = trees.get(event.tree_entity) else {
+            continue;
+        };
+        let mut iter = fruits.iter_many_mut(children);
+        while let Some(mut fruit) = iter.fetch_next() {
+            if fruit.is_ripe() {
+                let duration = fruit.ripe.duration();
+                fruit.ripe.set_elapsed(duration);
+            }
+        }
+    }
+}
+
+fn drop_fruits(
+    mut commands: Commands,
+    fruits: Query<(Entity, &Fruit, &GlobalTransform)>,
+    mut spawn_drops_events: EventWriter<SpawnDropsEvent>,
+) {
+    for (entity, fruit, transform) in fruits.iter() {
+        if !fruit.ripe.finished() {
+            continue;
+        }
+        spawn_drops_events.send(SpawnDropsEvent {
+            source: fruit.source,
+            pos: transform.translation(),
+        });
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,262 @@
+use bevy::prelude::*;
+
+use crate::{
+    health::DeathEvent,
+    pause::{Pause, PauseReason},
+    player::Body,
+    state::{AppState, RestartRunEvent},
+    ui_util::{ButtonColor, JustClicked, UiAssets, DEFAULT_BUTTON_COLOR},
+};
+
+const SPEEDS: [f32; 3] = [1.0, 2.0, 4.0];
+const SPEED_KEYS: [KeyCode; 3] = [KeyCode::F2, KeyCode::F3, KeyCode::F4];
+const PAUSE_KEY: KeyCode = KeyCode::P;
+// how slow the world goes right after the boss dies
+const SLOW_MOTION_SPEED: f32 = 0.2;
+// real seconds of slow motion, the last half eases back to the picked speed
+const SLOW_MOTION_SECONDS: f32 = 2.5;
+const ACTIVE_BUTTON_COLOR: Color = Color::rgba(0.6, 1.0, 0.6, 0.8);
+
+pub struct GameSpeedPlugin;
+
+impl Plugin for GameSpeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameSpeed>()
+            .add_systems(Startup, setup_speed_buttons)
+            .add_systems(OnEnter(AppState::Playing), reset_game_speed)
+            .add_systems(OnExit(AppState::Playing), reset_game_speed)
+            .add_systems(
+                PreUpdate,
+                reset_game_speed.run_if(on_event::<RestartRunEvent>()),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        speed_hotkeys,
+                        speed_button_clicked,
+                        slow_motion_on_boss_kill,
+                    )
+                        .run_if(in_state(AppState::Playing)),
+                    apply_game_speed,
+                    update_speed_buttons,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// How fast the game runs, scales virtual time.
+/// Everything on Res<Time> follows it, timers and cooldowns as well as the fixed steps physics runs in.
+#[derive(Resource)]
+pub struct GameSpeed {
+    // the speed picked with the buttons or hotkeys
+    pub speed: f32,
+    // counts down in real time, slows the world down while running
+    slow_motion: Option<Timer>,
+}
+
+impl Default for GameSpeed {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            slow_motion: None,
+        }
+    }
+}
+
+impl GameSpeed {
+    pub fn slow_motion(&mut self) {
+        self.slow_motion = Some(Timer::from_seconds(SLOW_MOTION_SECONDS, TimerMode::Once));
+    }
+
+    // the picked speed, slowed down while slow motion runs
+    fn effective_speed(&self) -> f32 {
+        let Some(timer) = &self.slow_motion else {
+            return self.speed;
+        };
+        // holds the slowest speed for the first half, then eases back
+        let t = ((timer.percent() - 0.5) * 2.0).clamp(0.0, 1.0);
+        SLOW_MOTION_SPEED + (self.speed - SLOW_MOTION_SPEED) * t * t
+    }
+}
+
+#[derive(Component)]
+struct SpeedControlsTag;
+
+#[derive(Component, Clone, Copy, PartialEq)]
+enum SpeedButton {
+    Pause,
+    // index into SPEEDS
+    Speed(usize),
+}
+
+fn setup_speed_buttons(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    let buttons = std::iter::once((SpeedButton::Pause, "Pause".to_string())).chain(
+        SPEEDS
+            .iter()
+            .enumerate()
+            .map(|(i, speed)| (SpeedButton::Speed(i), format!("{speed}x"))),
+    );
+    commands
+        .spawn((
+            SpeedControlsTag,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    column_gap: Val::Px(4.0),
+                    display: Display::None,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            for (button, label) in buttons {
+                parent
+                    .spawn((
+                        button,
+                        ButtonColor(DEFAULT_BUTTON_COLOR),
+                        ButtonBundle {
+                            style: Style {
+                                padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                                ..default()
+                            },
+                            background_color: DEFAULT_BUTTON_COLOR.into(),
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font: ui_assets.font.clone(),
+                                font_size: 21.0,
+                                color: Color::BLACK,
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn speed_hotkeys(
+    keyboard: Res<Input<KeyCode>>,
+    mut game_speed: ResMut<GameSpeed>,
+    mut pause: ResMut<Pause>,
+) {
+    if keyboard.just_pressed(PAUSE_KEY) {
+        toggle_speed_pause(&mut pause);
+    }
+    for (key, speed) in SPEED_KEYS.iter().zip(SPEEDS) {
+        if keyboard.just_pressed(*key) {
+            set_speed(&mut game_speed, &mut pause, speed);
+        }
+    }
+}
+
+fn speed_button_clicked(
+    buttons: Query<&SpeedButton, With<JustClicked>>,
+    mut game_speed: ResMut<GameSpeed>,
+    mut pause: ResMut<Pause>,
+) {
+    for button in buttons.iter() {
+        match button {
+            SpeedButton::Pause => toggle_speed_pause(&mut pause),
+            SpeedButton::Speed(i) => set_speed(&mut game_speed, &mut pause, SPEEDS[*i]),
+        }
+    }
+}
+
+fn toggle_speed_pause(pause: &mut Pause) {
+    match pause.is_paused_by(PauseReason::SpeedControls) {
+        true => pause.resume(PauseReason::SpeedControls),
+        false => pause.pause(PauseReason::SpeedControls),
+    }
+}
+
+// picking a speed also unpauses, like any speed control does
+fn set_speed(game_speed: &mut GameSpeed, pause: &mut Pause, speed: f32) {
+    game_speed.speed = speed;
+    pause.resume(PauseReason::SpeedControls);
+}
+
+fn slow_motion_on_boss_kill(
+    mut events: EventReader<DeathEvent>,
+    bodies: Query<&Body>,
+    mut game_speed: ResMut<GameSpeed>,
+) {
+    if events
+        .read()
+        .any(|e| matches!(bodies.get(e.entity), Ok(Body::Boss)))
+    {
+        game_speed.slow_motion();
+    }
+}
+
+// slow motion ticks in real time, the virtual time it slows down would drag it out
+fn apply_game_speed(
+    mut game_speed: ResMut<GameSpeed>,
+    mut time: ResMut<Time<Virtual>>,
+    real_time: Res<Time<Real>>,
+) {
+    if let Some(timer) = &mut game_speed.slow_motion {
+        if timer.tick(real_time.delta()).finished() {
+            game_speed.slow_motion = None;
+        }
+    }
+    let speed = game_speed.effective_speed();
+    if time.relative_speed() != speed {
+        time.set_relative_speed(speed);
+    }
+}
+
+fn reset_game_speed(mut game_speed: ResMut<GameSpeed>, mut pause: ResMut<Pause>) {
+    *game_speed = GameSpeed::default();
+    pause.resume(PauseReason::SpeedControls);
+}
+
+// only shown during a run, the picked speed is highlighted
+fn update_speed_buttons(
+    game_speed: Res<GameSpeed>,
+    pause: Res<Pause>,
+    state: Res<State<AppState>>,
+    mut controls: Query<&mut Style, With<SpeedControlsTag>>,
+    mut buttons: Query<(
+        &SpeedButton,
+        &Interaction,
+        &mut ButtonColor,
+        &mut BackgroundColor,
+    )>,
+) {
+    let Ok(mut style) = controls.get_single_mut() else {
+        return;
+    };
+    let display = match *state.get() == AppState::Playing {
+        true => Display::Flex,
+        false => Display::None,
+    };
+    if style.display != display {
+        style.display = display;
+    }
+    if !game_speed.is_changed() && !pause.is_changed() {
+        return;
+    }
+    let paused = pause.is_paused_by(PauseReason::SpeedControls);
+    for (button, interaction, mut button_color, mut background) in buttons.iter_mut() {
+        let active = match button {
+            SpeedButton::Pause => paused,
+            SpeedButton::Speed(i) => !paused && SPEEDS[*i] == game_speed.speed,
+        };
+        button_color.0 = match active {
+            true => ACTIVE_BUTTON_COLOR,
+            false => DEFAULT_BUTTON_COLOR,
+        };
+        // hovered and pressed buttons keep their color until the mouse leaves
+        if *interaction == Interaction::None {
+            background.0 = button_color.0;
+        }
+    }
+}
@@ -25,9 +25,16 @@ pub struct GroundMaterial {
     pub scale: f32,
     #[uniform(100)]
     pub noise_scale: f32,
+    // the dirt texture is tinted with this where the ground is sand
+    #[uniform(100)]
+    pub sand_color: Color,
     #[texture(110)]
     #[sampler(111)]
     pub color_texture: Handle<Image>,
+    // grass, dirt and sand weights in r, g and b, see MapLayout::splat_map
+    #[texture(112)]
+    #[sampler(113)]
+    pub splat_texture: Handle<Image>,
 }
 
 impl MaterialExtension for GroundMaterial {
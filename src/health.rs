@@ -1,7 +1,12 @@
-use bevy::prelude::*;
+use bevy::{ecs::query::Has, prelude::*};
 use bevy_vector_shapes::{prelude::ShapePainter, shapes::LinePainter};
 
-use crate::camera::MainCameraTag;
+use crate::{
+    camera::MainCameraTag,
+    item_pickups::{DropsOnDeath, SpawnDropsEvent},
+    mixer::PlaySfxEvent,
+    settings::Settings,
+};
 
 #[derive(Component, Debug)]
 pub struct Health {
@@ -15,8 +20,104 @@ pub struct ApplyHealthEvent {
     pub amount: i32,
     pub target_entity: Entity,
     pub caster_entity: Entity,
+    // a critical hit, see WeaponStats::roll_crit
+    pub is_crit: bool,
+    // the last swing of a melee combo, see Combo::is_finisher
+    pub is_finisher: bool,
 }
 
+// sent once when an entity's health reaches 0
+#[derive(Event)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    // whoever dealt the last blow
+    pub killer_entity: Entity,
+}
+
+/// Flat damage taken off every hit from someone else, hits still do at least 1 damage.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Armor(pub i32);
+
+// fraction of the max shield regenerated every second
+const SHIELD_REGEN_RATE: f32 = 0.25;
+// seconds a body with a death animation sticks around
+const DYING_TIME: f32 = 1.0;
+// seconds between swapping the hit flash colors
+const FLASH_INTERVAL: f32 = 0.08;
+// seconds a damaged mesh shows the flash material
+const HIT_FLASH_TIME: f32 = 0.1;
+
+/// Soaks up damage before health does, refills after a while without getting hit.
+#[derive(Component, Debug)]
+pub struct Shield {
+    pub current: f32,
+    pub max: f32,
+    // seconds without taking damage before it starts refilling
+    pub regen_delay: f32,
+    since_hit: f32,
+}
+
+impl Shield {
+    pub fn new(max: f32, regen_delay: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            regen_delay,
+            since_hit: 0.0,
+        }
+    }
+
+    // takes what it can of the damage, returns the rest
+    fn absorb(&mut self, damage: i32) -> i32 {
+        self.since_hit = 0.0;
+        let absorbed = self.current.min(damage as f32);
+        self.current -= absorbed;
+        damage - absorbed.floor() as i32
+    }
+}
+
+/// Plays out a death instead of despawning the moment health hits 0.
+#[derive(Component)]
+pub struct DeathAnimation;
+
+/// Dead and without health, despawned once the timer runs out.
+#[derive(Component)]
+pub struct Dying(Timer);
+
+impl Dying {
+    // 0.0 just died, 1.0 about to despawn
+    pub fn progress(&self) -> f32 {
+        self.0.percent()
+    }
+}
+
+/// Seconds an entity can't be damaged again after getting hit.
+#[derive(Component)]
+pub struct InvulnerableOnHit(pub f32);
+
+/// Ignores damage until the timer finishes, added by apply_health_events.
+#[derive(Component)]
+pub struct Invulnerable(pub Timer);
+
+/// Just took damage, every mesh below flashes until the timer finishes.
+/// Entities with InvulnerableOnHit blink while invulnerable instead.
+#[derive(Component)]
+pub struct HitFlash {
+    pub timer: Timer,
+    // crits flash red, other hits white
+    pub is_crit: bool,
+}
+
+#[derive(Resource)]
+struct FlashMaterials {
+    red: Handle<StandardMaterial>,
+    white: Handle<StandardMaterial>,
+}
+
+/// The material a mesh had before it started flashing, put back once the flash ends.
+#[derive(Component)]
+pub struct FlashedMaterial(pub Handle<StandardMaterial>);
+
 // if we have a hitbox as child of an entity with health.
 // we can reference the health entity
 #[derive(Component)]
@@ -32,51 +133,261 @@ pub struct ShowHealthBar;
 #[derive(Component)]
 pub struct DespawnOnHealth0;
 
+/// Id of a sound set in sounds.sfx.ron, played when the entity dies.
 #[derive(Component)]
-pub struct DeathSound(pub Handle<AudioSource>);
+pub struct DeathSound(pub String);
 
 impl Plugin for HealthPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<ApplyHealthEvent>().add_systems(
-            Update,
-            (apply_health_events, despawn_0_system, display_health),
-        );
+        app.add_event::<ApplyHealthEvent>()
+            .add_event::<DeathEvent>()
+            .add_systems(Startup, setup_flash_materials)
+            .add_systems(
+                Update,
+                (
+                    apply_health_events,
+                    regen_shields,
+                    (tick_invulnerable, flash_invulnerable).chain(),
+                    flash_hit,
+                    (despawn_0_system, tick_dying),
+                    display_health,
+                ),
+            );
     }
 }
 
-fn despawn_0_system(query: Query<(&Health, Entity, Option<&DeathSound>)>, mut commands: Commands) {
-    for (health, entity, death_sound) in query.iter() {
+fn despawn_0_system(
+    query: Query<(
+        &Health,
+        Entity,
+        Option<&DeathSound>,
+        Has<DeathAnimation>,
+        Option<&DropsOnDeath>,
+        Option<&GlobalTransform>,
+    )>,
+    mut commands: Commands,
+    mut spawn_drops_events: EventWriter<SpawnDropsEvent>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
+) {
+    for (health, entity, death_sound, animated, drops, transform) in query.iter() {
         if health.is_dead() {
-            commands.entity(entity).despawn_recursive();
+            if let (Some(drops), Some(transform)) = (drops, transform) {
+                spawn_drops_events.send(SpawnDropsEvent {
+                    source: drops.0,
+                    pos: transform.translation() + Vec3::Y,
+                });
+            }
+            match animated {
+                true => {
+                    commands
+                        .entity(entity)
+                        .remove::<(Health, ShowHealthBar)>()
+                        .insert(Dying(Timer::from_seconds(DYING_TIME, TimerMode::Once)));
+                }
+                false => commands.entity(entity).despawn_recursive(),
+            }
             if let Some(sound) = death_sound {
-                commands.spawn(AudioBundle {
-                    source: sound.0.clone(),
-                    settings: PlaybackSettings::DESPAWN,
+                sfx_events.send(PlaySfxEvent {
+                    position: transform.map(|t| t.translation()),
+                    ..PlaySfxEvent::new(&sound.0)
                 });
             }
         }
     }
 }
 
-fn apply_health_events(mut events: EventReader<ApplyHealthEvent>, mut query: Query<&mut Health>) {
+fn tick_dying(mut commands: Commands, mut query: Query<(Entity, &mut Dying)>, time: Res<Time>) {
+    for (entity, mut dying) in query.iter_mut() {
+        if dying.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub fn apply_health_events(
+    mut commands: Commands,
+    mut events: EventReader<ApplyHealthEvent>,
+    mut query: Query<(
+        &mut Health,
+        Option<&Armor>,
+        Option<&mut Shield>,
+        Option<&InvulnerableOnHit>,
+        Has<Invulnerable>,
+    )>,
+    mut death_events: EventWriter<DeathEvent>,
+) {
+    // Invulnerable isn't inserted until later, so remember who got hit this frame
+    let mut hit_this_frame = Vec::new();
     for event in events.read() {
-        let Ok(mut health) = query.get_mut(event.target_entity) else {
+        let Ok((mut health, armor, shield, on_hit, invulnerable)) =
+            query.get_mut(event.target_entity)
+        else {
             continue;
         };
-        *health += event.amount;
+        let mut amount = event.amount;
+        if amount < 0 {
+            if invulnerable || hit_this_frame.contains(&event.target_entity) {
+                continue;
+            }
+            if let Some(on_hit) = on_hit {
+                commands
+                    .entity(event.target_entity)
+                    .insert(Invulnerable(Timer::from_seconds(on_hit.0, TimerMode::Once)));
+                hit_this_frame.push(event.target_entity);
+            }
+        }
+        // heals and hurting yourself go straight through armor
+        if let Some(armor) =
+            armor.filter(|_| amount < 0 && event.caster_entity != event.target_entity)
+        {
+            amount = (amount + armor.0).min(-1);
+        }
+        if let Some(mut shield) = shield.filter(|_| amount < 0) {
+            amount = -shield.absorb(-amount);
+        }
+        if amount < 0 && on_hit.is_none() {
+            commands.entity(event.target_entity).insert(HitFlash {
+                timer: Timer::from_seconds(HIT_FLASH_TIME, TimerMode::Once),
+                is_crit: event.is_crit,
+            });
+        }
+        let was_alive = !health.is_dead();
+        *health += amount;
+        if was_alive && health.is_dead() {
+            death_events.send(DeathEvent {
+                entity: event.target_entity,
+                killer_entity: event.caster_entity,
+            });
+        }
+    }
+}
+
+fn regen_shields(mut query: Query<&mut Shield>, time: Res<Time>) {
+    for mut shield in query.iter_mut() {
+        shield.since_hit += time.delta_seconds();
+        if shield.since_hit >= shield.regen_delay {
+            let regen = shield.max * SHIELD_REGEN_RATE * time.delta_seconds();
+            shield.current = (shield.current + regen).min(shield.max);
+        }
+    }
+}
+
+fn tick_invulnerable(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Invulnerable)>,
+    time: Res<Time>,
+) {
+    for (entity, mut invulnerable) in query.iter_mut() {
+        if invulnerable.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+fn setup_flash_materials(mut commands: Commands, mut materials: ResMut<Assets<StandardMaterial>>) {
+    let mut flash = |color: Color| {
+        materials.add(StandardMaterial {
+            base_color: color,
+            unlit: true,
+            ..default()
+        })
+    };
+    commands.insert_resource(FlashMaterials {
+        red: flash(Color::RED),
+        white: flash(Color::WHITE),
+    });
+}
+
+// every mesh below an invulnerable entity blinks red and white, then gets its material back
+fn flash_invulnerable(
+    mut commands: Commands,
+    invulnerable: Query<(Entity, &Invulnerable)>,
+    mut removed: RemovedComponents<Invulnerable>,
+    children: Query<&Children>,
+    mut material_handles: Query<(&mut Handle<StandardMaterial>, Option<&FlashedMaterial>)>,
+    flash_materials: Res<FlashMaterials>,
+) {
+    for (entity, invulnerable) in invulnerable.iter() {
+        let flash = match (invulnerable.0.elapsed_secs() / FLASH_INTERVAL) as u32 % 2 {
+            0 => &flash_materials.red,
+            _ => &flash_materials.white,
+        };
+        for child in children.iter_descendants(entity) {
+            let Ok((mut handle, flashed)) = material_handles.get_mut(child) else {
+                continue;
+            };
+            if flashed.is_none() {
+                commands
+                    .entity(child)
+                    .insert(FlashedMaterial(handle.clone()));
+            }
+            *handle = flash.clone();
+        }
+    }
+    for entity in removed.read() {
+        for child in children.iter_descendants(entity) {
+            if let Ok((mut handle, Some(flashed))) = material_handles.get_mut(child) {
+                *handle = flashed.0.clone();
+                commands.entity(child).remove::<FlashedMaterial>();
+            }
+        }
+    }
+}
+
+// every mesh below a hit entity flashes, then gets its material back.
+// dying bodies get theirs back right away, so they dissolve in their own colors
+fn flash_hit(
+    mut commands: Commands,
+    mut flashing: Query<(Entity, &mut HitFlash, Has<Dying>)>,
+    children: Query<&Children>,
+    mut material_handles: Query<(&mut Handle<StandardMaterial>, Option<&FlashedMaterial>)>,
+    flash_materials: Res<FlashMaterials>,
+    time: Res<Time>,
+) {
+    for (entity, mut hit_flash, dying) in flashing.iter_mut() {
+        if dying || hit_flash.timer.tick(time.delta()).finished() {
+            for child in children.iter_descendants(entity) {
+                if let Ok((mut handle, Some(flashed))) = material_handles.get_mut(child) {
+                    *handle = flashed.0.clone();
+                    commands.entity(child).remove::<FlashedMaterial>();
+                }
+            }
+            commands.entity(entity).remove::<HitFlash>();
+            continue;
+        }
+        let flash = match hit_flash.is_crit {
+            true => &flash_materials.red,
+            false => &flash_materials.white,
+        };
+        for child in children.iter_descendants(entity) {
+            let Ok((mut handle, flashed)) = material_handles.get_mut(child) else {
+                continue;
+            };
+            if flashed.is_none() {
+                commands
+                    .entity(child)
+                    .insert(FlashedMaterial(handle.clone()));
+            }
+            *handle = flash.clone();
+        }
     }
 }
 
 fn display_health(
     mut painter: ShapePainter,
-    query: Query<(&Health, &GlobalTransform), With<ShowHealthBar>>,
+    query: Query<(&Health, &GlobalTransform, Option<&Armor>, Option<&Shield>), With<ShowHealthBar>>,
     q_camera: Query<&Transform, With<MainCameraTag>>,
+    settings: Res<Settings>,
 ) {
     const HEALTHBAR_LENGTH: f32 = 1.5;
+    const TICK_HEIGHT: f32 = 0.08;
+    const ARMOR_PIP_LENGTH: f32 = 0.12;
+    const ARMOR_PIP_SPACING: f32 = 0.18;
 
     let camera_tr = q_camera.single();
+    let accessibility = &settings.accessibility;
 
-    for (health, transform) in &query {
+    for (health, transform, armor, shield) in &query {
         painter.color = Color::GRAY;
         let healthbar_pos = transform.translation() + transform.up() * 4.0;
         let healthbar_left = healthbar_pos - camera_tr.right() * HEALTHBAR_LENGTH / 2.0;
@@ -87,11 +398,42 @@ fn display_health(
 
         let health_ratio = health.current as f32 / health.max as f32;
 
-        painter.color = Color::RED;
+        painter.color = accessibility.palette.remap(Color::RED);
         painter.line(
             healthbar_left,
             healthbar_left + camera_tr.right() * HEALTHBAR_LENGTH * health_ratio,
         );
+
+        // shield goes on the end of the health, past the bar when overhealed
+        if let Some(shield) = shield.filter(|s| s.current > 0.0) {
+            let shield_ratio = shield.current / health.max as f32;
+            let health_end = healthbar_left + camera_tr.right() * HEALTHBAR_LENGTH * health_ratio;
+            painter.color = accessibility.palette.remap(Color::BLUE);
+            painter.line(
+                health_end,
+                health_end + camera_tr.right() * HEALTHBAR_LENGTH * shield_ratio,
+            );
+        }
+
+        // ticks every 10% so the amount can be read without relying on color
+        if accessibility.shape_coding {
+            painter.color = Color::BLACK;
+            for i in 1..10 {
+                let tick = healthbar_left + camera_tr.right() * HEALTHBAR_LENGTH * i as f32 / 10.0;
+                painter.line(
+                    tick - camera_tr.up() * TICK_HEIGHT,
+                    tick + camera_tr.up() * TICK_HEIGHT,
+                );
+            }
+        }
+
+        // a pip under the bar for every point of armor
+        painter.color = Color::SILVER;
+        let pips_left = healthbar_left - camera_tr.up() * 0.15;
+        for i in 0..armor.map_or(0, |a| a.0) {
+            let pip = pips_left + camera_tr.right() * ARMOR_PIP_SPACING * i as f32;
+            painter.line(pip, pip + camera_tr.right() * ARMOR_PIP_LENGTH);
+        }
     }
 }
 
@@ -111,6 +453,12 @@ impl Health {
         let percent = self.current as f32 / self.max as f32;
         f32::clamp(percent, 0.0, 1.0)
     }
+
+    /// Raises the max, healing by the same amount.
+    pub fn raise_max(&mut self, amount: i32) {
+        self.max += amount;
+        self.current += amount;
+    }
 }
 
 impl std::ops::SubAssign<i32> for Health {
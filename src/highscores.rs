@@ -0,0 +1,187 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    health::Health,
+    persistence,
+    state::{AppState, GameResult, RestartRunEvent, Wave},
+    tree::TreeTrunkTag,
+    ui_util::UiAssets,
+};
+
+const HIGH_SCORES_FILE: &str = "highscores.ron";
+const MAX_ENTRIES: usize = 10;
+const SCORE_PER_WAVE: u32 = 1000;
+const SCORE_PER_TREE: u32 = 100;
+// seconds budgeted per cleared wave, every second under budget is a point
+const TIME_BONUS_PER_WAVE: f32 = 300.0;
+
+pub struct HighScorePlugin;
+
+impl Plugin for HighScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(
+            persistence::load_config::<HighScores>(HIGH_SCORES_FILE).unwrap_or_default(),
+        )
+        .init_resource::<RunTime>()
+        .add_systems(OnEnter(AppState::Playing), reset_run_time)
+        .add_systems(
+            PreUpdate,
+            reset_run_time.run_if(on_event::<RestartRunEvent>()),
+        )
+        .add_systems(Update, tick_run_time.run_if(in_state(AppState::Playing)))
+        .add_systems(
+            OnEnter(AppState::GameOver),
+            (record_high_score, setup_high_score_ui).chain(),
+        )
+        .add_systems(OnExit(AppState::GameOver), despawn_high_score_ui);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighScore {
+    pub score: u32,
+    pub waves_cleared: usize,
+    pub trees_alive: u32,
+    // seconds of unpaused play
+    pub time: f32,
+}
+
+/// The best runs on this machine, highest score first.
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighScores {
+    pub entries: Vec<HighScore>,
+    // where the run that just ended landed, None if it didn't make the table
+    #[serde(skip)]
+    latest: Option<usize>,
+}
+
+impl HighScores {
+    fn insert(&mut self, entry: HighScore) {
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.score < entry.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(index, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        self.latest = (index < MAX_ENTRIES).then_some(index);
+    }
+}
+
+// game time spent in the current run, stops while paused
+#[derive(Resource, Default)]
+struct RunTime(f32);
+
+fn reset_run_time(mut run_time: ResMut<RunTime>) {
+    run_time.0 = 0.0;
+}
+
+fn tick_run_time(time: Res<Time>, mut run_time: ResMut<RunTime>) {
+    run_time.0 += time.delta_seconds();
+}
+
+fn score(waves_cleared: usize, trees_alive: u32, time: f32) -> u32 {
+    let time_bonus = (waves_cleared as f32 * TIME_BONUS_PER_WAVE - time).max(0.0);
+    waves_cleared as u32 * SCORE_PER_WAVE + trees_alive * SCORE_PER_TREE + time_bonus as u32
+}
+
+fn record_high_score(
+    mut high_scores: ResMut<HighScores>,
+    result: Option<Res<GameResult>>,
+    wave: Res<Wave>,
+    run_time: Res<RunTime>,
+    trees: Query<&Health, With<TreeTrunkTag>>,
+) {
+    // a won run cleared the wave it ended on too
+    let waves_cleared = match result.as_deref() {
+        Some(GameResult::Won) => wave.0 + 1,
+        _ => wave.0,
+    };
+    let trees_alive = trees.iter().filter(|h| h.current > 0).count() as u32;
+    high_scores.insert(HighScore {
+        score: score(waves_cleared, trees_alive, run_time.0),
+        waves_cleared,
+        trees_alive,
+        time: run_time.0,
+    });
+    persistence::save_config(HIGH_SCORES_FILE, &*high_scores);
+}
+
+#[derive(Component)]
+struct HighScoreUiTag;
+
+// sits to the right of the game over menu, mirroring the run summary
+fn setup_high_score_ui(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    high_scores: Res<HighScores>,
+) {
+    commands
+        .spawn((
+            HighScoreUiTag,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Percent(5.0),
+                    top: Val::Percent(25.0),
+                    width: Val::Percent(25.0),
+                    padding: UiRect::all(Val::Px(20.0)),
+                    row_gap: Val::Px(6.0),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                background_color: Color::BLACK.with_a(0.7).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "High scores",
+                TextStyle {
+                    font: ui_assets.font.clone(),
+                    font_size: 36.0,
+                    color: Color::GOLD,
+                },
+            ));
+            if high_scores.latest.is_none() {
+                parent.spawn(TextBundle::from_section(
+                    "This run didn't make the top 10",
+                    TextStyle {
+                        font: ui_assets.font.clone(),
+                        font_size: 18.0,
+                        color: Color::GRAY,
+                    },
+                ));
+            }
+            for (i, entry) in high_scores.entries.iter().enumerate() {
+                let color = match high_scores.latest == Some(i) {
+                    true => Color::YELLOW,
+                    false => Color::WHITE,
+                };
+                parent.spawn(TextBundle::from_section(
+                    format!(
+                        "{}. {}  wave {}, {} trees, {}:{:02}",
+                        i + 1,
+                        entry.score,
+                        entry.waves_cleared,
+                        entry.trees_alive,
+                        entry.time as u32 / 60,
+                        entry.time as u32 % 60
+                    ),
+                    TextStyle {
+                        font: ui_assets.font.clone(),
+                        font_size: 22.0,
+                        color,
+                    },
+                ));
+            }
+        });
+}
+
+fn despawn_high_score_ui(mut commands: Commands, panels: Query<Entity, With<HighScoreUiTag>>) {
+    for entity in panels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,273 @@
+use bevy::{input::mouse::MouseWheel, prelude::*};
+
+use crate::{
+    ammo::Ammo,
+    camera::zoom_modifier_held,
+    cutscene::input_unlocked,
+    pause::not_paused,
+    player::PlayerControllerTag,
+    shop::{pointer_over_shop, shop_hotkeys_held, ShopScrollArea},
+    state::AppState,
+    ui_util::UiAssets,
+    weapon::{Weapon, WeaponAsset, WeaponCooldown, WeaponStats},
+};
+
+pub const MAX_SLOTS: usize = 3;
+const SLOT_KEYS: [KeyCode; MAX_SLOTS] = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3];
+const SLOT_SIZE: f32 = 72.0;
+
+pub struct HotbarPlugin;
+
+impl Plugin for HotbarPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Hotbar>()
+            .add_systems(Startup, setup_hotbar_ui)
+            .add_systems(
+                Update,
+                (
+                    switch_weapon
+                        .run_if(in_state(AppState::Playing))
+                        .run_if(input_unlocked),
+                    tick_stashed_cooldowns,
+                )
+                    .run_if(not_paused),
+            )
+            .add_systems(Update, (update_hotbar_ui, update_cooldown_overlays));
+    }
+}
+
+pub struct HotbarSlot {
+    pub weapon: Handle<WeaponAsset>,
+    // cooldown and ammo of the weapon while it's put away
+    cooldown: f32,
+    ammo: Option<Ammo>,
+}
+
+/// Weapons the player carries, only the active one sits on the player entity.
+#[derive(Resource, Default)]
+pub struct Hotbar {
+    slots: Vec<HotbarSlot>,
+    active: usize,
+}
+
+impl Hotbar {
+    pub fn new(weapons: impl IntoIterator<Item = Handle<WeaponAsset>>) -> Self {
+        let mut hotbar = Self::default();
+        for weapon in weapons {
+            hotbar.add(weapon);
+        }
+        hotbar
+    }
+
+    /// Puts the weapon in the first free slot, returns false if the hotbar is full.
+    pub fn add(&mut self, weapon: Handle<WeaponAsset>) -> bool {
+        if self.slots.len() >= MAX_SLOTS {
+            return false;
+        }
+        self.slots.push(HotbarSlot {
+            weapon,
+            cooldown: 0.0,
+            ammo: None,
+        });
+        true
+    }
+
+    pub fn active_weapon(&self) -> Option<&Handle<WeaponAsset>> {
+        self.slots.get(self.active).map(|s| &s.weapon)
+    }
+}
+
+fn switch_weapon(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    shop_scroll_areas: Query<&Interaction, With<ShopScrollArea>>,
+    mut hotbar: ResMut<Hotbar>,
+    mut player: Query<
+        (Entity, &mut Weapon, &mut WeaponCooldown, Option<&Ammo>),
+        With<PlayerControllerTag>,
+    >,
+) {
+    let slot_count = hotbar.slots.len();
+    let mut scroll: f32 = mouse_wheel.read().map(|e| e.y).sum();
+    // the shop list is being scrolled or the camera zoomed instead
+    if pointer_over_shop(&shop_scroll_areas) || zoom_modifier_held(&keyboard) {
+        scroll = 0.0;
+    }
+    let pressed = match shop_hotkeys_held(&keyboard) {
+        true => None,
+        false => SLOT_KEYS.iter().position(|k| keyboard.just_pressed(*k)),
+    };
+    let next = match (pressed, scroll) {
+        (Some(slot), _) => slot,
+        (None, scroll) if slot_count == 0 || scroll == 0.0 => return,
+        // scrolling up goes to the slot to the left
+        (None, scroll) if scroll > 0.0 => (hotbar.active + slot_count - 1) % slot_count,
+        (None, _) => (hotbar.active + 1) % slot_count,
+    };
+    if next >= slot_count || next == hotbar.active {
+        return;
+    }
+    let Ok((entity, mut weapon, mut cooldown, ammo)) = player.get_single_mut() else {
+        return;
+    };
+
+    let active = hotbar.active;
+    let current = &mut hotbar.slots[active];
+    current.cooldown = cooldown.time_left;
+    current.ammo = ammo.cloned();
+    commands.entity(entity).remove::<Ammo>();
+
+    let slot = &mut hotbar.slots[next];
+    weapon.0 = slot.weapon.clone();
+    cooldown.time_left = slot.cooldown;
+    if let Some(ammo) = slot.ammo.take() {
+        commands.entity(entity).insert(ammo);
+    }
+    hotbar.active = next;
+}
+
+// put away weapons still cool down
+fn tick_stashed_cooldowns(mut hotbar: ResMut<Hotbar>, time: Res<Time>) {
+    let active = hotbar.active;
+    for (i, slot) in hotbar.slots.iter_mut().enumerate() {
+        if i != active {
+            slot.cooldown -= time.delta_seconds();
+        }
+    }
+}
+
+#[derive(Component)]
+struct HotbarSlotUi(usize);
+
+#[derive(Component)]
+struct HotbarSlotText(usize);
+
+// darkens the slot from the bottom up, as tall as the cooldown left
+#[derive(Component)]
+struct HotbarCooldownUi(usize);
+
+fn setup_hotbar_ui(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(16.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(8.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for i in 0..MAX_SLOTS {
+                parent
+                    .spawn((
+                        HotbarSlotUi(i),
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Px(SLOT_SIZE),
+                                height: Val::Px(SLOT_SIZE),
+                                border: UiRect::all(Val::Px(3.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: Color::rgba(0.0, 0.0, 0.0, 0.3).into(),
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        // spawned before the text so the text stays on top
+                        parent.spawn((
+                            HotbarCooldownUi(i),
+                            NodeBundle {
+                                style: Style {
+                                    position_type: PositionType::Absolute,
+                                    bottom: Val::Px(0.0),
+                                    width: Val::Percent(100.0),
+                                    height: Val::Percent(0.0),
+                                    ..default()
+                                },
+                                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                                ..default()
+                            },
+                        ));
+                        parent.spawn((
+                            HotbarSlotText(i),
+                            TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font: ui_assets.font.clone(),
+                                    font_size: 18.0,
+                                    color: Color::WHITE,
+                                },
+                            ),
+                        ));
+                    });
+            }
+        });
+}
+
+fn update_hotbar_ui(
+    hotbar: Res<Hotbar>,
+    weapon_assets: Res<Assets<WeaponAsset>>,
+    mut slots: Query<(&HotbarSlotUi, &mut Style, &mut BorderColor)>,
+    mut texts: Query<(&HotbarSlotText, &mut Text)>,
+) {
+    for (slot, mut style, mut border) in slots.iter_mut() {
+        let display = match slot.0 < hotbar.slots.len() {
+            true => Display::Flex,
+            false => Display::None,
+        };
+        if style.display != display {
+            style.display = display;
+        }
+        let color = match slot.0 == hotbar.active {
+            true => Color::YELLOW,
+            false => Color::GRAY,
+        };
+        if border.0 != color {
+            border.0 = color;
+        }
+    }
+    for (slot, mut text) in texts.iter_mut() {
+        let name = hotbar
+            .slots
+            .get(slot.0)
+            .and_then(|s| weapon_assets.get(&s.weapon))
+            .map_or("", |w| w.name.as_str());
+        let value = format!("{}\n{}", slot.0 + 1, name);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+fn update_cooldown_overlays(
+    hotbar: Res<Hotbar>,
+    weapon_assets: Res<Assets<WeaponAsset>>,
+    player: Query<(&WeaponCooldown, &WeaponStats), With<PlayerControllerTag>>,
+    mut overlays: Query<(&HotbarCooldownUi, &mut Style)>,
+) {
+    let player = player.get_single().ok();
+    for (overlay, mut style) in overlays.iter_mut() {
+        let fraction = match (hotbar.slots.get(overlay.0), player) {
+            (Some(slot), Some((active_cooldown, stats))) => {
+                let time_left = match overlay.0 == hotbar.active {
+                    true => active_cooldown.time_left,
+                    false => slot.cooldown,
+                };
+                weapon_assets.get(&slot.weapon).map_or(0.0, |weapon| {
+                    time_left / (weapon.cooldown * stats.cooldown_mul).max(f32::EPSILON)
+                })
+            }
+            _ => 0.0,
+        };
+        let height = Val::Percent(fraction.clamp(0.0, 1.0) * 100.0);
+        if style.height != height {
+            style.height = height;
+        }
+    }
+}
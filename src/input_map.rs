@@ -0,0 +1,326 @@
+use bevy::{
+    input::{gamepad::GamepadConnectionEvent, mouse::MouseMotion},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::persistence;
+
+const BINDINGS_FILE: &str = "controls.ron";
+
+pub struct InputMapPlugin;
+
+impl Plugin for InputMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(
+            persistence::load_config::<InputBindings>(BINDINGS_FILE).unwrap_or_default(),
+        )
+        .init_resource::<InputMap>()
+        .add_systems(PreUpdate, (track_gamepads, detect_active_device).chain())
+        .add_systems(Update, save_bindings);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum InputDevice {
+    #[default]
+    KeyboardMouse,
+    Gamepad,
+}
+
+/// Something the player can rebind in the settings panel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Attack,
+    // free fly camera only
+    FlyUp,
+    FlyDown,
+    // follow camera only
+    OrbitLeft,
+    OrbitRight,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Attack,
+        Action::FlyUp,
+        Action::FlyDown,
+        Action::OrbitLeft,
+        Action::OrbitRight,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Attack => "Attack",
+            Action::FlyUp => "Fly up",
+            Action::FlyDown => "Fly down",
+            Action::OrbitLeft => "Orbit camera left",
+            Action::OrbitRight => "Orbit camera right",
+        }
+    }
+}
+
+/// A key or a mouse button, any action can be bound to either.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl Binding {
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => format!("Mouse {:?}", button),
+        }
+    }
+}
+
+/// Keyboard and mouse bindings, saved to the config dir.
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputBindings {
+    pub up: Binding,
+    pub down: Binding,
+    pub left: Binding,
+    pub right: Binding,
+    pub attack: Binding,
+    pub fly_up: Binding,
+    pub fly_down: Binding,
+    pub orbit_left: Binding,
+    pub orbit_right: Binding,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self {
+            up: Binding::Key(KeyCode::W),
+            down: Binding::Key(KeyCode::S),
+            left: Binding::Key(KeyCode::A),
+            right: Binding::Key(KeyCode::D),
+            attack: Binding::Mouse(MouseButton::Left),
+            fly_up: Binding::Key(KeyCode::Space),
+            fly_down: Binding::Key(KeyCode::ShiftLeft),
+            orbit_left: Binding::Key(KeyCode::Q),
+            orbit_right: Binding::Key(KeyCode::E),
+        }
+    }
+}
+
+impl InputBindings {
+    pub fn get(&self, action: Action) -> Binding {
+        match action {
+            Action::Up => self.up,
+            Action::Down => self.down,
+            Action::Left => self.left,
+            Action::Right => self.right,
+            Action::Attack => self.attack,
+            Action::FlyUp => self.fly_up,
+            Action::FlyDown => self.fly_down,
+            Action::OrbitLeft => self.orbit_left,
+            Action::OrbitRight => self.orbit_right,
+        }
+    }
+
+    pub fn set(&mut self, action: Action, binding: Binding) {
+        *self.binding_mut(action) = binding;
+    }
+
+    fn binding_mut(&mut self, action: Action) -> &mut Binding {
+        match action {
+            Action::Up => &mut self.up,
+            Action::Down => &mut self.down,
+            Action::Left => &mut self.left,
+            Action::Right => &mut self.right,
+            Action::Attack => &mut self.attack,
+            Action::FlyUp => &mut self.fly_up,
+            Action::FlyDown => &mut self.fly_down,
+            Action::OrbitLeft => &mut self.orbit_left,
+            Action::OrbitRight => &mut self.orbit_right,
+        }
+    }
+
+    pub fn pressed(
+        &self,
+        action: Action,
+        keyboard: &Input<KeyCode>,
+        mouse: &Input<MouseButton>,
+    ) -> bool {
+        match self.get(action) {
+            Binding::Key(key) => keyboard.pressed(key),
+            Binding::Mouse(button) => mouse.pressed(button),
+        }
+    }
+
+    /// 1.0 when only `positive` is held, -1.0 when only `negative` is
+    pub fn axis(
+        &self,
+        positive: Action,
+        negative: Action,
+        keyboard: &Input<KeyCode>,
+        mouse: &Input<MouseButton>,
+    ) -> f32 {
+        match (
+            self.pressed(positive, keyboard, mouse),
+            self.pressed(negative, keyboard, mouse),
+        ) {
+            (true, false) => 1.0,
+            (false, true) => -1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Gamepad state, plus the fixed gamepad layout, so gameplay code only asks for actions.
+#[derive(Resource)]
+pub struct InputMap {
+    pub gamepad_attack: GamepadButtonType,
+    pub gamepad_confirm: GamepadButtonType,
+    pub gamepad_next: GamepadButtonType,
+    pub gamepad_previous: GamepadButtonType,
+    // stick values below this are ignored
+    pub deadzone: f32,
+    // first connected gamepad
+    pub gamepad: Option<Gamepad>,
+    // whatever was used last, decides if aiming follows the mouse or the right stick
+    pub device: InputDevice,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self {
+            gamepad_attack: GamepadButtonType::RightTrigger2,
+            gamepad_confirm: GamepadButtonType::South,
+            gamepad_next: GamepadButtonType::DPadDown,
+            gamepad_previous: GamepadButtonType::DPadUp,
+            deadzone: 0.2,
+            gamepad: None,
+            device: InputDevice::default(),
+        }
+    }
+}
+
+impl InputMap {
+    fn stick(&self, axes: &Axis<GamepadAxis>, x: GamepadAxisType, y: GamepadAxisType) -> Vec2 {
+        let Some(gamepad) = self.gamepad else {
+            return Vec2::ZERO;
+        };
+        let stick = Vec2::new(
+            axes.get(GamepadAxis::new(gamepad, x)).unwrap_or(0.0),
+            axes.get(GamepadAxis::new(gamepad, y)).unwrap_or(0.0),
+        );
+        match stick.length() > self.deadzone {
+            true => stick,
+            false => Vec2::ZERO,
+        }
+    }
+
+    /// x is right, y is down the screen, like the keyboard layout
+    pub fn movement(
+        &self,
+        bindings: &InputBindings,
+        keyboard: &Input<KeyCode>,
+        mouse: &Input<MouseButton>,
+        axes: &Axis<GamepadAxis>,
+    ) -> Vec2 {
+        let keys = Vec2::new(
+            bindings.axis(Action::Right, Action::Left, keyboard, mouse),
+            bindings.axis(Action::Down, Action::Up, keyboard, mouse),
+        );
+        let stick = self.stick(
+            axes,
+            GamepadAxisType::LeftStickX,
+            GamepadAxisType::LeftStickY,
+        );
+        (keys + Vec2::new(stick.x, -stick.y)).clamp_length_max(1.0)
+    }
+
+    /// right stick direction, same space as `movement`
+    pub fn aim(&self, axes: &Axis<GamepadAxis>) -> Option<Vec2> {
+        let stick = self.stick(
+            axes,
+            GamepadAxisType::RightStickX,
+            GamepadAxisType::RightStickY,
+        );
+        (stick != Vec2::ZERO).then_some(Vec2::new(stick.x, -stick.y))
+    }
+
+    pub fn gamepad_pressed(
+        &self,
+        buttons: &Input<GamepadButton>,
+        button: GamepadButtonType,
+    ) -> bool {
+        self.gamepad
+            .is_some_and(|gamepad| buttons.pressed(GamepadButton::new(gamepad, button)))
+    }
+
+    pub fn gamepad_just_pressed(
+        &self,
+        buttons: &Input<GamepadButton>,
+        button: GamepadButtonType,
+    ) -> bool {
+        self.gamepad
+            .is_some_and(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, button)))
+    }
+}
+
+fn track_gamepads(
+    mut input_map: ResMut<InputMap>,
+    mut connection_events: EventReader<GamepadConnectionEvent>,
+    gamepads: Res<Gamepads>,
+) {
+    if connection_events.read().count() == 0 {
+        return;
+    }
+    input_map.gamepad = gamepads.iter().next();
+}
+
+fn detect_active_device(
+    mut input_map: ResMut<InputMap>,
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+) {
+    let used_mouse = mouse_motion.read().count() > 0;
+    let device = if keyboard.get_just_pressed().next().is_some()
+        || mouse.get_just_pressed().next().is_some()
+        || used_mouse
+    {
+        InputDevice::KeyboardMouse
+    } else if gamepad_buttons.get_just_pressed().next().is_some()
+        || input_map.stick(
+            &axes,
+            GamepadAxisType::LeftStickX,
+            GamepadAxisType::LeftStickY,
+        ) != Vec2::ZERO
+        || input_map.aim(&axes).is_some()
+    {
+        InputDevice::Gamepad
+    } else {
+        return;
+    };
+    // only touch the resource when it actually changes
+    if input_map.device != device {
+        input_map.device = device;
+    }
+}
+
+fn save_bindings(bindings: Res<InputBindings>) {
+    if bindings.is_changed() && !bindings.is_added() {
+        persistence::save_config(BINDINGS_FILE, &*bindings);
+    }
+}
@@ -1,8 +1,27 @@
-use bevy::{prelude::*, utils::HashMap};
-use serde::Deserialize;
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+    utils::HashMap,
+    window::PrimaryWindow,
+};
+use serde::{Deserialize, Serialize};
 use strum::{Display, EnumIter, IntoEnumIterator};
 
-use crate::{player::PlayerControllerTag, ui_util::UiAssets};
+use crate::{
+    asset_utils::CustomAssetLoaderError, crafting::RecipesAsset, item_pickups::SpawnItemEvent,
+    player::PlayerControllerTag, ui_util::UiAssets,
+};
+
+const SLOT_SIZE: f32 = 52.0;
+const SLOTS_PER_ROW: usize = 4;
+// where the tooltip sits relative to the cursor
+const TOOLTIP_OFFSET: Vec2 = Vec2::new(16.0, 16.0);
+// held while clicking a slot to drop one of the item, with shift the whole stack
+const DROP_MODIFIERS: [KeyCode; 2] = [KeyCode::ControlLeft, KeyCode::ControlRight];
+const DROP_STACK_MODIFIERS: [KeyCode; 2] = [KeyCode::ShiftLeft, KeyCode::ShiftRight];
+// dropped items spawn this far above the player so they don't clip the ground
+const DROP_HEIGHT: f32 = 1.0;
 
 pub struct InventoryPlugin;
 
@@ -10,19 +29,52 @@ impl Plugin for InventoryPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<Item>()
             .register_type::<Inventory>()
-            .add_systems(Startup, setup_inventory_ui)
-            .add_systems(Update, update_inventory_ui);
+            .init_asset::<ItemMetadataAsset>()
+            .init_asset_loader::<ItemMetadataAssetLoader>()
+            .add_systems(Startup, (setup_item_metadata, setup_inventory_ui))
+            .add_systems(Update, (update_inventory_ui, show_item_tooltip, drop_items));
     }
 }
 
-#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash, EnumIter, Reflect, Deserialize)]
+#[derive(
+    Debug, Display, Clone, Copy, PartialEq, Eq, Hash, EnumIter, Reflect, Serialize, Deserialize,
+)]
 pub enum Item {
     Log,
     Banana,
     Apple,
+    Quiver,
+    // dropped by robots, spent on towers
+    Scrap,
+    // crafted, see recipes.recipes.ron
+    Plank,
+    TowerKit,
+}
+
+/// How an item looks and reads in the inventory, see items.items.ron.
+#[derive(Debug, Deserialize)]
+pub struct ItemData {
+    // relative to the assets folder
+    pub icon: String,
+    pub description: String,
+    // no more of it can be picked up once this many are carried
+    #[serde(default)]
+    pub max_stack: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Asset, TypePath)]
+pub struct ItemMetadataAsset(pub HashMap<Item, ItemData>);
+
+impl ItemMetadataAsset {
+    pub fn max_stack(&self, item: Item) -> Option<u32> {
+        self.0.get(&item).and_then(|data| data.max_stack)
+    }
 }
 
-#[derive(Component, Default, Reflect)]
+#[derive(Resource)]
+pub struct ItemMetadata(pub Handle<ItemMetadataAsset>);
+
+#[derive(Component, Default, Clone, Reflect)]
 pub struct Inventory {
     items: HashMap<Item, u32>,
 }
@@ -51,11 +103,12 @@ impl Inventory {
         res
     }
 
+    pub fn has_items(&self, mut items: impl Iterator<Item = (Item, u32)>) -> bool {
+        items.all(|(item, c)| self.items.get(&item).is_some_and(|count| *count >= c))
+    }
+
     pub fn spend_items(&mut self, items: impl Iterator<Item = (Item, u32)> + Clone) -> bool {
-        if items
-            .clone()
-            .all(|(item, c)| self.items.get(&item).map_or(false, |count| *count >= c))
-        {
+        if self.has_items(items.clone()) {
             for (item, count) in items {
                 let c = self
                     .items
@@ -72,63 +125,261 @@ impl Inventory {
         }
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = (Item, u32)> + '_ {
+        self.items.iter().map(|(item, count)| (*item, *count))
+    }
+
+    /// Spends the recipe's inputs on its output, returning whether it could be crafted.
+    pub fn craft(&mut self, recipe_id: &str, recipes: &RecipesAsset) -> bool {
+        let Some(recipe) = recipes.get(recipe_id) else {
+            return false;
+        };
+        if !self.spend_items(recipe.inputs.iter().copied()) {
+            return false;
+        }
+        self.add_item(recipe.output.0, recipe.output.1);
+        true
+    }
+
+    /// Empties the inventory, yielding everything that was in it.
+    pub fn drain(&mut self) -> impl Iterator<Item = (Item, u32)> + '_ {
+        self.items.drain()
+    }
+
     pub fn get_item_count(&self, item: Item) -> u32 {
         self.items.get(&item).copied().unwrap_or(0)
     }
+
+    /// Whether another one fits under the item's max stack.
+    pub fn has_room(&self, item: Item, max_stack: Option<u32>) -> bool {
+        max_stack.is_none_or(|max| self.get_item_count(item) < max)
+    }
 }
 
+// one per item, hidden while the player has none
+#[derive(Component)]
+struct ItemSlot(Item);
+
+#[derive(Component)]
+struct ItemSlotIcon(Item);
+
 #[derive(Component)]
-struct ItemText(Item);
+struct ItemSlotText(Item);
+
+#[derive(Component)]
+struct ItemTooltip;
+
+fn setup_item_metadata(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ItemMetadata(asset_server.load("items.items.ron")));
+}
 
 fn setup_inventory_ui(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    let text_style = |size: f32| TextStyle {
+        font: ui_assets.font.clone(),
+        font_size: size,
+        color: Color::WHITE,
+    };
     commands
         .spawn(NodeBundle {
             style: Style {
                 position_type: PositionType::Absolute,
-                height: Val::Percent(1.0),
-                width: Val::Percent(1.0),
-                flex_direction: FlexDirection::Column,
+                left: Val::Px(10.0),
+                top: Val::Px(10.0),
+                width: Val::Px((SLOT_SIZE + 4.0) * SLOTS_PER_ROW as f32),
+                flex_wrap: FlexWrap::Wrap,
+                column_gap: Val::Px(4.0),
+                row_gap: Val::Px(4.0),
                 ..default()
             },
             ..default()
         })
         .with_children(|parent| {
-            for material in Item::iter() {
-                parent.spawn((
-                    ItemText(material),
-                    TextBundle::from_section(
-                        format!("{}: 0", material),
-                        TextStyle {
-                            font: ui_assets.font.clone(),
-                            font_size: 30.0,
-                            color: Color::WHITE,
+            for item in Item::iter() {
+                parent
+                    .spawn((
+                        ItemSlot(item),
+                        Interaction::default(),
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Px(SLOT_SIZE),
+                                height: Val::Px(SLOT_SIZE),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                display: Display::None,
+                                ..default()
+                            },
+                            background_color: Color::rgba(0.0, 0.0, 0.0, 0.3).into(),
+                            ..default()
                         },
-                    )
-                    .with_style(Style {
-                        display: Display::None,
-                        ..default()
-                    }),
-                ));
+                    ))
+                    .with_children(|parent| {
+                        // filled in once the item metadata is loaded
+                        parent.spawn((
+                            ItemSlotIcon(item),
+                            ImageBundle {
+                                style: Style {
+                                    width: Val::Percent(75.0),
+                                    height: Val::Percent(75.0),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                        ));
+                        parent.spawn((
+                            ItemSlotText(item),
+                            TextBundle::from_section("", text_style(16.0)).with_style(Style {
+                                position_type: PositionType::Absolute,
+                                right: Val::Px(2.0),
+                                bottom: Val::Px(0.0),
+                                ..default()
+                            }),
+                        ));
+                    });
             }
         });
+    commands.spawn((
+        ItemTooltip,
+        TextBundle::from_section("", text_style(18.0))
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                padding: UiRect::all(Val::Px(6.0)),
+                display: Display::None,
+                ..default()
+            })
+            .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.8)),
+    ));
 }
 
 fn update_inventory_ui(
-    player: Query<&Inventory, (With<PlayerControllerTag>, Changed<Inventory>)>,
-    mut material_text: Query<(&mut Text, &mut Style, &ItemText)>,
+    player: Query<&Inventory, With<PlayerControllerTag>>,
+    item_metadata: Res<ItemMetadata>,
+    metadata_assets: Res<Assets<ItemMetadataAsset>>,
+    asset_server: Res<AssetServer>,
+    mut slots: Query<(&mut Style, &ItemSlot)>,
+    mut icons: Query<(&mut UiImage, &ItemSlotIcon)>,
+    mut texts: Query<(&mut Text, &ItemSlotText)>,
+    mut icons_set: Local<bool>,
 ) {
+    let metadata = metadata_assets.get(&item_metadata.0);
+    if let (Some(metadata), false) = (metadata, *icons_set) {
+        for (mut image, icon) in icons.iter_mut() {
+            if let Some(data) = metadata.0.get(&icon.0) {
+                image.texture = asset_server.load(&data.icon);
+            }
+        }
+        *icons_set = true;
+    }
     let Ok(inventory) = player.get_single() else {
-        // Inventory hasn't changed, return.
         return;
     };
+    for (mut style, slot) in slots.iter_mut() {
+        let display = match inventory.get_item_count(slot.0) {
+            0 => Display::None,
+            _ => Display::Flex,
+        };
+        if style.display != display {
+            style.display = display;
+        }
+    }
+    for (mut text, slot) in texts.iter_mut() {
+        let count = inventory.get_item_count(slot.0);
+        let value = match metadata.and_then(|m| m.max_stack(slot.0)) {
+            Some(max) => format!("{count}/{max}"),
+            None => count.to_string(),
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
 
-    for (mut text, mut style, material) in material_text.iter_mut() {
-        let count = inventory.get_item_count(material.0);
-        if count > 0 {
-            style.display = Display::Flex;
-            text.sections[0].value = format!("{}: {}", material.0, count);
-        } else {
+fn show_item_tooltip(
+    slots: Query<(&Interaction, &ItemSlot)>,
+    item_metadata: Res<ItemMetadata>,
+    metadata_assets: Res<Assets<ItemMetadataAsset>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut tooltip: Query<(&mut Text, &mut Style), With<ItemTooltip>>,
+) {
+    let Ok((mut text, mut style)) = tooltip.get_single_mut() else {
+        return;
+    };
+    let hovered = slots
+        .iter()
+        .find(|(interaction, _)| **interaction != Interaction::None)
+        .map(|(_, slot)| slot.0);
+    let cursor = window.get_single().ok().and_then(|w| w.cursor_position());
+    let (Some(item), Some(cursor)) = (hovered, cursor) else {
+        if style.display != Display::None {
             style.display = Display::None;
         }
+        return;
+    };
+    style.display = Display::Flex;
+    style.left = Val::Px(cursor.x + TOOLTIP_OFFSET.x);
+    style.top = Val::Px(cursor.y + TOOLTIP_OFFSET.y);
+    text.sections[0].value = match metadata_assets
+        .get(&item_metadata.0)
+        .and_then(|m| m.0.get(&item))
+    {
+        Some(data) => format!("{item}\n{}\nCtrl+click to drop", data.description),
+        None => item.to_string(),
+    };
+}
+
+fn drop_items(
+    slots: Query<(&Interaction, &ItemSlot), Changed<Interaction>>,
+    keyboard: Res<Input<KeyCode>>,
+    mut player: Query<(Entity, &mut Inventory, &GlobalTransform), With<PlayerControllerTag>>,
+    mut spawn_item_events: EventWriter<SpawnItemEvent>,
+) {
+    if !keyboard.any_pressed(DROP_MODIFIERS) {
+        return;
+    }
+    let Ok((player_entity, mut inventory, transform)) = player.get_single_mut() else {
+        return;
+    };
+    for (interaction, slot) in slots.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let count = match keyboard.any_pressed(DROP_STACK_MODIFIERS) {
+            true => inventory.get_item_count(slot.0),
+            false => 1,
+        };
+        if count == 0 || !inventory.spend_item(slot.0, count) {
+            continue;
+        }
+        spawn_item_events.send_batch((0..count).map(|_| SpawnItemEvent {
+            item: slot.0,
+            pos: transform.translation() + Vec3::Y * DROP_HEIGHT,
+            dropped_by: Some(player_entity),
+        }));
+    }
+}
+
+#[derive(Default)]
+pub struct ItemMetadataAssetLoader;
+
+impl AssetLoader for ItemMetadataAssetLoader {
+    type Asset = ItemMetadataAsset;
+    type Settings = ();
+    type Error = CustomAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let asset = ron::de::from_bytes::<ItemMetadataAsset>(&bytes)?;
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["items.ron"]
     }
 }
@@ -1,34 +1,110 @@
-use std::ops::Range;
-
-use bevy::{ecs::query::Has, math::vec3, prelude::*, utils::HashMap};
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    ecs::query::Has,
+    math::vec3,
+    prelude::*,
+    reflect::TypePath,
+    utils::{HashMap, HashSet},
+};
 use bevy_rapier3d::prelude::*;
-use rand::{thread_rng, Rng};
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use serde::Deserialize;
 
 use crate::{
+    asset_utils::CustomAssetLoaderError,
+    camera::MainCameraTag,
     collision_groups::{COLLISION_CHARACTER, COLLISION_ITEM_PICKUP, COLLISION_WORLD},
-    inventory::{Inventory, Item},
-    pickup::{OnPickedUpEvent, PickupTag},
+    inventory::{Inventory, Item, ItemMetadata, ItemMetadataAsset},
+    mixer::{PlaySfxEvent, SfxLimit},
+    pickup::{DroppedBy, OnPickedUpEvent, PickupTag},
+    player::Body,
+    tree::Species,
+    ui_util::UiAssets,
 };
 
 const ITEM_LIFETIME: f32 = 20.0;
+// items blink for this many seconds before they vanish
+const BLINK_TIME: f32 = 5.0;
+// pickups of the same item this close together merge into one stack
+const MERGE_RADIUS: f32 = 1.0;
+// seconds between merge passes
+const MERGE_INTERVAL: f32 = 0.5;
+// picking up a whole pile at once plays a few quick pickup sounds, not one per item
+const PICKUP_SFX_LIMIT: SfxLimit = SfxLimit {
+    cooldown: 0.05,
+    max_playing: 3,
+};
 
-#[derive(Component)]
-pub struct SpawnItemEvery {
-    pub range: Range<f32>,
+#[derive(Event)]
+pub struct SpawnItemEvent {
     pub item: Item,
-    pub next: f64,
+    pub pos: Vec3,
+    // can't pick its own drop back up right away
+    pub dropped_by: Option<Entity>,
+}
+/// Whatever is dropping items, each has a table in drops.drops.ron.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum DropSource {
+    // anything with health getting hit
+    Hit,
+    BananaTree,
+    AppleTree,
+    // a tree of this species getting hit
+    Tree(Species),
+    Body(Body),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct WeightedDrop {
+    // None drops nothing
+    pub item: Option<Item>,
+    #[serde(default = "default_drop_count")]
+    pub count: u32,
+    pub weight: f32,
+}
+
+fn default_drop_count() -> u32 {
+    1
+}
+
+/// Every list picks one of its entries, weighted.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(transparent)]
+pub struct DropTable(pub Vec<Vec<WeightedDrop>>);
+
+impl DropTable {
+    /// Rolls every list, yielding the items that dropped and how many.
+    pub fn roll(&self) -> impl Iterator<Item = (Item, u32)> + '_ {
+        let mut rng = thread_rng();
+        self.0
+            .iter()
+            .filter_map(move |drops| drops.choose_weighted(&mut rng, |d| d.weight).ok())
+            .filter_map(|drop| Some((drop.item?, drop.count)))
+    }
 }
 
+#[derive(Debug, Deserialize, Asset, TypePath)]
+pub struct DropTablesAsset(pub HashMap<DropSource, DropTable>);
+
+#[derive(Resource)]
+pub struct DropTables(pub Handle<DropTablesAsset>);
+
+/// Rolls the source's drop table and spawns whatever dropped at `pos`.
 #[derive(Event)]
-pub struct SpawnItemEvent {
-    pub item: Item,
+pub struct SpawnDropsEvent {
+    pub source: DropSource,
     pub pos: Vec3,
 }
+
+/// Drops from this source's table when health reaches 0, see despawn_0_system.
+#[derive(Component)]
+pub struct DropsOnDeath(pub DropSource);
+
 #[derive(Resource)]
 pub struct ItemModels(HashMap<Item, Vec<Handle<Scene>>>);
 
 #[derive(Component)]
-pub struct ItemPickup(Item);
+pub struct ItemPickup(pub Item, pub u32);
 
 #[derive(Component)]
 pub struct DespawnAfter(f32);
@@ -36,78 +112,234 @@ pub struct DespawnAfter(f32);
 #[derive(Component)]
 pub struct PickupSound;
 
+// the count shown over a stacked pickup
+#[derive(Component)]
+struct StackLabel(Entity);
+
 pub struct ItemPickupPlugin;
 
 impl Plugin for ItemPickupPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<OnPickedUpEvent>()
+        app.init_asset::<DropTablesAsset>()
+            .init_asset_loader::<DropTablesAssetLoader>()
+            .add_event::<OnPickedUpEvent>()
             .add_event::<SpawnItemEvent>()
+            .add_event::<SpawnDropsEvent>()
             .add_systems(Startup, setup_item_pickup_resources)
             .add_systems(
                 Update,
-                (despawn_after, spawn_item_every, spawn_items, perform_pickup),
+                (
+                    despawn_after,
+                    (spawn_drops, spawn_items).chain(),
+                    perform_pickup,
+                    (merge_pickups, update_stack_labels).chain(),
+                ),
             );
     }
 }
 
 fn despawn_after(
     mut commands: Commands,
-    mut despawn: Query<(Entity, &mut DespawnAfter)>,
+    mut despawn: Query<(Entity, &mut DespawnAfter, Option<&mut Visibility>)>,
     time: Res<Time>,
 ) {
-    for (entity, mut despawn) in despawn.iter_mut() {
+    for (entity, mut despawn, visibility) in despawn.iter_mut() {
         despawn.0 -= time.delta_seconds();
         if despawn.0 <= 0.0 {
             commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let Some(mut visibility) = visibility else {
+            continue;
+        };
+        // blinks faster the closer it is to vanishing, merged stacks can get time back
+        let shown = match despawn.0 < BLINK_TIME {
+            true => {
+                let rate = 2.0 + (BLINK_TIME - despawn.0) * 2.0;
+                (despawn.0 * rate).fract() < 0.6
+            }
+            false => true,
+        };
+        let wanted = match shown {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+        if *visibility != wanted {
+            *visibility = wanted;
         }
     }
 }
 
-fn spawn_item_every(
-    mut spawn_item: EventWriter<SpawnItemEvent>,
-    time: Res<Time>,
-    mut spawn_item_every: Query<(&mut SpawnItemEvery, &GlobalTransform)>,
+fn spawn_drops(
+    mut events: EventReader<SpawnDropsEvent>,
+    mut spawn_item_events: EventWriter<SpawnItemEvent>,
+    drop_tables: Res<DropTables>,
+    drop_table_assets: Res<Assets<DropTablesAsset>>,
 ) {
-    spawn_item.send_batch(
-        spawn_item_every
-            .iter_mut()
-            .filter_map(|(mut spawn, transform)| {
-                if time.elapsed_seconds_f64() >= spawn.next {
-                    spawn.next = time.elapsed_seconds_f64()
-                        + thread_rng().gen_range(spawn.range.clone()) as f64;
-                    Some(SpawnItemEvent {
-                        item: spawn.item,
-                        pos: transform.translation(),
-                    })
-                } else {
-                    None
-                }
-            }),
-    );
+    let Some(tables) = drop_table_assets.get(&drop_tables.0) else {
+        return;
+    };
+    for event in events.read() {
+        let Some(table) = tables.0.get(&event.source) else {
+            continue;
+        };
+        for (item, count) in table.roll() {
+            spawn_item_events.send_batch((0..count).map(|_| SpawnItemEvent {
+                item,
+                pos: event.pos,
+                dropped_by: None,
+            }));
+        }
+    }
 }
 
 fn perform_pickup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
     mut pickup_events: EventReader<OnPickedUpEvent>,
     item_pickups: Query<&ItemPickup>,
-    mut receivers: Query<(&mut Inventory, Has<PickupSound>)>,
+    mut receivers: Query<(&mut Inventory, &GlobalTransform, Has<PickupSound>)>,
+    (item_metadata, metadata_assets): (Res<ItemMetadata>, Res<Assets<ItemMetadataAsset>>),
+    mut spawn_item_events: EventWriter<SpawnItemEvent>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
 ) {
+    let metadata = metadata_assets.get(&item_metadata.0);
     for event in pickup_events.read() {
         let Ok(item) = item_pickups.get(event.pickup_entity) else {
             continue;
         };
-        let Ok((mut receiver, sound)) = receivers.get_mut(event.receiver_entity) else {
+        let Ok((mut receiver, transform, sound)) = receivers.get_mut(event.receiver_entity) else {
             continue;
         };
 
-        receiver.add_item(item.0, 1);
+        // anything over the max stack is dropped back on the ground
+        let room = match metadata.and_then(|m| m.max_stack(item.0)) {
+            Some(max) => max.saturating_sub(receiver.get_item_count(item.0)),
+            None => item.1,
+        };
+        let added = item.1.min(room);
+        if added > 0 {
+            receiver.add_item(item.0, added);
+        }
+        spawn_item_events.send_batch((added..item.1).map(|_| SpawnItemEvent {
+            item: item.0,
+            pos: transform.translation(),
+            dropped_by: Some(event.receiver_entity),
+        }));
         if sound {
-            commands.spawn(AudioBundle {
-                source: asset_server.load("sounds/item_pickup.ogg"),
-                settings: PlaybackSettings::DESPAWN,
-            });
+            sfx_events.send(
+                PlaySfxEvent::new("item_pickup")
+                    .at(transform.translation())
+                    .limited(PICKUP_SFX_LIMIT),
+            );
+        }
+    }
+}
+
+fn merge_pickups(
+    mut commands: Commands,
+    mut pickups: Query<(Entity, &mut ItemPickup, &Transform, &mut DespawnAfter), With<PickupTag>>,
+    time: Res<Time>,
+    mut since_merge: Local<f32>,
+) {
+    *since_merge += time.delta_seconds();
+    if *since_merge < MERGE_INTERVAL {
+        return;
+    }
+    *since_merge = 0.0;
+    let mut stacks = pickups
+        .iter()
+        .map(|(entity, pickup, transform, despawn)| {
+            (entity, pickup.0, pickup.1, transform.translation, despawn.0)
+        })
+        .collect::<Vec<_>>();
+    let mut merged = vec![false; stacks.len()];
+    for i in 0..stacks.len() {
+        if merged[i] {
+            continue;
+        }
+        for j in (i + 1)..stacks.len() {
+            let (other, item, count, pos, lifetime) = stacks[j];
+            if merged[j]
+                || item != stacks[i].1
+                || pos.distance_squared(stacks[i].3) > MERGE_RADIUS.powi(2)
+            {
+                continue;
+            }
+            // the stack lives as long as its freshest item would have
+            stacks[i].2 += count;
+            stacks[i].4 = stacks[i].4.max(lifetime);
+            merged[j] = true;
+            commands.entity(other).despawn_recursive();
+        }
+    }
+    for (&(entity, _, count, _, lifetime), _) in stacks.iter().zip(merged).filter(|(_, m)| !m) {
+        if let Ok((_, mut pickup, _, mut despawn)) = pickups.get_mut(entity) {
+            if pickup.1 != count {
+                pickup.1 = count;
+                despawn.0 = lifetime;
+            }
+        }
+    }
+}
+
+// stacks on the ground get a label following them on screen
+fn update_stack_labels(
+    mut commands: Commands,
+    pickups: Query<(Entity, &ItemPickup, &GlobalTransform), With<PickupTag>>,
+    mut labels: Query<(Entity, &StackLabel, &mut Text, &mut Style)>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCameraTag>>,
+    ui_scale: Res<UiScale>,
+    ui_assets: Res<UiAssets>,
+) {
+    let Ok((camera, camera_transform)) = camera.get_single() else {
+        return;
+    };
+    let screen_pos = |pos: Vec3| {
+        camera
+            .world_to_viewport(camera_transform, pos + Vec3::Y * 0.5)
+            .map(|p| p / ui_scale.0 as f32)
+    };
+    let mut labeled = HashSet::new();
+    for (label_entity, label, mut text, mut style) in labels.iter_mut() {
+        let Some((pickup, pos)) = pickups
+            .get(label.0)
+            .ok()
+            .filter(|(_, pickup, _)| pickup.1 > 1)
+            .and_then(|(_, pickup, transform)| {
+                Some((pickup, screen_pos(transform.translation())?))
+            })
+        else {
+            commands.entity(label_entity).despawn_recursive();
+            continue;
+        };
+        labeled.insert(label.0);
+        text.sections[0].value = format!("x{}", pickup.1);
+        style.left = Val::Px(pos.x);
+        style.top = Val::Px(pos.y);
+    }
+    for (entity, pickup, transform) in pickups.iter() {
+        if pickup.1 <= 1 || labeled.contains(&entity) {
+            continue;
         }
+        let Some(pos) = screen_pos(transform.translation()) else {
+            continue;
+        };
+        commands.spawn((
+            StackLabel(entity),
+            TextBundle::from_section(
+                format!("x{}", pickup.1),
+                TextStyle {
+                    font: ui_assets.font.clone(),
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(pos.x),
+                top: Val::Px(pos.y),
+                ..default()
+            }),
+        ));
     }
 }
 
@@ -120,11 +352,15 @@ fn spawn_items(
     for event in events.read() {
         let model_handle = item_models.0[&event.item][0].clone();
 
+        let mut entity_commands = commands.spawn_empty();
+        if let Some(dropper) = event.dropped_by {
+            entity_commands.insert(DroppedBy::new(dropper));
+        }
         let collider_height = 0.4;
         let collider_radius = 0.1;
         let torque = 0.1;
-        commands.spawn((
-            ItemPickup(event.item),
+        entity_commands.insert((
+            ItemPickup(event.item, 1),
             PickupTag,
             SceneBundle {
                 scene: model_handle,
@@ -162,6 +398,7 @@ fn spawn_items(
 }
 
 fn setup_item_pickup_resources(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(DropTables(asset_server.load("drops.drops.ron")));
     commands.insert_resource(ItemModels(HashMap::from_iter([
         (
             Item::Log,
@@ -175,5 +412,41 @@ fn setup_item_pickup_resources(mut commands: Commands, asset_server: Res<AssetSe
             Item::Apple,
             vec![asset_server.load("models/items/apple_model.gltf#Scene0")],
         ),
+        (
+            Item::Quiver,
+            vec![asset_server.load("models/projectiles/arrow.gltf#Scene0")],
+        ),
+        // no scrap model yet
+        (
+            Item::Scrap,
+            vec![asset_server.load("models/projectiles/arrow.gltf#Scene0")],
+        ),
     ])));
 }
+
+#[derive(Default)]
+pub struct DropTablesAssetLoader;
+
+impl AssetLoader for DropTablesAssetLoader {
+    type Asset = DropTablesAsset;
+    type Settings = ();
+    type Error = CustomAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let asset = ron::de::from_bytes::<DropTablesAsset>(&bytes)?;
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["drops.ron"]
+    }
+}
@@ -1,4 +1,4 @@
-use crate::health::ApplyHealthEvent;
+use crate::{health::ApplyHealthEvent, weapon::WeaponStats};
 use bevy::prelude::*;
 use bevy_rapier3d::dynamics::Velocity;
 
@@ -7,27 +7,140 @@ pub struct KnockbackPlugin;
 #[derive(Component)]
 pub struct KnockbackRetriever;
 
+/// Push every body within `radius` away from `origin`.
+#[derive(Event, Clone, Copy)]
+pub struct ApplyKnockbackEvent {
+    pub origin: Vec3,
+    pub strength: f32,
+    pub radius: f32,
+    /// how much weaker the push is at the edge, 0.0 is the same everywhere, 1.0 is nothing at the edge
+    pub falloff: f32,
+}
+
+/// How much knockback is ignored, 0.0 takes all of it, 1.0 takes none.
+#[derive(Component)]
+pub struct KnockbackResistance(pub f32);
+
+#[derive(Resource, Reflect)]
+pub struct KnockbackSettings {
+    /// horizontal push every hit gives
+    pub base: f32,
+    /// extra horizontal push for every point of damage
+    pub per_damage: f32,
+    /// upwards velocity given to whatever got hit
+    pub vertical_pop: f32,
+    /// a single hit pushing harder than this staggers the target
+    pub stagger_threshold: f32,
+    /// seconds a stagger lasts
+    pub stagger_duration: f32,
+    /// critical hits push this many times harder
+    pub crit_mul: f32,
+}
+
+/// Can't move or attack until the timer runs out, see `player::stagger`
+#[derive(Component)]
+pub struct Staggered(pub Timer);
+
+impl Default for KnockbackSettings {
+    fn default() -> Self {
+        Self {
+            base: 14.0,
+            per_damage: 4.0,
+            vertical_pop: 7.0,
+            stagger_threshold: 30.0,
+            stagger_duration: 0.35,
+            crit_mul: 1.5,
+        }
+    }
+}
+
 impl Plugin for KnockbackPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, apply_knockback_on_health_event);
+        app.register_type::<KnockbackSettings>()
+            .add_event::<ApplyKnockbackEvent>()
+            .init_resource::<KnockbackSettings>()
+            .add_systems(
+                Update,
+                (apply_knockback_on_health_event, apply_radial_knockback),
+            );
     }
 }
 
 fn apply_knockback_on_health_event(
+    mut commands: Commands,
     mut events: EventReader<ApplyHealthEvent>,
-    mut query: Query<(&mut Velocity, &Transform)>,
+    mut query: Query<(&mut Velocity, &Transform, Option<&KnockbackResistance>)>,
+    casters: Query<(&GlobalTransform, Option<&WeaponStats>)>,
+    settings: Res<KnockbackSettings>,
 ) {
     for event in events.read() {
-        let Ok((_bd, transform)) = query.get_mut(event.caster_entity) else {
+        // heals and hurting yourself shouldn't push anything around
+        if event.amount >= 0 || event.caster_entity == event.target_entity {
+            continue;
+        }
+        let Ok((caster_transform, caster_stats)) = casters.get(event.caster_entity) else {
             continue;
         };
-        let caster_pos = transform.translation;
-        let Ok((mut bd, transform)) = query.get_mut(event.target_entity) else {
+        let Ok((mut bd, transform, resistance)) = query.get_mut(event.target_entity) else {
             continue;
         };
+        let resistance = resistance.map_or(0.0, |r| r.0.clamp(0.0, 1.0));
+        if resistance >= 1.0 {
+            continue;
+        }
+        let mut scale = (1.0 - resistance) * caster_stats.map_or(1.0, |s| s.knockback_mul);
+        // the last swing of a combo sends things flying
+        if let (Some(stats), true) = (caster_stats, event.is_finisher) {
+            scale *= stats.finisher_knockback_mul;
+        }
+        if event.is_crit {
+            scale *= settings.crit_mul;
+        }
+
+        let caster_pos = caster_transform.translation();
         let target_pos = transform.translation;
-        let to = (caster_pos - target_pos).normalize();
-        bd.linvel -= to * 20.0;
-        bd.linvel.y = 7.0;
+        let mut to = caster_pos - target_pos;
+        to.y = 0.0;
+        let to = to.normalize_or_zero();
+        let strength = settings.base + settings.per_damage * (-event.amount) as f32;
+        bd.linvel -= to * strength * scale;
+        bd.linvel.y = settings.vertical_pop * scale;
+
+        if strength * scale > settings.stagger_threshold {
+            commands
+                .entity(event.target_entity)
+                .insert(Staggered(Timer::from_seconds(
+                    settings.stagger_duration,
+                    TimerMode::Once,
+                )));
+        }
+    }
+}
+
+fn apply_radial_knockback(
+    mut events: EventReader<ApplyKnockbackEvent>,
+    mut query: Query<(
+        &mut Velocity,
+        &GlobalTransform,
+        Option<&KnockbackResistance>,
+    )>,
+    settings: Res<KnockbackSettings>,
+) {
+    for event in events.read() {
+        for (mut bd, transform, resistance) in query.iter_mut() {
+            let resistance = resistance.map_or(0.0, |r| r.0.clamp(0.0, 1.0));
+            let mut away = transform.translation() - event.origin;
+            away.y = 0.0;
+            let distance = away.length();
+            if distance > event.radius || resistance >= 1.0 {
+                continue;
+            }
+            let edge = (distance / event.radius.max(f32::EPSILON)).min(1.0);
+            let scale = (1.0 - event.falloff.clamp(0.0, 1.0) * edge) * (1.0 - resistance);
+            // standing right on the origin, pick any direction
+            let dir = away.try_normalize().unwrap_or(Vec3::X);
+            bd.linvel += dir * event.strength * scale;
+            bd.linvel.y = settings.vertical_pop * scale;
+        }
     }
 }
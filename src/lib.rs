@@ -1,3 +1,5 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
 mod collision_groups {
     pub const COLLISION_CHARACTER: u32 = 1;
     pub const COLLISION_WORLD: u32 = 1 << 1;
@@ -10,29 +12,80 @@ mod collision_groups {
     pub const COLLISION_BORDER: u32 = 1 << 7;
 }
 
+pub mod achievements;
+pub mod activity_lod;
+pub mod ammo;
 pub mod camera;
+pub mod camera_shake;
+pub mod combat_stats;
+pub mod companion;
+pub mod crafting;
+pub mod cutscene;
+pub mod demolish;
+pub mod depot;
+pub mod dissolve_material;
+pub mod experience;
+pub mod fixed_step;
+pub mod forest_hud;
+pub mod fruit;
+pub mod game_speed;
 pub mod health;
+pub mod highscores;
+pub mod hotbar;
+pub mod input_map;
 pub mod inventory;
 pub mod item_pickups;
 pub mod map;
+pub mod map_gen;
+pub mod menu;
+pub mod minimap;
+pub mod mixer;
+pub mod music;
 pub mod notification;
+pub mod offscreen_indicators;
+pub mod particles;
+pub mod pathfinding;
+pub mod pause;
+pub mod perks;
+pub mod persistence;
 pub mod pickup;
+pub mod placement;
 pub mod player;
 pub mod pointer;
+pub mod profile;
 pub mod projectile;
+pub mod repair;
+pub mod robot_ai;
+pub mod sapper;
+pub mod save_game;
+pub mod selection;
+pub mod settings;
 pub mod shop;
+pub mod shop_stall;
+pub mod shrine;
+pub mod sound_sets;
+pub mod spatial_grid;
 pub mod state;
+pub mod status_effect;
+pub mod stump;
+pub mod threat;
 pub mod tower;
+pub mod tower_upgrade;
 pub mod tree;
+pub mod tutorial;
 pub mod ui_util;
 pub mod utils;
+pub mod wall;
+pub mod wave_spawner;
 pub mod waves;
 pub mod weapon;
+pub mod weather;
 
 pub mod animation_linker;
 pub mod asset_utils;
 pub mod background;
 pub mod border_material;
+pub mod boss;
 pub mod foliage;
 pub mod ground_material;
 pub mod knockback;
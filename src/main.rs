@@ -1,49 +1,115 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 use bevy::{
-    audio::{Volume, VolumeLevel},
+    audio::{AudioPlugin, SpatialScale},
     math::vec3,
     prelude::*,
 };
 use bevy_rapier3d::prelude::*;
 use bevy_vector_shapes::ShapePlugin;
 use no_communication_0::{
+    achievements::AchievementsPlugin,
+    activity_lod::ActivityLodPlugin,
+    ammo::AmmoPlugin,
     animation_linker::AnimationEntityLinkPlugin,
     background::{setup_space_bg, SpaceMaterial},
     border_material::BorderMaterialPlugin,
+    boss::BossPlugin,
     camera::{CameraPlugin, DollyCamera, FollowPlayerCamera, MainCameraTag},
+    camera_shake::CameraShakePlugin,
+    combat_stats::CombatStatsPlugin,
+    companion::CompanionPlugin,
+    crafting::CraftingPlugin,
+    cutscene::CutscenePlugin,
+    demolish::DemolishPlugin,
+    depot::DepotPlugin,
+    dissolve_material::DissolveMaterialPlugin,
+    experience::ExperiencePlugin,
+    fixed_step::{FixedStepPlugin, FIXED_TIMESTEP},
     foliage::FoliagePlugin,
+    forest_hud::ForestHudPlugin,
+    fruit::FruitPlugin,
+    game_speed::GameSpeedPlugin,
     ground_material::GroundMaterialPlugin,
     health::HealthPlugin,
-    inventory::{InventoryPlugin, Item},
+    highscores::HighScorePlugin,
+    hotbar::{Hotbar, HotbarPlugin},
+    input_map::InputMapPlugin,
+    inventory::InventoryPlugin,
     item_pickups::ItemPickupPlugin,
     knockback::KnockbackPlugin,
-    map::{MapPlugin, MAP_SIZE_HALF},
+    map::{CurrentMap, MapPlugin},
+    map_gen::MapGenPlugin,
+    menu::MenuPlugin,
+    minimap::MinimapPlugin,
+    mixer::{MixerPlugin, SPATIAL_SCALE},
+    music::MusicPlugin,
     notification::{NotificationEvent, NotificationPlugin},
+    offscreen_indicators::OffscreenIndicatorPlugin,
+    particles::ParticlePlugin,
+    pathfinding::PathfindingPlugin,
+    pause::PausePlugin,
+    perks::PerkPlugin,
     pickup::PickupPlugin,
-    player::{Body, PlayerPlugin, SpawnPlayerEvent},
+    placement::PlacementPlugin,
+    player::{Body, Controller, PlayerPlugin, SpawnPlayerEvent},
     pointer::PointerPlugin,
+    profile::{Profile, ProfilePlugin, Unlock},
     projectile::ProjectilePlugin,
-    shop::{ShopItemData, ShopItemEffect, ShopPlugin, SpawnShopItemEvent},
-    state::{AppState, StatePlugin},
+    repair::RepairPlugin,
+    sapper::SapperPlugin,
+    save_game::SaveGamePlugin,
+    selection::SelectionPlugin,
+    settings::{load_settings, SettingsPlugin},
+    shop::ShopPlugin,
+    shop_stall::ShopStallPlugin,
+    shrine::ShrinePlugin,
+    sound_sets::SoundSetPlugin,
+    spatial_grid::SpatialGridPlugin,
+    state::{AppState, RestartRunEvent, StatePlugin},
+    status_effect::StatusEffectPlugin,
+    stump::StumpPlugin,
+    threat::ThreatPlugin,
     tower::TowerPlugin,
+    tower_upgrade::TowerUpgradePlugin,
     tree::{TreePlugin, TriggerSpawnTrees},
     tree_spawner::TreeSpawnerPlugin,
+    tutorial::TutorialPlugin,
     ui_util::UiUtilPlugin,
+    wall::WallPlugin,
+    wave_spawner::WaveSpawnerPlugin,
     waves::WavePlugin,
-    weapon::{AxeSfxCooldownTimer, ProjSfxCooldownTimer, WeaponPlugin, WeaponType},
+    weapon::WeaponPlugin,
+    weather::WeatherPlugin,
 };
 use rand::Rng;
 
+// every launch generates the same rocks, ponds and bumps for each map from this
+const MAP_SEED: u64 = 0;
+
 fn main() {
+    // before DefaultPlugins, the window is created the way the settings describe it
+    let settings = load_settings();
     App::new()
         .add_plugins((
-            DefaultPlugins,
-            RapierPhysicsPlugin::<NoUserData>::default(),
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(settings.display.window()),
+                    ..default()
+                })
+                .set(AudioPlugin {
+                    spatial_scale: SpatialScale::new(SPATIAL_SCALE),
+                    ..default()
+                }),
+            RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule(),
             ShapePlugin::default(),
         ))
         // Our plugins
         .add_plugins((
-            (BorderMaterialPlugin, GroundMaterialPlugin),
+            (
+                BorderMaterialPlugin,
+                GroundMaterialPlugin,
+                DissolveMaterialPlugin,
+            ),
             (
                 UiUtilPlugin,
                 CameraPlugin,
@@ -58,6 +124,7 @@ fn main() {
                 ShopPlugin,
                 PointerPlugin,
                 MapPlugin,
+                MapGenPlugin { seed: MAP_SEED },
                 NotificationPlugin,
             ),
             (
@@ -69,80 +136,79 @@ fn main() {
                 TreeSpawnerPlugin,
                 FoliagePlugin,
                 MaterialPlugin::<SpaceMaterial>::default(),
+                ThreatPlugin,
+                AmmoPlugin,
+                HotbarPlugin,
+                CombatStatsPlugin,
+                CraftingPlugin,
+                SelectionPlugin,
+                DemolishPlugin,
+            ),
+            (
+                CutscenePlugin,
+                BossPlugin,
+                AchievementsPlugin,
+                SettingsPlugin { settings },
+                PausePlugin,
+                CompanionPlugin,
+                PathfindingPlugin,
+                SaveGamePlugin,
+                MenuPlugin,
+                StatusEffectPlugin,
+                TowerUpgradePlugin,
+                PlacementPlugin,
+                InputMapPlugin,
+                WaveSpawnerPlugin,
+                SapperPlugin,
+            ),
+            (
+                RepairPlugin,
+                WallPlugin,
+                ShrinePlugin,
+                StumpPlugin,
+                FruitPlugin,
+                ForestHudPlugin,
+                DepotPlugin,
+                ExperiencePlugin,
+                PerkPlugin,
+                ProfilePlugin,
+                HighScorePlugin,
+                TutorialPlugin,
+                MinimapPlugin,
+                OffscreenIndicatorPlugin,
+                CameraShakePlugin,
+            ),
+            (
+                WeatherPlugin,
+                SpatialGridPlugin,
+                ActivityLodPlugin,
+                FixedStepPlugin,
+                GameSpeedPlugin,
+                MixerPlugin,
+                SoundSetPlugin,
+                MusicPlugin,
+                ParticlePlugin,
+                ShopStallPlugin,
             ),
         ))
-        // debug + large amount of rapier objects LAGS a lot, reduce MAP_SIZE_HALF in that case
+        // debug + large amount of rapier objects LAGS a lot, pick a small map in that case
         // .add_plugins(RapierDebugRenderPlugin::default())
         // edit camera settings in ui
         // .add_plugins(ResourceInspectorPlugin::<FollowCameraSettings>::default())
         // Enable for inspector
         // .add_plugins(bevy_inspector_egui::quick::WorldInspectorPlugin::new())
         .add_systems(Startup, (setup, setup_space_bg))
+        .add_systems(OnEnter(AppState::Playing), start_run)
+        .add_systems(PreUpdate, start_run.run_if(on_event::<RestartRunEvent>()))
         .run();
 }
 
-fn setup(
-    mut commands: Commands,
-    mut rapier_config: ResMut<RapierConfiguration>,
-    mut spawn_player_event: EventWriter<SpawnPlayerEvent>,
-    mut spawn_shop_item_event: EventWriter<SpawnShopItemEvent>,
-    mut notification_event: EventWriter<NotificationEvent>,
-    mut tree_trigger_writer: EventWriter<TriggerSpawnTrees>,
-    asset_server: Res<AssetServer>,
-) {
-    commands.spawn(AudioBundle {
-        source: asset_server.load("sounds/8bit-spaceshooter.ogg"),
-        settings: PlaybackSettings::LOOP.with_volume(Volume::Absolute(VolumeLevel::new(0.3))),
-    });
-    tree_trigger_writer.send(TriggerSpawnTrees(0.1));
-
+fn setup(mut commands: Commands, mut rapier_config: ResMut<RapierConfiguration>) {
     rapier_config.gravity = Vec3::NEG_Y * 100.0;
-
-    let mut rng = rand::thread_rng();
-    spawn_player_event.send(SpawnPlayerEvent {
-        pos: vec3(
-            rng.gen_range(-MAP_SIZE_HALF..MAP_SIZE_HALF),
-            1.0,
-            rng.gen_range(-MAP_SIZE_HALF..MAP_SIZE_HALF),
-        ),
-        is_main: true,
-        body: Body::Monkey,
-        weapon_type: WeaponType::Bow(asset_server.load("projectiles/bow.projectile.ron")),
-    });
-    let mut x = MAP_SIZE_HALF + rng.gen_range(10.0..20.0);
-    let mut z = MAP_SIZE_HALF + rng.gen_range(10.0..20.0);
-    x *= match rng.gen::<bool>() {
-        true => 1.0,
-        false => -1.0,
+    rapier_config.timestep_mode = TimestepMode::Fixed {
+        dt: FIXED_TIMESTEP as f32,
+        substeps: 1,
     };
-    z *= match rng.gen::<bool>() {
-        true => 1.0,
-        false => -1.0,
-    };
-
-    spawn_player_event.send(SpawnPlayerEvent {
-        pos: vec3(x, 4.0, z),
-        is_main: false,
-        body: Body::Robot,
-        weapon_type: WeaponType::Axe,
-    });
-
-    {
-        spawn_shop_item_event.send(SpawnShopItemEvent {
-            item: ShopItemData {
-                cost: vec![(Item::Log, 1)],
-                effects: vec![(ShopItemEffect::PlantTree)],
-                permanent: true,
-            },
-        });
-        spawn_shop_item_event.send(SpawnShopItemEvent {
-            item: ShopItemData {
-                cost: vec![(Item::Apple, 2)],
-                effects: vec![(ShopItemEffect::Heal(10))],
-                permanent: true,
-            },
-        });
-    }
 
     // light
     commands.insert_resource(AmbientLight {
@@ -168,11 +234,6 @@ fn setup(
     let pos = transform.translation;
     let rotation = transform.rotation;
 
-    // appstate
-    commands.insert_resource(AppState::Wave(0));
-    commands.insert_resource(AxeSfxCooldownTimer(0.0));
-    commands.insert_resource(ProjSfxCooldownTimer(0.0));
-
     // camera
     commands.spawn((
         MainCameraTag,
@@ -183,6 +244,43 @@ fn setup(
             ..default()
         },
     ));
+}
+
+// everything a new run starts with, runs again on "Restart"
+fn start_run(
+    mut commands: Commands,
+    mut spawn_player_event: EventWriter<SpawnPlayerEvent>,
+    mut notification_event: EventWriter<NotificationEvent>,
+    mut tree_trigger_writer: EventWriter<TriggerSpawnTrees>,
+    asset_server: Res<AssetServer>,
+    profile: Res<Profile>,
+    map: Res<CurrentMap>,
+) {
+    tree_trigger_writer.send(TriggerSpawnTrees {
+        chance: 0.1,
+        outside_of: None,
+    });
+
+    let bow = asset_server.load("weapons/bow.weapon.ron");
+    let mut hotbar = Hotbar::new([bow.clone(), asset_server.load("weapons/axe.weapon.ron")]);
+    if profile.is_unlocked(Unlock::Sledgehammer) {
+        hotbar.add(asset_server.load("weapons/sledgehammer.weapon.ron"));
+    }
+    commands.insert_resource(hotbar);
+    let mut rng = rand::thread_rng();
+    let size_half = map.0.size_half;
+    spawn_player_event.send(SpawnPlayerEvent {
+        pos: vec3(
+            rng.gen_range(-size_half.x..size_half.x),
+            1.0,
+            rng.gen_range(-size_half.y..size_half.y),
+        ),
+        controller: Controller::Player,
+        body: Body::Monkey,
+        weapon: bow,
+        scaling: default(),
+    });
+    // the first robots and shop items come from the first wave
 
     notification_event.send(NotificationEvent {
         text: "Protect The Trees!".into(),
@@ -1,9 +1,11 @@
 use std::f32::consts::FRAC_PI_2;
 
 use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
     math::{vec2, vec3},
     pbr::{ExtendedMaterial, NotShadowCaster, OpaqueRendererMethod},
     prelude::*,
+    reflect::TypePath,
     render::texture::{
         ImageAddressMode, ImageLoaderSettings, ImageSampler, ImageSamplerDescriptor,
     },
@@ -11,53 +13,284 @@ use bevy::{
 use bevy_rapier3d::prelude::*;
 use bracket_noise::prelude::*;
 use rand::Rng;
+use serde::Deserialize;
 
 use crate::{
+    asset_utils::CustomAssetLoaderError,
     border_material::BorderMaterial,
     collision_groups::{COLLISION_BORDER, COLLISION_WORLD},
     ground_material::GroundMaterial,
+    map_gen::{self, MapLayout},
+    notification::NotificationEvent,
+    state::{AppState, RestartRunEvent},
     tree::{SpawnTreeEvent, TreeBlueprint, TriggerSpawnTrees},
 };
 
-pub const MAP_SIZE_HALF: f32 = 20.0;
+// the navigation grid is sized for this, bigger maps are shrunk to fit
+pub const MAX_MAP_SIZE_HALF: f32 = 40.0;
+// covers the largest map, so expanding never runs off the edge
+const GROUND_SIZE: f32 = MAX_MAP_SIZE_HALF * 2.0 + 15.0;
+const WALL_THICKNESS: f32 = 0.5;
+const BORDER_HEIGHT: f32 = 4.0;
 
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup);
-        app.add_systems(Startup, setup_visual_border);
-        app.add_systems(Update, setup_trees);
+        app.init_asset::<MapDefinitionsAsset>()
+            .init_asset_loader::<MapAssetLoader>()
+            .init_resource::<CurrentMap>()
+            .init_resource::<MapBounds>()
+            .add_event::<ExpandMapEvent>()
+            .add_systems(Startup, setup_map_definitions)
+            .add_systems(OnEnter(AppState::Playing), reset_bounds)
+            .add_systems(
+                PreUpdate,
+                reset_bounds.run_if(on_event::<RestartRunEvent>()),
+            )
+            // also runs on the first frame, showing the default map behind the main menu
+            .add_systems(
+                Update,
+                (clear_map, setup, setup_visual_border)
+                    .chain()
+                    .after(map_gen::generate_layout)
+                    .run_if(resource_changed::<CurrentMap>()),
+            )
+            .add_systems(
+                Update,
+                (
+                    expand_map,
+                    update_border.run_if(resource_changed::<MapBounds>()),
+                    setup_trees.after(map_gen::generate_layout),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// How trees are scattered when a run starts.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TreeNoise {
+    pub seed: u64,
+    pub frequency: f32,
+    // noise above this can grow a tree
+    pub threshold: f32,
+}
+
+impl Default for TreeNoise {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            frequency: 100.0,
+            threshold: 0.2,
+        }
+    }
+}
+
+/// One of the maps in maps.map.ron.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MapDefinition {
+    pub name: String,
+    // half the width (x) and depth (z) of the walled area
+    pub size_half: Vec2,
+    // the shop stall moves between these, it stays in the middle when empty
+    #[serde(default)]
+    pub shop_spawns: Vec<Vec2>,
+    #[serde(default)]
+    pub trees: TreeNoise,
+}
+
+#[derive(Debug, Deserialize, Asset, TypePath)]
+pub struct MapDefinitionsAsset(pub Vec<MapDefinition>);
+
+#[derive(Resource)]
+pub struct MapDefinitions(pub Handle<MapDefinitionsAsset>);
+
+/// The map being played, the world is rebuilt when this changes.
+#[derive(Resource)]
+pub struct CurrentMap(pub MapDefinition);
+
+impl Default for CurrentMap {
+    fn default() -> Self {
+        Self(MapDefinition {
+            name: "Meadow".into(),
+            size_half: Vec2::splat(20.0),
+            shop_spawns: vec![],
+            trees: default(),
+        })
+    }
+}
+
+/// Ground, walls and everything else that belongs to the map instead of a run.
+#[derive(Component)]
+pub struct MapTerrain;
+
+/// Where the walls currently are, starts out at the map's size and grows with [`ExpandMapEvent`].
+#[derive(Resource)]
+pub struct MapBounds {
+    pub size_half: Vec2,
+}
+
+impl Default for MapBounds {
+    fn default() -> Self {
+        Self {
+            size_half: CurrentMap::default().0.size_half,
+        }
+    }
+}
+
+impl MapBounds {
+    /// whether `pos` (x and z) is inside the walls with `margin` to spare
+    pub fn contains(&self, pos: Vec2, margin: f32) -> bool {
+        (pos.abs() + margin).cmplt(self.size_half).all()
+    }
+
+    /// false once every wall is at [`MAX_MAP_SIZE_HALF`], the shop won't sell expansions then
+    pub fn can_expand(&self) -> bool {
+        self.size_half.cmplt(Vec2::splat(MAX_MAP_SIZE_HALF)).any()
+    }
+}
+
+/// Pushes every wall outwards by `step` meters, trees grow on the new land.
+#[derive(Event)]
+pub struct ExpandMapEvent {
+    pub step: f32,
+}
+
+/// North is -z.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapSide {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl MapSide {
+    const ALL: [MapSide; 4] = [MapSide::North, MapSide::East, MapSide::South, MapSide::West];
+
+    // the collider sits just outside the playable area
+    fn wall_transform(self, size_half: Vec2) -> Transform {
+        let offset = size_half + WALL_THICKNESS * 0.5;
+        Transform::from_translation(match self {
+            MapSide::North => vec3(0.0, 0.0, -offset.y),
+            MapSide::East => vec3(offset.x, 0.0, 0.0),
+            MapSide::South => vec3(0.0, 0.0, offset.y),
+            MapSide::West => vec3(-offset.x, 0.0, 0.0),
+        })
+    }
+
+    fn wall_collider(self, size_half: Vec2) -> Collider {
+        match self {
+            MapSide::North | MapSide::South => Collider::cuboid(size_half.x, 10.0, WALL_THICKNESS),
+            MapSide::East | MapSide::West => Collider::cuboid(WALL_THICKNESS, 10.0, size_half.y),
+        }
+    }
+
+    // the quads face inwards
+    fn visual_transform(self, size_half: Vec2) -> Transform {
+        let y = BORDER_HEIGHT * 0.5;
+        let (translation, rotation, length) = match self {
+            MapSide::North => (vec3(0.0, y, -size_half.y), 0.0, size_half.x),
+            MapSide::East => (vec3(size_half.x, y, 0.0), -FRAC_PI_2, size_half.y),
+            MapSide::South => (vec3(0.0, y, size_half.y), 0.0, size_half.x),
+            MapSide::West => (vec3(-size_half.x, y, 0.0), FRAC_PI_2, size_half.y),
+        };
+        Transform::from_translation(translation)
+            .with_rotation(Quat::from_rotation_y(rotation))
+            .with_scale(vec3(length * 2.0, 1.0, 1.0))
+    }
+}
+
+#[derive(Component)]
+struct BorderWall(MapSide);
+
+#[derive(Component)]
+struct BorderVisual(MapSide);
+
+fn setup_map_definitions(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(MapDefinitions(asset_server.load("maps.map.ron")));
+}
+
+fn clear_map(mut commands: Commands, terrain: Query<Entity, With<MapTerrain>>) {
+    for entity in terrain.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Every run starts with the walls where the map put them.
+pub fn reset_bounds(mut bounds: ResMut<MapBounds>, map: Res<CurrentMap>) {
+    bounds.size_half = map.0.size_half;
+}
+
+fn expand_map(
+    mut events: EventReader<ExpandMapEvent>,
+    mut bounds: ResMut<MapBounds>,
+    mut tree_trigger_writer: EventWriter<TriggerSpawnTrees>,
+    mut notification_event: EventWriter<NotificationEvent>,
+) {
+    for event in events.read() {
+        let old_size_half = bounds.size_half;
+        let size_half = (old_size_half + event.step).min(Vec2::splat(MAX_MAP_SIZE_HALF));
+        if size_half == old_size_half {
+            continue;
+        }
+        bounds.size_half = size_half;
+        tree_trigger_writer.send(TriggerSpawnTrees {
+            chance: 0.1,
+            outside_of: Some(old_size_half),
+        });
+        notification_event.send(NotificationEvent {
+            text: "The forest grows".into(),
+            show_for: 2.0,
+            color: Color::GREEN,
+        });
     }
 }
 
 fn setup_trees(
     mut ev_reader: EventReader<TriggerSpawnTrees>,
     mut tree_events: EventWriter<SpawnTreeEvent>,
+    layout: Res<MapLayout>,
+    map: Res<CurrentMap>,
+    bounds: Res<MapBounds>,
 ) {
-    let Some(TriggerSpawnTrees(noise_chance)) = ev_reader.read().next() else {
+    let Some(TriggerSpawnTrees {
+        chance: noise_chance,
+        outside_of,
+    }) = ev_reader.read().next()
+    else {
         return;
     };
 
-    let map_size_i = MAP_SIZE_HALF as i32;
+    let map_size_i = bounds.size_half.as_ivec2();
+    let tree_noise = &map.0.trees;
 
-    let mut noise = FastNoise::seeded(0);
+    let mut noise = FastNoise::seeded(tree_noise.seed);
     noise.set_noise_type(NoiseType::Simplex);
-    noise.set_frequency(100.0);
+    noise.set_frequency(tree_noise.frequency);
 
     let mut rng = rand::thread_rng();
 
-    for z in (-map_size_i + 1)..(map_size_i - 1) {
-        for x in (-map_size_i + 1)..(map_size_i - 1) {
+    for z in (-map_size_i.y + 1)..(map_size_i.y - 1) {
+        for x in (-map_size_i.x + 1)..(map_size_i.x - 1) {
             let noise = noise.get_noise(z as f32, x as f32);
             // 60% chance to discard randomly
             let random_discard = rng.gen_range(0.0..1.0) > *noise_chance;
 
-            if noise > 0.2 && !random_discard {
+            let pos = vec3(x as f32, 0.0, z as f32);
+            let new_land = outside_of.is_none_or(|inner| pos.xz().abs().cmpge(inner).any());
+            if noise > tree_noise.threshold
+                && !random_discard
+                && new_land
+                && !layout.is_blocked(pos)
+            {
                 tree_events.send(SpawnTreeEvent {
-                    pos: vec3(x as f32, 0.0, z as f32),
+                    pos,
                     blueprint: TreeBlueprint::Randomized,
                     play_sound: false,
+                    sapling: false,
                 });
             }
         }
@@ -70,7 +303,11 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, GroundMaterial>>>,
     asset_server: Res<AssetServer>,
+    bounds: Res<MapBounds>,
+    layout: Res<MapLayout>,
+    mut images: ResMut<Assets<Image>>,
 ) {
+    let size_half = bounds.size_half;
     let settings = move |s: &mut ImageLoaderSettings| {
         s.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
             address_mode_u: ImageAddressMode::Repeat,
@@ -82,7 +319,8 @@ fn setup(
     let ground_img = asset_server.load_with_settings("textures/Dirt_01.png", settings);
     // ground
     commands.spawn((
-        Collider::cuboid(MAP_SIZE_HALF * 4.0, 0.1, MAP_SIZE_HALF * 4.0),
+        MapTerrain,
+        Collider::cuboid(GROUND_SIZE, 0.1, GROUND_SIZE),
         // EXPLANATION: see docs/physics.txt
         CollisionGroups::new(
             Group::from_bits(COLLISION_WORLD).unwrap(), // part of world(1)
@@ -90,7 +328,7 @@ fn setup(
         ),
         MaterialMeshBundle {
             // mesh: meshes.add(shape::Plane::from_size(MAP_SIZE_HALF * 4.4).into()),
-            mesh: meshes.add(shape::Plane::from_size(MAP_SIZE_HALF * 2.0 + 15.0).into()),
+            mesh: meshes.add(shape::Plane::from_size(GROUND_SIZE).into()),
             // material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
             material: materials.add(ExtendedMaterial {
                 base: StandardMaterial {
@@ -102,6 +340,8 @@ fn setup(
                     scale: 13.0,
                     color_texture: ground_img,
                     noise_scale: 0.3,
+                    sand_color: Color::rgb(0.93, 0.82, 0.55),
+                    splat_texture: images.add(layout.splat_map(GROUND_SIZE)),
                     // filler: Default::default(),
                     // filler2: Default::default(),
                     // color_texture: todo!(),
@@ -112,84 +352,24 @@ fn setup(
         },
     ));
 
-    let wall_thickness = 0.5;
-    let wall_thickness_half = wall_thickness * 0.5;
-    // wall right
-    commands.spawn((
-        Collider::cuboid(wall_thickness, 10.0, MAP_SIZE_HALF),
-        RigidBody::Fixed,
-        ColliderMassProperties::Mass(100.0),
-        // EXPLANATION: see docs/physics.txt
-        CollisionGroups::new(
-            Group::from_bits(COLLISION_BORDER).unwrap(), // part of world(1)
-            Group::all(),                                // interacts with all
-        ),
-        PbrBundle {
-            transform: Transform::from_translation(vec3(
-                MAP_SIZE_HALF + wall_thickness_half,
-                0.0,
-                0.0,
-            )),
-            ..default()
-        },
-    ));
-    // wall left
-    commands.spawn((
-        Collider::cuboid(wall_thickness, 10.0, MAP_SIZE_HALF),
-        RigidBody::Fixed,
-        ColliderMassProperties::Mass(100.0),
-        // EXPLANATION: see docs/physics.txt
-        CollisionGroups::new(
-            Group::from_bits(COLLISION_BORDER).unwrap(), // part of world(1)
-            Group::all(),                                // interacts with all
-        ),
-        PbrBundle {
-            transform: Transform::from_translation(vec3(
-                -MAP_SIZE_HALF - wall_thickness_half,
-                0.0,
-                0.0,
-            )),
-            ..default()
-        },
-    ));
-    // wall +z
-    commands.spawn((
-        Collider::cuboid(MAP_SIZE_HALF, 10.0, wall_thickness),
-        RigidBody::Fixed,
-        ColliderMassProperties::Mass(100.0),
-        // EXPLANATION: see docs/physics.txt
-        CollisionGroups::new(
-            Group::from_bits(COLLISION_BORDER).unwrap(), // part of world(1)
-            Group::all(),                                // interacts with all
-        ),
-        PbrBundle {
-            transform: Transform::from_translation(vec3(
-                0.0,
-                0.0,
-                MAP_SIZE_HALF + wall_thickness_half,
-            )),
-            ..default()
-        },
-    ));
-    // wall -z
-    commands.spawn((
-        Collider::cuboid(MAP_SIZE_HALF, 10.0, wall_thickness),
-        RigidBody::Fixed,
-        ColliderMassProperties::Mass(100.0),
-        // EXPLANATION: see docs/physics.txt
-        CollisionGroups::new(
-            Group::from_bits(COLLISION_BORDER).unwrap(), // part of world(1)
-            Group::all(),                                // interacts with all
-        ),
-        PbrBundle {
-            transform: Transform::from_translation(vec3(
-                0.0,
-                0.0,
-                -MAP_SIZE_HALF - wall_thickness_half,
-            )),
-            ..default()
-        },
-    ));
+    for side in MapSide::ALL {
+        commands.spawn((
+            MapTerrain,
+            BorderWall(side),
+            side.wall_collider(size_half),
+            RigidBody::Fixed,
+            ColliderMassProperties::Mass(100.0),
+            // EXPLANATION: see docs/physics.txt
+            CollisionGroups::new(
+                Group::from_bits(COLLISION_BORDER).unwrap(), // part of world(1)
+                Group::all(),                                // interacts with all
+            ),
+            PbrBundle {
+                transform: side.wall_transform(size_half),
+                ..default()
+            },
+        ));
+    }
 }
 
 #[derive(Resource)]
@@ -200,7 +380,9 @@ fn setup_visual_border(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, BorderMaterial>>>,
     asset_server: Res<AssetServer>,
+    bounds: Res<MapBounds>,
 ) {
+    let size_half = bounds.size_half;
     let settings = move |s: &mut ImageLoaderSettings| {
         s.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
             address_mode_u: ImageAddressMode::Repeat,
@@ -212,9 +394,8 @@ fn setup_visual_border(
     // let border_img = asset_server.load("textures/border.png");
     commands.insert_resource(BorderHandle(border_img.clone()));
 
-    let wall_height = 4.0;
-
-    let mesh = meshes.add(shape::Quad::new(vec2(MAP_SIZE_HALF * 2.0, wall_height)).into());
+    // one unit wide, scaled to the length of its side
+    let mesh = meshes.add(shape::Quad::new(vec2(1.0, BORDER_HEIGHT)).into());
     let material = materials.add(ExtendedMaterial {
         base: StandardMaterial {
             opaque_render_method: OpaqueRendererMethod::Auto,
@@ -227,47 +408,62 @@ fn setup_visual_border(
         },
     });
 
-    // wall right
-    commands.spawn((
-        NotShadowCaster,
-        MaterialMeshBundle {
-            mesh: mesh.clone(),
-            transform: Transform::from_translation(vec3(MAP_SIZE_HALF, wall_height * 0.5, 0.0))
-                .with_rotation(Quat::from_rotation_y(-FRAC_PI_2)),
-            material: material.clone(),
-            ..default()
-        },
-    ));
-    // wall right
-    commands.spawn((
-        NotShadowCaster,
-        MaterialMeshBundle {
-            mesh: mesh.clone(),
-            transform: Transform::from_translation(vec3(-MAP_SIZE_HALF, wall_height * 0.5, 0.0))
-                .with_rotation(Quat::from_rotation_y(FRAC_PI_2)),
-            material: material.clone(),
-            ..default()
-        },
-    ));
-    // wall up
-    commands.spawn((
-        NotShadowCaster,
-        MaterialMeshBundle {
-            mesh: mesh.clone(),
-            transform: Transform::from_translation(vec3(0.0, wall_height * 0.5, -MAP_SIZE_HALF)),
-            material: material.clone(),
-            ..default()
-        },
-    ));
-    // wall bottom
-    commands.spawn((
-        NotShadowCaster,
-        MaterialMeshBundle {
-            mesh: mesh.clone(),
-            transform: Transform::from_translation(vec3(0.0, wall_height * 0.5, MAP_SIZE_HALF)),
-            material: material.clone(),
-            // .with_rotation(Quat::from_rotation_y(PI)),
-            ..default()
-        },
-    ));
+    for side in MapSide::ALL {
+        commands.spawn((
+            MapTerrain,
+            BorderVisual(side),
+            NotShadowCaster,
+            MaterialMeshBundle {
+                mesh: mesh.clone(),
+                transform: side.visual_transform(size_half),
+                material: material.clone(),
+                ..default()
+            },
+        ));
+    }
+}
+
+// walls follow the bounds when the map is expanded
+fn update_border(
+    bounds: Res<MapBounds>,
+    mut walls: Query<(&BorderWall, &mut Transform, &mut Collider), Without<BorderVisual>>,
+    mut visuals: Query<(&BorderVisual, &mut Transform), Without<BorderWall>>,
+) {
+    for (wall, mut transform, mut collider) in walls.iter_mut() {
+        *transform = wall.0.wall_transform(bounds.size_half);
+        *collider = wall.0.wall_collider(bounds.size_half);
+    }
+    for (visual, mut transform) in visuals.iter_mut() {
+        *transform = visual.0.visual_transform(bounds.size_half);
+    }
+}
+
+#[derive(Default)]
+pub struct MapAssetLoader;
+
+impl AssetLoader for MapAssetLoader {
+    type Asset = MapDefinitionsAsset;
+    type Settings = ();
+    type Error = CustomAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let mut asset = ron::de::from_bytes::<MapDefinitionsAsset>(&bytes)?;
+            for map in asset.0.iter_mut() {
+                map.size_half = map.size_half.min(Vec2::splat(MAX_MAP_SIZE_HALF));
+            }
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.ron"]
+    }
 }
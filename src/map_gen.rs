@@ -0,0 +1,348 @@
+use bevy::{
+    math::vec3,
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+};
+use bevy_rapier3d::prelude::*;
+use bracket_noise::prelude::{FastNoise, NoiseType};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Deserialize;
+
+use crate::{
+    collision_groups::{COLLISION_PROJECTILES, COLLISION_WORLD},
+    map::{CurrentMap, MapTerrain},
+    pathfinding::NavObstacle,
+};
+
+// obstacles are rolled on a grid this coarse, then nudged around within their cell
+const CELL_SIZE: f32 = 6.0;
+// the player starts in the middle, keep it open
+const CLEAR_RADIUS: f32 = 6.0;
+// trees and foliage keep this much room from blockers
+const BLOCKER_MARGIN: f32 = 0.8;
+// sand around the ponds
+const SHORE_WIDTH: f32 = 1.5;
+// counts as near water this far from a pond's edge
+const NEAR_WATER_DISTANCE: f32 = 5.0;
+// a bump rises this much per meter of radius, low enough that characters walk up it
+const BUMP_HEIGHT: f32 = 0.2;
+// a pond's collider stands this high, enough to keep characters out without stopping shots
+const POND_WALL_HEIGHT: f32 = 0.5;
+
+/// Scatters rocks, ponds and bumps over the map, the same seed always gives the same map.
+pub struct MapGenPlugin {
+    pub seed: u64,
+}
+
+impl Plugin for MapGenPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MapSeed(self.seed))
+            .init_resource::<MapLayout>()
+            .add_systems(
+                Update,
+                (generate_layout, spawn_obstacles)
+                    .chain()
+                    .run_if(resource_changed::<CurrentMap>()),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObstacleKind {
+    Rock,
+    Pond,
+    // a low mound of dirt
+    Bump,
+}
+
+/// Ground type of a spot on the map, blended together on the ground by a splat map.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Biome {
+    #[default]
+    Grass,
+    Dirt,
+    Sand,
+}
+
+impl Biome {
+    // one channel per ground texture, see ground_material.wgsl
+    fn splat(self) -> [u8; 4] {
+        match self {
+            Biome::Grass => [255, 0, 0, 255],
+            Biome::Dirt => [0, 255, 0, 255],
+            Biome::Sand => [0, 0, 255, 255],
+        }
+    }
+}
+
+/// Where a tree species likes to grow, see trees.species.ron.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Habitat {
+    Grass,
+    Dirt,
+    Sand,
+    // the northern third of the map, north is -z
+    North,
+    NearWater,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Obstacle {
+    pub kind: ObstacleKind,
+    pub pos: Vec3,
+    pub radius: f32,
+}
+
+#[derive(Resource)]
+pub struct MapSeed(pub u64);
+
+/// Everything the generator placed on the current map, kept for every run on it.
+#[derive(Resource, Default)]
+pub struct MapLayout {
+    pub obstacles: Vec<Obstacle>,
+    size_half: Vec2,
+    // one per square meter, row by row from -x -z
+    biomes: Vec<Biome>,
+}
+
+impl MapLayout {
+    fn generate(seed: u64, size_half: Vec2) -> Self {
+        let mut noise = FastNoise::seeded(seed);
+        noise.set_noise_type(NoiseType::Simplex);
+        noise.set_frequency(0.15);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let cells = (size_half * 2.0 / CELL_SIZE).as_ivec2();
+        let mut obstacles = Vec::new();
+        for z in 0..cells.y {
+            for x in 0..cells.x {
+                let center = Vec2::new(x as f32 + 0.5, z as f32 + 0.5) * CELL_SIZE - size_half;
+                let kind = match noise.get_noise(center.x, center.y) {
+                    n if n > 0.45 => ObstacleKind::Rock,
+                    n if n < -0.5 => ObstacleKind::Pond,
+                    n if n.abs() < 0.05 => ObstacleKind::Bump,
+                    _ => continue,
+                };
+                let radius = match kind {
+                    ObstacleKind::Rock => rng.gen_range(0.8..1.6),
+                    ObstacleKind::Pond => rng.gen_range(1.5..2.5),
+                    ObstacleKind::Bump => rng.gen_range(1.2..2.0),
+                };
+                let jitter = (CELL_SIZE * 0.5 - radius).max(0.0);
+                let pos = center
+                    + Vec2::new(
+                        rng.gen_range(-jitter..=jitter),
+                        rng.gen_range(-jitter..=jitter),
+                    );
+                if pos.length() < CLEAR_RADIUS + radius
+                    || (pos.abs() + radius).cmpgt(size_half).any()
+                {
+                    continue;
+                }
+                obstacles.push(Obstacle {
+                    kind,
+                    pos: vec3(pos.x, 0.0, pos.y),
+                    radius,
+                });
+            }
+        }
+
+        let mut biome_noise = FastNoise::seeded(seed + 1);
+        biome_noise.set_noise_type(NoiseType::Simplex);
+        biome_noise.set_frequency(0.06);
+        let cells = (size_half * 2.0).ceil().as_ivec2();
+        let mut biomes = Vec::with_capacity((cells.x * cells.y) as usize);
+        for z in 0..cells.y {
+            for x in 0..cells.x {
+                let pos = Vec2::new(x as f32 + 0.5, z as f32 + 0.5) - size_half;
+                let on_shore = obstacles.iter().any(|o| {
+                    o.kind == ObstacleKind::Pond
+                        && o.pos.xz().distance(pos) < o.radius + SHORE_WIDTH
+                });
+                biomes.push(match biome_noise.get_noise(pos.x, pos.y) {
+                    _ if on_shore => Biome::Sand,
+                    n if n > 0.35 => Biome::Dirt,
+                    n if n < -0.4 => Biome::Sand,
+                    _ => Biome::Grass,
+                });
+            }
+        }
+
+        Self {
+            obstacles,
+            size_half,
+            biomes,
+        }
+    }
+
+    /// grass outside of the map
+    pub fn biome_at(&self, pos: Vec3) -> Biome {
+        let cells = (self.size_half * 2.0).ceil().as_ivec2();
+        let cell = (pos.xz() + self.size_half).floor().as_ivec2();
+        if cell.cmplt(IVec2::ZERO).any() || cell.cmpge(cells).any() {
+            return Biome::Grass;
+        }
+        self.biomes[(cell.y * cells.x + cell.x) as usize]
+    }
+
+    pub fn is_habitat(&self, pos: Vec3, habitat: Habitat) -> bool {
+        match habitat {
+            Habitat::Grass => self.biome_at(pos) == Biome::Grass,
+            Habitat::Dirt => self.biome_at(pos) == Biome::Dirt,
+            Habitat::Sand => self.biome_at(pos) == Biome::Sand,
+            Habitat::North => pos.z < -self.size_half.y / 3.0,
+            Habitat::NearWater => self.obstacles.iter().any(|o| {
+                o.kind == ObstacleKind::Pond
+                    && o.pos.xz().distance(pos.xz()) < o.radius + NEAR_WATER_DISTANCE
+            }),
+        }
+    }
+
+    /// Biome weights for a square ground plane of `size` meters centered on the map,
+    /// one texel per meter and filtered so biomes fade into each other.
+    pub fn splat_map(&self, size: f32) -> Image {
+        let texels = size.ceil() as u32;
+        let mut data = Vec::with_capacity((texels * texels * 4) as usize);
+        for z in 0..texels {
+            for x in 0..texels {
+                let pos = (Vec2::new(x as f32, z as f32) + 0.5) / texels as f32 * size - size * 0.5;
+                data.extend(self.biome_at(vec3(pos.x, 0.0, pos.y)).splat());
+            }
+        }
+        let mut image = Image::new(
+            Extent3d {
+                width: texels,
+                height: texels,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8Unorm,
+        );
+        image.sampler = ImageSampler::linear();
+        image
+    }
+
+    /// whether something standing at `pos` would overlap an obstacle
+    pub fn is_blocked(&self, pos: Vec3) -> bool {
+        self.obstacles
+            .iter()
+            .any(|o| o.pos.xz().distance(pos.xz()) < o.radius + BLOCKER_MARGIN)
+    }
+}
+
+/// Rolls the obstacles for a newly picked map, anything placing trees runs after this.
+pub fn generate_layout(mut layout: ResMut<MapLayout>, seed: Res<MapSeed>, map: Res<CurrentMap>) {
+    *layout = MapLayout::generate(seed.0, map.0.size_half);
+}
+
+// the old map's obstacles are cleared with the rest of its terrain
+fn spawn_obstacles(
+    mut commands: Commands,
+    layout: Res<MapLayout>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let rock_mesh = meshes.add(
+        shape::Icosphere {
+            radius: 1.0,
+            subdivisions: 1,
+        }
+        .try_into()
+        .unwrap(),
+    );
+    let rock_material = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.45, 0.45, 0.5),
+        perceptual_roughness: 1.0,
+        ..default()
+    });
+    let pond_mesh = meshes.add(
+        shape::Cylinder {
+            radius: 1.0,
+            height: 0.05,
+            resolution: 24,
+            segments: 1,
+        }
+        .into(),
+    );
+    let pond_material = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.2, 0.45, 0.8),
+        perceptual_roughness: 0.1,
+        ..default()
+    });
+    let bump_mesh = meshes.add(shape::UVSphere::default().into());
+    let bump_material = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.35, 0.5, 0.25),
+        perceptual_roughness: 1.0,
+        ..default()
+    });
+
+    let all = Group::all().bits();
+    for obstacle in layout.obstacles.iter() {
+        let (name, mesh, material, transform, collider, filter) = match obstacle.kind {
+            ObstacleKind::Rock => (
+                "Rock",
+                rock_mesh.clone(),
+                rock_material.clone(),
+                Transform::from_translation(obstacle.pos)
+                    .with_scale(vec3(1.0, 0.7, 1.0) * obstacle.radius),
+                Collider::ball(1.0),
+                all,
+            ),
+            // a low wall around the water, shots fly over it
+            ObstacleKind::Pond => (
+                "Pond",
+                pond_mesh.clone(),
+                pond_material.clone(),
+                Transform::from_translation(obstacle.pos + Vec3::Y * 0.02).with_scale(vec3(
+                    obstacle.radius,
+                    1.0,
+                    obstacle.radius,
+                )),
+                Collider::cylinder(POND_WALL_HEIGHT * 0.5, 1.0),
+                all & !COLLISION_PROJECTILES,
+            ),
+            // the top of a big sphere sunk into the ground, it meets the ground at a shallow
+            // slope so characters walk over it instead of bumping into it
+            ObstacleKind::Bump => {
+                let height = obstacle.radius * BUMP_HEIGHT;
+                let sphere_radius = (obstacle.radius.powi(2) + height.powi(2)) / (2.0 * height);
+                (
+                    "Bump",
+                    bump_mesh.clone(),
+                    bump_material.clone(),
+                    Transform::from_translation(obstacle.pos + Vec3::Y * (height - sphere_radius))
+                        .with_scale(Vec3::splat(sphere_radius)),
+                    Collider::ball(1.0),
+                    all,
+                )
+            }
+        };
+        let mut entity = commands.spawn((
+            Name::new(name),
+            MapTerrain,
+            PbrBundle {
+                mesh,
+                material,
+                transform,
+                ..default()
+            },
+            RigidBody::Fixed,
+            collider,
+            // EXPLANATION: see docs/physics.txt
+            CollisionGroups::new(
+                Group::from_bits(COLLISION_WORLD).unwrap(),
+                Group::from_bits(filter).unwrap(),
+            ),
+        ));
+        // bumps are walked over, everything else is walked around
+        if obstacle.kind != ObstacleKind::Bump {
+            entity.insert(NavObstacle {
+                radius: obstacle.radius,
+            });
+        }
+    }
+}
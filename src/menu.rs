@@ -0,0 +1,350 @@
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    map::{CurrentMap, MapDefinition, MapDefinitions, MapDefinitionsAsset},
+    pause::{Pause, PauseReason},
+    settings::ToggleSettingsEvent,
+    state::{AppState, GameMode, GameOverEvent, GameResult, RestartRunEvent, Wave},
+    ui_util::{JustClicked, UiAssets},
+};
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::MainMenu), setup_main_menu)
+            .add_systems(OnExit(AppState::MainMenu), despawn_menu)
+            .add_systems(OnEnter(AppState::GameOver), setup_game_over_menu)
+            .add_systems(OnExit(AppState::GameOver), despawn_menu)
+            .add_systems(
+                Update,
+                (handle_menu_click, update_pause_menu, update_win_prompt).chain(),
+            );
+    }
+}
+
+#[derive(Component)]
+struct MenuUiTag;
+
+#[derive(Component)]
+struct PauseMenuUiTag;
+
+#[derive(Component)]
+struct WinPromptUiTag;
+
+#[derive(Component, Clone, Copy)]
+enum MenuButton {
+    Play,
+    PlayEndless,
+    // index into maps.map.ron
+    Map(usize),
+    Back,
+    KeepGoing,
+    Finish,
+    MainMenu,
+    Resume,
+    Restart,
+    Settings,
+    Quit,
+}
+
+/// A centered panel with a title and a column of buttons.
+pub fn spawn_menu<B: Component + Copy>(
+    commands: &mut Commands,
+    tag: impl Component,
+    ui_assets: &UiAssets,
+    title: &str,
+    title_color: Color,
+    subtitle: Option<String>,
+    buttons: &[(B, &str)],
+) {
+    commands
+        .spawn((
+            tag,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(35.0),
+                    top: Val::Percent(25.0),
+                    width: Val::Percent(30.0),
+                    padding: UiRect::all(Val::Px(20.0)),
+                    row_gap: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::BLACK.with_a(0.7).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                title,
+                TextStyle {
+                    font: ui_assets.font.clone(),
+                    font_size: 50.0,
+                    color: title_color,
+                },
+            ));
+            if let Some(subtitle) = subtitle {
+                parent.spawn(TextBundle::from_section(
+                    subtitle,
+                    TextStyle {
+                        font: ui_assets.font.clone(),
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            }
+            for (button, label) in buttons {
+                parent
+                    .spawn((
+                        *button,
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Percent(60.0),
+                                padding: UiRect::all(Val::Px(8.0)),
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            *label,
+                            TextStyle {
+                                font: ui_assets.font.clone(),
+                                font_size: 28.0,
+                                color: Color::BLACK,
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn setup_main_menu(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    spawn_main_menu(&mut commands, &ui_assets);
+}
+
+fn spawn_main_menu(commands: &mut Commands, ui_assets: &UiAssets) {
+    spawn_menu(
+        commands,
+        MenuUiTag,
+        ui_assets,
+        "Protect The Trees",
+        Color::GREEN,
+        None,
+        &[
+            (MenuButton::Play, "Play"),
+            (MenuButton::PlayEndless, "Endless"),
+            (MenuButton::Settings, "Settings"),
+            (MenuButton::Quit, "Quit"),
+        ],
+    );
+}
+
+// picked after the mode, "Restart" skips this and keeps the map
+fn spawn_map_menu(commands: &mut Commands, ui_assets: &UiAssets, maps: &[MapDefinition]) {
+    let buttons = maps
+        .iter()
+        .enumerate()
+        .map(|(i, map)| (MenuButton::Map(i), map.name.as_str()))
+        .chain([(MenuButton::Back, "Back")])
+        .collect::<Vec<_>>();
+    spawn_menu(
+        commands,
+        MenuUiTag,
+        ui_assets,
+        "Choose a map",
+        Color::GREEN,
+        None,
+        &buttons,
+    );
+}
+
+fn setup_game_over_menu(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    result: Option<Res<GameResult>>,
+    wave: Res<Wave>,
+) {
+    let (title, color) = match result.as_deref() {
+        Some(GameResult::Won) => ("You Win!", Color::GREEN),
+        _ => ("You Lost!", Color::RED),
+    };
+    // dims the world, below the menu and the summary panels
+    commands.spawn((
+        MenuUiTag,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: Color::BLACK.with_a(0.5).into(),
+            z_index: ZIndex::Global(-1),
+            ..default()
+        },
+    ));
+    spawn_menu(
+        &mut commands,
+        MenuUiTag,
+        &ui_assets,
+        title,
+        color,
+        Some(format!("Reached wave {}", wave.0 + 1)),
+        &[
+            (MenuButton::Play, "Restart"),
+            (MenuButton::MainMenu, "Main menu"),
+            (MenuButton::Quit, "Quit"),
+        ],
+    );
+}
+
+fn despawn_menu(mut commands: Commands, menus: Query<Entity, With<MenuUiTag>>) {
+    for entity in menus.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// the pause menu follows the pause reason, so escape and the resume button both close it
+fn update_pause_menu(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    pause: Res<Pause>,
+    menus: Query<Entity, With<PauseMenuUiTag>>,
+) {
+    if !pause.is_changed() {
+        return;
+    }
+    let is_open = !menus.is_empty();
+    match (pause.is_paused_by(PauseReason::Menu), is_open) {
+        (true, false) => spawn_menu(
+            &mut commands,
+            PauseMenuUiTag,
+            &ui_assets,
+            "Paused",
+            Color::BLUE,
+            None,
+            &[
+                (MenuButton::Resume, "Resume"),
+                (MenuButton::Restart, "Restart"),
+                (MenuButton::Settings, "Settings"),
+                (MenuButton::Quit, "Quit"),
+            ],
+        ),
+        (false, true) => {
+            for entity in menus.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn update_win_prompt(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    pause: Res<Pause>,
+    prompts: Query<Entity, With<WinPromptUiTag>>,
+) {
+    if !pause.is_changed() {
+        return;
+    }
+    let is_open = !prompts.is_empty();
+    match (pause.is_paused_by(PauseReason::WinPrompt), is_open) {
+        (true, false) => spawn_menu(
+            &mut commands,
+            WinPromptUiTag,
+            &ui_assets,
+            "You Win!",
+            Color::GREEN,
+            Some("Keep going in endless mode?".to_string()),
+            &[
+                (MenuButton::KeepGoing, "Keep going"),
+                (MenuButton::Finish, "Finish"),
+            ],
+        ),
+        (false, true) => {
+            for entity in prompts.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn handle_menu_click(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    buttons: Query<&MenuButton, With<JustClicked>>,
+    menus: Query<Entity, With<MenuUiTag>>,
+    map_definitions: Res<MapDefinitions>,
+    map_assets: Res<Assets<MapDefinitionsAsset>>,
+    mut current_map: ResMut<CurrentMap>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut pause: ResMut<Pause>,
+    mut mode: ResMut<GameMode>,
+    mut restart_event: EventWriter<RestartRunEvent>,
+    mut game_over_event: EventWriter<GameOverEvent>,
+    mut settings_event: EventWriter<ToggleSettingsEvent>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    let maps = map_assets
+        .get(&map_definitions.0)
+        .map(|maps| maps.0.as_slice())
+        .unwrap_or_default();
+    for button in buttons.iter() {
+        match button {
+            MenuButton::Play | MenuButton::PlayEndless if state.get() == &AppState::MainMenu => {
+                *mode = match button {
+                    MenuButton::PlayEndless => GameMode::Endless,
+                    _ => GameMode::Campaign,
+                };
+                // without map definitions there's nothing to choose from
+                if maps.is_empty() {
+                    next_state.set(AppState::Playing);
+                    continue;
+                }
+                for entity in menus.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                spawn_map_menu(&mut commands, &ui_assets, maps);
+            }
+            // "Restart" keeps the mode and map of the finished run
+            MenuButton::Play | MenuButton::PlayEndless => next_state.set(AppState::Playing),
+            MenuButton::Map(i) => {
+                if let Some(map) = maps.get(*i) {
+                    // only a different map rebuilds the world
+                    if map.name != current_map.0.name {
+                        current_map.0 = map.clone();
+                    }
+                    next_state.set(AppState::Playing);
+                }
+            }
+            MenuButton::Back => {
+                for entity in menus.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                spawn_main_menu(&mut commands, &ui_assets);
+            }
+            MenuButton::KeepGoing => {
+                *mode = GameMode::Endless;
+                pause.resume(PauseReason::WinPrompt);
+            }
+            MenuButton::Finish => game_over_event.send(GameOverEvent {
+                result: GameResult::Won,
+            }),
+            // the finished run is cleaned up when leaving GameOver
+            MenuButton::MainMenu => next_state.set(AppState::MainMenu),
+            MenuButton::Resume => pause.resume(PauseReason::Menu),
+            MenuButton::Restart => restart_event.send(RestartRunEvent),
+            MenuButton::Settings => settings_event.send(ToggleSettingsEvent),
+            MenuButton::Quit => app_exit.send(AppExit),
+        }
+    }
+}
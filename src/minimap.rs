@@ -0,0 +1,207 @@
+use bevy::prelude::*;
+
+use crate::{
+    map::MapBounds,
+    player::{PlayerControllerTag, RobotTag},
+    settings::Settings,
+    shop_stall::ShopStall,
+    state::AppState,
+    tower::TowerTag,
+    tree::TreeTrunkTag,
+    ui_util::set_position,
+};
+
+const MINIMAP_SIZE: f32 = 180.0;
+const DOT_SIZE: f32 = 6.0;
+const PLAYER_DOT_SIZE: f32 = 9.0;
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_minimap)
+            .add_systems(Update, update_minimap);
+    }
+}
+
+#[derive(Component)]
+struct MinimapTag;
+
+// outline of the playable area
+#[derive(Component)]
+struct MinimapBorder;
+
+// drawn above the other dots
+#[derive(Component)]
+struct MinimapPlayerDot;
+
+// reused between frames, extras are hidden
+#[derive(Component)]
+struct MinimapDot;
+
+fn setup_minimap(mut commands: Commands) {
+    commands
+        .spawn((
+            MinimapTag,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    width: Val::Px(MINIMAP_SIZE),
+                    height: Val::Px(MINIMAP_SIZE),
+                    overflow: Overflow::clip(),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::BLACK.with_a(0.6).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                MinimapBorder,
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        border: UiRect::all(Val::Px(2.0)),
+                        ..default()
+                    },
+                    border_color: Color::WHITE.with_a(0.5).into(),
+                    ..default()
+                },
+            ));
+            parent.spawn((
+                MinimapPlayerDot,
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(PLAYER_DOT_SIZE),
+                        height: Val::Px(PLAYER_DOT_SIZE),
+                        ..default()
+                    },
+                    background_color: Color::WHITE.into(),
+                    z_index: ZIndex::Local(1),
+                    ..default()
+                },
+            ));
+        });
+}
+
+// top down with -z up, centered on the player
+fn update_minimap(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    state: Res<State<AppState>>,
+    bounds: Res<MapBounds>,
+    mut minimaps: Query<(Entity, &mut Style), (With<MinimapTag>, Without<MinimapBorder>)>,
+    mut borders: Query<&mut Style, (With<MinimapBorder>, Without<MinimapDot>)>,
+    mut player_dots: Query<
+        &mut Style,
+        (
+            With<MinimapPlayerDot>,
+            Without<MinimapBorder>,
+            Without<MinimapDot>,
+            Without<MinimapTag>,
+        ),
+    >,
+    mut dots: Query<(&mut Style, &mut BackgroundColor), (With<MinimapDot>, Without<MinimapTag>)>,
+    players: Query<&GlobalTransform, With<PlayerControllerTag>>,
+    trees: Query<&GlobalTransform, With<TreeTrunkTag>>,
+    robots: Query<&GlobalTransform, With<RobotTag>>,
+    towers: Query<&GlobalTransform, With<TowerTag>>,
+    stalls: Query<&GlobalTransform, With<ShopStall>>,
+) {
+    let Ok((minimap, mut minimap_style)) = minimaps.get_single_mut() else {
+        return;
+    };
+    let player = players
+        .get_single()
+        .ok()
+        .map(|p| p.translation().xz())
+        .filter(|_| *state.get() == AppState::Playing);
+    let display = match player {
+        Some(_) => Display::Flex,
+        None => Display::None,
+    };
+    if minimap_style.display != display {
+        minimap_style.display = display;
+    }
+    let Some(player) = player else {
+        return;
+    };
+
+    // the longer side fits the minimap when not zoomed in
+    let size_half = bounds.size_half;
+    let scale = MINIMAP_SIZE / (size_half.max_element() * 2.0) * settings.general.minimap_zoom;
+    let to_map = |pos: Vec2| (pos - player) * scale + Vec2::splat(MINIMAP_SIZE * 0.5);
+
+    for mut style in borders.iter_mut() {
+        set_position(&mut style, to_map(-size_half));
+        let (width, height) = (
+            Val::Px(size_half.x * 2.0 * scale),
+            Val::Px(size_half.y * 2.0 * scale),
+        );
+        if style.width != width {
+            style.width = width;
+        }
+        if style.height != height {
+            style.height = height;
+        }
+    }
+
+    for mut style in player_dots.iter_mut() {
+        set_position(
+            &mut style,
+            to_map(player) - Vec2::splat(PLAYER_DOT_SIZE * 0.5),
+        );
+    }
+
+    let palette = settings.accessibility.palette;
+    let marks = trees
+        .iter()
+        .map(|t| (t, Color::GREEN))
+        .chain(towers.iter().map(|t| (t, Color::GOLD)))
+        .chain(stalls.iter().map(|t| (t, Color::ORANGE)))
+        .chain(robots.iter().map(|t| (t, Color::RED)));
+    let mut dots = dots.iter_mut();
+    for (transform, color) in marks {
+        let pos = to_map(transform.translation().xz()) - Vec2::splat(DOT_SIZE * 0.5);
+        let color = palette.remap(color);
+        match dots.next() {
+            Some((mut dot_style, mut dot_color)) => {
+                set_position(&mut dot_style, pos);
+                if dot_style.display != Display::Flex {
+                    dot_style.display = Display::Flex;
+                }
+                if dot_color.0 != color {
+                    dot_color.0 = color;
+                }
+            }
+            None => {
+                commands.entity(minimap).with_children(|parent| {
+                    parent.spawn((
+                        MinimapDot,
+                        NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                left: Val::Px(pos.x),
+                                top: Val::Px(pos.y),
+                                width: Val::Px(DOT_SIZE),
+                                height: Val::Px(DOT_SIZE),
+                                ..default()
+                            },
+                            background_color: color.into(),
+                            ..default()
+                        },
+                    ));
+                });
+            }
+        }
+    }
+    for (mut dot_style, _) in dots {
+        if dot_style.display != Display::None {
+            dot_style.display = Display::None;
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use bevy::{
+    audio::{PlaybackMode, SpatialAudioSink, SpatialListener, Volume, VolumeLevel},
+    prelude::*,
+    utils::HashMap,
+};
+
+use crate::{
+    camera::MainCameraTag,
+    settings::Settings,
+    sound_sets::{SoundSets, SoundSetsAsset},
+};
+
+/// meters to audio units, sounds are at full volume up to the follow camera's distance
+/// and fall off beyond it
+pub const SPATIAL_SCALE: f32 = 1.0 / 25.0;
+// meters between the camera's ears, far wider than a head so the side a sound
+// comes from is still clear from up where the camera is
+const EAR_GAP: f32 = 12.0;
+
+pub struct MixerPlugin;
+
+impl Plugin for MixerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlaySfxEvent>()
+            .init_resource::<SfxThrottle>()
+            .add_systems(Update, add_listener)
+            // PostUpdate, so sounds asked for anywhere in Update start this frame
+            .add_systems(PostUpdate, (play_sfx, apply_channel_volumes));
+    }
+}
+
+/// Every sound plays on a channel, the settings have a volume for each on top of the master volume.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AudioChannel {
+    Music,
+    Sfx,
+}
+
+impl AudioChannel {
+    /// what the settings turn this channel down to, master volume included
+    pub fn volume(&self, settings: &Settings) -> f32 {
+        let audio = &settings.audio;
+        let channel = match self {
+            AudioChannel::Music => audio.music_volume,
+            AudioChannel::Sfx => audio.sfx_volume,
+        };
+        audio.master_volume * channel
+    }
+}
+
+/// A playing sound, remembers its own volume so the channel volume can change while it plays.
+#[derive(Component)]
+pub struct MixerTrack {
+    pub channel: AudioChannel,
+    pub volume: f32,
+}
+
+/// A sound file, or the id of a set in sounds.sfx.ron that picks one of its variants.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SfxSound {
+    File(Handle<AudioSource>),
+    Set(String),
+}
+
+impl From<Handle<AudioSource>> for SfxSound {
+    fn from(handle: Handle<AudioSource>) -> Self {
+        SfxSound::File(handle)
+    }
+}
+
+impl From<&str> for SfxSound {
+    fn from(id: &str) -> Self {
+        SfxSound::Set(id.to_string())
+    }
+}
+
+impl From<&String> for SfxSound {
+    fn from(id: &String) -> Self {
+        SfxSound::Set(id.clone())
+    }
+}
+
+/// Plays a sound effect on the sfx channel, heard from where it happens if it has a position.
+/// Volume and speed multiply the ones a sound set picks.
+#[derive(Event, Clone)]
+pub struct PlaySfxEvent {
+    pub sound: SfxSound,
+    pub volume: f32,
+    pub speed: f32,
+    // replaces the set's random pitch, for sounds that always have to sound lower or higher
+    pub exact_speed: Option<f32>,
+    // None is heard the same everywhere, like ui and wave sounds
+    pub position: Option<Vec3>,
+    // None always plays
+    pub limit: Option<SfxLimit>,
+}
+
+impl PlaySfxEvent {
+    pub fn new(sound: impl Into<SfxSound>) -> Self {
+        Self {
+            sound: sound.into(),
+            volume: 1.0,
+            speed: 1.0,
+            exact_speed: None,
+            position: None,
+            limit: None,
+        }
+    }
+
+    pub fn limited(mut self, limit: SfxLimit) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn at(mut self, position: Vec3) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_exact_speed(mut self, speed: f32) -> Self {
+        self.exact_speed = Some(speed);
+        self
+    }
+}
+
+/// How often a sound may play, so a crowd swinging or shooting at once doesn't stack up into noise.
+#[derive(Clone, Copy, Debug)]
+pub struct SfxLimit {
+    /// seconds before the same sound plays again
+    pub cooldown: f32,
+    /// copies of the sound playing at once
+    pub max_playing: usize,
+}
+
+/// When each limited sound last played, keyed by the sound, so all variants of a set count as one.
+#[derive(Resource, Default)]
+pub struct SfxThrottle {
+    last_played: HashMap<SfxSound, f32>,
+}
+
+impl SfxThrottle {
+    // remembers the sound as played if it's allowed to
+    fn try_play(&mut self, sound: &SfxSound, limit: SfxLimit, now: f32, playing: usize) -> bool {
+        let cooled_down = self
+            .last_played
+            .get(&sound)
+            .map_or(true, |last| now - last >= limit.cooldown);
+        if !cooled_down || playing >= limit.max_playing {
+            return false;
+        }
+        self.last_played.insert(sound.clone(), now);
+        true
+    }
+}
+
+// a limited sound that is playing, counted against its max_playing
+#[derive(Component)]
+struct ThrottledSfx(SfxSound);
+
+fn play_sfx(
+    mut commands: Commands,
+    mut events: EventReader<PlaySfxEvent>,
+    settings: Res<Settings>,
+    mut throttle: ResMut<SfxThrottle>,
+    throttled: Query<&ThrottledSfx>,
+    time: Res<Time<Real>>,
+    sound_sets: Res<SoundSets>,
+    sound_set_assets: Res<Assets<SoundSetsAsset>>,
+) {
+    let channel = AudioChannel::Sfx;
+    // cooldowns are in real time, game speed doesn't change how crowded it sounds
+    let now = time.elapsed_seconds();
+    let mut playing = HashMap::<&SfxSound, usize>::new();
+    for sfx in throttled.iter() {
+        *playing.entry(&sfx.0).or_default() += 1;
+    }
+    let sound_sets = sound_set_assets.get(&sound_sets.0);
+    for event in events.read() {
+        let (source, volume, speed) = match &event.sound {
+            SfxSound::File(handle) => (handle.clone(), event.volume, event.speed),
+            SfxSound::Set(id) => {
+                let Some(set) = sound_sets.and_then(|sets| sets.0.get(id)) else {
+                    warn!("no sound set {:?} in sounds.sfx.ron", id);
+                    continue;
+                };
+                let Some((handle, volume, speed)) = set.pick() else {
+                    continue;
+                };
+                (handle, volume * event.volume, speed * event.speed)
+            }
+        };
+        let speed = event.exact_speed.unwrap_or(speed);
+        if let Some(limit) = event.limit {
+            let count = playing.entry(&event.sound).or_default();
+            if !throttle.try_play(&event.sound, limit, now, *count) {
+                continue;
+            }
+            *count += 1;
+        }
+        let mut sound = commands.spawn((
+            MixerTrack { channel, volume },
+            AudioBundle {
+                source,
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Despawn,
+                    volume: Volume::Relative(VolumeLevel::new(volume * channel.volume(&settings))),
+                    speed,
+                    spatial: event.position.is_some(),
+                    ..default()
+                },
+            },
+        ));
+        // the emitter stays where the sound started, even if what made it moves on or dies
+        if let Some(position) = event.position {
+            sound.insert(SpatialBundle::from_transform(Transform::from_translation(
+                position,
+            )));
+        }
+        if event.limit.is_some() {
+            sound.insert(ThrottledSfx(event.sound.clone()));
+        }
+    }
+}
+
+// positional sounds are heard from the camera
+fn add_listener(
+    mut commands: Commands,
+    cameras: Query<Entity, (With<MainCameraTag>, Without<SpatialListener>)>,
+) {
+    for entity in cameras.iter() {
+        commands
+            .entity(entity)
+            .insert(SpatialListener::new(EAR_GAP));
+    }
+}
+
+// sounds start at the right volume, this follows the settings while they play
+fn apply_channel_volumes(
+    settings: Res<Settings>,
+    tracks: Query<(&MixerTrack, Option<&AudioSink>, Option<&SpatialAudioSink>)>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (track, sink, spatial_sink) in tracks.iter() {
+        let volume = track.volume * track.channel.volume(&settings);
+        if let Some(sink) = sink {
+            sink.set_volume(volume);
+        }
+        if let Some(sink) = spatial_sink {
+            sink.set_volume(volume);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use bevy::{
+    audio::{PlaybackMode, Volume, VolumeLevel},
+    prelude::*,
+};
+
+use crate::{
+    health::Dying,
+    mixer::{AudioChannel, MixerTrack},
+    player::Body,
+    settings::Settings,
+    state::{AppState, WaveClearedEvent},
+};
+
+const SOUNDTRACK: &str = "sounds/8bit-spaceshooter.ogg";
+const MUSIC_VOLUME: f32 = 0.3;
+const STING: &str = "sounds/next-level.ogg";
+const STING_VOLUME: f32 = 1.0;
+// robots alive before the music turns to combat, and how few are left when it calms down,
+// so a fight hovering around the threshold doesn't flip back and forth
+const COMBAT_ROBOTS: usize = 4;
+const CALM_ROBOTS: usize = 1;
+// how far the music is turned down while the sting plays
+const STING_DUCK: f32 = 0.3;
+const STING_DUCK_SECONDS: f32 = 2.0;
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MusicDirector>()
+            .add_systems(Startup, spawn_soundtrack)
+            .add_systems(
+                Update,
+                (pick_intensity, play_sting, update_soundtrack).chain(),
+            );
+    }
+}
+
+/// How heated the run is.
+///
+/// There's only the one soundtrack so far, nothing plays differently per intensity
+/// until there are stems to fade between.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MusicIntensity {
+    Calm,
+    Combat,
+    Boss,
+}
+
+/// Picks how intense the music is from how the run is going.
+#[derive(Resource)]
+pub struct MusicDirector {
+    pub intensity: MusicIntensity,
+    // counts down in real time, the music is ducked while running
+    sting: Option<Timer>,
+}
+
+impl Default for MusicDirector {
+    fn default() -> Self {
+        Self {
+            intensity: MusicIntensity::Calm,
+            sting: None,
+        }
+    }
+}
+
+/// The looping background music.
+#[derive(Component)]
+pub struct SoundtrackTag;
+
+fn spawn_soundtrack(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+) {
+    let channel = AudioChannel::Music;
+    commands.spawn((
+        SoundtrackTag,
+        MixerTrack {
+            channel,
+            volume: MUSIC_VOLUME,
+        },
+        AudioBundle {
+            source: asset_server.load(SOUNDTRACK),
+            settings: PlaybackSettings::LOOP.with_volume(Volume::Relative(VolumeLevel::new(
+                MUSIC_VOLUME * channel.volume(&settings),
+            ))),
+        },
+    ));
+}
+
+// boss while a boss is alive, combat while robots crowd the map, calm otherwise
+fn pick_intensity(
+    mut director: ResMut<MusicDirector>,
+    state: Res<State<AppState>>,
+    bodies: Query<&Body, Without<Dying>>,
+) {
+    let intensity = match *state.get() == AppState::Playing {
+        true => {
+            let mut robots = 0;
+            let mut boss = false;
+            for body in bodies.iter() {
+                match body {
+                    Body::Monkey => {}
+                    Body::Boss => boss = true,
+                    _ => robots += 1,
+                }
+            }
+            match (boss, director.intensity) {
+                (true, _) => MusicIntensity::Boss,
+                (false, MusicIntensity::Calm) if robots < COMBAT_ROBOTS => MusicIntensity::Calm,
+                (false, MusicIntensity::Combat | MusicIntensity::Boss) if robots <= CALM_ROBOTS => {
+                    MusicIntensity::Calm
+                }
+                _ => MusicIntensity::Combat,
+            }
+        }
+        false => MusicIntensity::Calm,
+    };
+    if director.intensity != intensity {
+        director.intensity = intensity;
+    }
+}
+
+fn play_sting(
+    mut commands: Commands,
+    mut events: EventReader<WaveClearedEvent>,
+    mut director: ResMut<MusicDirector>,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let channel = AudioChannel::Music;
+    commands.spawn((
+        MixerTrack {
+            channel,
+            volume: STING_VOLUME,
+        },
+        AudioBundle {
+            source: asset_server.load(STING),
+            settings: PlaybackSettings {
+                mode: PlaybackMode::Despawn,
+                volume: Volume::Relative(VolumeLevel::new(
+                    STING_VOLUME * channel.volume(&settings),
+                )),
+                ..default()
+            },
+        },
+    ));
+    director.sting = Some(Timer::from_seconds(STING_DUCK_SECONDS, TimerMode::Once));
+}
+
+// ducks in real time, so the music doesn't stay down in slow motion or while paused
+fn update_soundtrack(
+    mut director: ResMut<MusicDirector>,
+    mut soundtrack: Query<(&mut MixerTrack, &AudioSink), With<SoundtrackTag>>,
+    settings: Res<Settings>,
+    time: Res<Time<Real>>,
+) {
+    let duck = match &mut director.sting {
+        Some(timer) => {
+            timer.tick(time.delta());
+            // ducked at the start, back up by the end
+            STING_DUCK + (1.0 - STING_DUCK) * timer.percent()
+        }
+        None => 1.0,
+    };
+    if director.sting.as_ref().is_some_and(|t| t.finished()) {
+        director.sting = None;
+    }
+    // the sink shows up once the file has loaded
+    let Ok((mut track, sink)) = soundtrack.get_single_mut() else {
+        return;
+    };
+    let volume = MUSIC_VOLUME * duck;
+    if track.volume != volume {
+        track.volume = volume;
+        sink.set_volume(volume * track.channel.volume(&settings));
+    }
+}
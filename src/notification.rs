@@ -1,6 +1,9 @@
 use bevy::prelude::*;
 
-use crate::ui_util::UiAssets;
+use crate::{
+    settings::{signal_symbol, Settings},
+    ui_util::UiAssets,
+};
 
 pub struct NotificationPlugin;
 
@@ -51,20 +54,28 @@ fn spawn_notifications(
     ui_assets: Res<UiAssets>,
     mut notification_event: EventReader<NotificationEvent>,
     node: Query<Entity, With<NotificationUiTag>>,
+    settings: Res<Settings>,
 ) {
     let node = node.single();
+    let accessibility = &settings.accessibility;
     for notification in notification_event.read() {
+        let text = match signal_symbol(notification.color) {
+            Some(symbol) if accessibility.shape_coding => {
+                format!("{} {}", symbol, notification.text)
+            }
+            _ => notification.text.clone(),
+        };
         commands
             .spawn((
                 Notification {
                     time_left: notification.show_for,
                 },
                 TextBundle::from_section(
-                    &notification.text,
+                    text,
                     TextStyle {
                         font: ui_assets.font.clone(),
                         font_size: 60.0,
-                        color: notification.color,
+                        color: accessibility.palette.remap(notification.color),
                     },
                 ),
             ))
@@ -0,0 +1,106 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    camera::MainCameraTag, player::RobotTag, settings::Settings, state::AppState,
+    ui_util::set_position,
+};
+
+const ARROW_SIZE: f32 = 28.0;
+// distance from the screen edge to the arrow center
+const EDGE_MARGIN: f32 = 24.0;
+// arrows are fully visible once the robot is this many pixels off screen
+const FADE_DISTANCE: f32 = 80.0;
+
+pub struct OffscreenIndicatorPlugin;
+
+impl Plugin for OffscreenIndicatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_offscreen_indicators);
+    }
+}
+
+// reused between frames, extras are hidden
+#[derive(Component)]
+struct OffscreenArrow;
+
+// every robot outside the view gets an arrow at the screen edge pointing at it
+fn update_offscreen_indicators(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    state: Res<State<AppState>>,
+    ui_scale: Res<UiScale>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCameraTag>>,
+    robots: Query<&GlobalTransform, With<RobotTag>>,
+    mut arrows: Query<(&mut Style, &mut Transform, &mut BackgroundColor), With<OffscreenArrow>>,
+) {
+    let mut arrows = arrows.iter_mut();
+    let view = windows
+        .get_single()
+        .ok()
+        .zip(camera.get_single().ok())
+        .filter(|_| *state.get() == AppState::Playing);
+    if let Some((window, (camera, camera_transform))) = view {
+        let scale = ui_scale.0 as f32;
+        let size = Vec2::new(window.width(), window.height()) / scale;
+        let center = size * 0.5;
+        let half_extent = center - Vec2::splat(EDGE_MARGIN);
+        let color = settings.accessibility.palette.remap(Color::RED);
+        for transform in robots.iter() {
+            let Some(pos) = camera
+                .world_to_viewport(camera_transform, transform.translation())
+                .map(|p| p / scale)
+            else {
+                continue;
+            };
+            let outside = (pos - size).max(-pos).max_element();
+            if outside <= 0.0 {
+                continue;
+            }
+            let dir = pos - center;
+            let to_edge = (half_extent / dir.abs()).min_element().min(1.0);
+            let edge = center + dir * to_edge - Vec2::splat(ARROW_SIZE * 0.5);
+            let rotation = Quat::from_rotation_z(dir.y.atan2(dir.x));
+            let color = color.with_a((outside / FADE_DISTANCE).min(1.0));
+            match arrows.next() {
+                Some((mut arrow_style, mut arrow_transform, mut arrow_color)) => {
+                    set_position(&mut arrow_style, edge);
+                    if arrow_style.display != Display::Flex {
+                        arrow_style.display = Display::Flex;
+                    }
+                    if arrow_transform.rotation != rotation {
+                        arrow_transform.rotation = rotation;
+                    }
+                    if arrow_color.0 != color {
+                        arrow_color.0 = color;
+                    }
+                }
+                None => {
+                    commands.spawn((
+                        OffscreenArrow,
+                        ImageBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                left: Val::Px(edge.x),
+                                top: Val::Px(edge.y),
+                                width: Val::Px(ARROW_SIZE),
+                                height: Val::Px(ARROW_SIZE),
+                                ..default()
+                            },
+                            image: UiImage::new(asset_server.load("icons/offscreen_arrow.png")),
+                            background_color: color.into(),
+                            transform: Transform::from_rotation(rotation),
+                            ..default()
+                        },
+                    ));
+                }
+            }
+        }
+    }
+    for (mut arrow_style, _, _) in arrows {
+        if arrow_style.display != Display::None {
+            arrow_style.display = Display::None;
+        }
+    }
+}
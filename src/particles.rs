@@ -0,0 +1,348 @@
+use std::ops::Range;
+
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    utils::HashMap,
+};
+use rand::{thread_rng, Rng};
+
+use crate::{
+    health::{ApplyHealthEvent, DeathEvent},
+    pause::not_paused,
+    player::Body,
+    projectile::Projectile,
+    tree::{SpawnTreeEvent, TreeTrunkTag},
+};
+
+// particles alive at once, bursts past this are cut short so a big fight can't tank the framerate
+const MAX_PARTICLES: usize = 800;
+// seconds between trail puffs behind each projectile
+const TRAIL_INTERVAL: f32 = 0.04;
+// hits and deaths burst from about the middle of a robot or trunk, not the ground under it
+const HIT_HEIGHT: f32 = 0.8;
+
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpawnParticlesEvent>()
+            .add_systems(Startup, setup_particle_assets)
+            .add_systems(
+                Update,
+                (
+                    particles_on_hit,
+                    particles_on_death,
+                    particles_on_plant,
+                    projectile_trails,
+                    spawn_particles,
+                    update_particles,
+                )
+                    .chain()
+                    .run_if(not_paused),
+            );
+    }
+}
+
+/// Kinds of particles, each with its own look and motion, see [`ParticleEffect::emitter`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ParticleEffect {
+    WoodChips,
+    Sparks,
+    // nuts and bolts flying off a dead robot
+    Bolts,
+    Smoke,
+    Trail,
+    Dust,
+}
+
+impl ParticleEffect {
+    const ALL: [ParticleEffect; 6] = [
+        ParticleEffect::WoodChips,
+        ParticleEffect::Sparks,
+        ParticleEffect::Bolts,
+        ParticleEffect::Smoke,
+        ParticleEffect::Trail,
+        ParticleEffect::Dust,
+    ];
+
+    fn emitter(self) -> Emitter {
+        match self {
+            ParticleEffect::WoodChips => Emitter {
+                count: 6..10,
+                color: Color::rgb(0.55, 0.38, 0.2),
+                size: 0.08..0.14,
+                speed: 2.0..5.0,
+                up: 0.6,
+                gravity: 12.0,
+                drag: 1.0,
+                lifetime: 0.5..0.9,
+            },
+            ParticleEffect::Sparks => Emitter {
+                count: 5..9,
+                color: Color::rgb(1.0, 0.8, 0.3),
+                size: 0.04..0.07,
+                speed: 4.0..8.0,
+                up: 0.3,
+                gravity: 6.0,
+                drag: 3.0,
+                lifetime: 0.15..0.35,
+            },
+            ParticleEffect::Bolts => Emitter {
+                count: 8..12,
+                color: Color::rgb(0.45, 0.47, 0.5),
+                size: 0.07..0.12,
+                speed: 3.0..6.0,
+                up: 1.0,
+                gravity: 14.0,
+                drag: 0.5,
+                lifetime: 0.7..1.1,
+            },
+            ParticleEffect::Smoke => Emitter {
+                count: 6..9,
+                color: Color::rgba(0.25, 0.25, 0.25, 0.7),
+                size: 0.3..0.5,
+                speed: 0.5..1.5,
+                up: 1.5,
+                gravity: -1.5,
+                drag: 2.0,
+                lifetime: 0.8..1.4,
+            },
+            ParticleEffect::Trail => Emitter {
+                count: 1..2,
+                color: Color::rgba(0.9, 0.9, 0.85, 0.5),
+                size: 0.06..0.09,
+                speed: 0.0..0.2,
+                up: 0.0,
+                gravity: 0.0,
+                drag: 0.0,
+                lifetime: 0.2..0.3,
+            },
+            ParticleEffect::Dust => Emitter {
+                count: 6..10,
+                color: Color::rgba(0.6, 0.5, 0.35, 0.6),
+                size: 0.15..0.25,
+                speed: 1.0..2.0,
+                up: 0.3,
+                gravity: -0.5,
+                drag: 3.0,
+                lifetime: 0.5..0.8,
+            },
+        }
+    }
+}
+
+// how a burst of one effect looks and moves
+struct Emitter {
+    count: Range<usize>,
+    color: Color,
+    // edge length of the cube at the start, it shrinks away over its lifetime
+    size: Range<f32>,
+    speed: Range<f32>,
+    // added to the random direction before scaling by speed, higher throws particles upwards
+    up: f32,
+    // downwards acceleration, negative rises like smoke
+    gravity: f32,
+    // velocity lost per second, as a fraction
+    drag: f32,
+    lifetime: Range<f32>,
+}
+
+/// Bursts particles of an effect at a point.
+#[derive(Event)]
+pub struct SpawnParticlesEvent {
+    pub effect: ParticleEffect,
+    pub pos: Vec3,
+}
+
+#[derive(Resource)]
+struct ParticleAssets {
+    mesh: Handle<Mesh>,
+    materials: HashMap<ParticleEffect, Handle<StandardMaterial>>,
+}
+
+#[derive(Component)]
+struct Particle {
+    vel: Vec3,
+    gravity: f32,
+    drag: f32,
+    size: f32,
+    lifetime: Timer,
+}
+
+fn setup_particle_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let materials = ParticleEffect::ALL
+        .into_iter()
+        .map(|effect| {
+            let color = effect.emitter().color;
+            let material = materials.add(StandardMaterial {
+                base_color: color,
+                alpha_mode: match color.a() < 1.0 {
+                    true => AlphaMode::Blend,
+                    false => AlphaMode::Opaque,
+                },
+                unlit: true,
+                ..default()
+            });
+            (effect, material)
+        })
+        .collect();
+    commands.insert_resource(ParticleAssets {
+        mesh: meshes.add(shape::Cube::new(1.0).into()),
+        materials,
+    });
+}
+
+// trees splinter, everything else sparks
+fn particles_on_hit(
+    mut events: EventReader<ApplyHealthEvent>,
+    targets: Query<(&GlobalTransform, Has<TreeTrunkTag>)>,
+    mut particle_events: EventWriter<SpawnParticlesEvent>,
+) {
+    for event in events.read() {
+        if event.amount >= 0 {
+            continue;
+        }
+        let Ok((transform, tree)) = targets.get(event.target_entity) else {
+            continue;
+        };
+        particle_events.send(SpawnParticlesEvent {
+            effect: match tree {
+                true => ParticleEffect::WoodChips,
+                false => ParticleEffect::Sparks,
+            },
+            pos: transform.translation() + Vec3::Y * HIT_HEIGHT,
+        });
+    }
+}
+
+fn particles_on_death(
+    mut events: EventReader<DeathEvent>,
+    robots: Query<(&GlobalTransform, &Body)>,
+    mut particle_events: EventWriter<SpawnParticlesEvent>,
+) {
+    for event in events.read() {
+        let Ok((transform, body)) = robots.get(event.entity) else {
+            continue;
+        };
+        if *body == Body::Monkey {
+            continue;
+        }
+        let pos = transform.translation() + Vec3::Y * HIT_HEIGHT;
+        particle_events.send_batch(
+            [ParticleEffect::Bolts, ParticleEffect::Smoke]
+                .map(|effect| SpawnParticlesEvent { effect, pos }),
+        );
+    }
+}
+
+// only freshly planted saplings kick up dust, not the trees a map starts with
+fn particles_on_plant(
+    mut events: EventReader<SpawnTreeEvent>,
+    mut particle_events: EventWriter<SpawnParticlesEvent>,
+) {
+    for event in events.read() {
+        if event.sapling {
+            particle_events.send(SpawnParticlesEvent {
+                effect: ParticleEffect::Dust,
+                pos: event.pos,
+            });
+        }
+    }
+}
+
+// pooled projectiles have their Projectile removed, so hidden ones don't leave a trail
+fn projectile_trails(
+    projectiles: Query<&GlobalTransform, With<Projectile>>,
+    mut particle_events: EventWriter<SpawnParticlesEvent>,
+    time: Res<Time>,
+    mut since_puff: Local<f32>,
+) {
+    *since_puff += time.delta_seconds();
+    if *since_puff < TRAIL_INTERVAL {
+        return;
+    }
+    *since_puff = 0.0;
+    particle_events.send_batch(projectiles.iter().map(|transform| SpawnParticlesEvent {
+        effect: ParticleEffect::Trail,
+        pos: transform.translation(),
+    }));
+}
+
+fn spawn_particles(
+    mut commands: Commands,
+    mut events: EventReader<SpawnParticlesEvent>,
+    assets: Res<ParticleAssets>,
+    particles: Query<(), With<Particle>>,
+) {
+    let mut rng = thread_rng();
+    let mut alive = particles.iter().count();
+    for event in events.read() {
+        let emitter = event.effect.emitter();
+        let count = rng
+            .gen_range(emitter.count.clone())
+            .min(MAX_PARTICLES.saturating_sub(alive));
+        alive += count;
+        for _ in 0..count {
+            let dir = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0) + emitter.up,
+                rng.gen_range(-1.0..1.0),
+            )
+            .normalize_or_zero();
+            let size = rng.gen_range(emitter.size.clone());
+            commands.spawn((
+                Name::new("Particle"),
+                Particle {
+                    vel: dir * rng.gen_range(emitter.speed.clone()),
+                    gravity: emitter.gravity,
+                    drag: emitter.drag,
+                    size,
+                    lifetime: Timer::from_seconds(
+                        rng.gen_range(emitter.lifetime.clone()),
+                        TimerMode::Once,
+                    ),
+                },
+                NotShadowCaster,
+                NotShadowReceiver,
+                PbrBundle {
+                    mesh: assets.mesh.clone(),
+                    material: assets.materials[&event.effect].clone(),
+                    transform: Transform::from_translation(event.pos)
+                        .with_rotation(Quat::from_euler(
+                            EulerRot::XYZ,
+                            rng.gen_range(0.0..std::f32::consts::TAU),
+                            rng.gen_range(0.0..std::f32::consts::TAU),
+                            0.0,
+                        ))
+                        .with_scale(Vec3::splat(size)),
+                    ..default()
+                },
+            ));
+        }
+    }
+}
+
+// particles run on game time, they freeze while paused and speed up with the game
+fn update_particles(
+    mut commands: Commands,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform)>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut particle, mut transform) in particles.iter_mut() {
+        if particle.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let drag = (1.0 - particle.drag * dt).max(0.0);
+        particle.vel.y -= particle.gravity * dt;
+        particle.vel *= drag;
+        transform.translation += particle.vel * dt;
+        transform.scale = Vec3::splat(particle.size * particle.lifetime.percent_left());
+    }
+}
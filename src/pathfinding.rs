@@ -0,0 +1,276 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{
+    math::Vec3Swizzles,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::map::MAX_MAP_SIZE_HALF;
+
+// robots spawn outside of the walls, so the grid reaches past them
+const NAV_SIZE_HALF: f32 = MAX_MAP_SIZE_HALF + 30.0;
+const CELL_SIZE: f32 = 1.0;
+const GRID_CELLS: i32 = (NAV_SIZE_HALF * 2.0 / CELL_SIZE) as i32;
+// obstacles are grown by this so characters don't clip them
+const AGENT_RADIUS: f32 = 0.5;
+// planning ends this close to the goal, the goal itself is often an obstacle (a tree)
+const GOAL_REACHED_DISTANCE: f32 = 2.0;
+const WAYPOINT_REACHED_DISTANCE: f32 = 0.6;
+// goals that moved less than this keep their old path
+const REPATH_DISTANCE: f32 = 1.5;
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+// cells A* looks at before giving up, an unreachable goal would otherwise flood the whole grid
+const MAX_EXPANDED: usize = 6000;
+
+pub struct PathfindingPlugin;
+
+impl Plugin for PathfindingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavGrid>()
+            .add_systems(PreUpdate, (update_nav_grid, plan_paths).chain());
+    }
+}
+
+/// Blocks the navigation grid around this entity.
+#[derive(Component)]
+pub struct NavObstacle {
+    pub radius: f32,
+}
+
+/// Where the entity wants to go, a path gets planned into `PathFollow` when this changes.
+#[derive(Component)]
+pub struct PathRequest {
+    pub goal: Vec3,
+}
+
+impl PathRequest {
+    /// only touches the request (and causes a repath) if the goal moved far enough
+    pub fn set_goal(request: &mut Mut<PathRequest>, goal: Vec3) {
+        if request.goal.xz().distance(goal.xz()) > REPATH_DISTANCE {
+            request.goal = goal;
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct PathFollow {
+    waypoints: Vec<Vec3>,
+    // grid cells the path walks through, it's only replanned when one of them changes
+    cells: HashSet<usize>,
+    // no way around was found, walking straight at the goal until the grid changes
+    straight: bool,
+}
+
+impl PathFollow {
+    fn straight(goal: Vec3) -> Self {
+        Self {
+            waypoints: vec![goal],
+            cells: HashSet::default(),
+            straight: true,
+        }
+    }
+
+    // whether the grid changed somewhere that matters to this path
+    fn needs_replan(&self, changed: &HashSet<usize>) -> bool {
+        !changed.is_empty() && (self.straight || !self.cells.is_disjoint(changed))
+    }
+
+    /// whether every waypoint has been reached
+    pub fn is_empty(&self) -> bool {
+        self.waypoints.is_empty()
+    }
+
+    /// direction towards the next waypoint, drops waypoints as they are reached.
+    /// None when there's nowhere left to go
+    pub fn direction(&mut self, pos: Vec3) -> Option<Vec3> {
+        while let Some(next) = self.waypoints.first() {
+            if next.xz().distance(pos.xz()) > WAYPOINT_REACHED_DISTANCE {
+                let diff = *next - pos;
+                return Some(Vec3::new(diff.x, 0.0, diff.z));
+            }
+            self.waypoints.remove(0);
+        }
+        None
+    }
+}
+
+#[derive(Resource)]
+pub struct NavGrid {
+    blocked: Vec<bool>,
+    // cells that were blocked or freed by the last rebuild
+    changed: HashSet<usize>,
+}
+
+impl Default for NavGrid {
+    fn default() -> Self {
+        Self {
+            blocked: vec![false; (GRID_CELLS * GRID_CELLS) as usize],
+            changed: HashSet::default(),
+        }
+    }
+}
+
+impl NavGrid {
+    fn cell(pos: Vec3) -> IVec2 {
+        let cell = ((pos.xz() + NAV_SIZE_HALF) / CELL_SIZE).floor().as_ivec2();
+        cell.clamp(IVec2::ZERO, IVec2::splat(GRID_CELLS - 1))
+    }
+
+    fn center(cell: IVec2) -> Vec3 {
+        let pos = (cell.as_vec2() + 0.5) * CELL_SIZE - NAV_SIZE_HALF;
+        Vec3::new(pos.x, 0.0, pos.y)
+    }
+
+    fn index(cell: IVec2) -> usize {
+        (cell.y * GRID_CELLS + cell.x) as usize
+    }
+
+    fn in_bounds(cell: IVec2) -> bool {
+        cell.cmpge(IVec2::ZERO).all() && cell.cmplt(IVec2::splat(GRID_CELLS)).all()
+    }
+
+    pub fn is_blocked(&self, pos: Vec3) -> bool {
+        self.blocked[Self::index(Self::cell(pos))]
+    }
+
+    fn block_circle(&mut self, pos: Vec3, radius: f32) {
+        let min = Self::cell(pos - Vec3::splat(radius));
+        let max = Self::cell(pos + Vec3::splat(radius));
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let cell = IVec2::new(x, y);
+                if Self::center(cell).xz().distance(pos.xz()) <= radius {
+                    self.blocked[Self::index(cell)] = true;
+                }
+            }
+        }
+    }
+
+    /// A* over the grid, 8 directions without cutting corners.
+    /// Returns waypoints from start to goal, None if the goal can't be reached
+    /// or is too far around obstacles to find.
+    pub fn find_path(&self, start: Vec3, goal: Vec3) -> Option<PathFollow> {
+        let start_cell = Self::cell(start);
+        let heuristic = |cell: IVec2| {
+            let d = (Self::cell(goal) - cell).abs();
+            DIAGONAL_COST * d.min_element() as u32
+                + STRAIGHT_COST * (d.max_element() - d.min_element()) as u32
+        };
+
+        // only the cells the search reaches, not the whole grid
+        let mut cost: HashMap<IVec2, u32> = HashMap::default();
+        let mut came_from: HashMap<IVec2, IVec2> = HashMap::default();
+        let mut open = BinaryHeap::new();
+        let mut expanded = 0;
+        cost.insert(start_cell, 0);
+        open.push(Reverse((heuristic(start_cell), start_cell.x, start_cell.y)));
+
+        while let Some(Reverse((_, x, y))) = open.pop() {
+            let cell = IVec2::new(x, y);
+            if Self::center(cell).xz().distance(goal.xz()) <= GOAL_REACHED_DISTANCE {
+                return Some(Self::build_path(&came_from, cell, goal));
+            }
+            expanded += 1;
+            if expanded > MAX_EXPANDED {
+                return None;
+            }
+            let cell_cost = cost[&cell];
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    if dx == 0 && dy == 0 {
+                        continue;
+                    }
+                    let next = cell + IVec2::new(dx, dy);
+                    if !Self::in_bounds(next) || self.blocked[Self::index(next)] {
+                        continue;
+                    }
+                    let diagonal = dx != 0 && dy != 0;
+                    if diagonal
+                        && (self.blocked[Self::index(cell + IVec2::new(dx, 0))]
+                            || self.blocked[Self::index(cell + IVec2::new(0, dy))])
+                    {
+                        continue;
+                    }
+                    let step = if diagonal {
+                        DIAGONAL_COST
+                    } else {
+                        STRAIGHT_COST
+                    };
+                    let next_cost = cell_cost + step;
+                    if cost.get(&next).is_none_or(|&c| next_cost < c) {
+                        cost.insert(next, next_cost);
+                        came_from.insert(next, cell);
+                        open.push(Reverse((next_cost + heuristic(next), next.x, next.y)));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn build_path(came_from: &HashMap<IVec2, IVec2>, end: IVec2, goal: Vec3) -> PathFollow {
+        let mut cells = vec![end];
+        while let Some(&prev) = came_from.get(cells.last().unwrap()) {
+            cells.push(prev);
+        }
+        cells.reverse();
+
+        // only keep the corners, walking straight between them
+        let mut waypoints: Vec<Vec3> = cells
+            .windows(3)
+            .filter(|w| w[1] - w[0] != w[2] - w[1])
+            .map(|w| Self::center(w[1]))
+            .collect();
+        waypoints.push(Self::center(end));
+        waypoints.push(goal);
+        PathFollow {
+            waypoints,
+            cells: cells.into_iter().map(Self::index).collect(),
+            straight: false,
+        }
+    }
+}
+
+fn update_nav_grid(
+    mut nav_grid: ResMut<NavGrid>,
+    obstacles: Query<(&GlobalTransform, &NavObstacle)>,
+    added: Query<(), Added<NavObstacle>>,
+    mut removed: RemovedComponents<NavObstacle>,
+) {
+    // obstacles don't move, so only rebuild when they come and go
+    let any_removed = removed.read().count() > 0;
+    if added.is_empty() && !any_removed {
+        return;
+    }
+    let old = std::mem::take(&mut *nav_grid);
+    for (transform, obstacle) in obstacles.iter() {
+        nav_grid.block_circle(transform.translation(), obstacle.radius + AGENT_RADIUS);
+    }
+    nav_grid.changed = old
+        .blocked
+        .iter()
+        .zip(nav_grid.blocked.iter())
+        .enumerate()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(index, _)| index)
+        .collect();
+}
+
+fn plan_paths(
+    nav_grid: Res<NavGrid>,
+    mut query: Query<(Ref<PathRequest>, &mut PathFollow, &GlobalTransform)>,
+) {
+    for (request, mut follow, transform) in query.iter_mut() {
+        let grid_changed = nav_grid.is_changed() && follow.needs_replan(&nav_grid.changed);
+        if !request.is_changed() && !grid_changed {
+            continue;
+        }
+        // straight line when there's no way around, the stuck check can still chop through
+        *follow = nav_grid
+            .find_path(transform.translation(), request.goal)
+            .unwrap_or_else(|| PathFollow::straight(request.goal));
+    }
+}
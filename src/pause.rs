@@ -0,0 +1,131 @@
+use bevy::{prelude::*, utils::HashSet, window::WindowFocused};
+use bevy_rapier3d::plugin::RapierConfiguration;
+
+use crate::{
+    notification::NotificationEvent,
+    settings::Settings,
+    state::{AppState, RestartRunEvent},
+};
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Pause>()
+            .add_systems(
+                Update,
+                (
+                    pause_on_focus_loss,
+                    toggle_pause_menu.run_if(in_state(AppState::Playing)),
+                    apply_pause,
+                )
+                    .chain(),
+            )
+            .add_systems(OnExit(AppState::Playing), close_pause_menu)
+            .add_systems(
+                PreUpdate,
+                close_pause_menu.run_if(on_event::<RestartRunEvent>()),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PauseReason {
+    FocusLost,
+    // the escape menu
+    Menu,
+    // the last campaign wave was cleared, waiting for finish or endless
+    WinPrompt,
+    // picking a boost after leveling up
+    LevelUp,
+    // picking a perk after a cleared wave
+    PerkDraft,
+    // the pause button next to the game speed buttons
+    SpeedControls,
+}
+
+/// The game is paused while there is at least one reason for it,
+/// so resuming from one reason doesn't cancel another.
+#[derive(Resource, Default)]
+pub struct Pause {
+    reasons: HashSet<PauseReason>,
+}
+
+impl Pause {
+    pub fn is_paused(&self) -> bool {
+        !self.reasons.is_empty()
+    }
+
+    pub fn is_paused_by(&self, reason: PauseReason) -> bool {
+        self.reasons.contains(&reason)
+    }
+
+    pub fn pause(&mut self, reason: PauseReason) {
+        self.reasons.insert(reason);
+    }
+
+    pub fn resume(&mut self, reason: PauseReason) {
+        self.reasons.remove(&reason);
+    }
+}
+
+/// run condition for systems that should stop while paused
+pub fn not_paused(pause: Res<Pause>) -> bool {
+    !pause.is_paused()
+}
+
+fn pause_on_focus_loss(
+    mut focus_events: EventReader<WindowFocused>,
+    mut pause: ResMut<Pause>,
+    settings: Res<Settings>,
+    mut notification_event: EventWriter<NotificationEvent>,
+) {
+    for event in focus_events.read() {
+        match event.focused {
+            false if settings.general.pause_on_focus_loss => {
+                pause.pause(PauseReason::FocusLost);
+                // notifications tick on game time, so this one stays up until we resume
+                notification_event.send(NotificationEvent {
+                    text: "Paused".to_string(),
+                    show_for: 0.5,
+                    color: Color::BLUE,
+                });
+            }
+            true => pause.resume(PauseReason::FocusLost),
+            _ => {}
+        }
+    }
+}
+
+fn toggle_pause_menu(keyboard: Res<Input<KeyCode>>, mut pause: ResMut<Pause>) {
+    if !keyboard.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    match pause.is_paused_by(PauseReason::Menu) {
+        true => pause.resume(PauseReason::Menu),
+        false => pause.pause(PauseReason::Menu),
+    }
+}
+
+fn close_pause_menu(mut pause: ResMut<Pause>) {
+    pause.resume(PauseReason::Menu);
+    pause.resume(PauseReason::WinPrompt);
+    pause.resume(PauseReason::LevelUp);
+    pause.resume(PauseReason::PerkDraft);
+}
+
+// freezing virtual time stops every timer and cooldown driven by Res<Time>
+fn apply_pause(
+    pause: Res<Pause>,
+    mut time: ResMut<Time<Virtual>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    if !pause.is_changed() {
+        return;
+    }
+    match pause.is_paused() {
+        true => time.pause(),
+        false => time.unpause(),
+    }
+    rapier_config.physics_pipeline_active = !pause.is_paused();
+}
@@ -0,0 +1,262 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    ecs::event::ManualEventReader,
+    prelude::*,
+    reflect::TypePath,
+};
+use rand::seq::IteratorRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asset_utils::CustomAssetLoaderError,
+    health::ApplyHealthEvent,
+    menu::spawn_menu,
+    pause::{Pause, PauseReason},
+    player::{Player, PlayerControllerTag, RobotTag},
+    state::{AppState, RestartRunEvent, WaveClearedEvent},
+    tree::TreeTrunkTag,
+    ui_util::{JustClicked, UiAssets},
+    weapon::WeaponStats,
+};
+
+// perks offered after every cleared wave
+const DRAFT_SIZE: usize = 3;
+
+pub struct PerkPlugin;
+
+impl Plugin for PerkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<PerksAsset>()
+            .init_asset_loader::<PerksAssetLoader>()
+            .init_resource::<PerkModifiers>()
+            .init_resource::<PendingDrafts>()
+            .add_systems(Startup, setup_perks)
+            .add_systems(OnEnter(AppState::Playing), reset_perks)
+            .add_systems(OnExit(AppState::Playing), close_draft)
+            .add_systems(
+                PreUpdate,
+                (close_draft, reset_perks).run_if(on_event::<RestartRunEvent>()),
+            )
+            .add_systems(
+                Update,
+                (queue_drafts, open_draft, perk_clicked, thorns)
+                    .chain()
+                    .run_if(in_state(AppState::Playing)),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum PerkEffect {
+    // one more projectile per shot, fanned out
+    ExtraProjectile,
+    Damage(i32),
+    CooldownMul(f32),
+    MovementSpeedMul(f32),
+    // pickups fly to the player this much faster
+    PickupFlightMul(f32),
+    // robots hitting a tree take this much damage back
+    Thorns(i32),
+    // extra max health for trees planted from now on
+    TreeHealth(i32),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Perk {
+    pub name: String,
+    pub description: String,
+    pub effect: PerkEffect,
+}
+
+#[derive(Debug, Deserialize, Asset, TypePath)]
+pub struct PerksAsset(pub Vec<Perk>);
+
+#[derive(Resource)]
+pub struct Perks(pub Handle<PerksAsset>);
+
+/// Perk effects that aren't a stat on the player, reset every run.
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PerkModifiers {
+    pub pickup_flight_mul: f32,
+    pub thorns: i32,
+    pub tree_health: i32,
+}
+
+impl Default for PerkModifiers {
+    fn default() -> Self {
+        Self {
+            pickup_flight_mul: 1.0,
+            thorns: 0,
+            tree_health: 0,
+        }
+    }
+}
+
+/// Cleared waves the player hasn't picked a perk for yet.
+#[derive(Resource, Default)]
+pub struct PendingDrafts(pub u32);
+
+#[derive(Component)]
+struct PerkDraftUiTag;
+
+// index into the perks asset
+#[derive(Component, Clone, Copy)]
+struct PerkChoice(usize);
+
+fn setup_perks(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Perks(asset_server.load("perks.perks.ron")));
+}
+
+fn reset_perks(mut modifiers: ResMut<PerkModifiers>, mut pending: ResMut<PendingDrafts>) {
+    *modifiers = PerkModifiers::default();
+    pending.0 = 0;
+}
+
+/// False while a cleared wave's perk draft hasn't been picked, the next wave waits for it.
+pub fn no_pending_drafts(pending: Res<PendingDrafts>) -> bool {
+    pending.0 == 0
+}
+
+fn queue_drafts(mut events: EventReader<WaveClearedEvent>, mut pending: ResMut<PendingDrafts>) {
+    pending.0 += events.read().count() as u32;
+}
+
+fn open_draft(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    perks: Res<Perks>,
+    perk_assets: Res<Assets<PerksAsset>>,
+    pending: Res<PendingDrafts>,
+    menus: Query<(), With<PerkDraftUiTag>>,
+    mut pause: ResMut<Pause>,
+) {
+    // waits for the level up menu, both at once would overlap
+    if pending.0 == 0 || !menus.is_empty() || pause.is_paused_by(PauseReason::LevelUp) {
+        return;
+    }
+    let Some(perks) = perk_assets.get(&perks.0) else {
+        return;
+    };
+    let offered = (0..perks.0.len()).choose_multiple(&mut rand::thread_rng(), DRAFT_SIZE);
+    let labels: Vec<_> = offered
+        .iter()
+        .map(|i| {
+            let perk = &perks.0[*i];
+            (
+                PerkChoice(*i),
+                format!("{}: {}", perk.name, perk.description),
+            )
+        })
+        .collect();
+    let buttons: Vec<_> = labels
+        .iter()
+        .map(|(choice, label)| (*choice, label.as_str()))
+        .collect();
+    pause.pause(PauseReason::PerkDraft);
+    spawn_menu(
+        &mut commands,
+        PerkDraftUiTag,
+        &ui_assets,
+        "Wave cleared!",
+        Color::GREEN,
+        Some("Pick a perk".to_string()),
+        &buttons,
+    );
+}
+
+fn perk_clicked(
+    mut commands: Commands,
+    buttons: Query<&PerkChoice, With<JustClicked>>,
+    menus: Query<Entity, With<PerkDraftUiTag>>,
+    perks: Res<Perks>,
+    perk_assets: Res<Assets<PerksAsset>>,
+    mut players: Query<(&mut Player, &mut WeaponStats), With<PlayerControllerTag>>,
+    mut modifiers: ResMut<PerkModifiers>,
+    mut pending: ResMut<PendingDrafts>,
+    mut pause: ResMut<Pause>,
+) {
+    let Some(choice) = buttons.iter().next() else {
+        return;
+    };
+    let Some(perk) = perk_assets.get(&perks.0).and_then(|p| p.0.get(choice.0)) else {
+        return;
+    };
+    if let Ok((mut player, mut stats)) = players.get_single_mut() {
+        match perk.effect {
+            PerkEffect::ExtraProjectile => stats.extra_projectiles += 1,
+            PerkEffect::Damage(amount) => stats.damage_add += amount,
+            PerkEffect::CooldownMul(mul) => stats.cooldown_mul *= mul,
+            PerkEffect::MovementSpeedMul(mul) => player.movement_speed *= mul,
+            PerkEffect::PickupFlightMul(mul) => modifiers.pickup_flight_mul *= mul,
+            PerkEffect::Thorns(amount) => modifiers.thorns += amount,
+            PerkEffect::TreeHealth(amount) => modifiers.tree_health += amount,
+        }
+    }
+    pending.0 = pending.0.saturating_sub(1);
+    for entity in menus.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    pause.resume(PauseReason::PerkDraft);
+}
+
+fn close_draft(mut commands: Commands, menus: Query<Entity, With<PerkDraftUiTag>>) {
+    for entity in menus.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// reads and sends health events, so it can't use an EventReader and EventWriter together
+fn thorns(
+    mut events: ResMut<Events<ApplyHealthEvent>>,
+    mut reader: Local<ManualEventReader<ApplyHealthEvent>>,
+    modifiers: Res<PerkModifiers>,
+    trees: Query<(), With<TreeTrunkTag>>,
+    robots: Query<(), With<RobotTag>>,
+) {
+    let hits: Vec<_> = reader
+        .read(&events)
+        .filter(|e| e.amount < 0 && trees.contains(e.target_entity))
+        .filter(|e| robots.contains(e.caster_entity))
+        .map(|e| (e.target_entity, e.caster_entity))
+        .collect();
+    if modifiers.thorns <= 0 {
+        return;
+    }
+    for (tree, robot) in hits {
+        events.send(ApplyHealthEvent {
+            amount: -modifiers.thorns,
+            target_entity: robot,
+            caster_entity: tree,
+            is_crit: false,
+            is_finisher: false,
+        });
+    }
+}
+
+#[derive(Default)]
+pub struct PerksAssetLoader;
+
+impl AssetLoader for PerksAssetLoader {
+    type Asset = PerksAsset;
+    type Settings = ();
+    type Error = CustomAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let asset = ron::de::from_bytes::<PerksAsset>(&bytes)?;
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["perks.ron"]
+    }
+}
@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+const GAME_DIR: &str = "no_communication_0";
+
+/// where save files like achievements and high scores are stored
+pub fn data_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|h| PathBuf::from(h).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+    };
+    base.map(|b| b.join(GAME_DIR))
+}
+
+/// where settings are stored
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|h| PathBuf::from(h).join("Library/Preferences"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+    };
+    base.map(|b| b.join(GAME_DIR))
+}
+
+/// read a ron file from the data dir, None if it's missing or broken
+pub fn load<T: DeserializeOwned>(file_name: &str) -> Option<T> {
+    load_from(data_dir()?, file_name)
+}
+
+/// write a ron file to the data dir, failing is not fatal so it only warns
+pub fn save<T: Serialize>(file_name: &str, value: &T) {
+    save_to(data_dir(), file_name, value);
+}
+
+/// read a ron file from the config dir, None if it's missing or broken
+pub fn load_config<T: DeserializeOwned>(file_name: &str) -> Option<T> {
+    load_from(config_dir()?, file_name)
+}
+
+/// write a ron file to the config dir, failing is not fatal so it only warns
+pub fn save_config<T: Serialize>(file_name: &str, value: &T) {
+    save_to(config_dir(), file_name, value);
+}
+
+fn load_from<T: DeserializeOwned>(dir: PathBuf, file_name: &str) -> Option<T> {
+    let path = dir.join(file_name);
+    let text = std::fs::read_to_string(&path).ok()?;
+    match ron::from_str(&text) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warn!("could not parse {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+fn save_to<T: Serialize>(dir: Option<PathBuf>, file_name: &str, value: &T) {
+    let Some(dir) = dir else {
+        warn!("no data directory, can't save {}", file_name);
+        return;
+    };
+    let text = match ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()) {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("could not serialize {}: {}", file_name, e);
+            return;
+        }
+    };
+    if let Err(e) =
+        std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(dir.join(file_name), text))
+    {
+        warn!("could not save {}: {}", file_name, e);
+    }
+}
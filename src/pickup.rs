@@ -1,19 +1,63 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-use crate::{inventory::Item, item_pickups::SpawnItemEvent};
+use crate::{
+    inventory::{Inventory, Item, ItemMetadata, ItemMetadataAsset},
+    item_pickups::{ItemPickup, SpawnItemEvent},
+    perks::PerkModifiers,
+};
 
 pub const PICKUP_FLY_SPEED: f32 = 10.0;
 pub const TIME_TO_FLY: f32 = 0.4;
+// seconds between the auto vacuum pulling in every pickup on the map
+const VACUUM_INTERVAL: f32 = 15.0;
+// how long a dropped item ignores whoever dropped it
+const DROP_PICKUP_DELAY: f32 = 3.0;
 
 #[derive(Component)]
 pub struct PickupMagnet {
     pub root_entity: Entity,
+    // pickups this close fly to the root, the sensor collider is resized to match
+    pub radius: f32,
+    // bought from the shop, pulls in everything when it goes off
+    vacuum: Option<Timer>,
+}
+
+impl PickupMagnet {
+    pub fn new(root_entity: Entity, radius: f32) -> Self {
+        Self {
+            root_entity,
+            radius,
+            vacuum: None,
+        }
+    }
+
+    pub fn enable_vacuum(&mut self) {
+        if self.vacuum.is_none() {
+            self.vacuum = Some(Timer::from_seconds(VACUUM_INTERVAL, TimerMode::Repeating));
+        }
+    }
 }
 
 #[derive(Component)]
 pub struct PickupTag;
 
+/// Dropped on purpose, the dropper can't pick it up until the timer runs out.
+#[derive(Component)]
+pub struct DroppedBy {
+    pub entity: Entity,
+    timer: Timer,
+}
+
+impl DroppedBy {
+    pub fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            timer: Timer::from_seconds(DROP_PICKUP_DELAY, TimerMode::Once),
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct FlyToEntity {
     pub entity: Entity,
@@ -35,6 +79,7 @@ pub struct PickupPlugin;
 impl Plugin for PickupPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(PreUpdate, (detect_pickup, fly_to_target))
+            .add_systems(Update, (resize_magnets, vacuum_pickups, tick_dropped_by))
             .add_systems(Last, destroy_pickups);
     }
 }
@@ -46,6 +91,7 @@ fn fly_to_target(
     time: Res<Time>,
     mut pickup_event: EventWriter<OnPickedUpEvent>,
     mut spawn_item_event: EventWriter<SpawnItemEvent>, // in case the target entity dies
+    perks: Res<PerkModifiers>,
 ) {
     for (pickup_entity, mut transform, mut fly_to_entity) in query.iter_mut() {
         let Ok(target_transform) = transforms.get(fly_to_entity.entity) else {
@@ -53,12 +99,14 @@ fn fly_to_target(
             spawn_item_event.send(SpawnItemEvent {
                 item: Item::Log,
                 pos: transform.translation,
+                dropped_by: None,
             });
 
             continue;
         };
         fly_to_entity.progress += time.delta_seconds();
-        let percent = (fly_to_entity.progress / TIME_TO_FLY).clamp(0.0, 1.0);
+        let time_to_fly = TIME_TO_FLY / perks.pickup_flight_mul;
+        let percent = (fly_to_entity.progress / time_to_fly).clamp(0.0, 1.0);
 
         let mut lerped = fly_to_entity
             .initial_pos
@@ -87,12 +135,48 @@ fn destroy_pickups(mut pickup_event: EventReader<OnPickedUpEvent>, mut commands:
     }
 }
 
+fn tick_dropped_by(
+    mut commands: Commands,
+    mut dropped: Query<(Entity, &mut DroppedBy)>,
+    time: Res<Time>,
+) {
+    for (entity, mut dropped_by) in dropped.iter_mut() {
+        if dropped_by.timer.tick(time.delta()).just_finished() {
+            commands.entity(entity).remove::<DroppedBy>();
+        }
+    }
+}
+
+// full stacks and fresh drops of the receiver itself are left on the ground
+fn can_pick_up(
+    receiver: Entity,
+    pickup: Entity,
+    inventories: &Query<&Inventory>,
+    items: &Query<(&ItemPickup, Option<&DroppedBy>)>,
+    metadata: Option<&ItemMetadataAsset>,
+) -> bool {
+    let Ok((item, dropped_by)) = items.get(pickup) else {
+        return true;
+    };
+    if dropped_by.is_some_and(|d| d.entity == receiver) {
+        return false;
+    }
+    let Ok(inventory) = inventories.get(receiver) else {
+        return true;
+    };
+    inventory.has_room(item.0, metadata.and_then(|m| m.max_stack(item.0)))
+}
+
 fn detect_pickup(
     mut events: EventReader<CollisionEvent>,
     pickup_magnets: Query<&PickupMagnet>,
     pickups: Query<(Entity, &GlobalTransform), With<PickupTag>>,
     mut commands: Commands,
+    (inventories, items): (Query<&Inventory>, Query<(&ItemPickup, Option<&DroppedBy>)>),
+    item_metadata: Res<ItemMetadata>,
+    metadata_assets: Res<Assets<ItemMetadataAsset>>,
 ) {
+    let metadata = metadata_assets.get(&item_metadata.0);
     for event in events.read() {
         let CollisionEvent::Started(e1, e2, _event_flags) = event else {
             continue;
@@ -111,16 +195,73 @@ fn detect_pickup(
             (Err(_), Err(_), Ok(m), Ok(p)) => (m, p),
             _ => continue,
         };
+        if !can_pick_up(
+            magnet.root_entity,
+            pickup_entity,
+            &inventories,
+            &items,
+            metadata,
+        ) {
+            continue;
+        }
+
+        fly_to(
+            &mut commands,
+            pickup_entity,
+            pickup_transform.translation(),
+            magnet.root_entity,
+        );
+    }
+}
+
+fn fly_to(commands: &mut Commands, pickup_entity: Entity, pos: Vec3, target: Entity) {
+    commands
+        .entity(pickup_entity)
+        .insert(FlyToEntity {
+            entity: target,
+            initial_pos: pos,
+            progress: 0.0,
+        })
+        .remove::<RigidBody>()
+        .remove::<Collider>()
+        .remove::<PickupTag>();
+}
 
-        commands
-            .entity(pickup_entity)
-            .insert(FlyToEntity {
-                entity: magnet.root_entity,
-                initial_pos: pickup_transform.translation(),
-                progress: 0.0,
-            })
-            .remove::<RigidBody>()
-            .remove::<Collider>()
-            .remove::<PickupTag>();
+fn resize_magnets(mut magnets: Query<(&PickupMagnet, &mut Collider), Changed<PickupMagnet>>) {
+    for (magnet, mut collider) in magnets.iter_mut() {
+        *collider = Collider::ball(magnet.radius);
+    }
+}
+
+fn vacuum_pickups(
+    mut commands: Commands,
+    mut magnets: Query<&mut PickupMagnet>,
+    pickups: Query<(Entity, &GlobalTransform), With<PickupTag>>,
+    time: Res<Time>,
+    (inventories, items): (Query<&Inventory>, Query<(&ItemPickup, Option<&DroppedBy>)>),
+    item_metadata: Res<ItemMetadata>,
+    metadata_assets: Res<Assets<ItemMetadataAsset>>,
+) {
+    let metadata = metadata_assets.get(&item_metadata.0);
+    for mut magnet in magnets.iter_mut() {
+        let root_entity = magnet.root_entity;
+        // ticking isn't a change, resize_magnets would rebuild the collider every frame
+        let Some(vacuum) = magnet.bypass_change_detection().vacuum.as_mut() else {
+            continue;
+        };
+        if !vacuum.tick(time.delta()).just_finished() {
+            continue;
+        }
+        for (pickup_entity, transform) in pickups.iter() {
+            if !can_pick_up(root_entity, pickup_entity, &inventories, &items, metadata) {
+                continue;
+            }
+            fly_to(
+                &mut commands,
+                pickup_entity,
+                transform.translation(),
+                root_entity,
+            );
+        }
     }
 }
@@ -0,0 +1,401 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_rapier3d::prelude::{Collider, CollisionGroups, Group, QueryFilter, RapierContext};
+
+use crate::{
+    collision_groups::{COLLISION_BORDER, COLLISION_CHARACTER, COLLISION_TREES, COLLISION_WORLD},
+    depot::{DepotAssets, SpawnDepotEvent, DEPOT_SIZE},
+    inventory::Inventory,
+    map::MapBounds,
+    notification::NotificationEvent,
+    pause::not_paused,
+    player::Body,
+    pointer::PointerPos,
+    settings::Settings,
+    shop::{ShopItemData, SpawnShopItemEvent},
+    shrine::{ShrineAssets, SpawnShrineEvent, SHRINE_HEIGHT},
+    state::{AppState, RestartRunEvent},
+    tower::SpawnTowerEvent,
+    tree_spawner::SpawnTreeSpawnerEvent,
+    wall::{SpawnWallEvent, WallAssets, WALL_HEIGHT, WALL_LENGTH, WALL_THICKNESS},
+};
+
+// turns the wall being placed by a quarter
+const ROTATE_KEY: KeyCode = KeyCode::Q;
+// walls always snap to a grid this big so segments line up
+const WALL_SNAP: f32 = WALL_LENGTH / 2.0;
+// everything else snaps to this when the placement grid setting is on
+const GRID_SNAP: f32 = 1.0;
+
+pub struct PlacementPlugin;
+
+impl Plugin for PlacementPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<StartPlacementEvent>()
+            .init_resource::<Placement>()
+            .add_systems(Startup, setup_ghost_materials)
+            .add_systems(
+                Update,
+                (start_placement, update_placement, tint_ghost)
+                    .chain()
+                    .run_if(not_paused),
+            )
+            .add_systems(OnExit(AppState::Playing), drop_placement)
+            .add_systems(
+                PreUpdate,
+                drop_placement.run_if(on_event::<RestartRunEvent>()),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Building {
+    Tower,
+    TreeSpawner,
+    Wall,
+    Shrine,
+    Depot,
+}
+
+impl Building {
+    // walls, shrines and depots are plain meshes instead
+    fn model(self) -> Option<&'static str> {
+        match self {
+            Building::Tower => Some("models/buildings/tower.glb#Scene0"),
+            Building::TreeSpawner => Some("models/buildings/tree_spawner.gltf#Scene0"),
+            Building::Wall | Building::Shrine | Building::Depot => None,
+        }
+    }
+
+    // where the spawned model ends up relative to the position it's built at
+    fn model_offset(self) -> Vec3 {
+        match self {
+            Building::Tower => Vec3::Y * 5.0,
+            Building::TreeSpawner => Vec3::X,
+            Building::Wall => Vec3::Y * WALL_HEIGHT / 2.0,
+            Building::Shrine => Vec3::Y * SHRINE_HEIGHT / 2.0,
+            Building::Depot => Vec3::Y * DEPOT_SIZE.y / 2.0,
+        }
+    }
+
+    // nothing else may be this close to the building
+    fn footprint(self) -> f32 {
+        match self {
+            Building::Tower => 1.5,
+            Building::TreeSpawner => 1.0,
+            Building::Wall => WALL_LENGTH / 2.0,
+            Building::Shrine => 1.0,
+            Building::Depot => DEPOT_SIZE.x / 2.0,
+        }
+    }
+
+    // checked for overlaps before the building is placed
+    fn shape(self) -> Collider {
+        match self {
+            Building::Wall => {
+                Collider::cuboid(WALL_LENGTH / 2.0, WALL_HEIGHT / 2.0, WALL_THICKNESS / 2.0)
+            }
+            _ => Collider::cylinder(1.0, self.footprint()),
+        }
+    }
+}
+
+/// Bought a building, let the player pick where it goes.
+#[derive(Event)]
+pub struct StartPlacementEvent {
+    pub building: Building,
+    pub buyer: Entity,
+    // handed back if the placement is cancelled
+    pub item: ShopItemData,
+}
+
+struct ActivePlacement {
+    building: Building,
+    buyer: Entity,
+    item: ShopItemData,
+    ghost: Entity,
+    // why it can't be placed where the pointer is
+    invalid: Option<InvalidPlacement>,
+    // only walls can be turned
+    rotation: Quat,
+}
+
+#[derive(Clone, Copy)]
+enum InvalidPlacement {
+    OutsideMap,
+    Blocked,
+}
+
+impl InvalidPlacement {
+    fn text(self) -> &'static str {
+        match self {
+            InvalidPlacement::OutsideMap => "Can't build outside the map",
+            InvalidPlacement::Blocked => "Something is in the way",
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Placement(Option<ActivePlacement>);
+
+impl Placement {
+    pub fn is_active(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+/// run condition for mouse input that shouldn't happen while placing a building
+pub fn not_placing(placement: Res<Placement>) -> bool {
+    !placement.is_active()
+}
+
+#[derive(Component)]
+struct GhostTag;
+
+#[derive(Resource)]
+struct GhostMaterials {
+    valid: Handle<StandardMaterial>,
+    invalid: Handle<StandardMaterial>,
+}
+
+fn setup_ghost_materials(mut commands: Commands, mut materials: ResMut<Assets<StandardMaterial>>) {
+    let mut ghost = |color: Color| {
+        materials.add(StandardMaterial {
+            base_color: color.with_a(0.4),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        })
+    };
+    commands.insert_resource(GhostMaterials {
+        valid: ghost(Color::GREEN),
+        invalid: ghost(Color::RED),
+    });
+}
+
+fn start_placement(
+    mut commands: Commands,
+    mut events: EventReader<StartPlacementEvent>,
+    mut placement: ResMut<Placement>,
+    mut inventory: Query<&mut Inventory>,
+    mut spawn_shop_item_event: EventWriter<SpawnShopItemEvent>,
+    asset_server: Res<AssetServer>,
+    wall_assets: Res<WallAssets>,
+    shrine_assets: Res<ShrineAssets>,
+    depot_assets: Res<DepotAssets>,
+) {
+    for event in events.read() {
+        // buying another building while placing gives the first one back
+        if let Some(previous) = placement.0.take() {
+            refund(&previous, &mut inventory, &mut spawn_shop_item_event);
+            commands.entity(previous.ghost).despawn_recursive();
+        }
+        let ghost = match event.building.model() {
+            Some(model) => commands.spawn((
+                GhostTag,
+                SceneBundle {
+                    scene: asset_server.load(model),
+                    ..default()
+                },
+            )),
+            // the ghost material goes on the children, so the mesh is one
+            None => {
+                let mesh = match event.building {
+                    Building::Shrine => shrine_assets.mesh.clone(),
+                    Building::Depot => depot_assets.mesh.clone(),
+                    _ => wall_assets.mesh.clone(),
+                };
+                let mut ghost = commands.spawn((GhostTag, SpatialBundle::default()));
+                ghost.with_children(|parent| {
+                    parent.spawn(PbrBundle { mesh, ..default() });
+                });
+                ghost
+            }
+        }
+        .id();
+        placement.0 = Some(ActivePlacement {
+            building: event.building,
+            buyer: event.buyer,
+            item: event.item.clone(),
+            ghost,
+            invalid: None,
+            rotation: Quat::IDENTITY,
+        });
+    }
+}
+
+fn refund(
+    placement: &ActivePlacement,
+    inventory: &mut Query<&mut Inventory>,
+    spawn_shop_item_event: &mut EventWriter<SpawnShopItemEvent>,
+) {
+    if let Ok(mut inventory) = inventory.get_mut(placement.buyer) {
+        for (item, count) in placement.item.cost.iter() {
+            inventory.add_item(*item, *count);
+        }
+    }
+    // one-off items were removed from the shop when bought
+    if !placement.item.permanent {
+        spawn_shop_item_event.send(SpawnShopItemEvent {
+            item: placement.item.clone(),
+        });
+    }
+}
+
+fn update_placement(
+    mut commands: Commands,
+    mut placement: ResMut<Placement>,
+    pointer: Res<PointerPos>,
+    mouse: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    rapier_context: Res<RapierContext>,
+    mut ghosts: Query<&mut Transform, With<GhostTag>>,
+    mut inventory: Query<&mut Inventory>,
+    mut spawn_shop_item_event: EventWriter<SpawnShopItemEvent>,
+    mut spawn_tower_event: EventWriter<SpawnTowerEvent>,
+    mut spawn_tree_spawner_event: EventWriter<SpawnTreeSpawnerEvent>,
+    mut spawn_wall_event: EventWriter<SpawnWallEvent>,
+    mut spawn_shrine_event: EventWriter<SpawnShrineEvent>,
+    (characters, settings, mut notification_event, mut spawn_depot_event): (
+        Query<(), With<Body>>,
+        Res<Settings>,
+        EventWriter<NotificationEvent>,
+        EventWriter<SpawnDepotEvent>,
+    ),
+    bounds: Res<MapBounds>,
+) {
+    let Some(active) = placement.0.as_mut() else {
+        return;
+    };
+
+    if mouse.just_pressed(MouseButton::Right) {
+        refund(active, &mut inventory, &mut spawn_shop_item_event);
+        commands.entity(active.ghost).despawn_recursive();
+        placement.0 = None;
+        return;
+    }
+
+    let Some(mut pos) = pointer.ground_pos else {
+        return;
+    };
+    let building = active.building;
+    if building == Building::Wall {
+        if keyboard.just_pressed(ROTATE_KEY) {
+            active.rotation *= Quat::from_rotation_y(FRAC_PI_2);
+        }
+        pos = (pos / WALL_SNAP).round() * WALL_SNAP;
+        pos.y = 0.0;
+    } else if settings.general.placement_grid {
+        pos = (pos / GRID_SNAP).round() * GRID_SNAP;
+        pos.y = 0.0;
+    }
+    let center = pos + building.model_offset() * Vec3::new(1.0, 0.0, 1.0);
+    if let Ok(mut transform) = ghosts.get_mut(active.ghost) {
+        transform.translation = pos + building.model_offset();
+        transform.rotation = active.rotation;
+    }
+
+    let inside_map = bounds.contains(center.xz(), building.footprint());
+    // lifted off the ground so the ground collider itself doesn't count.
+    // tree spawners are in the character group and towers only interact with characters,
+    // so both groups are checked and the characters themselves are skipped instead
+    let shape = building.shape();
+    let not_character = |entity| !characters.contains(entity);
+    let filter = QueryFilter {
+        groups: Some(CollisionGroups::new(
+            Group::from_bits(COLLISION_CHARACTER | COLLISION_WORLD).unwrap(),
+            Group::from_bits(
+                COLLISION_CHARACTER | COLLISION_WORLD | COLLISION_TREES | COLLISION_BORDER,
+            )
+            .unwrap(),
+        )),
+        predicate: Some(&not_character),
+        ..default()
+    }
+    .exclude_sensors();
+    let mut overlapping = false;
+    rapier_context.intersections_with_shape(
+        center + Vec3::Y * 1.5,
+        active.rotation,
+        &shape,
+        filter,
+        |_| {
+            overlapping = true;
+            false
+        },
+    );
+    active.invalid = match (inside_map, overlapping) {
+        (false, _) => Some(InvalidPlacement::OutsideMap),
+        (_, true) => Some(InvalidPlacement::Blocked),
+        _ => None,
+    };
+
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    if let Some(invalid) = active.invalid {
+        notification_event.send(NotificationEvent {
+            text: invalid.text().into(),
+            show_for: 2.0,
+            color: Color::RED,
+        });
+    } else {
+        match building {
+            Building::Tower => spawn_tower_event.send(SpawnTowerEvent {
+                pos,
+                cost: active.item.cost.clone(),
+            }),
+            Building::TreeSpawner => spawn_tree_spawner_event.send(SpawnTreeSpawnerEvent {
+                pos,
+                cost: active.item.cost.clone(),
+            }),
+            Building::Wall => spawn_wall_event.send(SpawnWallEvent {
+                pos,
+                rotation: active.rotation,
+                cost: active.item.cost.clone(),
+            }),
+            Building::Shrine => spawn_shrine_event.send(SpawnShrineEvent {
+                pos,
+                cost: active.item.cost.clone(),
+            }),
+            Building::Depot => spawn_depot_event.send(SpawnDepotEvent {
+                pos,
+                cost: active.item.cost.clone(),
+                items: vec![],
+            }),
+        }
+        commands.entity(active.ghost).despawn_recursive();
+        placement.0 = None;
+    }
+}
+
+// every mesh of the ghost model is drawn with the same see-through material
+fn tint_ghost(
+    placement: Res<Placement>,
+    ghost_materials: Res<GhostMaterials>,
+    children: Query<&Children>,
+    mut material_handles: Query<&mut Handle<StandardMaterial>>,
+) {
+    let Some(active) = placement.0.as_ref() else {
+        return;
+    };
+    let material = match active.invalid {
+        None => &ghost_materials.valid,
+        Some(_) => &ghost_materials.invalid,
+    };
+    for child in children.iter_descendants(active.ghost) {
+        if let Ok(mut handle) = material_handles.get_mut(child) {
+            if *handle != *material {
+                *handle = material.clone();
+            }
+        }
+    }
+}
+
+// the run is over, the building is lost with it
+fn drop_placement(mut commands: Commands, mut placement: ResMut<Placement>) {
+    if let Some(active) = placement.0.take() {
+        commands.entity(active.ghost).despawn_recursive();
+    }
+}
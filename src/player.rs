@@ -1,31 +1,50 @@
-use std::cmp::Ordering;
-
 use bevy::{math::vec3, prelude::*, utils::HashMap};
 use bevy_rapier3d::prelude::*;
-use rand::{thread_rng, Rng};
+use bevy_vector_shapes::{prelude::ShapePainter, shapes::LinePainter};
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    activity_lod::Dormant,
     animation_linker::{AnimationEntityLink, AnimationEntityLinkTrap},
+    boss::boss_behavior,
     camera::MainCameraTag,
     collision_groups::{
         COLLISION_BORDER, COLLISION_CHARACTER, COLLISION_ITEM_PICKUP, COLLISION_POINTER,
         COLLISION_PROJECTILES, COLLISION_WORLD,
     },
-    health::{DeathSound, Health, ShowHealthBar},
+    companion::{companion_ai, CompanionController},
+    cutscene::input_unlocked,
+    experience::Experience,
+    fixed_step::{FixedStepSet, InterpolatedTransform},
+    health::{
+        Armor, DeathAnimation, DeathSound, Dying, Health, InvulnerableOnHit, Shield, ShowHealthBar,
+    },
+    input_map::{Action, InputBindings, InputDevice, InputMap},
     inventory::Inventory,
-    item_pickups::PickupSound,
+    item_pickups::{DropSource, DropsOnDeath, PickupSound},
+    knockback::{KnockbackResistance, Staggered},
+    pathfinding::{PathFollow, PathRequest},
+    pause::not_paused,
     pickup::PickupMagnet,
+    placement::Placement,
     pointer::PointerPos,
-    tree::TreeTrunkTag,
-    tree_spawner::TreeSpawner,
-    utils::movement_axis,
-    weapon::{TryCastWeaponEvent, WeaponCooldown, WeaponStats, WeaponType},
+    robot_ai::{robot_ai, AiProfile, RobotController},
+    sapper::StructureTag,
+    settings::Settings,
+    state::AppState,
+    status_effect::{apply_stun, StatusEffects},
+    threat::ThreatTable,
+    tree::TreeRootTag,
+    weapon::{
+        Combo, TryCastWeaponEvent, Weapon, WeaponAsset, WeaponCooldown, WeaponStats, COMBO_LENGTH,
+    },
 };
 
 pub const PLAYER_HEALTH: i32 = 20;
 pub const ROBOT_HEALTH: i32 = 10;
 pub const BOSS_HEALTH: i32 = 100;
 pub const FAST_ROBOT_HEALTH: i32 = 6;
+pub const SAPPER_HEALTH: i32 = 5;
 pub const PLAYER_PICKUP_RADIUS: f32 = 3.0;
 
 #[derive(Component)]
@@ -35,28 +54,72 @@ pub struct Player {
     pub rotation_speed: f32,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Component)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Component, Serialize, Deserialize)]
 pub enum Body {
     Monkey,
     Robot,
     FastRobot,
     Boss,
+    // runs for buildings and blows up next to them
+    Sapper,
+}
+
+impl Body {
+    pub fn name(self) -> &'static str {
+        match self {
+            Body::Monkey => "Monkey",
+            Body::Robot => "Robot",
+            Body::FastRobot => "Fast robot",
+            Body::Boss => "Boss",
+            Body::Sapper => "Sapper",
+        }
+    }
+
+    // xp the player gets for killing one
+    pub fn xp_reward(self) -> u32 {
+        match self {
+            Body::Monkey => 0,
+            Body::Robot => 1,
+            Body::FastRobot | Body::Sapper => 2,
+            Body::Boss => 10,
+        }
+    }
+}
+
+// who decides what a spawned player does
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Controller {
+    Player,
+    Robot,
+    Companion,
 }
 
 #[derive(Event)]
 pub struct SpawnPlayerEvent {
     pub pos: Vec3,
-    pub is_main: bool,
+    pub controller: Controller,
     pub body: Body,
-    pub weapon_type: WeaponType,
+    pub weapon: Handle<WeaponAsset>,
+    pub scaling: EnemyScaling,
 }
 
-#[derive(Component)]
-pub struct RobotController {
-    target: Option<Entity>,
-    attack_monkey_range: f32,
-    /// Keeps track of where we were at certain intervals, to determine if we're stuck or not.
-    last_position_check: Option<(f64, Vec3)>,
+/// Makes enemies of later waves tougher than their body alone would.
+#[derive(Clone, Copy, Debug)]
+pub struct EnemyScaling {
+    pub health_mul: f32,
+    pub speed_mul: f32,
+    // on top of the body's own WeaponStats::damage_add
+    pub damage_add: i32,
+}
+
+impl Default for EnemyScaling {
+    fn default() -> Self {
+        Self {
+            health_mul: 1.0,
+            speed_mul: 1.0,
+            damage_add: 0,
+        }
+    }
 }
 
 #[derive(Component)]
@@ -70,13 +133,31 @@ pub struct MonkeyTag;
 #[derive(Component)]
 pub struct RobotTag;
 
+// the scene children holding the visuals of a body
+#[derive(Component, Default)]
+pub struct BodyGraphics {
+    // how far stagger and topple_dying have tipped it over, on top of its own rotation
+    tilt: f32,
+}
+
+impl BodyGraphics {
+    // tips the graphics to angle around their own x axis, keeping whatever rotation they had
+    fn set_tilt(&mut self, transform: &mut Transform, angle: f32) {
+        transform.rotate_local_x(angle - self.tilt);
+        self.tilt = angle;
+    }
+}
+
 // input controller + ai can set these values to controll the wanted actions
 // see playercontrollerTag and dumpplayercontroller
 #[derive(Component, Default)]
 pub struct PlayerInput {
     pub movement: Vec3,
     pub jump: bool,
+    // the controlled player's attack stays set until a fixed step sends it, see apply_attack
     pub attack: Option<(Vec3, Option<Entity>)>,
+    // how far a charge attack is charged up, 0.0 to 1.0
+    pub charge: f32,
 }
 
 pub struct PlayerPlugin;
@@ -86,157 +167,228 @@ impl Plugin for PlayerPlugin {
         app.add_event::<SpawnPlayerEvent>()
             .add_systems(Startup, load_character_models)
             .add_systems(Update, spawn_players)
-            .add_systems(Update, animate_farmer)
+            .add_systems(Update, (animate_farmer, draw_charge_bar))
             .add_systems(Update, (input, update_farmer_animation).chain())
             .add_systems(
                 Update,
                 (
-                    (movement_input, attack_input, robot_ai),
+                    (movement_input, attack_input).run_if(input_unlocked),
+                    clear_controller_input.run_if(not(input_unlocked)),
+                )
+                    .run_if(not_paused),
+            )
+            // player input is read every frame, the ai and movement step with physics
+            .add_systems(
+                FixedUpdate,
+                (
+                    // the game over screen stops the ai, everyone stands still behind it
+                    (robot_ai, companion_ai, boss_behavior)
+                        .chain()
+                        .run_if(in_state(AppState::Playing)),
+                    (stagger, apply_stun),
+                    topple_dying,
                     (apply_movement, apply_attack),
                 )
-                    .chain(),
+                    .chain()
+                    .in_set(FixedStepSet::Simulate)
+                    .run_if(not_paused),
             );
     }
 }
 
-fn robot_ai(
-    mut robots: Query<(
-        &mut PlayerInput,
-        &mut RobotController,
-        &Player,
-        &GlobalTransform,
-    )>,
-    monkeys: Query<(Entity, &GlobalTransform), With<MonkeyTag>>,
-    trees: Query<(Entity, &GlobalTransform), With<TreeTrunkTag>>,
-    tree_spawners: Query<(Entity, &GlobalTransform), With<TreeSpawner>>,
-    transforms: Query<&GlobalTransform>,
-    entity_query: Query<Entity, With<Health>>,
+pub fn attack_input(
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    input_map: Res<InputMap>,
+    bindings: Res<InputBindings>,
+    mut query: Query<
+        (Entity, &mut PlayerInput, &GlobalTransform, Ref<Weapon>),
+        With<PlayerControllerTag>,
+    >,
+    cameras: Query<&Transform, With<MainCameraTag>>,
+    pointer: Res<PointerPos>,
+    // clicking these selects them instead
+    no_attack: Query<(), Or<(With<StructureTag>, With<TreeRootTag>)>>,
+    placement: Res<Placement>,
+    weapon_assets: Res<Assets<WeaponAsset>>,
     time: Res<Time>,
+    // seconds the attack has been held, for charge attacks
+    mut held: Local<f32>,
+    // where the charge was last aimed, letting go of the stick or trigger doesn't aim anywhere
+    mut charge_aim: Local<Option<(Vec3, Option<Entity>)>>,
 ) {
-    for (mut player_input, mut controller, player, transform) in robots.iter_mut() {
-        let dist_map = |(e, t): (Entity, &GlobalTransform)| {
-            (
-                t.translation().distance_squared(transform.translation()),
-                e,
-                *t,
-            )
+    let Ok((player_entity, mut player_input, transform, weapon)) = query.get_single_mut() else {
+        return;
+    };
+    // frames can go by without a fixed step, a released attack waits for one with its charge
+    if player_input.attack.is_none() {
+        player_input.charge = 0.0;
+    }
+    // clicks are confirming a building, and a new weapon starts its charge over
+    if placement.is_active() || weapon.is_changed() {
+        player_input.attack = None;
+        *held = 0.0;
+        *charge_aim = None;
+        return;
+    }
+    let (pressed, aim) = if input_map.device == InputDevice::Gamepad {
+        // the right stick aims and attacks, the trigger attacks straight ahead
+        let aim = input_map.aim(&axes).map(|aim| vec3(aim.x, 0.0, aim.y));
+        let attack_pressed = input_map.gamepad_pressed(&gamepad_buttons, input_map.gamepad_attack);
+        let dir = match (aim, attack_pressed) {
+            (Some(aim), _) => Some(camera_rotation(cameras.single()) * aim),
+            (None, true) => Some(transform.back()),
+            (None, false) => None,
         };
-        let float_cmp =
-            |a: &(f32, _, _), b: &(f32, _, _)| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Greater);
+        (dir.is_some(), dir.map(|dir| (dir, None)))
+    } else {
+        let aim = pointer
+            .pointer_on
+            // don't attack self, and clicking buildings or trees opens the selection panel
+            .filter(|p| p.entity != player_entity && !no_attack.contains(p.entity))
+            .map(|p| (p.wpos - transform.translation(), Some(p.entity)));
+        (bindings.pressed(Action::Attack, &keyboard, &mouse), aim)
+    };
 
-        player_input.attack = None;
-        if let Some((t, p)) = controller.last_position_check {
-            let check_interval = 0.1;
-            let min_move_distance = check_interval as f32 * player.movement_speed / 5.0;
-            if (time.elapsed_seconds_f64() - t) >= check_interval {
-                if p.distance_squared(transform.translation()) <= min_move_distance.powi(2)
-                    && player_input.movement.length_squared() > 0.0
-                {
-                    player_input.attack = Some((player_input.movement, None));
-                }
-                controller.last_position_check =
-                    Some((time.elapsed_seconds_f64(), transform.translation()));
-            }
-        } else {
-            controller.last_position_check =
-                Some((time.elapsed_seconds_f64(), transform.translation()));
+    let Some(charge) = weapon_assets.get(&weapon.0).and_then(|w| w.charge) else {
+        if pressed {
+            player_input.attack = aim;
         }
+        return;
+    };
+    // hold to charge up, let go to attack
+    if pressed {
+        *held += time.delta_seconds();
+        player_input.charge = (*held / charge.max_time).min(1.0);
+        if aim.is_some() {
+            *charge_aim = aim;
+        }
+    } else if *held > 0.0 {
+        player_input.attack = aim.or(charge_aim.take());
+        player_input.charge = (*held / charge.max_time).min(1.0);
+        *held = 0.0;
+    }
+}
 
-        let mut attack_target = |target: &GlobalTransform| {
-            let attack_distance: f32 = 2.0;
-            let mut diff = target.translation() - transform.translation();
-            if transform
-                .translation()
-                .distance_squared(target.translation())
-                < attack_distance.powi(2)
-            {
-                player_input.attack = Some((diff, None));
-            } else {
-                diff.y = 0.0;
-                player_input.movement = diff;
-            }
-        };
+// stop the controlled player from running off while input is locked
+fn clear_controller_input(mut query: Query<&mut PlayerInput, With<PlayerControllerTag>>) {
+    for mut player_input in query.iter_mut() {
+        player_input.movement = Vec3::ZERO;
+        player_input.attack = None;
+        player_input.charge = 0.0;
+    }
+}
 
-        // If we have a monkey as a target, follow and attack that
-        if let Some((_, target)) = controller.target.and_then(|e| monkeys.get(e).ok()) {
-            attack_target(target);
-        }
-        // Otherwise check if we are close enough to the closest monkey, if so target it
-        else if let Some((_, monkey_entity, _)) = monkeys
-            .iter()
-            .map(dist_map)
-            .filter(|(t, _, _)| *t < controller.attack_monkey_range.powi(2))
-            .min_by(float_cmp)
-        {
-            controller.target = Some(monkey_entity);
+// staggered players can't do anything, and lean back for a moment
+fn stagger(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut PlayerInput, &mut Staggered, &Children), Without<Dying>>,
+    mut graphics: Query<(&mut BodyGraphics, &mut Transform)>,
+    time: Res<Time>,
+) {
+    const FLINCH_ANGLE: f32 = 0.5;
+    for (entity, mut player_input, mut staggered, children) in query.iter_mut() {
+        staggered.0.tick(time.delta());
+        player_input.movement = Vec3::ZERO;
+        player_input.attack = None;
+
+        let flinch = match staggered.0.finished() {
+            true => 0.0,
+            false => (staggered.0.percent() * std::f32::consts::PI).sin() * FLINCH_ANGLE,
+        };
+        let mut iter = graphics.iter_many_mut(children);
+        while let Some((mut body_graphics, mut transform)) = iter.fetch_next() {
+            body_graphics.set_tilt(&mut transform, -flinch);
         }
-        // If we don't have any monkeys to target attack choose the non-monkey target if we have one
-        else if let Some(target) = controller.target.and_then(|e| transforms.get(e).ok()) {
-            attack_target(target);
+
+        if staggered.0.finished() {
+            commands.entity(entity).remove::<Staggered>();
         }
+    }
+}
 
-        if let Some(target) = controller.target {
-            if entity_query.get(target).is_ok() {
-                continue;
-            } else {
-                controller.target = None;
-            }
+// dying bodies stop where they are and tip over
+fn topple_dying(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        Ref<Dying>,
+        &mut PlayerInput,
+        &Children,
+        &mut CollisionGroups,
+    )>,
+    mut graphics: Query<(&mut BodyGraphics, &mut Transform)>,
+) {
+    for (entity, dying, mut player_input, children, mut groups) in query.iter_mut() {
+        if dying.is_added() {
+            commands.entity(entity).insert(RigidBody::Fixed);
+            // shots fly through the body instead of being wasted on it
+            // EXPLANATION: see docs/physics.txt
+            groups
+                .filters
+                .remove(Group::from_bits(COLLISION_PROJECTILES).unwrap());
         }
-        let closest_tree = trees.iter().map(dist_map).min_by(float_cmp);
-        let closest_spawner = tree_spawners.iter().map(dist_map).min_by(float_cmp);
-        // 5 % chance to attack spawner
-        let target = match thread_rng().gen_range(0.0..1.0) < 0.1 {
-            true => match closest_spawner {
-                Some(c) => Some(c.1),
-                None => closest_tree.map(|t| t.1),
-            },
-            false => match closest_tree {
-                Some(c) => Some(c.1),
-                None => closest_spawner.map(|t| t.1),
-            },
-        };
-        if let Some(target) = target {
-            controller.target = Some(target);
-        } else {
-            controller.target = None;
+        *player_input = PlayerInput::default();
+        let mut iter = graphics.iter_many_mut(children);
+        while let Some((mut body_graphics, mut transform)) = iter.fetch_next() {
+            body_graphics.set_tilt(
+                &mut transform,
+                -dying.progress() * std::f32::consts::FRAC_PI_2,
+            );
         }
     }
 }
 
-pub fn attack_input(
-    mouse: Res<Input<MouseButton>>,
-    mut query: Query<(Entity, &mut PlayerInput, &GlobalTransform), With<PlayerControllerTag>>,
-    pointer: Res<PointerPos>,
+// a small bar above the player while charging an attack
+fn draw_charge_bar(
+    mut painter: ShapePainter,
+    query: Query<(&PlayerInput, &GlobalTransform), With<PlayerControllerTag>>,
+    q_camera: Query<&Transform, With<MainCameraTag>>,
+    settings: Res<Settings>,
 ) {
-    let Ok((player_entity, mut player_input, transform)) = query.get_single_mut() else {
+    const CHARGE_BAR_LENGTH: f32 = 1.0;
+
+    let Ok(camera_tr) = q_camera.get_single() else {
         return;
     };
-    player_input.attack = None;
-    if mouse.pressed(MouseButton::Left) {
-        // don't attack self
-        if Some(player_entity) == pointer.pointer_on.map(|p| p.entity) {
-            return;
+    for (input, transform) in query.iter() {
+        if input.charge <= 0.0 {
+            continue;
         }
-        player_input.attack = pointer
-            .pointer_on
-            .map(|p| (p.wpos - transform.translation(), Some(p.entity)));
+        let left =
+            transform.translation() + Vec3::Y * 3.4 - camera_tr.right() * CHARGE_BAR_LENGTH / 2.0;
+        painter.color = Color::GRAY;
+        painter.line(left, left + camera_tr.right() * CHARGE_BAR_LENGTH);
+        painter.color = settings.accessibility.palette.remap(Color::YELLOW);
+        painter.line(
+            left,
+            left + camera_tr.right() * CHARGE_BAR_LENGTH * input.charge,
+        );
     }
 }
 
+// yaw of the camera, so movement stays screen relative when the camera orbits
+fn camera_rotation(camera_transform: &Transform) -> Quat {
+    let forward = camera_transform.forward();
+    Quat::from_rotation_y(f32::atan2(-forward.x, -forward.z))
+}
+
 fn movement_input(
     input: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    input_map: Res<InputMap>,
+    bindings: Res<InputBindings>,
     mut query: Query<&mut PlayerInput, With<PlayerControllerTag>>,
     cameras: Query<&Transform, With<MainCameraTag>>,
 ) {
-    let camera_transform = cameras.single();
-
-    let forward = camera_transform.right();
-    let rotation = Quat::from_axis_angle(Vec3::Y, forward.y);
+    let rotation = camera_rotation(cameras.single());
 
     for mut player_input in query.iter_mut() {
-        let x = movement_axis(&input, KeyCode::D, KeyCode::A);
-        let z = movement_axis(&input, KeyCode::S, KeyCode::W);
-        let dir = vec3(x, 0.0, z).normalize_or_zero();
+        let movement = input_map.movement(&bindings, &input, &mouse, &axes);
+        let dir = vec3(movement.x, 0.0, movement.y).normalize_or_zero();
         let dir = rotation * dir;
         player_input.movement = dir;
     }
@@ -244,49 +396,62 @@ fn movement_input(
 
 fn animate_farmer(
     // input: Res<Input<KeyCode>>,
-    mut query: Query<(&mut PlayerInput, &mut FarmerAnimator), With<PlayerControllerTag>>,
+    mut query: Query<(&mut PlayerInput, &mut FarmerAnimator, &Combo)>,
 ) {
-    for (player_input, mut animator) in query.iter_mut() {
+    for (player_input, mut animator, combo) in query.iter_mut() {
         if player_input.movement.length() > 0.0 {
             animator.play(FarmerAnimation::Run);
         } else {
             animator.play(FarmerAnimation::Idle);
         }
         if player_input.attack.is_some() {
-            animator.play(FarmerAnimation::Attack);
+            animator.play(FarmerAnimation::Attack(combo.step));
         }
     }
 }
 
 fn apply_attack(
-    query: Query<(&PlayerInput, Entity)>,
+    mut query: Query<(&mut PlayerInput, Entity, Has<PlayerControllerTag>)>,
     mut attack_events: EventWriter<TryCastWeaponEvent>,
 ) {
-    for (input, entity) in query.iter() {
+    for (mut input, entity, controlled) in query.iter_mut() {
         if let Some((dir, target)) = input.attack {
             attack_events.send(TryCastWeaponEvent {
                 caster_entity: entity,
                 target_entity: target,
                 dir,
+                charge: input.charge,
             });
+            // player input is read in Update, the attack is used up once sent.
+            // the ai sets its attack every step itself
+            if controlled {
+                input.attack = None;
+            }
         }
     }
 }
 
 fn apply_movement(
-    mut query: Query<(
-        &PlayerInput,
-        &mut Transform,
-        &Player,
-        &mut Velocity,
-        Option<&MonkeyTag>,
-    )>,
+    mut query: Query<
+        (
+            &PlayerInput,
+            &mut Transform,
+            &Player,
+            &mut Velocity,
+            Option<&PlayerControllerTag>,
+            Option<&StatusEffects>,
+        ),
+        // a body left alone can settle and sleep, writing its velocity would keep it awake
+        Without<Dormant>,
+    >,
     time: Res<Time>,
     pointer: Res<PointerPos>,
+    input_map: Res<InputMap>,
 ) {
-    for (input, mut transform, player, mut velocity, monkey_tag) in query.iter_mut() {
+    for (input, mut transform, player, mut velocity, controlled, statuses) in query.iter_mut() {
         let normalized_input = input.movement.normalize_or_zero();
-        let desired_velocity = normalized_input * player.movement_speed;
+        let speed_multiplier = statuses.map_or(1.0, |s| s.speed_multiplier());
+        let desired_velocity = normalized_input * player.movement_speed * speed_multiplier;
         let true_velocity = velocity.linvel;
 
         velocity.linvel = Vec3::lerp(true_velocity, desired_velocity, time.delta_seconds() * 10.0);
@@ -294,12 +459,15 @@ fn apply_movement(
             Quat::from_rotation_y(f32::atan2(normalized_input.x, normalized_input.z));
 
         // rotate to where we are heading
-        if monkey_tag.is_some() {
+        if controlled.is_some() && input_map.device == InputDevice::KeyboardMouse {
             if let Some(pointer_on) = pointer.pointer_on {
                 let target = pointer_on.wpos;
                 let target = Vec3::new(target.x, 0.0, target.z) - transform.translation;
                 desired_quat = Quat::from_rotation_y(f32::atan2(target.x, target.z));
             }
+        } else if let Some((dir, _)) = input.attack.filter(|_| controlled.is_some()) {
+            // gamepad players face where they attack
+            desired_quat = Quat::from_rotation_y(f32::atan2(dir.x, dir.z));
         } else if normalized_input.length() > 0.1 {
             transform.rotation = Quat::lerp(
                 transform.rotation,
@@ -332,16 +500,24 @@ pub struct FarmerAnimator {
     idle: (Entity, Handle<AnimationClip>),
     run: (Entity, Handle<AnimationClip>),
     attack: (Entity, Handle<AnimationClip>),
-    next_anim: Option<(Entity, Handle<AnimationClip>)>,
+    // model, clip and playback speed
+    next_anim: Option<(Entity, Handle<AnimationClip>, f32)>,
 }
 
+// there's a single attack clip, combo steps play it at different speeds
+const COMBO_ANIMATION_SPEEDS: [f32; COMBO_LENGTH] = [1.0, 1.3, 0.75];
+
 impl FarmerAnimator {
     pub fn play(&mut self, anim: FarmerAnimation) {
-        match anim {
-            FarmerAnimation::Idle => self.next_anim = Some(self.idle.clone()),
-            FarmerAnimation::Run => self.next_anim = Some(self.run.clone()),
-            FarmerAnimation::Attack => self.next_anim = Some(self.attack.clone()),
+        let ((entity, clip), speed) = match anim {
+            FarmerAnimation::Idle => (self.idle.clone(), 1.0),
+            FarmerAnimation::Run => (self.run.clone(), 1.0),
+            FarmerAnimation::Attack(step) => (
+                self.attack.clone(),
+                COMBO_ANIMATION_SPEEDS[step.min(COMBO_LENGTH - 1)],
+            ),
         };
+        self.next_anim = Some((entity, clip, speed));
     }
 
     pub fn model_entities(&self) -> [Entity; 3] {
@@ -352,7 +528,8 @@ impl FarmerAnimator {
 pub enum FarmerAnimation {
     Idle,
     Run,
-    Attack,
+    // combo step
+    Attack(usize),
 }
 
 fn input(input: Res<Input<KeyCode>>, mut farmer_animator: Query<&mut FarmerAnimator>) {
@@ -366,7 +543,7 @@ fn input(input: Res<Input<KeyCode>>, mut farmer_animator: Query<&mut FarmerAnima
         farmer_animator.play(FarmerAnimation::Run);
     }
     if input.just_pressed(KeyCode::Y) {
-        farmer_animator.play(FarmerAnimation::Attack);
+        farmer_animator.play(FarmerAnimation::Attack(0));
     }
 }
 
@@ -375,32 +552,32 @@ fn update_farmer_animation(
     mut root_players: Query<(&AnimationEntityLink, &mut Visibility)>,
     mut animation_players: Query<&mut AnimationPlayer>,
 ) {
-    let Ok(mut farmer_animator) = farmer_animator.get_single_mut() else {
-        return;
-    };
-    let Some(next_anim) = farmer_animator.next_anim.take() else {
-        return;
-    };
-    let Ok((animation_link, mut visibility)) = root_players.get_mut(next_anim.0) else {
-        return;
-    };
-    *visibility = Visibility::Inherited;
-    animation_players
-        .get_mut(animation_link.0)
-        .unwrap()
-        .play(next_anim.1.clone())
-        .repeat();
-
-    // hide others
-    for entity in farmer_animator.model_entities().iter() {
-        // skip the one we are showing
-        if entity == &next_anim.0 {
+    for mut farmer_animator in farmer_animator.iter_mut() {
+        let Some(next_anim) = farmer_animator.next_anim.take() else {
             continue;
-        }
-        let Ok((_animation_link, mut visibility)) = root_players.get_mut(*entity) else {
+        };
+        let Ok((animation_link, mut visibility)) = root_players.get_mut(next_anim.0) else {
             continue;
         };
-        *visibility = Visibility::Hidden;
+        *visibility = Visibility::Inherited;
+        animation_players
+            .get_mut(animation_link.main)
+            .unwrap()
+            .play(next_anim.1.clone())
+            .set_speed(next_anim.2)
+            .repeat();
+
+        // hide others
+        for entity in farmer_animator.model_entities().iter() {
+            // skip the one we are showing
+            if entity == &next_anim.0 {
+                continue;
+            }
+            let Ok((_animation_link, mut visibility)) = root_players.get_mut(*entity) else {
+                continue;
+            };
+            *visibility = Visibility::Hidden;
+        }
     }
 }
 
@@ -430,6 +607,10 @@ fn load_character_models(mut commands: Commands, asset_server: Res<AssetServer>)
             Body::Boss,
             asset_server.load("models/characters/boss.glb#Scene0"),
         ),
+        (
+            Body::Sapper,
+            asset_server.load("models/characters/fast_robot.gltf#Scene0"),
+        ),
     ])));
 }
 
@@ -446,7 +627,8 @@ fn spawn_players(
             Body::Robot => 10.0,
             Body::FastRobot => 14.0,
             Body::Boss => 7.5,
-        };
+            Body::Sapper => 12.0,
+        } * event.scaling.speed_mul;
         let collision_groups = match event.body {
             Body::Monkey => {
                 // EXPLANATION: see docs/physics.txt
@@ -461,7 +643,7 @@ fn spawn_players(
                     .unwrap(),
                 )
             }
-            Body::Robot | Body::FastRobot | Body::Boss => {
+            Body::Robot | Body::FastRobot | Body::Boss | Body::Sapper => {
                 // EXPLANATION: see docs/physics.txt
                 CollisionGroups::new(
                     Group::from_bits(COLLISION_CHARACTER).unwrap(),
@@ -471,25 +653,42 @@ fn spawn_players(
             }
         };
         let health = match event.body {
-            Body::Monkey => Health::new(PLAYER_HEALTH),
-            Body::Robot => Health::new(ROBOT_HEALTH),
-            Body::FastRobot => Health::new(FAST_ROBOT_HEALTH),
-            Body::Boss => Health::new(BOSS_HEALTH),
+            Body::Monkey => PLAYER_HEALTH,
+            Body::Robot => ROBOT_HEALTH,
+            Body::FastRobot => FAST_ROBOT_HEALTH,
+            Body::Boss => BOSS_HEALTH,
+            Body::Sapper => SAPPER_HEALTH,
         };
-        let weapon_stats = match event.body {
-            Body::Monkey => WeaponStats::default(),
+        let health = Health::new((health as f32 * event.scaling.health_mul).round() as i32);
+        let mut weapon_stats = match event.body {
+            Body::Monkey => WeaponStats {
+                crit_chance: 0.1,
+                ..default()
+            },
             Body::Robot => WeaponStats {
                 cooldown_mul: 1.0,
                 damage_add: 1,
+                ..default()
             },
             Body::FastRobot => WeaponStats {
                 cooldown_mul: 0.8,
                 damage_add: 0,
+                knockback_mul: 0.8,
+                ..default()
             },
             Body::Boss => WeaponStats {
                 cooldown_mul: 1.0,
                 damage_add: 1,
+                knockback_mul: 1.5,
+                ..default()
             },
+            Body::Sapper => WeaponStats::default(),
+        };
+        weapon_stats.damage_add += event.scaling.damage_add;
+        let armor = match event.body {
+            Body::Robot => Armor(1),
+            Body::Boss => Armor(2),
+            _ => Armor(0),
         };
 
         let player_root = commands
@@ -499,6 +698,7 @@ fn spawn_players(
                     RigidBody::Dynamic,
                     Collider::capsule(Vec3::ZERO, Vec3::Y, 0.5),
                     TransformBundle::from(Transform::from_translation(event.pos)),
+                    InterpolatedTransform::new(Transform::from_translation(event.pos)),
                     Velocity::default(),
                     ColliderMassProperties::Mass(1.0),
                     ExternalForce {
@@ -517,13 +717,14 @@ fn spawn_players(
                         rotation_speed: 15.0,
                     },
                     PlayerInput::default(),
-                    event.weapon_type.clone(),
+                    Weapon(event.weapon.clone()),
                     WeaponCooldown { time_left: 0.0 },
                     health,
                 ),
                 (
                     ShowHealthBar,
                     weapon_stats,
+                    armor,
                     ExternalImpulse::default(),
                     VisibilityBundle::default(),
                     collision_groups,
@@ -534,9 +735,7 @@ fn spawn_players(
 
         let pickup_collider = commands
             .spawn((
-                PickupMagnet {
-                    root_entity: player_root,
-                },
+                PickupMagnet::new(player_root, PLAYER_PICKUP_RADIUS),
                 Sensor,
                 ActiveEvents::COLLISION_EVENTS,
                 Collider::ball(PLAYER_PICKUP_RADIUS),
@@ -550,12 +749,19 @@ fn spawn_players(
 
         commands.entity(pickup_collider).set_parent(player_root);
 
+        if event.body == Body::Boss {
+            commands
+                .entity(player_root)
+                .insert(KnockbackResistance(1.0));
+        }
+
         match event.body {
             Body::Monkey => {
                 let y_offset = 0.0;
                 let idle = commands
                     .spawn((
                         AnimationEntityLinkTrap,
+                        BodyGraphics::default(),
                         SceneBundle {
                             scene: farmer_animations.idle_model.clone(),
                             transform: Transform::from_translation(vec3(0.0, y_offset, 0.0)),
@@ -567,6 +773,7 @@ fn spawn_players(
                 let run = commands
                     .spawn((
                         AnimationEntityLinkTrap,
+                        BodyGraphics::default(),
                         SceneBundle {
                             scene: farmer_animations.run_model.clone(),
                             transform: Transform::from_translation(vec3(0.0, y_offset, 0.0)),
@@ -579,6 +786,7 @@ fn spawn_players(
                 let attack = commands
                     .spawn((
                         AnimationEntityLinkTrap,
+                        BodyGraphics::default(),
                         SceneBundle {
                             scene: farmer_animations.attack_model.clone(),
                             transform: Transform::from_translation(vec3(0.0, y_offset, 0.0)),
@@ -589,56 +797,86 @@ fn spawn_players(
                     .set_parent(player_root)
                     .id();
 
-                commands.entity(player_root).insert(FarmerAnimator {
-                    idle: (idle, farmer_animations.idle.clone()),
-                    run: (run, farmer_animations.run.clone()),
-                    attack: (attack, farmer_animations.attack.clone()),
-                    next_anim: None,
-                });
+                commands.entity(player_root).insert((
+                    FarmerAnimator {
+                        idle: (idle, farmer_animations.idle.clone()),
+                        run: (run, farmer_animations.run.clone()),
+                        attack: (attack, farmer_animations.attack.clone()),
+                        next_anim: None,
+                    },
+                    // only monkeys chain melee combos
+                    Combo::default(),
+                    // empty until bought in the shop
+                    Shield::new(0.0, 3.0),
+                    InvulnerableOnHit(0.6),
+                ));
             }
-            Body::Robot | Body::FastRobot | Body::Boss => {
+            Body::Robot | Body::FastRobot | Body::Boss | Body::Sapper => {
                 let scene = character_models.0[&event.body].clone();
+                // sappers are small fast robots
+                let scale = match event.body {
+                    Body::Sapper => 0.7,
+                    _ => 1.0,
+                };
                 let graphics = commands
-                    .spawn(SceneBundle {
-                        scene,
-                        transform: Transform::from_translation(vec3(0.0, 0.5, 0.0)),
-                        ..default()
-                    })
+                    .spawn((
+                        BodyGraphics::default(),
+                        SceneBundle {
+                            scene,
+                            transform: Transform::from_translation(vec3(0.0, 0.5, 0.0))
+                                .with_scale(Vec3::splat(scale)),
+                            ..default()
+                        },
+                    ))
                     .id();
                 commands.entity(graphics).set_parent(player_root);
             }
         }
 
-        if event.is_main {
-            commands.entity(player_root).insert((
-                PlayerControllerTag,
-                MonkeyTag,
-                PickupSound,
-                Name::new("player"),
-            ));
-        } else {
-            commands
-                .entity(player_root)
-                .insert((
-                    Name::new("enemy"),
-                    RobotTag,
-                    RobotController {
-                        target: None,
-                        attack_monkey_range: 5.0,
-                        last_position_check: None,
-                    },
-                    DeathSound(asset_server.load("sounds/robot-death.ogg")),
-                ))
-                .with_children(|cmds| {
-                    cmds.spawn((
-                        SpatialBundle::INHERITED_IDENTITY,
-                        Collider::cylinder(0.5, 2.0),
-                        CollisionGroups::new(
-                            Group::from_bits(COLLISION_POINTER).unwrap(),
-                            Group::from_bits(COLLISION_POINTER).unwrap(),
-                        ),
-                    ));
-                });
+        match event.controller {
+            Controller::Player => {
+                commands.entity(player_root).insert((
+                    PlayerControllerTag,
+                    MonkeyTag,
+                    PickupSound,
+                    Experience::default(),
+                    Name::new("player"),
+                ));
+            }
+            Controller::Companion => {
+                commands.entity(player_root).insert((
+                    CompanionController::default(),
+                    MonkeyTag,
+                    PathRequest { goal: event.pos },
+                    PathFollow::default(),
+                    Name::new("companion"),
+                ));
+            }
+            Controller::Robot => {
+                commands
+                    .entity(player_root)
+                    .insert((
+                        Name::new("enemy"),
+                        RobotTag,
+                        RobotController::new(event.body),
+                        ThreatTable::new(AiProfile::for_body(event.body).aggro_range),
+                        PathRequest { goal: event.pos },
+                        PathFollow::default(),
+                        DeathSound("robot_death".into()),
+                        DeathAnimation,
+                        DropsOnDeath(DropSource::Body(event.body)),
+                    ))
+                    .with_children(|cmds| {
+                        cmds.spawn((
+                            SpatialBundle::INHERITED_IDENTITY,
+                            Collider::cylinder(0.5, 2.0),
+                            CollisionGroups::new(
+                                Group::from_bits(COLLISION_POINTER).unwrap(),
+                                Group::from_bits(COLLISION_POINTER).unwrap(),
+                            ),
+                        ));
+                    });
+            }
         }
     }
 }
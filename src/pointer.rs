@@ -7,6 +7,8 @@ use bevy_vector_shapes::{prelude::ShapePainter, shapes::RectPainter};
 use crate::{
     camera::MainCameraTag,
     collision_groups::{COLLISION_CHARACTER, COLLISION_POINTER, COLLISION_PROJECTILES},
+    player::RobotTag,
+    settings::Settings,
 };
 
 pub struct PointerPlugin;
@@ -60,6 +62,8 @@ pub struct PointerTarget {
 #[derive(Resource, Default)]
 pub struct PointerPos {
     pub pointer_on: Option<PointerTarget>,
+    // where the cursor meets the ground plane, even when nothing is under it
+    pub ground_pos: Option<Vec3>,
 }
 
 pub fn update_pointer_pos(
@@ -69,36 +73,70 @@ pub fn update_pointer_pos(
     camera: Query<(&GlobalTransform, &Camera), With<MainCameraTag>>,
     q_transform: Query<&GlobalTransform>,
     q_parent: Query<&Parent>,
+    robots: Query<(), With<RobotTag>>,
 ) {
     let window = window.single();
     let (camera_t, camera) = camera.single();
+    pointer.ground_pos = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_t, cursor))
+        .and_then(|ray| {
+            ray.intersect_plane(Vec3::ZERO, Vec3::Y)
+                .map(|d| ray.get_point(d))
+        });
     pointer.pointer_on = window.cursor_position().and_then(|cursor| {
         let ray = camera.viewport_to_world(camera_t, cursor)?;
 
-        let mut filter = QueryFilter::default();
-        // EXPLANATION: see docs/physics.txt
-        filter.groups = Some(CollisionGroups::new(
-            Group::from_bits(COLLISION_POINTER | COLLISION_PROJECTILES).unwrap(),
-            Group::from_bits(COLLISION_POINTER | COLLISION_CHARACTER).unwrap(),
-        ));
-        let (collider_entity, _) =
-            rapier.cast_ray(ray.origin, ray.direction, f32::MAX, true, filter)?;
-
-        let entity = q_parent
-            .iter_ancestors(collider_entity)
-            .last()
-            .unwrap_or(collider_entity);
+        let filter = QueryFilter {
+            // EXPLANATION: see docs/physics.txt
+            groups: Some(CollisionGroups::new(
+                Group::from_bits(COLLISION_POINTER | COLLISION_PROJECTILES).unwrap(),
+                Group::from_bits(COLLISION_POINTER | COLLISION_CHARACTER).unwrap(),
+            )),
+            ..default()
+        };
+        // the closest hit, but a robot anywhere under the cursor wins,
+        // so robots next to or behind a tree can still be clicked to attack them
+        let mut closest: Option<(Entity, f32)> = None;
+        let mut closest_robot: Option<(Entity, f32)> = None;
+        rapier.intersections_with_ray(
+            ray.origin,
+            ray.direction,
+            f32::MAX,
+            true,
+            filter,
+            |collider_entity, intersection| {
+                let entity = q_parent
+                    .iter_ancestors(collider_entity)
+                    .last()
+                    .unwrap_or(collider_entity);
+                let best = match robots.contains(entity) {
+                    true => &mut closest_robot,
+                    false => &mut closest,
+                };
+                if best.map_or(true, |(_, toi)| intersection.toi < toi) {
+                    *best = Some((entity, intersection.toi));
+                }
+                true
+            },
+        );
+        let (entity, _) = closest_robot.or(closest)?;
         let wpos = q_transform.get(entity).unwrap().translation();
 
         Some(PointerTarget { entity, wpos })
     });
 }
 
-fn display_pointer(time: Res<Time>, mut painter: ShapePainter, pointer: Res<PointerPos>) {
+fn display_pointer(
+    time: Res<Time>,
+    mut painter: ShapePainter,
+    pointer: Res<PointerPos>,
+    settings: Res<Settings>,
+) {
     let Some(target) = pointer.pointer_on else {
         return;
     };
-    painter.color = Color::RED;
+    painter.color = settings.accessibility.palette.remap(Color::RED);
     painter.set_rotation(Quat::default());
     painter.rotate_x(TAU / 4.0);
     painter.rotate_z(time.elapsed_seconds());
@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::{
+    health::DeathEvent,
+    inventory::Item,
+    item_pickups::ItemPickup,
+    notification::NotificationEvent,
+    persistence,
+    pickup::OnPickedUpEvent,
+    player::{PlayerControllerTag, RobotTag},
+    state::{AppState, Wave},
+};
+
+const PROFILE_FILE: &str = "profile.ron";
+
+pub struct ProfilePlugin;
+
+impl Plugin for ProfilePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(persistence::load::<Profile>(PROFILE_FILE).unwrap_or_default())
+            .add_systems(
+                Update,
+                ((track_wave, track_robots, track_bananas), unlock_rewards)
+                    .chain()
+                    .run_if(in_state(AppState::Playing)),
+            )
+            // saving on every kill would write the file constantly
+            .add_systems(OnExit(AppState::Playing), save_profile)
+            .add_systems(Last, save_profile.run_if(resource_changed::<Wave>()));
+    }
+}
+
+/// Something a profile earns for good, carried over into every later run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumIter, Serialize, Deserialize)]
+pub enum Unlock {
+    // starts in the third hotbar slot
+    Sledgehammer,
+    // stocked in the shop, see `requires` in shop.shop.ron
+    AutoVacuum,
+    StorageDepot,
+}
+
+impl Unlock {
+    pub fn name(self) -> &'static str {
+        match self {
+            Unlock::Sledgehammer => "Starting sledgehammer",
+            Unlock::AutoVacuum => "Auto vacuum in the shop",
+            Unlock::StorageDepot => "Storage depot in the shop",
+        }
+    }
+
+    fn is_earned(self, profile: &Profile) -> bool {
+        match self {
+            Unlock::Sledgehammer => profile.best_wave >= 5,
+            Unlock::AutoVacuum => profile.robots_destroyed >= 250,
+            Unlock::StorageDepot => profile.bananas_collected >= 150,
+        }
+    }
+}
+
+/// Stats over every run, saved next to the achievements.
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    // 1 is the first wave
+    pub best_wave: usize,
+    pub robots_destroyed: u32,
+    pub bananas_collected: u32,
+    unlocked: HashSet<Unlock>,
+}
+
+impl Profile {
+    pub fn is_unlocked(&self, unlock: Unlock) -> bool {
+        self.unlocked.contains(&unlock)
+    }
+}
+
+fn track_wave(wave: Res<Wave>, mut profile: ResMut<Profile>) {
+    if wave.is_changed() && wave.0 + 1 > profile.best_wave {
+        profile.best_wave = wave.0 + 1;
+    }
+}
+
+fn track_robots(
+    mut events: EventReader<DeathEvent>,
+    mut profile: ResMut<Profile>,
+    robots: Query<(), With<RobotTag>>,
+) {
+    let destroyed = events.read().filter(|e| robots.contains(e.entity)).count() as u32;
+    if destroyed > 0 {
+        profile.robots_destroyed += destroyed;
+    }
+}
+
+fn track_bananas(
+    mut events: EventReader<OnPickedUpEvent>,
+    mut profile: ResMut<Profile>,
+    players: Query<(), With<PlayerControllerTag>>,
+    pickups: Query<&ItemPickup>,
+) {
+    for event in events.read() {
+        if !players.contains(event.receiver_entity) {
+            continue;
+        }
+        if let Ok(ItemPickup(Item::Banana, count)) = pickups.get(event.pickup_entity) {
+            profile.bananas_collected += count;
+        }
+    }
+}
+
+// unlocks apply from the next run on
+fn unlock_rewards(
+    mut profile: ResMut<Profile>,
+    mut notification_event: EventWriter<NotificationEvent>,
+) {
+    if !profile.is_changed() {
+        return;
+    }
+    for unlock in Unlock::iter() {
+        if profile.is_unlocked(unlock) || !unlock.is_earned(&profile) {
+            continue;
+        }
+        profile.unlocked.insert(unlock);
+        notification_event.send(NotificationEvent {
+            text: format!("Unlocked for next run: {}", unlock.name()),
+            show_for: 4.0,
+            color: Color::GOLD,
+        });
+    }
+}
+
+fn save_profile(profile: Res<Profile>) {
+    persistence::save(PROFILE_FILE, &*profile);
+}
@@ -1,16 +1,32 @@
+use std::f32::consts::TAU;
+
 use bevy::{
     asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
     prelude::*,
     reflect::{erased_serde::__private::serde::Deserialize, TypePath},
+    utils::{HashMap, HashSet},
 };
-use bevy_rapier3d::prelude::{CollisionGroups, Group, QueryFilter, RapierContext};
+use bevy_rapier3d::prelude::{Collider, CollisionGroups, Group, QueryFilter, RapierContext};
+use bevy_vector_shapes::{prelude::ShapePainter, shapes::DiscPainter};
 
 use crate::{
     asset_utils::CustomAssetLoaderError,
     collision_groups::{COLLISION_CHARACTER, COLLISION_PROJECTILES},
+    fixed_step::{FixedStepSet, InterpolatedTransform},
     health::{ApplyHealthEvent, Health, HealthRoot},
+    knockback::ApplyKnockbackEvent,
+    pause::not_paused,
+    player::RobotTag,
+    sapper::StructureTag,
+    settings::Settings,
+    status_effect::{ApplyStatusEvent, StatusEffect},
 };
 
+// how long the blast ring stays on screen
+const BLAST_TIME: f32 = 0.4;
+// hidden projectiles kept per model, a big volley past this is despawned instead of kept forever
+const MAX_POOLED: usize = 64;
+
 #[derive(Debug, Deserialize, TypePath, Asset)]
 pub struct ProjectileAsset {
     pub speed: f32,
@@ -19,7 +35,64 @@ pub struct ProjectileAsset {
     pub damage: i32,
     // hits until despawn
     pub max_hits: i32,
+    // keeps flying through everything it hits, max_hits is ignored
+    #[serde(default)]
+    pub pierce: bool,
+    // bounces off whatever it hits instead of stopping
+    #[serde(default)]
+    pub bounce: Option<Bounce>,
     pub model: String,
+    // applied to everything the projectile hits
+    #[serde(default)]
+    pub on_hit: Option<StatusEffect>,
+    // damages everything around where the projectile stops
+    #[serde(default)]
+    pub explosion: Option<Explosion>,
+    // despawns after flying this far
+    #[serde(default = "default_max_range")]
+    pub max_range: f32,
+    // 0.0 flies straight, 1.0 turns all the way towards the target every frame (at 60 fps)
+    #[serde(default = "default_homing_strength")]
+    pub homing_strength: f32,
+    // radians per second the projectile can turn at most
+    #[serde(default = "default_max_turn_rate")]
+    pub max_turn_rate: f32,
+}
+
+fn default_max_range() -> f32 {
+    60.0
+}
+
+fn default_homing_strength() -> f32 {
+    1.0
+}
+
+fn default_max_turn_rate() -> f32 {
+    f32::INFINITY
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct Bounce {
+    pub count: u32,
+    // fraction of the speed kept every bounce
+    pub speed_retention: f32,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct Explosion {
+    pub radius: f32,
+    pub damage: i32,
+    pub knockback: f32,
+}
+
+// a projectile with an explosion stopped at `pos`
+#[derive(Event)]
+pub struct ExplosionEvent {
+    pub pos: Vec3,
+    pub explosion: Explosion,
+    pub caster_entity: Entity,
+    // see Projectile::spares_structures
+    pub spares_structures: bool,
 }
 
 #[derive(Event)]
@@ -30,6 +103,10 @@ pub struct SpawnProjectileEvent {
     pub dir: Vec3,
     pub projectile_asset: Handle<ProjectileAsset>,
     pub additional_damage: i32,
+    // charged shots fly faster and hit harder
+    pub damage_mul: f32,
+    pub speed_mul: f32,
+    pub is_crit: bool,
 }
 
 pub struct ProjectilePlugin;
@@ -40,8 +117,17 @@ pub struct ProjectileAssetLoader;
 impl Plugin for ProjectilePlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SpawnProjectileEvent>()
+            .add_event::<ExplosionEvent>()
+            .init_resource::<ProjectilePool>()
             .init_asset::<ProjectileAsset>()
-            .add_systems(Update, (spawn_projectile, (projectile_aim, update).chain()))
+            .add_systems(Update, (spawn_projectile, draw_blasts).run_if(not_paused))
+            .add_systems(
+                FixedUpdate,
+                (update, explode)
+                    .chain()
+                    .in_set(FixedStepSet::Simulate)
+                    .run_if(not_paused),
+            )
             .init_asset_loader::<ProjectileAssetLoader>();
     }
 }
@@ -56,25 +142,70 @@ pub struct Projectile {
     pub vel: Vec3,
     pub asset_handle: Handle<ProjectileAsset>,
     pub additional_damage: i32,
+    pub damage_mul: f32,
+    pub is_crit: bool,
+    // distance flown so far
+    pub traveled: f32,
+    pub bounces: u32,
+    // never hit the same thing twice
+    pub hit_entities: Vec<Entity>,
+    // fired by the player's side, which doesn't damage its own buildings.
+    // decided when fired, the caster may be gone by the time it hits
+    pub spares_structures: bool,
 }
 
-pub fn projectile_aim(
-    mut q_projectile: Query<(&mut Transform, &mut Projectile)>,
-    q_target_transform: Query<&GlobalTransform>,
-) {
-    for (mut projectile_tr, mut projectile) in &mut q_projectile {
-        let Some(target_entity) = projectile.target_entity else {
-            continue;
-        };
-        let Ok(target) = q_target_transform.get(target_entity) else {
-            continue;
-        };
+/// Hidden projectiles waiting to be fired again, so towers don't spawn and despawn scenes all the time.
+/// Kept per model, reusing an entity then never has to respawn its scene.
+#[derive(Resource, Default)]
+pub struct ProjectilePool {
+    free: HashMap<String, Vec<Entity>>,
+}
+
+impl ProjectilePool {
+    /// A hidden projectile of this model to fire again, if there is one.
+    pub fn take(&mut self, model: &str) -> Option<Entity> {
+        self.free.get_mut(model).and_then(|free| free.pop())
+    }
+
+    /// Hides the projectile and keeps it around for the next shot, unless the pool is full.
+    pub fn recycle(&mut self, commands: &mut Commands, entity: Entity, model: &str) {
+        let free = self.free.entry(model.to_string()).or_default();
+        if free.len() >= MAX_POOLED {
+            commands.entity(entity).despawn_recursive();
+            return;
+        }
+        commands
+            .entity(entity)
+            .remove::<Projectile>()
+            .insert(Visibility::Hidden);
+        free.push(entity);
+    }
+}
 
-        let to_target_dir = (target.translation() - projectile_tr.translation).normalize();
+// expanding ring left behind by an explosion
+#[derive(Component)]
+struct Blast {
+    radius: f32,
+    timer: Timer,
+}
 
-        projectile_tr.rotation = Quat::from_rotation_arc(-Vec3::Z, to_target_dir);
-        projectile.vel = to_target_dir * projectile.vel.length();
+// turns the projectile towards its target, limited by the asset's homing settings
+fn steer(projectile: &mut Projectile, asset: &ProjectileAsset, pos: Vec3, target: Vec3, dt: f32) {
+    let (Some(current), Some(wanted)) = (
+        projectile.vel.try_normalize(),
+        (target - pos).try_normalize(),
+    ) else {
+        return;
+    };
+    let angle = current.angle_between(wanted);
+    if angle <= f32::EPSILON {
+        return;
     }
+    // framerate independent fraction of the angle to close this frame
+    let fraction = 1.0 - (1.0 - asset.homing_strength.clamp(0.0, 1.0)).powf(dt * 60.0);
+    let turn = (angle * fraction).min(asset.max_turn_rate * dt);
+    let rotation = Quat::IDENTITY.slerp(Quat::from_rotation_arc(current, wanted), turn / angle);
+    projectile.vel = rotation * projectile.vel;
 }
 
 pub fn update(
@@ -84,7 +215,12 @@ pub fn update(
     rapier_context: Res<RapierContext>,
     mut commands: Commands,
     hit_query: Query<(Option<&Health>, Option<&HealthRoot>)>,
+    structures: Query<(), With<StructureTag>>,
+    targets: Query<&GlobalTransform>,
+    mut pool: ResMut<ProjectilePool>,
     mut apply_health_events: EventWriter<ApplyHealthEvent>,
+    mut apply_status_events: EventWriter<ApplyStatusEvent>,
+    mut explosion_events: EventWriter<ExplosionEvent>,
 ) {
     for (projectile_entity, mut transform, mut projectile) in query.iter_mut() {
         let Some(projectile_asset) = projectile_assets.get(&projectile.asset_handle) else {
@@ -93,19 +229,36 @@ pub fn update(
         };
         let prev_pos = transform.translation;
 
+        if let Some(target) = projectile.target_entity.and_then(|e| targets.get(e).ok()) {
+            steer(
+                &mut projectile,
+                projectile_asset,
+                prev_pos,
+                target.translation(),
+                time.delta_seconds(),
+            );
+            if let Some(dir) = projectile.vel.try_normalize() {
+                transform.rotation = Quat::from_rotation_arc(-Vec3::Z, dir);
+            }
+        }
         projectile.vel -= projectile_asset.gravity * time.delta_seconds();
         transform.translation += projectile.vel * time.delta_seconds();
 
-        // transform.rotation = projectile.vel
-
         let current_pos = transform.translation;
         let max_toi = prev_pos.distance(current_pos);
-        let mut filter = QueryFilter::default();
-        // EXPLANATION: see docs/physics.txt
-        filter.groups = Some(CollisionGroups::new(
-            Group::from_bits(COLLISION_PROJECTILES).unwrap(), // this projectile is part of projectile layer (4)
-            Group::from_bits(COLLISION_CHARACTER).unwrap(),   // collide with ALL
-        ));
+        projectile.traveled += max_toi;
+        // where the projectile stopped this frame, if it did
+        let mut stopped_at = None;
+        // hit point and normal to bounce off
+        let mut bounced = None;
+        let filter = QueryFilter {
+            // EXPLANATION: see docs/physics.txt
+            groups: Some(CollisionGroups::new(
+                Group::from_bits(COLLISION_PROJECTILES).unwrap(), // this projectile is part of projectile layer (4)
+                Group::from_bits(COLLISION_CHARACTER).unwrap(),   // collide with ALL
+            )),
+            ..default()
+        };
 
         rapier_context.intersections_with_ray(
             prev_pos,
@@ -113,7 +266,7 @@ pub fn update(
             max_toi,
             true,
             filter,
-            |hit_entity, _intersection| {
+            |hit_entity, intersection| {
                 let Ok((health, health_root)) = hit_query.get(hit_entity) else {
                     return true; // continue ray
                 };
@@ -124,24 +277,168 @@ pub fn update(
                     _ => return true, // continue search, hit something with no health
                 };
 
-                // don't hurt self
-                if health_entity == projectile.caster_entity {
+                // don't hurt self or buildings on the caster's own side
+                if health_entity == projectile.caster_entity
+                    || projectile.hit_entities.contains(&health_entity)
+                    || (projectile.spares_structures && structures.contains(health_entity))
+                {
                     return true; // continue ray
                 }
+                projectile.hit_entities.push(health_entity);
 
+                let damage = (projectile_asset.damage + projectile.additional_damage) as f32
+                    * projectile.damage_mul;
                 apply_health_events.send(ApplyHealthEvent {
-                    amount: -projectile_asset.damage - projectile.additional_damage,
+                    amount: -damage.round() as i32,
                     target_entity: health_entity,
                     caster_entity: projectile.caster_entity,
+                    is_crit: projectile.is_crit,
+                    is_finisher: false,
                 });
+                if let Some(effect) = projectile_asset.on_hit {
+                    apply_status_events.send(ApplyStatusEvent {
+                        effect,
+                        target_entity: health_entity,
+                        caster_entity: projectile.caster_entity,
+                    });
+                }
+                if projectile_asset.pierce {
+                    return true; // continue ray
+                }
                 projectile.hits += 1;
-                if projectile.hits >= projectile_asset.max_hits {
-                    commands.entity(projectile_entity).despawn_recursive();
-                    return false; // stop ray
+                if projectile.hits < projectile_asset.max_hits {
+                    return true; // continue ray
+                }
+                let hit_pos = prev_pos + projectile.vel.normalize() * intersection.toi;
+                match projectile_asset.bounce {
+                    Some(bounce) if projectile.bounces < bounce.count => {
+                        bounced = Some((hit_pos, intersection.normal));
+                    }
+                    _ => stopped_at = Some(hit_pos),
+                }
+                false // stop ray
+            },
+        );
+        if let Some((hit_pos, normal)) = bounced {
+            let bounce = projectile_asset.bounce.unwrap();
+            // started inside the collider, there's no normal to bounce off so just turn around
+            let normal = normal
+                .try_normalize()
+                .unwrap_or(-projectile.vel.normalize_or_zero());
+            projectile.vel = (projectile.vel - 2.0 * projectile.vel.dot(normal) * normal)
+                * bounce.speed_retention;
+            projectile.bounces += 1;
+            projectile.hits = 0;
+            // homing would just steer it back into what it bounced off
+            projectile.target_entity = None;
+            transform.translation = hit_pos + normal * 0.05;
+            if let Some(dir) = projectile.vel.try_normalize() {
+                transform.rotation = Quat::from_rotation_arc(-Vec3::Z, dir);
+            }
+        }
+        if stopped_at.is_none() && projectile.traveled >= projectile_asset.max_range {
+            stopped_at = Some(current_pos);
+        }
+        let Some(pos) = stopped_at else {
+            continue;
+        };
+        pool.recycle(&mut commands, projectile_entity, &projectile_asset.model);
+        if let Some(explosion) = projectile_asset.explosion {
+            explosion_events.send(ExplosionEvent {
+                pos,
+                explosion,
+                caster_entity: projectile.caster_entity,
+                spares_structures: projectile.spares_structures,
+            });
+        }
+    }
+}
+
+fn explode(
+    mut events: EventReader<ExplosionEvent>,
+    rapier_context: Res<RapierContext>,
+    hit_query: Query<(Option<&Health>, Option<&HealthRoot>)>,
+    structures: Query<(), With<StructureTag>>,
+    mut apply_health_events: EventWriter<ApplyHealthEvent>,
+    mut knockback_events: EventWriter<ApplyKnockbackEvent>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let explosion = event.explosion;
+        // several colliders can share one health root, only hurt it once
+        let mut hit = HashSet::new();
+        rapier_context.intersections_with_shape(
+            event.pos,
+            Quat::IDENTITY,
+            &Collider::ball(explosion.radius),
+            // same layers the projectile itself can hit
+            QueryFilter {
+                groups: Some(CollisionGroups::new(
+                    Group::from_bits(COLLISION_PROJECTILES).unwrap(),
+                    Group::from_bits(COLLISION_CHARACTER).unwrap(),
+                )),
+                ..default()
+            },
+            |hit_entity| {
+                let health_entity = match hit_query.get(hit_entity) {
+                    Ok((Some(_), None)) => hit_entity,
+                    Ok((None, Some(health_root))) => health_root.entity,
+                    _ => return true,
+                };
+                if health_entity != event.caster_entity
+                    && !(event.spares_structures && structures.contains(health_entity))
+                    && hit.insert(health_entity)
+                {
+                    apply_health_events.send(ApplyHealthEvent {
+                        amount: -explosion.damage,
+                        target_entity: health_entity,
+                        caster_entity: event.caster_entity,
+                        is_crit: false,
+                        is_finisher: false,
+                    });
                 }
-                return true; // continue ray
+                true
             },
         );
+        knockback_events.send(ApplyKnockbackEvent {
+            origin: event.pos,
+            strength: explosion.knockback,
+            radius: explosion.radius,
+            falloff: 0.5,
+        });
+        commands.spawn((
+            Blast {
+                radius: explosion.radius,
+                timer: Timer::from_seconds(BLAST_TIME, TimerMode::Once),
+            },
+            TransformBundle::from_transform(Transform::from_translation(event.pos)),
+        ));
+    }
+}
+
+fn draw_blasts(
+    mut commands: Commands,
+    mut painter: ShapePainter,
+    mut blasts: Query<(Entity, &mut Blast, &GlobalTransform)>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    for (entity, mut blast, transform) in blasts.iter_mut() {
+        if blast.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let progress = blast.timer.percent();
+        painter.color = settings
+            .accessibility
+            .palette
+            .remap(Color::ORANGE)
+            .with_a(1.0 - progress);
+        painter.thickness = 0.2;
+        painter.hollow = true;
+        painter.set_rotation(Quat::from_rotation_x(TAU / 4.0));
+        painter.set_translation(transform.translation());
+        painter.circle(blast.radius * (0.3 + progress * 0.7));
     }
 }
 
@@ -150,27 +447,50 @@ pub fn spawn_projectile(
     projectile_assets: Res<Assets<ProjectileAsset>>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    mut pool: ResMut<ProjectilePool>,
+    robots: Query<(), With<RobotTag>>,
 ) {
     for event in events.read() {
         let Some(projectile) = projectile_assets.get(&event.projectile_asset) else {
             error!("no such projectile: {:?}", event.projectile_asset);
             continue;
         };
-        commands.spawn((
-            SceneBundle {
-                scene: asset_server.load(&projectile.model),
-                transform: Transform::from_translation(event.pos).looking_to(event.dir, Vec3::Y),
-                ..default()
-            },
-            Projectile {
-                vel: event.dir * projectile.speed,
-                asset_handle: event.projectile_asset.clone(),
-                additional_damage: event.additional_damage,
-                caster_entity: event.caster_entity,
-                target_entity: event.target_entity,
-                hits: 0,
-            },
-        ));
+        let transform = Transform::from_translation(event.pos).looking_to(event.dir, Vec3::Y);
+        let state = Projectile {
+            vel: event.dir * projectile.speed * event.speed_mul,
+            asset_handle: event.projectile_asset.clone(),
+            additional_damage: event.additional_damage,
+            damage_mul: event.damage_mul,
+            is_crit: event.is_crit,
+            caster_entity: event.caster_entity,
+            target_entity: event.target_entity,
+            hits: 0,
+            traveled: 0.0,
+            bounces: 0,
+            hit_entities: Vec::new(),
+            spares_structures: !robots.contains(event.caster_entity),
+        };
+        match pool.take(&projectile.model) {
+            Some(entity) => {
+                commands.entity(entity).insert((
+                    transform,
+                    InterpolatedTransform::new(transform),
+                    Visibility::Inherited,
+                    state,
+                ));
+            }
+            None => {
+                commands.spawn((
+                    SceneBundle {
+                        scene: asset_server.load(&projectile.model),
+                        transform,
+                        ..default()
+                    },
+                    InterpolatedTransform::new(transform),
+                    state,
+                ));
+            }
+        }
     }
 }
 
@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+
+use crate::{
+    health::{ApplyHealthEvent, Health},
+    inventory::{Inventory, Item},
+    player::PlayerControllerTag,
+    sapper::StructureTag,
+    selection::{Selection, SelectionActionsTag},
+    ui_util::{JustClicked, UiAssets},
+};
+
+// every click on the repair button spends this and heals the building by REPAIR_AMOUNT
+const REPAIR_COST: (Item, u32) = (Item::Log, 1);
+const REPAIR_AMOUNT: i32 = 5;
+
+pub struct RepairPlugin;
+
+impl Plugin for RepairPlugin {
+    fn build(&self, app: &mut App) {
+        // the selection panel it goes in is spawned on startup
+        app.add_systems(PostStartup, setup_repair_button)
+            .add_systems(Update, (repair_clicked, update_repair_button).chain());
+    }
+}
+
+#[derive(Component)]
+struct RepairButton;
+
+fn setup_repair_button(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    actions: Query<Entity, With<SelectionActionsTag>>,
+) {
+    commands
+        .spawn((
+            RepairButton,
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::all(Val::Px(5.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            let (item, count) = REPAIR_COST;
+            parent.spawn(TextBundle::from_section(
+                format!("Repair +{REPAIR_AMOUNT} ({count} {item})"),
+                TextStyle {
+                    font: ui_assets.font.clone(),
+                    font_size: 21.0,
+                    color: Color::BLACK,
+                },
+            ));
+        })
+        .set_parent(actions.single());
+}
+
+fn repair_clicked(
+    buttons: Query<(), (With<RepairButton>, With<JustClicked>)>,
+    selection: Res<Selection>,
+    buildings: Query<&Health, With<StructureTag>>,
+    mut player: Query<(Entity, &mut Inventory), With<PlayerControllerTag>>,
+    mut apply_health_event: EventWriter<ApplyHealthEvent>,
+) {
+    if buttons.is_empty() {
+        return;
+    }
+    let Some((building, health)) = selection
+        .0
+        .and_then(|e| buildings.get(e).ok().map(|h| (e, h)))
+    else {
+        return;
+    };
+    let Ok((player, mut inventory)) = player.get_single_mut() else {
+        return;
+    };
+    if health.current >= health.max || !inventory.spend_items(std::iter::once(REPAIR_COST)) {
+        return;
+    }
+    apply_health_event.send(ApplyHealthEvent {
+        amount: REPAIR_AMOUNT,
+        target_entity: building,
+        caster_entity: player,
+        is_crit: false,
+        is_finisher: false,
+    });
+}
+
+// only shown for damaged buildings
+fn update_repair_button(
+    selection: Res<Selection>,
+    buildings: Query<&Health, With<StructureTag>>,
+    mut button: Query<&mut Style, With<RepairButton>>,
+) {
+    let Ok(mut style) = button.get_single_mut() else {
+        return;
+    };
+    let damaged = selection
+        .0
+        .and_then(|e| buildings.get(e).ok())
+        .is_some_and(|h| h.current < h.max);
+    let display = match damaged {
+        true => Display::Flex,
+        false => Display::None,
+    };
+    if style.display != display {
+        style.display = display;
+    }
+}
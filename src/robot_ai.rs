@@ -0,0 +1,337 @@
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+
+use crate::{
+    activity_lod::Dormant,
+    health::Health,
+    pathfinding::{PathFollow, PathRequest},
+    player::{Body, MonkeyTag, Player, PlayerInput},
+    spatial_grid::SpatialGrids,
+    threat::ThreatTable,
+    wall::WALL_LENGTH,
+};
+
+const ATTACK_DISTANCE: f32 = 2.0;
+// seconds between checks if the robot got stuck
+const STUCK_CHECK_INTERVAL: f64 = 0.1;
+// flanking robots walk straight in once this close
+const FLANK_STOP_DISTANCE: f32 = 4.0;
+const FLEE_TIME: f32 = 2.0;
+// a single hit is enough to get a robot's attention
+const AGGRO_THREAT: f32 = 5.0;
+// chance to pick a tree spawner instead of a tree as the next goal
+const SPAWNER_GOAL_CHANCE: f64 = 0.1;
+// a stuck robot swings at buildings this close instead of where it was walking,
+// far enough to reach the middle of a wall from its end
+const OBSTACLE_REACH: f32 = ATTACK_DISTANCE + WALL_LENGTH / 2.0;
+
+/// What a robot is doing right now, robot_ai moves between these.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RobotState {
+    // walk to the goal and hit it once in reach
+    Seek,
+    // chase and hit whatever built up the most threat
+    AttackTarget(Entity),
+    // didn't move while trying to, swing at whatever is in the way
+    BreakObstacle { dir: Vec3 },
+    // hurt too badly, run away from monkeys for a bit
+    Flee { time_left: f32 },
+}
+
+/// How a body plays, every robot runs the same states.
+#[derive(Clone, Copy, Debug)]
+pub struct AiProfile {
+    // monkeys closer than this build up threat
+    pub aggro_range: f32,
+    // chased targets further away than this are given up on
+    pub leash_range: f32,
+    // radians to the side monkeys are approached from
+    pub flank_angle: f32,
+    // flees once when health drops below this fraction
+    pub flee_below: Option<f32>,
+    // goes for buildings before trees
+    pub prefers_structures: bool,
+}
+
+impl AiProfile {
+    pub fn for_body(body: Body) -> Self {
+        let robot = AiProfile {
+            aggro_range: 5.0,
+            leash_range: 10.0,
+            flank_angle: 0.0,
+            flee_below: None,
+            prefers_structures: false,
+        };
+        match body {
+            Body::Monkey | Body::Robot => robot,
+            Body::FastRobot => AiProfile {
+                flank_angle: 0.7,
+                flee_below: Some(0.3),
+                ..robot
+            },
+            Body::Boss => AiProfile {
+                aggro_range: 12.0,
+                leash_range: 25.0,
+                ..robot
+            },
+            Body::Sapper => AiProfile {
+                aggro_range: 0.0,
+                prefers_structures: true,
+                ..robot
+            },
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct RobotController {
+    pub state: RobotState,
+    // tree, spawner or building the robot is walking to
+    goal: Option<Entity>,
+    profile: AiProfile,
+    has_fled: bool,
+    /// Keeps track of where we were at certain intervals, to determine if we're stuck or not.
+    last_position_check: Option<(f64, Vec3)>,
+}
+
+impl RobotController {
+    pub fn new(body: Body) -> Self {
+        Self {
+            state: RobotState::Seek,
+            goal: None,
+            profile: AiProfile::for_body(body),
+            has_fled: false,
+            last_position_check: None,
+        }
+    }
+
+    /// Seeking with nothing to walk to.
+    pub fn is_idle(&self) -> bool {
+        self.state == RobotState::Seek && self.goal.is_none()
+    }
+
+    // moves to the next state, the goal itself is picked in robot_ai
+    fn update_state(
+        &mut self,
+        pos: Vec3,
+        stuck: Option<Vec3>,
+        health_percent: f32,
+        threat: &mut ThreatTable,
+        targets: &Query<&GlobalTransform, With<Health>>,
+        dt: f32,
+    ) {
+        let profile = self.profile;
+        if !self.has_fled
+            && profile
+                .flee_below
+                .is_some_and(|below| health_percent < below)
+        {
+            self.has_fled = true;
+            self.state = RobotState::Flee {
+                time_left: FLEE_TIME,
+            };
+            return;
+        }
+        if let RobotState::Flee { time_left } = self.state {
+            if time_left > dt {
+                self.state = RobotState::Flee {
+                    time_left: time_left - dt,
+                };
+                return;
+            }
+        }
+        if let Some(dir) = stuck {
+            self.state = RobotState::BreakObstacle { dir };
+            return;
+        }
+        // sappers only care about buildings
+        if profile.prefers_structures {
+            self.state = RobotState::Seek;
+            return;
+        }
+        // a target that got away is ignored until it builds up threat again
+        if let RobotState::AttackTarget(target) = self.state {
+            let escaped = targets
+                .get(target)
+                .is_ok_and(|t| t.translation().distance_squared(pos) > profile.leash_range.powi(2));
+            if escaped {
+                threat.forget(target);
+            }
+        }
+        self.state = match threat.highest() {
+            Some((target, t)) if t >= AGGRO_THREAT && targets.contains(target) => {
+                RobotState::AttackTarget(target)
+            }
+            _ => RobotState::Seek,
+        };
+    }
+}
+
+pub fn robot_ai(
+    mut robots: Query<
+        (
+            Entity,
+            &mut PlayerInput,
+            &mut RobotController,
+            &Player,
+            &Health,
+            &GlobalTransform,
+            &mut PathRequest,
+            &mut PathFollow,
+            &mut ThreatTable,
+        ),
+        Without<Dormant>,
+    >,
+    monkeys: Query<&GlobalTransform, With<MonkeyTag>>,
+    grids: Res<SpatialGrids>,
+    goals: Query<&GlobalTransform, With<Health>>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds_f64();
+    for (
+        entity,
+        mut input,
+        mut controller,
+        player,
+        health,
+        transform,
+        mut path_request,
+        mut path,
+        mut threat,
+    ) in robots.iter_mut()
+    {
+        let pos = transform.translation();
+        let profile = controller.profile;
+
+        // the goal is gone, pick a new one
+        if controller.goal.is_some_and(|g| !goals.contains(g)) {
+            controller.goal = None;
+        }
+        if controller.goal.is_none() {
+            let closest_structure = grids
+                .structures
+                .nearest(pos)
+                .filter(|_| profile.prefers_structures);
+            let closest_tree = grids.trees.nearest(pos);
+            let closest_spawner = grids.tree_spawners.nearest(pos);
+            let (first, second) = match thread_rng().gen_bool(SPAWNER_GOAL_CHANCE) {
+                true => (closest_spawner, closest_tree),
+                false => (closest_tree, closest_spawner),
+            };
+            controller.goal = closest_structure.or(first).or(second).map(|(_, e)| e);
+        }
+
+        // compare against where we were a moment ago, while still holding last frame's input
+        let mut stuck = None;
+        match controller.last_position_check {
+            Some((t, p)) if now - t >= STUCK_CHECK_INTERVAL => {
+                let min_move_distance = STUCK_CHECK_INTERVAL as f32 * player.movement_speed / 5.0;
+                if p.distance_squared(pos) <= min_move_distance.powi(2)
+                    && input.movement.length_squared() > 0.0
+                {
+                    stuck = Some(input.movement);
+                }
+                controller.last_position_check = Some((now, pos));
+            }
+            Some(_) => {}
+            None => controller.last_position_check = Some((now, pos)),
+        }
+
+        controller.update_state(
+            pos,
+            stuck,
+            health.percent(),
+            &mut threat,
+            &goals,
+            time.delta_seconds(),
+        );
+
+        input.attack = None;
+        match controller.state {
+            RobotState::Seek => match controller.goal.and_then(|g| goals.get(g).ok()) {
+                Some(goal) => {
+                    let reached = go_to(
+                        &mut input,
+                        &mut path_request,
+                        &mut path,
+                        pos,
+                        goal.translation(),
+                        ATTACK_DISTANCE,
+                        0.0,
+                    );
+                    if let Some(dir) = reached {
+                        input.attack = Some((dir, None));
+                    }
+                }
+                None => input.movement = Vec3::ZERO,
+            },
+            RobotState::AttackTarget(target) => {
+                let Ok(target) = goals.get(target) else {
+                    continue;
+                };
+                // half the flankers go left, the other half right
+                let side = match entity.index() % 2 {
+                    0 => 1.0,
+                    _ => -1.0,
+                };
+                let reached = go_to(
+                    &mut input,
+                    &mut path_request,
+                    &mut path,
+                    pos,
+                    target.translation(),
+                    ATTACK_DISTANCE,
+                    profile.flank_angle * side,
+                );
+                if let Some(dir) = reached {
+                    input.attack = Some((dir, None));
+                }
+            }
+            RobotState::BreakObstacle { dir } => {
+                // aimed at the wall or building in the way, so the swing's cone doesn't miss it
+                let obstacle = grids
+                    .structures
+                    .within(pos, OBSTACLE_REACH)
+                    .map(|(_, p)| Vec3::new(p.x - pos.x, 0.0, p.z - pos.z))
+                    .filter(|to| to.length() <= OBSTACLE_REACH)
+                    .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+                    .and_then(Vec3::try_normalize);
+                input.attack = Some((obstacle.unwrap_or(dir), None));
+            }
+            RobotState::Flee { .. } => {
+                let away = grids
+                    .monkeys
+                    .nearest(pos)
+                    .and_then(|(_, m)| monkeys.get(m).ok())
+                    .map(|m| pos - m.translation())
+                    .unwrap_or_default();
+                input.movement = Vec3::new(away.x, 0.0, away.z);
+            }
+        }
+    }
+}
+
+/// Walks along the planned path towards `target`, robots and companions both move with this.
+/// Once within `reach` it stops steering and returns the direction to the target, to attack in.
+pub fn go_to(
+    input: &mut PlayerInput,
+    path_request: &mut Mut<PathRequest>,
+    path: &mut PathFollow,
+    pos: Vec3,
+    target: Vec3,
+    reach: f32,
+    flank_angle: f32,
+) -> Option<Vec3> {
+    let mut diff = target - pos;
+    if diff.length_squared() < reach.powi(2) {
+        return Some(diff);
+    }
+    PathRequest::set_goal(path_request, target);
+    diff.y = 0.0;
+    let mut movement = path.direction(pos).unwrap_or(diff);
+    if diff.length() > FLANK_STOP_DISTANCE {
+        movement = Quat::from_rotation_y(flank_angle) * movement;
+    }
+    input.movement = movement;
+    None
+}
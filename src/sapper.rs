@@ -0,0 +1,77 @@
+use bevy::{ecs::query::Has, math::Vec3Swizzles, prelude::*};
+
+use crate::{
+    health::{ApplyHealthEvent, Health},
+    pause::not_paused,
+    player::Body,
+};
+
+// blows up when a structure is this close
+const FUSE_RANGE: f32 = 3.0;
+const BLAST_RADIUS: f32 = 4.5;
+const BLAST_DAMAGE: i32 = 4;
+// structures take this many times the blast damage
+const STRUCTURE_DAMAGE_MUL: i32 = 3;
+
+pub struct SapperPlugin;
+
+impl Plugin for SapperPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, sapper_explode.run_if(not_paused));
+    }
+}
+
+/// Buildings placed by the player, sappers go for these before anything else.
+#[derive(Component)]
+pub struct StructureTag;
+
+// the blast already went off, the sapper is dead once its health event is applied
+#[derive(Component)]
+struct Detonated;
+
+fn sapper_explode(
+    mut commands: Commands,
+    sappers: Query<(Entity, &Body, &GlobalTransform, &Health), Without<Detonated>>,
+    structures: Query<&GlobalTransform, With<StructureTag>>,
+    targets: Query<(Entity, &GlobalTransform, Has<StructureTag>), With<Health>>,
+    mut apply_health_events: EventWriter<ApplyHealthEvent>,
+) {
+    for (sapper, body, transform, health) in sappers.iter() {
+        if *body != Body::Sapper || health.is_dead() {
+            continue;
+        }
+        let pos = transform.translation();
+        let flat_distance = |other: Vec3| pos.xz().distance(other.xz());
+        let in_range = structures
+            .iter()
+            .any(|s| flat_distance(s.translation()) < FUSE_RANGE);
+        if !in_range {
+            continue;
+        }
+        for (target, target_transform, is_structure) in targets.iter() {
+            if target == sapper || flat_distance(target_transform.translation()) > BLAST_RADIUS {
+                continue;
+            }
+            let damage = match is_structure {
+                true => BLAST_DAMAGE * STRUCTURE_DAMAGE_MUL,
+                false => BLAST_DAMAGE,
+            };
+            apply_health_events.send(ApplyHealthEvent {
+                amount: -damage,
+                target_entity: target,
+                caster_entity: sapper,
+                is_crit: false,
+                is_finisher: false,
+            });
+        }
+        // dies like any other robot, so its death sound and drops still happen
+        apply_health_events.send(ApplyHealthEvent {
+            amount: -health.current,
+            target_entity: sapper,
+            caster_entity: sapper,
+            is_crit: false,
+            is_finisher: false,
+        });
+        commands.entity(sapper).insert(Detonated);
+    }
+}
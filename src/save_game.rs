@@ -0,0 +1,430 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    companion::CompanionController,
+    depot::{Depot, SpawnDepotEvent},
+    experience::Experience,
+    health::Health,
+    inventory::{Inventory, Item},
+    map::{CurrentMap, MapBounds, MapDefinitions, MapDefinitionsAsset},
+    notification::NotificationEvent,
+    perks::{PendingDrafts, PerkModifiers},
+    persistence,
+    player::{Body, Controller, Player, PlayerControllerTag, SpawnPlayerEvent},
+    shop::{ShopItem, ShopItemData, SpawnShopItemEvent},
+    shrine::{Shrine, SpawnShrineEvent},
+    state::{AppState, RunEntities, Wave, WaveCleared},
+    tower::{SpawnTowerEvent, TowerTag},
+    tree::{SpawnTreeEvent, TreeBlueprint, TreeRootTag, TreeTrunkTag},
+    tree_spawner::{SpawnTreeSpawnerEvent, TreeSpawner},
+    wall::{SpawnWallEvent, WallTag},
+    wave_spawner::WaveSpawner,
+    weapon::{Weapon, WeaponStats},
+};
+
+const SAVE_FILE: &str = "savegame.ron";
+
+pub struct SaveGamePlugin;
+
+impl Plugin for SaveGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveGameEvent>()
+            .add_event::<LoadGameEvent>()
+            .init_resource::<PendingCharacters>()
+            .init_resource::<PendingTreeHealth>()
+            .add_systems(
+                Update,
+                (save_load_input, save_game).run_if(in_state(AppState::Playing)),
+            )
+            // loading in PreUpdate lets the spawn events be handled in the same frame,
+            // so the loss check never sees a world without player or trees
+            .add_systems(PreUpdate, load_game.run_if(in_state(AppState::Playing)))
+            .add_systems(PostUpdate, (restore_characters, restore_tree_health));
+    }
+}
+
+#[derive(Event)]
+pub struct SaveGameEvent;
+
+#[derive(Event)]
+pub struct LoadGameEvent;
+
+/// Everything needed to pick a run back up.
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub wave: usize,
+    // name of the map, older saves load on whatever map is current
+    #[serde(default)]
+    pub map: String,
+    // where the walls were pushed to, None for older saves
+    #[serde(default)]
+    pub map_size_half: Option<Vec2>,
+    pub characters: Vec<CharacterSave>,
+    pub trees: Vec<Vec3>,
+    // health of each tree in the same order as trees, older saves load them at full health
+    #[serde(default)]
+    pub tree_health: Vec<i32>,
+    pub towers: Vec<Vec3>,
+    pub tree_spawners: Vec<Vec3>,
+    // older saves have no walls
+    #[serde(default)]
+    pub walls: Vec<(Vec3, Quat)>,
+    #[serde(default)]
+    pub shrines: Vec<Vec3>,
+    // each with what it's storing
+    #[serde(default)]
+    pub depots: Vec<(Vec3, Vec<(Item, u32)>)>,
+    pub shop_items: Vec<ShopItemData>,
+    // perks that aren't on a character, older saves have none
+    #[serde(default)]
+    pub perks: PerkModifiers,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CharacterSave {
+    pub controller: Controller,
+    pub body: Body,
+    pub pos: Vec3,
+    pub health: i32,
+    pub weapon: String,
+    pub damage_add: i32,
+    pub cooldown_mul: f32,
+    pub inventory: Vec<(Item, u32)>,
+    // raised by level ups and perks, older saves keep what the character spawned with
+    #[serde(default)]
+    pub max_health: Option<i32>,
+    #[serde(default)]
+    pub movement_speed: Option<f32>,
+    #[serde(default)]
+    pub extra_projectiles: u32,
+    // only the player levels up
+    #[serde(default)]
+    pub experience: Option<Experience>,
+}
+
+// characters waiting to get their health, stats and items back after being spawned
+#[derive(Resource, Default)]
+struct PendingCharacters(Vec<CharacterSave>);
+
+// where loaded trees were planted and the health to give them back once spawned
+#[derive(Resource, Default)]
+struct PendingTreeHealth(Vec<(Vec3, i32)>);
+
+fn save_load_input(
+    keyboard: Res<Input<KeyCode>>,
+    mut save_event: EventWriter<SaveGameEvent>,
+    mut load_event: EventWriter<LoadGameEvent>,
+) {
+    if keyboard.just_pressed(KeyCode::F5) {
+        save_event.send(SaveGameEvent);
+    }
+    if keyboard.just_pressed(KeyCode::F9) {
+        load_event.send(LoadGameEvent);
+    }
+}
+
+fn save_game(
+    mut events: EventReader<SaveGameEvent>,
+    wave: Res<Wave>,
+    characters: Query<(
+        &Transform,
+        &Body,
+        (&Health, &Player),
+        &Weapon,
+        &WeaponStats,
+        &Inventory,
+        Option<&Experience>,
+        Has<PlayerControllerTag>,
+        Has<CompanionController>,
+    )>,
+    trees: Query<(&Parent, &Health), With<TreeTrunkTag>>,
+    transforms: Query<&Transform>,
+    towers: Query<&Transform, With<TowerTag>>,
+    tree_spawners: Query<&Transform, With<TreeSpawner>>,
+    walls: Query<&Transform, With<WallTag>>,
+    shrines: Query<&Transform, With<Shrine>>,
+    depots: Query<(&Transform, &Inventory), With<Depot>>,
+    shop_items: Query<&ShopItem>,
+    mut notification_event: EventWriter<NotificationEvent>,
+    (map, bounds, perks): (Res<CurrentMap>, Res<MapBounds>, Res<PerkModifiers>),
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let characters = characters
+        .iter()
+        .map(
+            |(
+                transform,
+                body,
+                (health, player),
+                weapon,
+                stats,
+                inventory,
+                experience,
+                is_player,
+                is_companion,
+            )| {
+                CharacterSave {
+                    controller: match (is_player, is_companion) {
+                        (true, _) => Controller::Player,
+                        (_, true) => Controller::Companion,
+                        _ => Controller::Robot,
+                    },
+                    body: *body,
+                    pos: transform.translation,
+                    health: health.current,
+                    weapon: weapon.0.path().map(|p| p.to_string()).unwrap_or_default(),
+                    damage_add: stats.damage_add,
+                    cooldown_mul: stats.cooldown_mul,
+                    inventory: inventory.iter().collect(),
+                    max_health: Some(health.max),
+                    movement_speed: Some(player.movement_speed),
+                    extra_projectiles: stats.extra_projectiles,
+                    experience: experience.cloned(),
+                }
+            },
+        )
+        .collect();
+    // trunks sway around, the root is where the tree was planted
+    let (trees, tree_health) = trees
+        .iter()
+        .filter_map(|(parent, health)| {
+            let root = transforms.get(parent.get()).ok()?;
+            Some((root.translation, health.current))
+        })
+        .unzip();
+    let save = SaveGame {
+        wave: wave.0,
+        map: map.0.name.clone(),
+        map_size_half: Some(bounds.size_half),
+        characters,
+        trees,
+        tree_health,
+        towers: towers.iter().map(|t| t.translation).collect(),
+        // spawners are placed one unit to the side of where they're built
+        tree_spawners: tree_spawners
+            .iter()
+            .map(|t| t.translation - Vec3::X)
+            .collect(),
+        walls: walls
+            .iter()
+            .map(|t| (t.translation * Vec3::new(1.0, 0.0, 1.0), t.rotation))
+            .collect(),
+        shrines: shrines
+            .iter()
+            .map(|t| t.translation * Vec3::new(1.0, 0.0, 1.0))
+            .collect(),
+        depots: depots
+            .iter()
+            .map(|(t, inventory)| {
+                (
+                    t.translation * Vec3::new(1.0, 0.0, 1.0),
+                    inventory.iter().collect(),
+                )
+            })
+            .collect(),
+        shop_items: shop_items.iter().map(|s| s.0.clone()).collect(),
+        perks: perks.clone(),
+    };
+    persistence::save(SAVE_FILE, &save);
+    notification_event.send(NotificationEvent {
+        text: "Game saved".into(),
+        show_for: 1.5,
+        color: Color::BLUE,
+    });
+}
+
+fn load_game(
+    mut commands: Commands,
+    mut events: EventReader<LoadGameEvent>,
+    asset_server: Res<AssetServer>,
+    despawn: Query<Entity, RunEntities>,
+    (mut pending, mut pending_trees): (ResMut<PendingCharacters>, ResMut<PendingTreeHealth>),
+    mut spawn_player_event: EventWriter<SpawnPlayerEvent>,
+    mut spawn_tree_event: EventWriter<SpawnTreeEvent>,
+    mut spawn_tower_event: EventWriter<SpawnTowerEvent>,
+    mut spawn_tree_spawner_event: EventWriter<SpawnTreeSpawnerEvent>,
+    mut spawn_wall_event: EventWriter<SpawnWallEvent>,
+    mut spawn_shrine_event: EventWriter<SpawnShrineEvent>,
+    mut spawn_depot_event: EventWriter<SpawnDepotEvent>,
+    mut spawn_shop_item_event: EventWriter<SpawnShopItemEvent>,
+    mut notification_event: EventWriter<NotificationEvent>,
+    (mut current_map, map_definitions, map_assets, mut bounds, mut perks): (
+        ResMut<CurrentMap>,
+        Res<MapDefinitions>,
+        Res<Assets<MapDefinitionsAsset>>,
+        ResMut<MapBounds>,
+        ResMut<PerkModifiers>,
+    ),
+    (mut spawner, mut wave_cleared, mut pending_drafts): (
+        ResMut<WaveSpawner>,
+        ResMut<WaveCleared>,
+        ResMut<PendingDrafts>,
+    ),
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let Some(save) = persistence::load::<SaveGame>(SAVE_FILE) else {
+        notification_event.send(NotificationEvent {
+            text: "No saved game".into(),
+            show_for: 1.5,
+            color: Color::RED,
+        });
+        return;
+    };
+
+    for entity in despawn.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // the saved robots are all there is of the wave, nothing more comes out of portals
+    spawner.clear();
+    wave_cleared.0 = false;
+    pending_drafts.0 = 0;
+    // switching maps rebuilds the terrain around the loaded run
+    if save.map != current_map.0.name {
+        if let Some(map) = map_assets
+            .get(&map_definitions.0)
+            .and_then(|maps| maps.0.iter().find(|m| m.name == save.map))
+        {
+            current_map.0 = map.clone();
+        }
+    }
+    if let Some(size_half) = save.map_size_half {
+        bounds.size_half = size_half;
+    }
+
+    for character in save.characters.iter() {
+        spawn_player_event.send(SpawnPlayerEvent {
+            pos: character.pos,
+            controller: character.controller,
+            body: character.body,
+            weapon: asset_server.load(&character.weapon),
+            // saved health is restored afterwards
+            scaling: default(),
+        });
+    }
+    pending.0 = save.characters;
+    pending_trees.0 = save.trees.iter().copied().zip(save.tree_health).collect();
+    for pos in save.trees {
+        spawn_tree_event.send(SpawnTreeEvent {
+            pos,
+            blueprint: TreeBlueprint::Randomized,
+            play_sound: false,
+            sapling: false,
+        });
+    }
+    // what was paid for loaded buildings isn't saved, demolishing them gives nothing back
+    for pos in save.towers {
+        spawn_tower_event.send(SpawnTowerEvent { pos, cost: vec![] });
+    }
+    for pos in save.tree_spawners {
+        spawn_tree_spawner_event.send(SpawnTreeSpawnerEvent { pos, cost: vec![] });
+    }
+    for (pos, rotation) in save.walls {
+        spawn_wall_event.send(SpawnWallEvent {
+            pos,
+            rotation,
+            cost: vec![],
+        });
+    }
+    for pos in save.shrines {
+        spawn_shrine_event.send(SpawnShrineEvent { pos, cost: vec![] });
+    }
+    for (pos, items) in save.depots {
+        spawn_depot_event.send(SpawnDepotEvent {
+            pos,
+            cost: vec![],
+            items,
+        });
+    }
+    for item in save.shop_items {
+        spawn_shop_item_event.send(SpawnShopItemEvent { item });
+    }
+    commands.insert_resource(Wave(save.wave));
+    *perks = save.perks;
+
+    notification_event.send(NotificationEvent {
+        text: "Game loaded".into(),
+        show_for: 1.5,
+        color: Color::BLUE,
+    });
+}
+
+// spawned characters are found again by body and position, they are spawned exactly where they were saved
+fn restore_characters(
+    mut pending: ResMut<PendingCharacters>,
+    mut characters: Query<
+        (
+            &Transform,
+            &Body,
+            (&mut Player, &mut Health),
+            &mut WeaponStats,
+            &mut Inventory,
+            Option<&mut Experience>,
+        ),
+        Added<Player>,
+    >,
+) {
+    if pending.0.is_empty() {
+        return;
+    }
+    for (transform, body, (mut player, mut health), mut stats, mut inventory, experience) in
+        characters.iter_mut()
+    {
+        let Some(index) = pending
+            .0
+            .iter()
+            .position(|c| c.body == *body && c.pos.distance(transform.translation) < 0.1)
+        else {
+            continue;
+        };
+        let saved = pending.0.swap_remove(index);
+        if let Some(max) = saved.max_health {
+            health.max = max;
+        }
+        health.current = saved.health;
+        if let Some(speed) = saved.movement_speed {
+            player.movement_speed = speed;
+        }
+        stats.damage_add = saved.damage_add;
+        stats.cooldown_mul = saved.cooldown_mul;
+        stats.extra_projectiles = saved.extra_projectiles;
+        if let (Some(mut experience), Some(saved_experience)) = (experience, saved.experience) {
+            *experience = saved_experience;
+        }
+        for (item, count) in saved.inventory {
+            inventory.add_item(item, count);
+        }
+    }
+    // everything loaded spawns in the same frame, leftovers would be matched against a later load
+    pending.0.clear();
+}
+
+// trunks are found again by where their root was planted
+fn restore_tree_health(
+    mut pending: ResMut<PendingTreeHealth>,
+    mut trunks: Query<(&Parent, &mut Health), Added<TreeTrunkTag>>,
+    roots: Query<&Transform, With<TreeRootTag>>,
+) {
+    if pending.0.is_empty() {
+        return;
+    }
+    for (parent, mut health) in trunks.iter_mut() {
+        let Ok(root) = roots.get(parent.get()) else {
+            continue;
+        };
+        let Some(index) = pending
+            .0
+            .iter()
+            .position(|(pos, _)| pos.distance(root.translation) < 0.1)
+        else {
+            continue;
+        };
+        let (_, saved) = pending.0.swap_remove(index);
+        health.current = saved.min(health.max);
+    }
+    pending.0.clear();
+}
@@ -0,0 +1,269 @@
+use std::f32::consts::TAU;
+
+use bevy::{math::vec3, prelude::*};
+use bevy_vector_shapes::{prelude::ShapePainter, shapes::DiscPainter};
+
+use crate::{
+    depot::Depot,
+    fruit::FruitTree,
+    health::Health,
+    inventory::Inventory,
+    placement::not_placing,
+    player::{Body, RobotTag},
+    pointer::PointerPos,
+    sapper::StructureTag,
+    settings::Settings,
+    shrine::Shrine,
+    tower::TowerTag,
+    tree::{Growing, Species, TreeRootTag, TreeTrunkTag},
+    tree_spawner::TreeSpawner,
+    ui_util::{JustClicked, UiAssets},
+    wall::WallTag,
+    weapon::{Weapon, WeaponAsset, WeaponCooldown, WeaponStats},
+};
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Selection>()
+            .add_systems(Startup, setup_selection_panel)
+            .add_systems(
+                Update,
+                (
+                    select_entity.run_if(not_placing),
+                    handle_close_click,
+                    update_selection_panel,
+                    draw_selection_ring,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// What the info panel is open for, clicking a building, tree or robot selects it.
+#[derive(Resource, Default)]
+pub struct Selection(pub Option<Entity>);
+
+/// Row at the bottom of the selection panel, other modules put their buttons for the selected entity here.
+#[derive(Component)]
+pub struct SelectionActionsTag;
+
+#[derive(Component)]
+struct SelectionPanelTag;
+
+#[derive(Component)]
+struct SelectionPanelText;
+
+#[derive(Component)]
+struct CloseSelectionButton;
+
+fn setup_selection_panel(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    commands
+        .spawn((
+            SelectionPanelTag,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(35.0),
+                    bottom: Val::Px(10.0),
+                    width: Val::Percent(30.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    row_gap: Val::Px(5.0),
+                    flex_direction: FlexDirection::Column,
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::BLACK.with_a(0.7).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                SelectionPanelText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: ui_assets.font.clone(),
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                    },
+                ),
+            ));
+            parent.spawn((
+                SelectionActionsTag,
+                NodeBundle {
+                    style: Style {
+                        row_gap: Val::Px(5.0),
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+            parent
+                .spawn((
+                    CloseSelectionButton,
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(5.0)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Close",
+                        TextStyle {
+                            font: ui_assets.font.clone(),
+                            font_size: 21.0,
+                            color: Color::BLACK,
+                        },
+                    ));
+                });
+        });
+}
+
+fn select_entity(
+    mouse: Res<Input<MouseButton>>,
+    pointer: Res<PointerPos>,
+    selectable: Query<(), Or<(With<StructureTag>, With<RobotTag>)>>,
+    tree_roots: Query<&Children, With<TreeRootTag>>,
+    trunks: Query<(), With<TreeTrunkTag>>,
+    mut selection: ResMut<Selection>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    // clicking anything else keeps the panel, so its own buttons can be used
+    let Some(target) = pointer.pointer_on else {
+        return;
+    };
+    if selectable.contains(target.entity) {
+        selection.0 = Some(target.entity);
+    }
+    // the pointer hits the tree root, its health and timers are on the trunk
+    if let Ok(children) = tree_roots.get(target.entity) {
+        if let Some(trunk) = children.iter().find(|c| trunks.contains(**c)) {
+            selection.0 = Some(*trunk);
+        }
+    }
+}
+
+fn handle_close_click(
+    buttons: Query<(), (With<CloseSelectionButton>, With<JustClicked>)>,
+    mut selection: ResMut<Selection>,
+) {
+    if !buttons.is_empty() {
+        selection.0 = None;
+    }
+}
+
+fn update_selection_panel(
+    mut selection: ResMut<Selection>,
+    names: Query<(
+        Has<TowerTag>,
+        Has<TreeTrunkTag>,
+        Has<TreeSpawner>,
+        Has<WallTag>,
+        Has<Shrine>,
+        Has<Depot>,
+        Option<&Body>,
+    )>,
+    healths: Query<&Health>,
+    weapons: Query<(&Weapon, &WeaponStats, Option<&WeaponCooldown>)>,
+    spawners: Query<&TreeSpawner>,
+    fruit_trees: Query<&FruitTree>,
+    growing: Query<&Growing>,
+    species: Query<&Species>,
+    depots: Query<&Inventory, With<Depot>>,
+    weapon_assets: Res<Assets<WeaponAsset>>,
+    mut panel: Query<&mut Style, With<SelectionPanelTag>>,
+    mut panel_text: Query<&mut Text, With<SelectionPanelText>>,
+) {
+    let Ok(mut style) = panel.get_single_mut() else {
+        return;
+    };
+    // dying robots lose their health, so they can't be inspected either
+    let selected = selection
+        .0
+        .filter(|e| names.contains(*e) && healths.contains(*e));
+    if selected.is_none() {
+        selection.0 = None;
+    }
+    let Some(entity) = selected else {
+        if style.display != Display::None {
+            style.display = Display::None;
+        }
+        return;
+    };
+    style.display = Display::Flex;
+
+    let Ok((is_tower, is_tree, is_spawner, is_wall, is_shrine, is_depot, body)) = names.get(entity)
+    else {
+        return;
+    };
+    let name = match (
+        is_tower, is_tree, is_spawner, is_wall, is_shrine, is_depot, body,
+    ) {
+        (true, ..) => String::from("Tower"),
+        (_, true, ..) => species.get(entity).map_or("Tree", |s| s.name()).to_string(),
+        (_, _, true, ..) => String::from("Tree spawner"),
+        (_, _, _, true, ..) => String::from("Wall"),
+        (_, _, _, _, true, ..) => String::from("Healing shrine"),
+        (_, _, _, _, _, true, _) => String::from("Storage depot"),
+        (.., Some(body)) => body.name().to_string(),
+        _ => String::new(),
+    };
+    let mut lines = vec![name];
+    if let Ok(health) = healths.get(entity) {
+        lines.push(format!("Health {}/{}", health.current, health.max));
+    }
+    if let Ok((weapon, stats, cooldown)) = weapons.get(entity) {
+        if let Some(weapon) = weapon_assets.get(&weapon.0) {
+            lines.push(format!(
+                "{}: damage +{}, cooldown x{:.2}",
+                weapon.name, stats.damage_add, stats.cooldown_mul
+            ));
+        }
+        if let Some(cooldown) = cooldown.filter(|c| c.time_left > 0.0) {
+            lines.push(format!("Attacks in {:.1}s", cooldown.time_left));
+        }
+    }
+    if let Ok(spawner) = spawners.get(entity) {
+        lines.push(format!("Next tree in {:.0}s", spawner.time_left()));
+    }
+    if let Ok(growing) = growing.get(entity) {
+        lines.push(format!("Grown in {:.0}s", growing.time_left()));
+    }
+    if let Ok(fruit) = fruit_trees.get(entity) {
+        lines.push(format!("Next fruit in {:.0}s", fruit.time_left()));
+    }
+    if let Ok(depot) = depots.get(entity) {
+        let stored: u32 = depot.iter().map(|(_, count)| count).sum();
+        lines.push(format!("Storing {stored} items"));
+    }
+    if let Ok(mut text) = panel_text.get_single_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+fn draw_selection_ring(
+    mut painter: ShapePainter,
+    selection: Res<Selection>,
+    transforms: Query<&GlobalTransform>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let Some(transform) = selection.0.and_then(|e| transforms.get(e).ok()) else {
+        return;
+    };
+    let pos = transform.translation();
+    painter.color = settings.accessibility.palette.remap(Color::WHITE);
+    painter.thickness = 0.08;
+    painter.hollow = true;
+    painter.set_rotation(Quat::from_rotation_x(TAU / 4.0));
+    painter.set_translation(vec3(pos.x, 0.05, pos.z));
+    painter.circle(1.4 + (time.elapsed_seconds() * 4.0).sin() * 0.1);
+}
@@ -0,0 +1,734 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    prelude::*,
+    window::{PresentMode, PrimaryWindow, WindowMode, WindowResolution},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    input_map::{Action, Binding, InputBindings},
+    persistence,
+    ui_util::{JustClicked, UiAssets},
+};
+
+const SETTINGS_FILE: &str = "settings.ron";
+pub const LARGE_TEXT_SCALE: f64 = 1.3;
+pub const RESOLUTIONS: [(u32, u32); 4] = [(1280, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+pub const FRAME_CAPS: [Option<u32>; 5] = [None, Some(30), Some(60), Some(120), Some(144)];
+pub const MINIMAP_ZOOMS: [f32; 4] = [1.0, 1.5, 2.0, 3.0];
+pub const UI_SCALES: [f64; 4] = [0.75, 1.0, 1.25, 1.5];
+pub const VOLUMES: [f32; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+/// Takes the settings from `load_settings`, so the window they describe can be created first.
+pub struct SettingsPlugin {
+    pub settings: Settings,
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone())
+            .init_resource::<Rebinding>()
+            .add_event::<ToggleSettingsEvent>()
+            .add_systems(Startup, setup_settings_ui)
+            .add_systems(
+                Update,
+                (
+                    toggle_settings_ui,
+                    // before the click is handled, so the click itself isn't bound
+                    capture_rebind,
+                    handle_setting_click,
+                    handle_rebind_click,
+                    (
+                        apply_ui_scale,
+                        apply_display_settings,
+                        update_settings_ui,
+                        update_rebind_ui,
+                        save_settings,
+                    ),
+                )
+                    .chain(),
+            )
+            .add_systems(Last, limit_frame_rate);
+    }
+}
+
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub general: GeneralSettings,
+    pub display: DisplaySettings,
+    pub audio: AudioSettings,
+    pub accessibility: AccessibilitySettings,
+}
+
+/// the saved settings, defaults if there are none yet
+pub fn load_settings() -> Settings {
+    persistence::load_config::<Settings>(SETTINGS_FILE).unwrap_or_default()
+}
+
+/// Opens or closes the settings panel, like F1 does.
+#[derive(Event)]
+pub struct ToggleSettingsEvent;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneralSettings {
+    pub pause_on_focus_loss: bool,
+    // ask before buying expensive upgrades in the shop
+    pub confirm_purchases: bool,
+    // buildings snap to 1m cells while placing them
+    pub placement_grid: bool,
+    // purchases take what the player is missing from storage depots
+    pub draw_from_depots: bool,
+    // 1 fits the whole map on the minimap
+    pub minimap_zoom: f32,
+    // the camera shows where each wave comes from before it starts
+    pub wave_sweeps: bool,
+    // hit trees wobble on physics joints, off animates them instead which is far cheaper on big maps
+    pub tree_physics: bool,
+}
+
+impl Default for GeneralSettings {
+    fn default() -> Self {
+        Self {
+            pause_on_focus_loss: true,
+            confirm_purchases: true,
+            placement_grid: true,
+            draw_from_depots: true,
+            minimap_zoom: 1.0,
+            wave_sweeps: true,
+            tree_physics: true,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub window_mode: DisplayMode,
+    /// only used in windowed mode
+    pub resolution: (u32, u32),
+    pub vsync: bool,
+    /// frames per second, None is uncapped
+    pub frame_cap: Option<u32>,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            window_mode: DisplayMode::Windowed,
+            resolution: RESOLUTIONS[0],
+            vsync: true,
+            frame_cap: None,
+        }
+    }
+}
+
+impl DisplaySettings {
+    /// the primary window as these settings describe it
+    pub fn window(&self) -> Window {
+        let (width, height) = self.resolution;
+        Window {
+            mode: self.window_mode.window_mode(),
+            resolution: WindowResolution::new(width as f32, height as f32),
+            present_mode: self.present_mode(),
+            ..default()
+        }
+    }
+
+    fn present_mode(&self) -> PresentMode {
+        match self.vsync {
+            true => PresentMode::AutoVsync,
+            false => PresentMode::AutoNoVsync,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum DisplayMode {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl DisplayMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DisplayMode::Windowed => "Windowed",
+            DisplayMode::Borderless => "Borderless",
+            DisplayMode::Fullscreen => "Fullscreen",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            DisplayMode::Windowed => DisplayMode::Borderless,
+            DisplayMode::Borderless => DisplayMode::Fullscreen,
+            DisplayMode::Fullscreen => DisplayMode::Windowed,
+        }
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        match self {
+            DisplayMode::Windowed => WindowMode::Windowed,
+            DisplayMode::Borderless => WindowMode::BorderlessFullscreen,
+            DisplayMode::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+        }
+    }
+}
+
+// step to the entry after `current`, wrapping around. unknown values start over
+fn cycle<T: PartialEq + Copy>(options: &[T], current: T) -> T {
+    let index = options.iter().position(|o| *o == current);
+    options[index.map_or(0, |i| (i + 1) % options.len())]
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    pub palette: Palette,
+    /// add shapes and symbols next to colors, so they don't carry meaning alone
+    pub shape_coding: bool,
+    pub screen_shake: ScreenShake,
+    pub large_text: bool,
+    /// size of the whole ui, large text scales it further
+    pub ui_scale: f64,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            palette: Palette::default(),
+            shape_coding: false,
+            screen_shake: ScreenShake::default(),
+            large_text: false,
+            ui_scale: 1.0,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ScreenShake {
+    #[default]
+    Full,
+    Reduced,
+    Off,
+}
+
+impl ScreenShake {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScreenShake::Full => "Full",
+            ScreenShake::Reduced => "Reduced",
+            ScreenShake::Off => "Off",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ScreenShake::Full => ScreenShake::Reduced,
+            ScreenShake::Reduced => ScreenShake::Off,
+            ScreenShake::Off => ScreenShake::Full,
+        }
+    }
+
+    /// multiplier for how far the camera shakes
+    pub fn strength(&self) -> f32 {
+        match self {
+            ScreenShake::Full => 1.0,
+            ScreenShake::Reduced => 0.3,
+            ScreenShake::Off => 0.0,
+        }
+    }
+}
+
+/// The game uses red for danger, green for good and blue for info.
+/// Palettes swap those colors for ones that are easier to tell apart.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Palette {
+    #[default]
+    Default,
+    RedGreenSafe,
+    HighContrast,
+}
+
+impl Palette {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Default => "Default",
+            Palette::RedGreenSafe => "Red-green safe",
+            Palette::HighContrast => "High contrast",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Palette::Default => Palette::RedGreenSafe,
+            Palette::RedGreenSafe => Palette::HighContrast,
+            Palette::HighContrast => Palette::Default,
+        }
+    }
+
+    /// swap a signal color for this palette's version, other colors are left alone
+    pub fn remap(&self, color: Color) -> Color {
+        let alpha = color.a();
+        let opaque = color.with_a(1.0);
+        let remapped = match self {
+            Palette::Default => opaque,
+            Palette::RedGreenSafe => match opaque {
+                c if c == Color::RED => Color::rgb(0.84, 0.37, 0.0),
+                c if c == Color::GREEN => Color::rgb(0.0, 0.45, 0.7),
+                c if c == Color::BLUE => Color::rgb(0.8, 0.47, 0.65),
+                c if c == Color::YELLOW => Color::rgb(0.94, 0.89, 0.26),
+                c => c,
+            },
+            Palette::HighContrast => match opaque {
+                c if c == Color::RED => Color::rgb(1.0, 0.0, 1.0),
+                c if c == Color::GREEN => Color::CYAN,
+                c if c == Color::BLUE => Color::WHITE,
+                c if c == Color::YELLOW => Color::YELLOW,
+                c => c,
+            },
+        };
+        remapped.with_a(alpha)
+    }
+}
+
+/// symbol that carries the same meaning as a signal color
+pub fn signal_symbol(color: Color) -> Option<&'static str> {
+    match color.with_a(1.0) {
+        c if c == Color::RED => Some("(!)"),
+        c if c == Color::GREEN => Some("(+)"),
+        c if c == Color::BLUE => Some("(>)"),
+        _ => None,
+    }
+}
+
+#[derive(Component)]
+struct SettingsUiTag;
+
+#[derive(Component)]
+struct CloseSettingsButton;
+
+#[derive(Component, Clone, Copy)]
+struct RebindButton(Action);
+
+// action waiting for a key or mouse button
+#[derive(Resource, Default)]
+struct Rebinding(Option<Action>);
+
+#[derive(Component, Clone, Copy)]
+enum SettingButton {
+    PauseOnFocusLoss,
+    ConfirmPurchases,
+    PlacementGrid,
+    DrawFromDepots,
+    MinimapZoom,
+    WaveSweeps,
+    TreePhysics,
+    WindowMode,
+    Resolution,
+    Vsync,
+    FrameCap,
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    Palette,
+    ShapeCoding,
+    ScreenShake,
+    LargeText,
+    UiScale,
+}
+
+impl SettingButton {
+    fn label(&self, settings: &Settings) -> String {
+        let on_off = |b: bool| if b { "On" } else { "Off" };
+        let d = &settings.display;
+        let a = &settings.accessibility;
+        let percent = |v: f32| format!("{}%", (v * 100.0).round());
+        match self {
+            SettingButton::PauseOnFocusLoss => format!(
+                "Pause when unfocused: {}",
+                on_off(settings.general.pause_on_focus_loss)
+            ),
+            SettingButton::ConfirmPurchases => format!(
+                "Confirm expensive purchases: {}",
+                on_off(settings.general.confirm_purchases)
+            ),
+            SettingButton::PlacementGrid => format!(
+                "Snap buildings to grid: {}",
+                on_off(settings.general.placement_grid)
+            ),
+            SettingButton::DrawFromDepots => format!(
+                "Buy with depot stock: {}",
+                on_off(settings.general.draw_from_depots)
+            ),
+            SettingButton::MinimapZoom => {
+                format!("Minimap zoom: {}x", settings.general.minimap_zoom)
+            }
+            SettingButton::WaveSweeps => format!(
+                "Show where waves come from: {}",
+                on_off(settings.general.wave_sweeps)
+            ),
+            SettingButton::TreePhysics => format!(
+                "Tree physics (new trees): {}",
+                on_off(settings.general.tree_physics)
+            ),
+            SettingButton::WindowMode => format!("Window: {}", d.window_mode.name()),
+            SettingButton::Resolution => {
+                format!("Resolution: {}x{}", d.resolution.0, d.resolution.1)
+            }
+            SettingButton::Vsync => format!("Vsync: {}", on_off(d.vsync)),
+            SettingButton::FrameCap => match d.frame_cap {
+                Some(fps) => format!("Frame cap: {}", fps),
+                None => "Frame cap: Off".to_string(),
+            },
+            SettingButton::MasterVolume => {
+                format!("Master volume: {}", percent(settings.audio.master_volume))
+            }
+            SettingButton::MusicVolume => {
+                format!("Music volume: {}", percent(settings.audio.music_volume))
+            }
+            SettingButton::SfxVolume => {
+                format!("Effects volume: {}", percent(settings.audio.sfx_volume))
+            }
+            SettingButton::Palette => format!("Palette: {}", a.palette.name()),
+            SettingButton::ShapeCoding => format!("Shape coding: {}", on_off(a.shape_coding)),
+            SettingButton::ScreenShake => format!("Screen shake: {}", a.screen_shake.name()),
+            SettingButton::LargeText => format!("Large text: {}", on_off(a.large_text)),
+            SettingButton::UiScale => format!("UI scale: {}x", a.ui_scale),
+        }
+    }
+
+    fn apply(&self, settings: &mut Settings) {
+        let g = &mut settings.general;
+        let d = &mut settings.display;
+        let audio = &mut settings.audio;
+        let a = &mut settings.accessibility;
+        match self {
+            SettingButton::PauseOnFocusLoss => g.pause_on_focus_loss = !g.pause_on_focus_loss,
+            SettingButton::ConfirmPurchases => g.confirm_purchases = !g.confirm_purchases,
+            SettingButton::PlacementGrid => g.placement_grid = !g.placement_grid,
+            SettingButton::DrawFromDepots => g.draw_from_depots = !g.draw_from_depots,
+            SettingButton::MinimapZoom => g.minimap_zoom = cycle(&MINIMAP_ZOOMS, g.minimap_zoom),
+            SettingButton::WaveSweeps => g.wave_sweeps = !g.wave_sweeps,
+            SettingButton::TreePhysics => g.tree_physics = !g.tree_physics,
+            SettingButton::WindowMode => d.window_mode = d.window_mode.next(),
+            SettingButton::Resolution => d.resolution = cycle(&RESOLUTIONS, d.resolution),
+            SettingButton::Vsync => d.vsync = !d.vsync,
+            SettingButton::FrameCap => d.frame_cap = cycle(&FRAME_CAPS, d.frame_cap),
+            SettingButton::MasterVolume => {
+                audio.master_volume = cycle(&VOLUMES, audio.master_volume)
+            }
+            SettingButton::MusicVolume => audio.music_volume = cycle(&VOLUMES, audio.music_volume),
+            SettingButton::SfxVolume => audio.sfx_volume = cycle(&VOLUMES, audio.sfx_volume),
+            SettingButton::Palette => a.palette = a.palette.next(),
+            SettingButton::ShapeCoding => a.shape_coding = !a.shape_coding,
+            SettingButton::ScreenShake => a.screen_shake = a.screen_shake.next(),
+            SettingButton::LargeText => a.large_text = !a.large_text,
+            SettingButton::UiScale => a.ui_scale = cycle(&UI_SCALES, a.ui_scale),
+        }
+    }
+}
+
+// labels are filled in by update_settings_ui and update_rebind_ui
+fn spawn_button(parent: &mut ChildBuilder, font: &Handle<Font>, kind: impl Component) {
+    parent
+        .spawn((
+            kind,
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::all(Val::Px(5.0)),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 21.0,
+                    color: Color::BLACK,
+                },
+            ));
+        });
+}
+
+fn setup_settings_ui(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    let heading = |text: &str| {
+        TextBundle::from_section(
+            text,
+            TextStyle {
+                font: ui_assets.font.clone(),
+                font_size: 30.0,
+                color: Color::WHITE,
+            },
+        )
+    };
+    commands
+        .spawn((
+            SettingsUiTag,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(30.0),
+                    top: Val::Percent(15.0),
+                    width: Val::Percent(40.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    row_gap: Val::Px(5.0),
+                    flex_direction: FlexDirection::Column,
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::BLACK.with_a(0.7).into(),
+                // opened from the menus, so it goes on top of them
+                z_index: ZIndex::Global(1),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(heading("Settings"));
+            for setting in [
+                SettingButton::PauseOnFocusLoss,
+                SettingButton::ConfirmPurchases,
+                SettingButton::PlacementGrid,
+                SettingButton::DrawFromDepots,
+                SettingButton::MinimapZoom,
+                SettingButton::WaveSweeps,
+                SettingButton::TreePhysics,
+            ] {
+                spawn_button(parent, &ui_assets.font, setting);
+            }
+            parent.spawn(heading("Display"));
+            for setting in [
+                SettingButton::WindowMode,
+                SettingButton::Resolution,
+                SettingButton::Vsync,
+                SettingButton::FrameCap,
+            ] {
+                spawn_button(parent, &ui_assets.font, setting);
+            }
+            parent.spawn(heading("Audio"));
+            for setting in [
+                SettingButton::MasterVolume,
+                SettingButton::MusicVolume,
+                SettingButton::SfxVolume,
+            ] {
+                spawn_button(parent, &ui_assets.font, setting);
+            }
+            parent.spawn(heading("Accessibility"));
+            for setting in [
+                SettingButton::Palette,
+                SettingButton::ShapeCoding,
+                SettingButton::ScreenShake,
+                SettingButton::LargeText,
+                SettingButton::UiScale,
+            ] {
+                spawn_button(parent, &ui_assets.font, setting);
+            }
+            parent.spawn(heading("Controls"));
+            for action in Action::ALL {
+                spawn_button(parent, &ui_assets.font, RebindButton(action));
+            }
+            parent
+                .spawn((
+                    CloseSettingsButton,
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(5.0)),
+                            margin: UiRect::top(Val::Px(10.0)),
+                            justify_content: JustifyContent::Center,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Close",
+                        TextStyle {
+                            font: ui_assets.font.clone(),
+                            font_size: 21.0,
+                            color: Color::BLACK,
+                        },
+                    ));
+                });
+        });
+}
+
+// F1, the close button, or a settings button in the menus
+fn toggle_settings_ui(
+    keyboard: Res<Input<KeyCode>>,
+    mut toggle_events: EventReader<ToggleSettingsEvent>,
+    close_buttons: Query<(), (With<CloseSettingsButton>, With<JustClicked>)>,
+    mut panel: Query<&mut Style, With<SettingsUiTag>>,
+) {
+    let clicked = toggle_events.read().count() > 0 || !close_buttons.is_empty();
+    if !clicked && !keyboard.just_pressed(KeyCode::F1) {
+        return;
+    }
+    for mut style in panel.iter_mut() {
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+fn handle_setting_click(
+    buttons: Query<&SettingButton, With<JustClicked>>,
+    mut settings: ResMut<Settings>,
+) {
+    for setting in buttons.iter() {
+        setting.apply(&mut settings);
+    }
+}
+
+fn handle_rebind_click(
+    buttons: Query<&RebindButton, With<JustClicked>>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    for button in buttons.iter() {
+        rebinding.0 = Some(button.0);
+    }
+}
+
+// the next key or mouse button pressed is bound, escape cancels
+fn capture_rebind(
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    let Some(action) = rebinding.0 else {
+        return;
+    };
+    if keyboard.just_pressed(KeyCode::Escape) {
+        rebinding.0 = None;
+        return;
+    }
+    let pressed = keyboard
+        .get_just_pressed()
+        .next()
+        .map(|key| Binding::Key(*key))
+        .or_else(|| {
+            mouse
+                .get_just_pressed()
+                .next()
+                .map(|button| Binding::Mouse(*button))
+        });
+    if let Some(binding) = pressed {
+        bindings.set(action, binding);
+        rebinding.0 = None;
+    }
+}
+
+fn apply_ui_scale(settings: Res<Settings>, mut ui_scale: ResMut<UiScale>) {
+    if !settings.is_changed() {
+        return;
+    }
+    let a = &settings.accessibility;
+    ui_scale.0 = match a.large_text {
+        true => a.ui_scale * LARGE_TEXT_SCALE,
+        false => a.ui_scale,
+    };
+}
+
+// only touch the window when display settings change, so a manual resize sticks.
+// the window starts out with the loaded settings, so there's nothing to apply until they change
+fn apply_display_settings(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut applied: Local<Option<DisplaySettings>>,
+) {
+    let display = &settings.display;
+    let Some(previous) = applied.replace(display.clone()) else {
+        return;
+    };
+    if previous == *display {
+        return;
+    }
+    for mut window in windows.iter_mut() {
+        window.mode = display.window_mode.window_mode();
+        let (width, height) = display.resolution;
+        window.resolution.set(width as f32, height as f32);
+        window.present_mode = display.present_mode();
+    }
+}
+
+// sleep away what's left of the frame budget
+fn limit_frame_rate(settings: Res<Settings>, mut last_frame: Local<Option<Instant>>) {
+    if let (Some(fps), Some(last)) = (settings.display.frame_cap, *last_frame) {
+        let budget = Duration::from_secs_f64(1.0 / fps as f64);
+        if let Some(left) = budget.checked_sub(last.elapsed()) {
+            std::thread::sleep(left);
+        }
+    }
+    *last_frame = Some(Instant::now());
+}
+
+fn update_settings_ui(
+    settings: Res<Settings>,
+    buttons: Query<(&SettingButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (setting, children) in buttons.iter() {
+        let mut iter = texts.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            text.sections[0].value = setting.label(&settings);
+        }
+    }
+}
+
+fn update_rebind_ui(
+    bindings: Res<InputBindings>,
+    rebinding: Res<Rebinding>,
+    buttons: Query<(&RebindButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !bindings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+    for (button, children) in buttons.iter() {
+        let action = button.0;
+        let label = match rebinding.0 == Some(action) {
+            true => format!("{}: press a key...", action.name()),
+            false => format!("{}: {}", action.name(), bindings.get(action).name()),
+        };
+        let mut iter = texts.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+fn save_settings(settings: Res<Settings>) {
+    if settings.is_changed() && !settings.is_added() {
+        persistence::save_config(SETTINGS_FILE, &*settings);
+    }
+}
@@ -1,13 +1,30 @@
-use bevy::prelude::*;
-use serde::Deserialize;
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    reflect::TypePath,
+    window::PrimaryWindow,
+};
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator};
 
 use crate::{
-    health::ApplyHealthEvent,
+    asset_utils::CustomAssetLoaderError,
+    depot::{Depot, DepotStock, DepotStockMut},
+    health::{ApplyHealthEvent, Armor, Shield},
+    input_map::{InputDevice, InputMap},
     inventory::{Inventory, Item},
-    player::PlayerControllerTag,
-    tower::SpawnTowerEvent,
+    map::{ExpandMapEvent, MapBounds},
+    notification::NotificationEvent,
+    pickup::PickupMagnet,
+    placement::{Building, StartPlacementEvent},
+    player::{Body, Controller, PlayerControllerTag, RobotTag, SpawnPlayerEvent},
+    profile::{Profile, Unlock},
+    settings::Settings,
+    shop_stall::{in_shop_range, ShopStall},
+    status_effect::{ApplyStatusEvent, StatusEffect, StatusKind},
+    stump::RegrowStumpsEvent,
     tree::{SpawnTreeEvent, TreeBlueprint},
-    tree_spawner::SpawnTreeSpawnerEvent,
     ui_util::{ButtonColor, JustClicked, UiAssets},
     weapon::WeaponStats,
 };
@@ -16,17 +33,71 @@ pub struct ShopPlugin;
 
 impl Plugin for ShopPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<SpawnShopItemEvent>()
+        app.init_asset::<ShopCatalogueAsset>()
+            .init_asset_loader::<ShopCatalogueAssetLoader>()
+            .add_event::<SpawnShopItemEvent>()
             .add_event::<BuyEvent>()
-            .add_systems(Startup, setup_shop_ui)
+            .init_resource::<ShopSelection>()
+            .init_resource::<ShopTab>()
+            .init_resource::<PendingPurchase>()
+            .add_systems(
+                Startup,
+                (setup_shop_catalogue, setup_shop_ui, setup_shop_tooltip),
+            )
             .add_systems(
                 Update,
-                (spawn_shop_items, handle_shop_item_click, buy_items),
+                (
+                    spawn_shop_items,
+                    handle_shop_item_click,
+                    (
+                        handle_shop_tab_click,
+                        gamepad_shop_navigation,
+                        (show_shop_tab, display_shop_selection),
+                        (buy_with_hotkeys, update_shop_hotkey_hints),
+                    )
+                        .chain(),
+                    scroll_shop,
+                    (handle_purchase_dialog, buy_items, show_purchase_dialog).chain(),
+                    update_shop_item_costs,
+                    gray_out_unaffordable,
+                    show_shop_tooltip,
+                ),
             );
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+/// Tabs of the shop, every item sits under the category of its first effect.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumIter)]
+pub enum ShopCategory {
+    #[default]
+    Buildings,
+    Upgrades,
+    Consumables,
+}
+
+impl ShopCategory {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShopCategory::Buildings => "Buildings",
+            ShopCategory::Upgrades => "Upgrades",
+            ShopCategory::Consumables => "Consumables",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            ShopCategory::Buildings => ShopCategory::Upgrades,
+            ShopCategory::Upgrades => ShopCategory::Consumables,
+            ShopCategory::Consumables => ShopCategory::Buildings,
+        }
+    }
+
+    fn previous(self) -> Self {
+        self.next().next()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ShopItemEffect {
     PlantTree,
     IncreaseDamage(i32),
@@ -34,17 +105,172 @@ pub enum ShopItemEffect {
     Heal(i32),
     BuildTower,
     BuildTreeSpawner,
+    // a short wall segment to funnel robots
+    BuildWall,
+    // heals monkeys and trees around it
+    BuildShrine,
+    // stores items, purchases can draw from it
+    BuildDepot,
+    RecruitCompanion,
+    AddArmor(i32),
+    // raises the max shield
+    EnergyShield(f32),
+    // hits every robot currently on the map
+    InflictOnRobots(StatusEffect),
+    // every stump grows back into a sapling right away
+    WaterStumps,
+    // pickups further away fly to the buyer
+    IncreasePickupRadius(f32),
+    // every pickup on the map flies to the buyer every now and then
+    AutoVacuum,
+    // pushes the walls out by this many meters on every side
+    ExpandMap(f32),
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ShopItemData {
     pub cost: Vec<(Item, u32)>,
     pub effects: Vec<ShopItemEffect>,
     #[serde(default)]
     pub permanent: bool,
+    // permanent items get this much more expensive every time they're bought
+    #[serde(default = "default_price_growth")]
+    pub price_growth: f32,
+    // image shown on the shop button, relative to the assets folder
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+// upgrades costing at least this many items in total ask for confirmation
+const EXPENSIVE_COST: u32 = 5;
+
+fn default_price_growth() -> f32 {
+    1.0
+}
+
+/// A shop item and the wave it shows up in, see shop.shop.ron.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CatalogueItem {
+    // 0 is the first wave
+    #[serde(default)]
+    pub unlock_wave: usize,
+    // only stocked once the profile has earned it
+    #[serde(default)]
+    pub requires: Option<Unlock>,
+    pub item: ShopItemData,
+}
+
+#[derive(Debug, Deserialize, Asset, TypePath)]
+pub struct ShopCatalogueAsset(pub Vec<CatalogueItem>);
+
+impl ShopCatalogueAsset {
+    /// Items to stock once the wave starts.
+    pub fn unlocked_at<'a>(
+        &'a self,
+        wave: usize,
+        profile: &'a Profile,
+    ) -> impl Iterator<Item = &'a ShopItemData> {
+        self.0
+            .iter()
+            .filter(move |c| c.unlock_wave == wave)
+            .filter(|c| c.requires.is_none_or(|u| profile.is_unlocked(u)))
+            .map(|c| &c.item)
+    }
+}
+
+#[derive(Resource)]
+pub struct ShopCatalogue(pub Handle<ShopCatalogueAsset>);
+
+impl ShopItemEffect {
+    pub fn category(&self) -> ShopCategory {
+        match self {
+            ShopItemEffect::PlantTree
+            | ShopItemEffect::BuildTower
+            | ShopItemEffect::BuildTreeSpawner
+            | ShopItemEffect::BuildWall
+            | ShopItemEffect::BuildShrine
+            | ShopItemEffect::BuildDepot
+            | ShopItemEffect::RecruitCompanion
+            | ShopItemEffect::ExpandMap(_) => ShopCategory::Buildings,
+            ShopItemEffect::IncreaseDamage(_)
+            | ShopItemEffect::MultiplyCooldown(_)
+            | ShopItemEffect::AddArmor(_)
+            | ShopItemEffect::EnergyShield(_)
+            | ShopItemEffect::IncreasePickupRadius(_)
+            | ShopItemEffect::AutoVacuum => ShopCategory::Upgrades,
+            ShopItemEffect::Heal(_)
+            | ShopItemEffect::InflictOnRobots(_)
+            | ShopItemEffect::WaterStumps => ShopCategory::Consumables,
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            ShopItemEffect::PlantTree => "icons/plant_tree.png",
+            ShopItemEffect::IncreaseDamage(_) => "icons/increase_damage.png",
+            ShopItemEffect::MultiplyCooldown(_) => "icons/multiply_cooldown.png",
+            ShopItemEffect::Heal(_) => "icons/heal.png",
+            ShopItemEffect::BuildTower => "icons/build_tower.png",
+            ShopItemEffect::BuildTreeSpawner => "icons/build_tree_spawner.png",
+            ShopItemEffect::BuildWall => "icons/build_wall.png",
+            ShopItemEffect::BuildShrine => "icons/build_shrine.png",
+            ShopItemEffect::BuildDepot => "icons/build_depot.png",
+            ShopItemEffect::RecruitCompanion => "icons/recruit_companion.png",
+            ShopItemEffect::AddArmor(_) => "icons/add_armor.png",
+            ShopItemEffect::EnergyShield(_) => "icons/energy_shield.png",
+            ShopItemEffect::InflictOnRobots(_) => "icons/inflict_on_robots.png",
+            ShopItemEffect::WaterStumps => "icons/water_stumps.png",
+            ShopItemEffect::IncreasePickupRadius(_) => "icons/pickup_radius.png",
+            ShopItemEffect::AutoVacuum => "icons/auto_vacuum.png",
+            ShopItemEffect::ExpandMap(_) => "icons/expand_map.png",
+        }
+    }
 }
 
+// shown for an item without any effects in the shop's ron file
+const NO_EFFECT_ICON: &str = "icons/plant_tree.png";
+
 impl ShopItemData {
+    // the category of its first effect
+    pub fn category(&self) -> ShopCategory {
+        self.effects
+            .first()
+            .map_or(ShopCategory::default(), ShopItemEffect::category)
+    }
+
+    // the item's own icon, or the one of its first effect
+    pub fn icon_path(&self) -> String {
+        self.icon.clone().unwrap_or_else(|| {
+            self.effects
+                .first()
+                .map_or(NO_EFFECT_ICON, ShopItemEffect::icon)
+                .to_string()
+        })
+    }
+
+    pub fn is_expensive(&self) -> bool {
+        self.category() == ShopCategory::Upgrades
+            && self.cost.iter().map(|(_, c)| c).sum::<u32>() >= EXPENSIVE_COST
+    }
+
+    pub fn cost_text(&self) -> String {
+        self.cost
+            .iter()
+            .map(|(item, amount)| format!("{amount}x {item}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Multiplies the cost by price_growth, every cost goes up by at least one unless the growth is 1.
+    pub fn raise_price(&mut self) {
+        if self.price_growth == 1.0 {
+            return;
+        }
+        for (_, amount) in self.cost.iter_mut() {
+            *amount = ((*amount as f32 * self.price_growth).ceil() as u32).max(*amount + 1);
+        }
+    }
+
     pub fn name(&self) -> String {
         self.effects
             .iter()
@@ -55,79 +281,331 @@ impl ShopItemData {
                 ShopItemEffect::Heal(h) => format!("Heal (+{h})"),
                 ShopItemEffect::BuildTower => String::from("Build defense tower"),
                 ShopItemEffect::BuildTreeSpawner => String::from("Build tree spawner"),
+                ShopItemEffect::BuildWall => String::from("Build wall"),
+                ShopItemEffect::BuildShrine => String::from("Build healing shrine"),
+                ShopItemEffect::BuildDepot => String::from("Build storage depot"),
+                ShopItemEffect::RecruitCompanion => String::from("Recruit companion"),
+                ShopItemEffect::AddArmor(a) => format!("Armor (+{a})"),
+                ShopItemEffect::EnergyShield(s) => format!("Energy shield (+{s})"),
+                ShopItemEffect::InflictOnRobots(effect) => {
+                    let kind = match effect.kind {
+                        StatusKind::Poison => "Poison",
+                        StatusKind::Burn => "Burn",
+                        StatusKind::Slow => "Slow",
+                        StatusKind::Stun => "Stun",
+                    };
+                    format!("{kind} all robots ({}s)", effect.duration)
+                }
+                ShopItemEffect::WaterStumps => String::from("Water stumps (regrow now)"),
+                ShopItemEffect::IncreasePickupRadius(r) => format!("Pickup radius (+{r})"),
+                ShopItemEffect::AutoVacuum => String::from("Auto-vacuum pickups"),
+                ShopItemEffect::ExpandMap(step) => format!("Expand the map (+{step}m)"),
             })
             .map(|s| format!("> {s}\n"))
             .collect()
     }
 
     pub fn color(&self) -> Color {
-        match self.effects[0] {
+        let Some(effect) = self.effects.first() else {
+            return Color::GRAY.with_a(0.5);
+        };
+        match effect {
             ShopItemEffect::BuildTower => Color::GOLD,
             ShopItemEffect::Heal(_) => Color::RED,
             ShopItemEffect::IncreaseDamage(_) => Color::PURPLE,
             ShopItemEffect::MultiplyCooldown(_) => Color::PURPLE,
             ShopItemEffect::PlantTree => Color::BEIGE,
             ShopItemEffect::BuildTreeSpawner => Color::TEAL,
+            ShopItemEffect::BuildWall => Color::OLIVE,
+            ShopItemEffect::BuildShrine => Color::PINK,
+            ShopItemEffect::BuildDepot => Color::BEIGE,
+            ShopItemEffect::RecruitCompanion => Color::ORANGE,
+            ShopItemEffect::AddArmor(_) => Color::SILVER,
+            ShopItemEffect::EnergyShield(_) => Color::CYAN,
+            ShopItemEffect::InflictOnRobots(_) => Color::LIME_GREEN,
+            ShopItemEffect::WaterStumps => Color::BLUE,
+            ShopItemEffect::IncreasePickupRadius(_) => Color::YELLOW_GREEN,
+            ShopItemEffect::AutoVacuum => Color::VIOLET,
+            ShopItemEffect::ExpandMap(_) => Color::DARK_GREEN,
         }
         .with_a(0.5)
     }
 }
 
+// the list the shop item buttons are children of
 #[derive(Component)]
 struct ShopUiTag;
 
+// clips the shop list, scrolled with the mouse wheel while hovered
+#[derive(Component)]
+pub struct ShopScrollArea;
+
+#[derive(Component)]
+struct ShopTabButton(ShopCategory);
+
+#[derive(Resource, Default)]
+struct ShopTab(ShopCategory);
+
 #[derive(Event)]
 pub struct SpawnShopItemEvent {
     pub item: ShopItemData,
 }
 
-// The data should not be mutated, since then ui would also need to be updated.
+// Changing the data refreshes the cost text, see update_shop_item_costs.
 #[derive(Component)]
-struct ShopItem(ShopItemData);
+pub struct ShopItem(pub ShopItemData);
 
-#[derive(Event)]
+#[derive(Component)]
+struct ShopItemCostText;
+
+// shows which hotkey buys the item
+#[derive(Component)]
+struct ShopItemHotkeyText;
+
+// held together with a number key to buy the item in that slot of the open tab,
+// the number keys alone switch weapons
+const HOTKEY_MODIFIERS: [KeyCode; 2] = [KeyCode::AltLeft, KeyCode::AltRight];
+const HOTKEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+// shop item picked with the gamepad, index into the items of the open tab
+#[derive(Resource, Default)]
+struct ShopSelection(Option<usize>);
+
+// text next to the cursor listing what's missing to buy the hovered item
+#[derive(Component)]
+struct ShopTooltip;
+
+// an expensive purchase waiting for the player to confirm it,
+// buying the same item again also confirms it
+#[derive(Resource, Default)]
+struct PendingPurchase(Option<BuyEvent>);
+
+#[derive(Component)]
+struct PurchaseDialogTag;
+
+// buy or cancel
+#[derive(Component)]
+struct PurchaseDialogButton(bool);
+
+#[derive(Event, Clone, Copy, PartialEq)]
 pub struct BuyEvent {
     pub buyer: Entity,
     pub item: Entity,
 }
 
-fn setup_shop_ui(mut commands: Commands) {
-    commands.spawn((
-        ShopUiTag,
-        NodeBundle {
+fn setup_shop_catalogue(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ShopCatalogue(asset_server.load("shop.shop.ron")));
+}
+
+/// Whether number keys are currently buying shop items instead of switching weapons.
+pub fn shop_hotkeys_held(keyboard: &Input<KeyCode>) -> bool {
+    keyboard.any_pressed(HOTKEY_MODIFIERS)
+}
+
+/// Whether the mouse is over the shop list, so scrolling it doesn't also switch weapons.
+pub fn pointer_over_shop(areas: &Query<&Interaction, With<ShopScrollArea>>) -> bool {
+    areas.iter().any(|i| *i != Interaction::None)
+}
+
+fn setup_shop_ui(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    commands
+        .spawn(NodeBundle {
             style: Style {
-                grid_auto_rows: vec![GridTrack::max_content()],
-                grid_template_columns: vec![GridTrack::max_content()],
-                column_gap: Val::Px(5.0),
-                row_gap: Val::Px(5.0),
                 position_type: PositionType::Absolute,
-                height: Val::Percent(1.0),
-                width: Val::Percent(1.0),
-                right: Val::Percent(0.0),
-                justify_content: JustifyContent::End,
-                justify_items: JustifyItems::End,
+                right: Val::Px(0.0),
                 padding: UiRect::all(Val::Px(10.0)),
-                display: Display::Grid,
+                row_gap: Val::Px(5.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::End,
                 ..default()
             },
             ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(5.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for category in ShopCategory::iter() {
+                        parent
+                            .spawn((
+                                ShopTabButton(category),
+                                ButtonBundle {
+                                    style: Style {
+                                        padding: UiRect::all(Val::Px(5.0)),
+                                        border: UiRect::all(Val::Px(3.0)),
+                                        ..default()
+                                    },
+                                    border_color: Color::BLACK.into(),
+                                    ..default()
+                                },
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    category.name(),
+                                    TextStyle {
+                                        font: ui_assets.font.clone(),
+                                        font_size: 18.0,
+                                        color: Color::BLACK,
+                                    },
+                                ));
+                            });
+                    }
+                });
+            parent
+                .spawn((
+                    ShopScrollArea,
+                    Interaction::default(),
+                    NodeBundle {
+                        style: Style {
+                            max_height: Val::Vh(70.0),
+                            flex_direction: FlexDirection::Column,
+                            overflow: Overflow::clip_y(),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        ShopUiTag,
+                        NodeBundle {
+                            style: Style {
+                                row_gap: Val::Px(5.0),
+                                flex_direction: FlexDirection::Column,
+                                align_items: AlignItems::End,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+fn setup_shop_tooltip(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    commands.spawn((
+        ShopTooltip,
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                padding: UiRect::all(Val::Px(5.0)),
+                display: Display::None,
+                ..default()
+            },
+            background_color: Color::BLACK.with_a(0.8).into(),
+            z_index: ZIndex::Global(10),
+            ..TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: ui_assets.font.clone(),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+            )
         },
     ));
 }
 
+fn handle_shop_tab_click(
+    buttons: Query<&ShopTabButton, With<JustClicked>>,
+    mut tab: ResMut<ShopTab>,
+) {
+    for button in buttons.iter() {
+        tab.0 = button.0;
+    }
+}
+
+// hides the items of the other tabs, the list starts at the top again when switching
+fn show_shop_tab(
+    tab: Res<ShopTab>,
+    mut items: Query<(&ShopItem, &mut Style), Without<ShopUiTag>>,
+    added: Query<(), Added<ShopItem>>,
+    mut tab_buttons: Query<(&ShopTabButton, &mut BorderColor)>,
+    mut list: Query<&mut Style, With<ShopUiTag>>,
+) {
+    if !tab.is_changed() && added.is_empty() {
+        return;
+    }
+    for (item, mut style) in items.iter_mut() {
+        style.display = match item.0.category() == tab.0 {
+            true => Display::Flex,
+            false => Display::None,
+        };
+    }
+    for (button, mut border) in tab_buttons.iter_mut() {
+        border.0 = match button.0 == tab.0 {
+            true => Color::YELLOW,
+            false => Color::BLACK,
+        };
+    }
+    if tab.is_changed() {
+        for mut style in list.iter_mut() {
+            style.top = Val::Px(0.0);
+        }
+    }
+}
+
+fn scroll_shop(
+    mut mouse_wheel: EventReader<MouseWheel>,
+    areas: Query<(&Interaction, &Node), With<ShopScrollArea>>,
+    mut list: Query<(&mut Style, &Node), With<ShopUiTag>>,
+) {
+    const LINE_HEIGHT: f32 = 30.0;
+    let scroll: f32 = mouse_wheel
+        .read()
+        .map(|e| match e.unit {
+            MouseScrollUnit::Line => e.y * LINE_HEIGHT,
+            MouseScrollUnit::Pixel => e.y,
+        })
+        .sum();
+    let (Ok((interaction, area)), Ok((mut style, list))) =
+        (areas.get_single(), list.get_single_mut())
+    else {
+        return;
+    };
+    if scroll == 0.0 || *interaction == Interaction::None {
+        return;
+    }
+    let max_scroll = (list.size().y - area.size().y).max(0.0);
+    let top = match style.top {
+        Val::Px(top) => top,
+        _ => 0.0,
+    };
+    style.top = Val::Px((top + scroll).clamp(-max_scroll, 0.0));
+}
+
 fn spawn_shop_items(
     mut commands: Commands,
     mut shop_items: EventReader<SpawnShopItemEvent>,
     shop_node: Query<Entity, With<ShopUiTag>>,
     ui_assets: Res<UiAssets>,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
 ) {
     let shop_node = shop_node.single();
 
     for ev in shop_items.read() {
+        let color = settings.accessibility.palette.remap(ev.item.color());
         commands
             .spawn((
                 ShopItem(ev.item.clone()),
-                ButtonColor(ev.item.color()),
+                ButtonColor(color),
                 ButtonBundle {
                     style: Style {
                         min_width: Val::Px(50.0),
@@ -139,14 +617,42 @@ fn spawn_shop_items(
                         padding: UiRect::all(Val::Px(3.0)),
                         ..default()
                     },
-                    background_color: BackgroundColor(ev.item.color()),
+                    background_color: BackgroundColor(color),
                     border_color: Color::BLACK.into(),
                     ..default()
                 },
             ))
             .with_children(|parent| {
+                parent.spawn((
+                    ShopItemHotkeyText,
+                    TextBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            left: Val::Px(2.0),
+                            top: Val::Px(0.0),
+                            ..default()
+                        },
+                        ..TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: ui_assets.font.clone(),
+                                font_size: 12.0,
+                                color: Color::BLACK,
+                            },
+                        )
+                    },
+                ));
+                parent.spawn(ImageBundle {
+                    style: Style {
+                        width: Val::Px(32.0),
+                        height: Val::Px(32.0),
+                        ..default()
+                    },
+                    image: asset_server.load(ev.item.icon_path()).into(),
+                    ..default()
+                });
                 parent.spawn(TextBundle::from_section(
-                    &ev.item.name(),
+                    ev.item.name(),
                     TextStyle {
                         font: ui_assets.font.clone(),
                         font_size: 21.0,
@@ -154,23 +660,44 @@ fn spawn_shop_items(
                     },
                 ));
 
-                parent.spawn(TextBundle::from_sections(ev.item.cost.iter().map(
-                    |(item, amount)| {
-                        TextSection::new(
-                            format!("{amount}x {item}"),
-                            TextStyle {
-                                font: ui_assets.font.clone(),
-                                font_size: 14.0,
-                                color: Color::BLACK,
-                            },
-                        )
-                    },
-                )));
+                parent.spawn((
+                    ShopItemCostText,
+                    TextBundle::from_sections(cost_sections(&ev.item, &ui_assets)),
+                ));
             })
             .set_parent(shop_node);
     }
 }
 
+fn cost_sections(item: &ShopItemData, ui_assets: &UiAssets) -> Vec<TextSection> {
+    item.cost
+        .iter()
+        .map(|(item, amount)| {
+            TextSection::new(
+                format!("{amount}x {item}"),
+                TextStyle {
+                    font: ui_assets.font.clone(),
+                    font_size: 14.0,
+                    color: Color::BLACK,
+                },
+            )
+        })
+        .collect()
+}
+
+fn update_shop_item_costs(
+    shop_items: Query<(&ShopItem, &Children), Changed<ShopItem>>,
+    mut cost_texts: Query<&mut Text, With<ShopItemCostText>>,
+    ui_assets: Res<UiAssets>,
+) {
+    for (shop_item, children) in shop_items.iter() {
+        let mut texts = cost_texts.iter_many_mut(children);
+        while let Some(mut text) = texts.fetch_next() {
+            text.sections = cost_sections(&shop_item.0, &ui_assets);
+        }
+    }
+}
+
 fn handle_shop_item_click(
     mut buy_event: EventWriter<BuyEvent>,
     shop_buttons: Query<Entity, (With<ShopItem>, With<JustClicked>)>,
@@ -186,71 +713,548 @@ fn handle_shop_item_click(
     }));
 }
 
+// shop items in the open tab, in the order they're listed
+fn tab_items(children: &Children, shop_items: &Query<&ShopItem>, tab: ShopCategory) -> Vec<Entity> {
+    children
+        .iter()
+        .copied()
+        .filter(|e| shop_items.get(*e).is_ok_and(|i| i.0.category() == tab))
+        .collect()
+}
+
+// stepping past either end of the list moves on to the next tab
+fn gamepad_shop_navigation(
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    input_map: Res<InputMap>,
+    mut selection: ResMut<ShopSelection>,
+    mut tab: ResMut<ShopTab>,
+    shop_node: Query<&Children, With<ShopUiTag>>,
+    shop_items: Query<&ShopItem>,
+    player: Query<Entity, With<PlayerControllerTag>>,
+    mut buy_event: EventWriter<BuyEvent>,
+) {
+    if input_map.device != InputDevice::Gamepad {
+        selection.0 = None;
+        return;
+    }
+    let Ok(children) = shop_node.get_single() else {
+        return;
+    };
+    let items = tab_items(children, &shop_items, tab.0);
+    let count = items.len();
+    let just_pressed = |button| input_map.gamepad_just_pressed(&gamepad_buttons, button);
+    if just_pressed(input_map.gamepad_next) {
+        match selection.0 {
+            Some(i) if i + 1 < count => selection.0 = Some(i + 1),
+            None if count > 0 => selection.0 = Some(0),
+            _ => {
+                tab.0 = tab.0.next();
+                selection.0 = Some(0);
+                return;
+            }
+        }
+    }
+    if just_pressed(input_map.gamepad_previous) {
+        match selection.0 {
+            Some(i) if i > 0 && count > 0 => selection.0 = Some((i - 1).min(count - 1)),
+            _ => {
+                tab.0 = tab.0.previous();
+                selection.0 = Some(usize::MAX);
+                return;
+            }
+        }
+    }
+    if count == 0 {
+        return;
+    }
+    // items might have been bought or removed since
+    let Some(index) = selection.0.map(|i| i.min(count - 1)) else {
+        return;
+    };
+    selection.0 = Some(index);
+    if just_pressed(input_map.gamepad_confirm) {
+        if let Ok(player) = player.get_single() {
+            buy_event.send(BuyEvent {
+                buyer: player,
+                item: items[index],
+            });
+        }
+    }
+}
+
+// the button keeps its color while hovered, update_button_color takes over from there
+fn gray_out_unaffordable(
+    player: Query<Ref<Inventory>, With<PlayerControllerTag>>,
+    depot_stock: DepotStock,
+    mut shop_items: Query<(
+        Ref<ShopItem>,
+        &Interaction,
+        &mut ButtonColor,
+        &mut BackgroundColor,
+        &Children,
+    )>,
+    mut texts: Query<&mut Text>,
+    mut images: Query<&mut BackgroundColor, (With<UiImage>, Without<ShopItem>)>,
+    settings: Res<Settings>,
+) {
+    let Ok(own) = player.get_single() else {
+        return;
+    };
+    // combining the depots clones the inventory, skip it on frames where nothing changed
+    let stock_changed = own.is_changed() || depot_stock.is_changed() || settings.is_changed();
+    if !stock_changed && !shop_items.iter().any(|(item, ..)| item.is_changed()) {
+        return;
+    }
+    let inventory = depot_stock.combined(&own);
+    for (shop_item, interaction, mut button_color, mut background, children) in
+        shop_items.iter_mut()
+    {
+        if !stock_changed && !shop_item.is_changed() {
+            continue;
+        }
+        let affordable = inventory.has_items(shop_item.0.cost.iter().copied());
+        let color = settings.accessibility.palette.remap(shop_item.0.color());
+        let (color, content_color) = match affordable {
+            true => (color, Color::BLACK),
+            false => (Color::GRAY.with_a(0.5), Color::DARK_GRAY),
+        };
+        button_color.0 = color;
+        if *interaction == Interaction::None {
+            background.0 = color;
+        }
+        let mut iter = texts.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            for section in text.sections.iter_mut() {
+                section.style.color = content_color;
+            }
+        }
+        let mut iter = images.iter_many_mut(children);
+        while let Some(mut image) = iter.fetch_next() {
+            image.0 = match affordable {
+                true => Color::WHITE,
+                false => Color::GRAY,
+            };
+        }
+    }
+}
+
+fn show_shop_tooltip(
+    player: Query<&Inventory, With<PlayerControllerTag>>,
+    depot_stock: DepotStock,
+    shop_items: Query<(&ShopItem, &Interaction)>,
+    mut tooltip: Query<(&mut Text, &mut Style), With<ShopTooltip>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let Ok((mut text, mut style)) = tooltip.get_single_mut() else {
+        return;
+    };
+    let hovered = shop_items
+        .iter()
+        .find(|(_, i)| **i != Interaction::None)
+        .map(|(item, _)| item);
+    let cursor = window
+        .get_single()
+        .ok()
+        .and_then(|w| w.cursor_position().map(|cursor| (cursor, w.width())));
+    let (Some(hovered), Ok(inventory), Some((cursor, width))) =
+        (hovered, player.get_single(), cursor)
+    else {
+        style.display = Display::None;
+        return;
+    };
+    let inventory = depot_stock.combined(inventory);
+    let missing: Vec<_> = hovered
+        .0
+        .cost
+        .iter()
+        .filter_map(|(item, amount)| {
+            let missing = amount.saturating_sub(inventory.get_item_count(*item));
+            (missing > 0).then(|| format!("{missing}x {item}"))
+        })
+        .collect();
+    if missing.is_empty() {
+        style.display = Display::None;
+        return;
+    }
+    text.sections[0].value = format!("Missing: {}", missing.join(", "));
+    // the shop is on the right, so the tooltip goes left of the cursor
+    style.display = Display::Flex;
+    style.right = Val::Px(width - cursor.x + 10.0);
+    style.top = Val::Px(cursor.y);
+}
+
+fn show_purchase_dialog(
+    mut commands: Commands,
+    pending: Res<PendingPurchase>,
+    dialogs: Query<Entity, With<PurchaseDialogTag>>,
+    shop_items: Query<&ShopItem>,
+    ui_assets: Res<UiAssets>,
+) {
+    if !pending.is_changed() {
+        return;
+    }
+    for entity in dialogs.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(item) = pending.0.and_then(|p| shop_items.get(p.item).ok()) else {
+        return;
+    };
+    let text_style = TextStyle {
+        font: ui_assets.font.clone(),
+        font_size: 21.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn((
+            PurchaseDialogTag,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        padding: UiRect::all(Val::Px(20.0)),
+                        row_gap: Val::Px(10.0),
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: Color::BLACK.with_a(0.8).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        format!("Buy for {}?\n{}", item.0.cost_text(), item.0.name()),
+                        text_style.clone(),
+                    ));
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                column_gap: Val::Px(10.0),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for (confirm, label) in [(true, "Buy"), (false, "Cancel")] {
+                                parent
+                                    .spawn((
+                                        PurchaseDialogButton(confirm),
+                                        ButtonBundle {
+                                            style: Style {
+                                                padding: UiRect::all(Val::Px(5.0)),
+                                                ..default()
+                                            },
+                                            ..default()
+                                        },
+                                    ))
+                                    .with_children(|parent| {
+                                        parent.spawn(TextBundle::from_section(
+                                            label,
+                                            TextStyle {
+                                                color: Color::BLACK,
+                                                ..text_style.clone()
+                                            },
+                                        ));
+                                    });
+                            }
+                        });
+                });
+        });
+}
+
+fn handle_purchase_dialog(
+    buttons: Query<&PurchaseDialogButton, With<JustClicked>>,
+    mut pending: ResMut<PendingPurchase>,
+    mut buy_event: EventWriter<BuyEvent>,
+) {
+    for button in buttons.iter() {
+        match (button.0, pending.0) {
+            (true, Some(purchase)) => buy_event.send(purchase),
+            _ => pending.0 = None,
+        }
+    }
+}
+
+fn buy_with_hotkeys(
+    keyboard: Res<Input<KeyCode>>,
+    tab: Res<ShopTab>,
+    shop_node: Query<&Children, With<ShopUiTag>>,
+    shop_items: Query<&ShopItem>,
+    player: Query<Entity, With<PlayerControllerTag>>,
+    mut buy_event: EventWriter<BuyEvent>,
+) {
+    if !shop_hotkeys_held(&keyboard) {
+        return;
+    }
+    let Some(slot) = HOTKEYS.iter().position(|k| keyboard.just_pressed(*k)) else {
+        return;
+    };
+    let (Ok(children), Ok(player)) = (shop_node.get_single(), player.get_single()) else {
+        return;
+    };
+    if let Some(item) = tab_items(children, &shop_items, tab.0).get(slot) {
+        buy_event.send(BuyEvent {
+            buyer: player,
+            item: *item,
+        });
+    }
+}
+
+// numbers the items of the open tab, they shift when an item is bought
+fn update_shop_hotkey_hints(
+    tab: Res<ShopTab>,
+    shop_node: Query<&Children, With<ShopUiTag>>,
+    shop_items: Query<&ShopItem>,
+    item_children: Query<&Children, With<ShopItem>>,
+    mut hint_texts: Query<&mut Text, With<ShopItemHotkeyText>>,
+) {
+    let Ok(children) = shop_node.get_single() else {
+        return;
+    };
+    for (i, item) in tab_items(children, &shop_items, tab.0).iter().enumerate() {
+        let Ok(item_children) = item_children.get(*item) else {
+            continue;
+        };
+        let hint = match i < HOTKEYS.len() {
+            true => format!("Alt+{}", i + 1),
+            false => String::new(),
+        };
+        let mut texts = hint_texts.iter_many_mut(item_children);
+        while let Some(mut text) = texts.fetch_next() {
+            if text.sections[0].value != hint {
+                text.sections[0].value = hint.clone();
+            }
+        }
+    }
+}
+
+fn display_shop_selection(
+    selection: Res<ShopSelection>,
+    tab: Res<ShopTab>,
+    shop_node: Query<&Children, With<ShopUiTag>>,
+    shop_items: Query<&ShopItem>,
+    mut borders: Query<&mut BorderColor, With<ShopItem>>,
+) {
+    let Ok(children) = shop_node.get_single() else {
+        return;
+    };
+    let items = tab_items(children, &shop_items, tab.0);
+    let selected = selection.0.map(|i| i.min(items.len().saturating_sub(1)));
+    for (i, item) in items.iter().enumerate() {
+        let Ok(mut border) = borders.get_mut(*item) else {
+            continue;
+        };
+        let color = match selected == Some(i) {
+            true => Color::WHITE,
+            false => Color::BLACK,
+        };
+        if border.0 != color {
+            border.0 = color;
+        }
+    }
+}
+
 fn buy_items(
     mut commands: Commands,
     mut buy_event: EventReader<BuyEvent>,
-    shop_item: Query<&ShopItem>,
+    mut shop_item: Query<&mut ShopItem>,
     mut spawn_tree_event: EventWriter<SpawnTreeEvent>,
-    mut spawn_tower_event: EventWriter<SpawnTowerEvent>,
+    mut start_placement_event: EventWriter<StartPlacementEvent>,
     mut weapon: Query<&mut WeaponStats>,
-    mut inventory: Query<&mut Inventory>,
+    mut armor: Query<&mut Armor>,
+    mut shield: Query<&mut Shield>,
+    mut inventory: Query<&mut Inventory, Without<Depot>>,
     mut apply_health_event: EventWriter<ApplyHealthEvent>,
-    mut tree_spawner: EventWriter<SpawnTreeSpawnerEvent>,
     transform: Query<&GlobalTransform>,
+    mut spawn_player_event: EventWriter<SpawnPlayerEvent>,
+    asset_server: Res<AssetServer>,
+    robots: Query<Entity, With<RobotTag>>,
+    mut apply_status_event: EventWriter<ApplyStatusEvent>,
+    (
+        settings,
+        mut pending,
+        mut regrow_stumps_event,
+        mut magnets,
+        mut depot_stock,
+        mut expand_map_event,
+        bounds,
+        mut notification_event,
+        stalls,
+    ): (
+        Res<Settings>,
+        ResMut<PendingPurchase>,
+        EventWriter<RegrowStumpsEvent>,
+        Query<&mut PickupMagnet>,
+        DepotStockMut,
+        EventWriter<ExpandMapEvent>,
+        Res<MapBounds>,
+        EventWriter<NotificationEvent>,
+        Query<&GlobalTransform, With<ShopStall>>,
+    ),
 ) {
-    let mut apply_effect = |effect: &ShopItemEffect, buyer: Entity| match effect {
-        ShopItemEffect::PlantTree => {
-            if let Ok(transform) = transform.get(buyer) {
-                let mut pos = transform.translation();
-                pos.y = 0.0;
-                spawn_tree_event.send(SpawnTreeEvent {
-                    pos,
-                    blueprint: TreeBlueprint::Randomized,
-                    play_sound: true,
-                });
+    let mut apply_effect =
+        |effect: &ShopItemEffect, item: &ShopItemData, buyer: Entity| match effect {
+            ShopItemEffect::PlantTree => {
+                if let Ok(transform) = transform.get(buyer) {
+                    let mut pos = transform.translation();
+                    pos.y = 0.0;
+                    spawn_tree_event.send(SpawnTreeEvent {
+                        pos,
+                        blueprint: TreeBlueprint::Randomized,
+                        play_sound: true,
+                        sapling: true,
+                    });
+                }
             }
-        }
-        ShopItemEffect::IncreaseDamage(amount) => {
-            if let Ok(mut weapon) = weapon.get_mut(buyer) {
-                weapon.damage_add += amount;
+            ShopItemEffect::IncreaseDamage(amount) => {
+                if let Ok(mut weapon) = weapon.get_mut(buyer) {
+                    weapon.damage_add += amount;
+                }
             }
-        }
-        ShopItemEffect::MultiplyCooldown(amount) => {
-            if let Ok(mut weapon) = weapon.get_mut(buyer) {
-                weapon.cooldown_mul *= amount;
+            ShopItemEffect::MultiplyCooldown(amount) => {
+                if let Ok(mut weapon) = weapon.get_mut(buyer) {
+                    weapon.cooldown_mul *= amount;
+                }
             }
-        }
-        ShopItemEffect::Heal(amount) => apply_health_event.send(ApplyHealthEvent {
-            amount: *amount,
-            target_entity: buyer,
-            caster_entity: buyer,
-        }),
-        ShopItemEffect::BuildTower => {
-            if let Ok(transform) = transform.get(buyer) {
-                let mut pos = transform.translation();
-                pos.y = 0.0;
-                spawn_tower_event.send(SpawnTowerEvent { pos });
+            ShopItemEffect::Heal(amount) => apply_health_event.send(ApplyHealthEvent {
+                amount: *amount,
+                target_entity: buyer,
+                caster_entity: buyer,
+                is_crit: false,
+                is_finisher: false,
+            }),
+            ShopItemEffect::BuildTower => start_placement_event.send(StartPlacementEvent {
+                building: Building::Tower,
+                buyer,
+                item: item.clone(),
+            }),
+            ShopItemEffect::BuildTreeSpawner => start_placement_event.send(StartPlacementEvent {
+                building: Building::TreeSpawner,
+                buyer,
+                item: item.clone(),
+            }),
+            ShopItemEffect::BuildWall => start_placement_event.send(StartPlacementEvent {
+                building: Building::Wall,
+                buyer,
+                item: item.clone(),
+            }),
+            ShopItemEffect::BuildShrine => start_placement_event.send(StartPlacementEvent {
+                building: Building::Shrine,
+                buyer,
+                item: item.clone(),
+            }),
+            ShopItemEffect::BuildDepot => start_placement_event.send(StartPlacementEvent {
+                building: Building::Depot,
+                buyer,
+                item: item.clone(),
+            }),
+            ShopItemEffect::AddArmor(amount) => {
+                if let Ok(mut armor) = armor.get_mut(buyer) {
+                    armor.0 += amount;
+                }
             }
-        }
-        ShopItemEffect::BuildTreeSpawner => {
-            if let Ok(transform) = transform.get(buyer) {
-                let mut pos = transform.translation();
-                pos.y = 0.0;
-                tree_spawner.send(SpawnTreeSpawnerEvent { pos });
+            ShopItemEffect::EnergyShield(amount) => {
+                if let Ok(mut shield) = shield.get_mut(buyer) {
+                    shield.max += amount;
+                    shield.current += amount;
+                }
             }
-        }
-    };
+            ShopItemEffect::RecruitCompanion => {
+                if let Ok(transform) = transform.get(buyer) {
+                    spawn_player_event.send(SpawnPlayerEvent {
+                        pos: transform.translation() + Vec3::X * 2.0,
+                        controller: Controller::Companion,
+                        body: Body::Monkey,
+                        weapon: asset_server.load("weapons/tower.weapon.ron"),
+                        scaling: default(),
+                    });
+                }
+            }
+            ShopItemEffect::InflictOnRobots(effect) => {
+                apply_status_event.send_batch(robots.iter().map(|robot| ApplyStatusEvent {
+                    effect: *effect,
+                    target_entity: robot,
+                    caster_entity: buyer,
+                }))
+            }
+            ShopItemEffect::WaterStumps => regrow_stumps_event.send(RegrowStumpsEvent),
+            ShopItemEffect::IncreasePickupRadius(amount) => {
+                for mut magnet in magnets.iter_mut().filter(|m| m.root_entity == buyer) {
+                    magnet.radius += amount;
+                }
+            }
+            ShopItemEffect::AutoVacuum => {
+                for mut magnet in magnets.iter_mut().filter(|m| m.root_entity == buyer) {
+                    magnet.enable_vacuum();
+                }
+            }
+            ShopItemEffect::ExpandMap(step) => {
+                expand_map_event.send(ExpandMapEvent { step: *step });
+            }
+        };
 
     for event in buy_event.read() {
-        if let (Some(e), Ok(shop_item)) =
-            (commands.get_entity(event.item), shop_item.get(event.item))
-        {
-            if inventory
-                .get_mut(event.buyer)
-                .map_or(false, |mut inventory| {
-                    inventory.spend_items(shop_item.0.cost.iter().copied())
-                })
+        let confirmed = pending.0 == Some(*event);
+        if confirmed {
+            pending.0 = None;
+        }
+        if let (Some(e), Ok(mut shop_item)) = (
+            commands.get_entity(event.item),
+            shop_item.get_mut(event.item),
+        ) {
+            let near_stall = transform
+                .get(event.buyer)
+                .is_ok_and(|t| in_shop_range(t.translation(), &stalls));
+            if !near_stall {
+                notification_event.send(NotificationEvent {
+                    text: "Walk over to the shop to buy".into(),
+                    show_for: 2.0,
+                    color: Color::RED,
+                });
+                continue;
+            }
+            // turned away before paying, not charged for land that isn't there
+            let expands_map = shop_item
+                .0
+                .effects
+                .iter()
+                .any(|e| matches!(e, ShopItemEffect::ExpandMap(_)));
+            if expands_map && !bounds.can_expand() {
+                notification_event.send(NotificationEvent {
+                    text: "The map can't grow any further".into(),
+                    show_for: 2.0,
+                    color: Color::RED,
+                });
+                continue;
+            }
+            let affordable = inventory.get(event.buyer).is_ok_and(|inventory| {
+                depot_stock
+                    .combined(inventory)
+                    .has_items(shop_item.0.cost.iter().copied())
+            });
+            if affordable
+                && !confirmed
+                && settings.general.confirm_purchases
+                && shop_item.0.is_expensive()
             {
+                pending.0 = Some(*event);
+                continue;
+            }
+            if inventory.get_mut(event.buyer).is_ok_and(|mut inventory| {
+                depot_stock.spend_items(&mut inventory, shop_item.0.cost.iter().copied())
+            }) {
                 if !shop_item.0.permanent {
                     e.despawn_recursive();
                 }
@@ -259,8 +1263,38 @@ fn buy_items(
                     .0
                     .effects
                     .iter()
-                    .for_each(|e| apply_effect(e, event.buyer));
+                    .for_each(|e| apply_effect(e, &shop_item.0, event.buyer));
+                if shop_item.0.permanent {
+                    shop_item.0.raise_price();
+                }
             }
         }
     }
 }
+
+#[derive(Default)]
+pub struct ShopCatalogueAssetLoader;
+
+impl AssetLoader for ShopCatalogueAssetLoader {
+    type Asset = ShopCatalogueAsset;
+    type Settings = ();
+    type Error = CustomAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let asset = ron::de::from_bytes::<ShopCatalogueAsset>(&bytes)?;
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["shop.ron"]
+    }
+}
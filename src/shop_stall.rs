@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::{Collider, CollisionGroups, Group, RigidBody};
+use rand::seq::SliceRandom;
+
+use crate::{
+    collision_groups::{COLLISION_CHARACTER, COLLISION_WORLD},
+    map::CurrentMap,
+    pathfinding::NavObstacle,
+    state::{AppState, Wave},
+};
+
+/// The shop can only be bought from this close to the stall.
+pub const SHOP_RANGE: f32 = 4.0;
+const STALL_SIZE: Vec3 = Vec3::new(1.6, 1.2, 1.0);
+
+pub struct ShopStallPlugin;
+
+impl Plugin for ShopStallPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_stall_assets)
+            .add_systems(Update, place_shop_stall.run_if(in_state(AppState::Playing)));
+    }
+}
+
+/// Where the shop is, it moves to another of the map's shop spawns every wave.
+#[derive(Component)]
+pub struct ShopStall;
+
+#[derive(Resource)]
+struct StallAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+/// Whether a buyer standing at `pos` is close enough to a stall to shop.
+pub fn in_shop_range(pos: Vec3, stalls: &Query<&GlobalTransform, With<ShopStall>>) -> bool {
+    stalls
+        .iter()
+        .any(|stall| stall.translation().xz().distance(pos.xz()) <= SHOP_RANGE)
+}
+
+fn setup_stall_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(StallAssets {
+        mesh: meshes.add(Mesh::from(shape::Box::new(
+            STALL_SIZE.x,
+            STALL_SIZE.y,
+            STALL_SIZE.z,
+        ))),
+        material: materials.add(StandardMaterial {
+            base_color: Color::ORANGE,
+            ..default()
+        }),
+    });
+}
+
+// when a run starts or a new wave begins, maps without shop spawns keep it in the middle
+fn place_shop_stall(
+    mut commands: Commands,
+    wave: Res<Wave>,
+    map: Res<CurrentMap>,
+    stall_assets: Res<StallAssets>,
+    stalls: Query<(Entity, &Transform), With<ShopStall>>,
+) {
+    if !wave.is_changed() && !stalls.is_empty() {
+        return;
+    }
+    // never the spot it's already on, unless there's no other
+    let current = stalls.iter().next().map(|(_, t)| t.translation.xz());
+    let spawns: Vec<Vec2> = map
+        .0
+        .shop_spawns
+        .iter()
+        .copied()
+        .filter(|pos| Some(*pos) != current)
+        .collect();
+    let pos = spawns
+        .choose(&mut rand::thread_rng())
+        .copied()
+        .or(current)
+        .unwrap_or(Vec2::ZERO);
+    for (entity, _) in stalls.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.spawn((
+        Name::new("Shop stall"),
+        ShopStall,
+        NavObstacle {
+            radius: STALL_SIZE.x * 0.5,
+        },
+        PbrBundle {
+            mesh: stall_assets.mesh.clone(),
+            material: stall_assets.material.clone(),
+            transform: Transform::from_xyz(pos.x, STALL_SIZE.y * 0.5, pos.y),
+            ..default()
+        },
+        RigidBody::Fixed,
+        Collider::cuboid(STALL_SIZE.x * 0.5, STALL_SIZE.y * 0.5, STALL_SIZE.z * 0.5),
+        // EXPLANATION: see docs/physics.txt
+        CollisionGroups::new(
+            Group::from_bits(COLLISION_WORLD).unwrap(),
+            Group::from_bits(COLLISION_CHARACTER | COLLISION_WORLD).unwrap(),
+        ),
+    ));
+}
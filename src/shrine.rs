@@ -0,0 +1,287 @@
+use std::f32::consts::TAU;
+
+use bevy::{math::vec3, prelude::*};
+use bevy_rapier3d::prelude::{Collider, CollisionGroups, Group, RigidBody};
+use bevy_vector_shapes::{painter::ShapePainter, shapes::DiscPainter};
+
+use crate::{
+    collision_groups::{COLLISION_CHARACTER, COLLISION_POINTER, COLLISION_WORLD},
+    demolish::BuildCost,
+    health::{ApplyHealthEvent, Health, ShowHealthBar},
+    inventory::{Inventory, Item},
+    knockback::KnockbackResistance,
+    mixer::PlaySfxEvent,
+    pathfinding::NavObstacle,
+    player::{MonkeyTag, PlayerControllerTag},
+    sapper::StructureTag,
+    selection::{Selection, SelectionActionsTag},
+    settings::Settings,
+    tree::TreeTrunkTag,
+    ui_util::{JustClicked, UiAssets},
+};
+
+pub const SHRINE_HEIGHT: f32 = 2.0;
+pub const SHRINE_RADIUS: f32 = 0.5;
+const SHRINE_HEALTH: i32 = 10;
+// every monkey and tree in range is healed this much every SHRINE_INTERVAL seconds
+const SHRINE_HEAL: i32 = 2;
+const SHRINE_INTERVAL: f32 = 3.0;
+const SHRINE_RANGE: f32 = 6.0;
+// each upgrade widens the aura this much, up to MAX_SHRINE_LEVEL times
+const SHRINE_RANGE_PER_LEVEL: f32 = 3.0;
+const MAX_SHRINE_LEVEL: u32 = 2;
+const SHRINE_UPGRADE_COST: [(Item, u32); 2] = [(Item::Log, 3), (Item::Apple, 2)];
+
+pub struct ShrinePlugin;
+
+impl Plugin for ShrinePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpawnShrineEvent>()
+            .add_systems(Startup, setup_shrine_assets)
+            // the selection panel it goes in is spawned on startup
+            .add_systems(PostStartup, setup_upgrade_button)
+            .add_systems(
+                Update,
+                (
+                    spawn_shrines,
+                    heal_around_shrines,
+                    visualize_range,
+                    (upgrade_clicked, update_upgrade_button).chain(),
+                ),
+            );
+    }
+}
+
+/// Heals monkeys and trees around it.
+#[derive(Component)]
+pub struct Shrine {
+    timer: Timer,
+    level: u32,
+}
+
+impl Shrine {
+    pub fn range(&self) -> f32 {
+        SHRINE_RANGE + SHRINE_RANGE_PER_LEVEL * self.level as f32
+    }
+}
+
+#[derive(Resource)]
+pub struct ShrineAssets {
+    pub mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+#[derive(Event)]
+pub struct SpawnShrineEvent {
+    pub pos: Vec3,
+    // refunded in part when it's demolished
+    pub cost: Vec<(Item, u32)>,
+}
+
+#[derive(Component)]
+struct ShrineUpgradeButton;
+
+fn setup_shrine_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(ShrineAssets {
+        mesh: meshes.add(Mesh::from(shape::Cylinder {
+            radius: SHRINE_RADIUS,
+            height: SHRINE_HEIGHT,
+            resolution: 12,
+            segments: 1,
+        })),
+        material: materials.add(StandardMaterial {
+            base_color: Color::PINK,
+            emissive: Color::PINK * 0.5,
+            ..default()
+        }),
+    });
+}
+
+fn spawn_shrines(
+    mut commands: Commands,
+    mut events: EventReader<SpawnShrineEvent>,
+    shrine_assets: Res<ShrineAssets>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
+) {
+    for ev in events.read() {
+        sfx_events.send(PlaySfxEvent::new("build").at(ev.pos));
+        commands
+            .spawn((
+                Name::new("Shrine"),
+                Shrine {
+                    timer: Timer::from_seconds(SHRINE_INTERVAL, TimerMode::Repeating),
+                    level: 0,
+                },
+                StructureTag,
+                BuildCost(ev.cost.clone()),
+                Health::new(SHRINE_HEALTH),
+                ShowHealthBar,
+                KnockbackResistance(1.0),
+                NavObstacle {
+                    radius: SHRINE_RADIUS,
+                },
+                PbrBundle {
+                    mesh: shrine_assets.mesh.clone(),
+                    material: shrine_assets.material.clone(),
+                    transform: Transform::from_translation(ev.pos + Vec3::Y * SHRINE_HEIGHT / 2.0),
+                    ..default()
+                },
+                RigidBody::Fixed,
+                Collider::cylinder(SHRINE_HEIGHT / 2.0, SHRINE_RADIUS),
+                // EXPLANATION: see docs/physics.txt
+                CollisionGroups::new(
+                    Group::from_bits(COLLISION_WORLD).unwrap(),
+                    Group::from_bits(COLLISION_CHARACTER | COLLISION_WORLD).unwrap(),
+                ),
+            ))
+            .with_children(|parent| {
+                // lets the shrine be clicked to select it
+                parent.spawn((
+                    TransformBundle::default(),
+                    Collider::cylinder(SHRINE_HEIGHT / 2.0, SHRINE_RADIUS),
+                    CollisionGroups::new(
+                        Group::from_bits(COLLISION_POINTER).unwrap(),
+                        Group::from_bits(COLLISION_POINTER).unwrap(),
+                    ),
+                ));
+            });
+    }
+}
+
+fn heal_around_shrines(
+    mut shrines: Query<(Entity, &mut Shrine, &GlobalTransform)>,
+    targets: Query<(Entity, &GlobalTransform, &Health), Or<(With<MonkeyTag>, With<TreeTrunkTag>)>>,
+    mut apply_health_event: EventWriter<ApplyHealthEvent>,
+    time: Res<Time>,
+) {
+    for (shrine_entity, mut shrine, shrine_transform) in shrines.iter_mut() {
+        if !shrine.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        let range = shrine.range();
+        let center = shrine_transform.translation() * Vec3::new(1.0, 0.0, 1.0);
+        for (entity, transform, health) in targets.iter() {
+            let pos = transform.translation() * Vec3::new(1.0, 0.0, 1.0);
+            if health.current >= health.max || pos.distance_squared(center) > range.powi(2) {
+                continue;
+            }
+            apply_health_event.send(ApplyHealthEvent {
+                amount: SHRINE_HEAL,
+                target_entity: entity,
+                caster_entity: shrine_entity,
+                is_crit: false,
+                is_finisher: false,
+            });
+        }
+    }
+}
+
+fn visualize_range(
+    mut painter: ShapePainter,
+    query: Query<(&Shrine, &Transform)>,
+    settings: Res<Settings>,
+) {
+    for (shrine, transform) in query.iter() {
+        painter.color = settings.accessibility.palette.remap(Color::PINK);
+        painter.thickness = 0.05;
+        painter.hollow = true;
+        painter.set_rotation(Quat::from_rotation_x(TAU / 4.0));
+        painter.set_translation(vec3(transform.translation.x, 0.0, transform.translation.z));
+        painter.circle(shrine.range());
+    }
+}
+
+fn setup_upgrade_button(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    actions: Query<Entity, With<SelectionActionsTag>>,
+) {
+    commands
+        .spawn((
+            ShrineUpgradeButton,
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::all(Val::Px(5.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            // filled in by update_upgrade_button
+            parent.spawn(TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: ui_assets.font.clone(),
+                    font_size: 21.0,
+                    color: Color::BLACK,
+                },
+            ));
+        })
+        .set_parent(actions.single());
+}
+
+fn upgrade_clicked(
+    buttons: Query<(), (With<ShrineUpgradeButton>, With<JustClicked>)>,
+    selection: Res<Selection>,
+    mut shrines: Query<(&mut Shrine, &mut BuildCost)>,
+    mut player: Query<&mut Inventory, With<PlayerControllerTag>>,
+) {
+    if buttons.is_empty() {
+        return;
+    }
+    let Some(Ok((mut shrine, mut build_cost))) = selection.0.map(|e| shrines.get_mut(e)) else {
+        return;
+    };
+    let Ok(mut inventory) = player.get_single_mut() else {
+        return;
+    };
+    if shrine.level >= MAX_SHRINE_LEVEL
+        || !inventory.spend_items(SHRINE_UPGRADE_COST.iter().copied())
+    {
+        return;
+    }
+    shrine.level += 1;
+    build_cost.add(&SHRINE_UPGRADE_COST);
+}
+
+fn update_upgrade_button(
+    selection: Res<Selection>,
+    shrines: Query<&Shrine>,
+    mut button: Query<(&mut Style, &Children), With<ShrineUpgradeButton>>,
+    mut texts: Query<&mut Text>,
+) {
+    let Ok((mut style, children)) = button.get_single_mut() else {
+        return;
+    };
+    let Some(shrine) = selection.0.and_then(|e| shrines.get(e).ok()) else {
+        if style.display != Display::None {
+            style.display = Display::None;
+        }
+        return;
+    };
+    style.display = Display::Flex;
+    let label = match shrine.level < MAX_SHRINE_LEVEL {
+        true => {
+            let cost = SHRINE_UPGRADE_COST
+                .iter()
+                .map(|(item, count)| format!("{count} {item}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "Widen aura to {:.0}m ({cost})",
+                shrine.range() + SHRINE_RANGE_PER_LEVEL
+            )
+        }
+        false => format!("Aura {:.0}m (max)", shrine.range()),
+    };
+    let mut iter = texts.iter_many_mut(children);
+    while let Some(mut text) = iter.fetch_next() {
+        text.sections[0].value = label.clone();
+    }
+}
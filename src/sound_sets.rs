@@ -0,0 +1,106 @@
+use std::ops::RangeInclusive;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::HashMap,
+};
+use rand::{seq::SliceRandom, Rng};
+use serde::Deserialize;
+
+use crate::asset_utils::CustomAssetLoaderError;
+
+pub struct SoundSetPlugin;
+
+impl Plugin for SoundSetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<SoundSetsAsset>()
+            .init_asset_loader::<SoundSetsAssetLoader>()
+            .add_systems(Startup, load_sound_sets);
+    }
+}
+
+/// Sound ids mapped to the files they pick from, see sounds.sfx.ron.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct SoundSetsAsset(pub HashMap<String, SoundSet>);
+
+#[derive(Resource)]
+pub struct SoundSets(pub Handle<SoundSetsAsset>);
+
+/// Variations of one sound, every play picks a file, volume and pitch at random.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SoundSet {
+    // paths to the sound files
+    pub variants: Vec<String>,
+    pub volume: (f32, f32),
+    // playback speed, which is what changes the pitch
+    pub pitch: (f32, f32),
+    #[serde(skip)]
+    pub handles: Vec<Handle<AudioSource>>,
+}
+
+impl Default for SoundSet {
+    fn default() -> Self {
+        Self {
+            variants: Vec::new(),
+            volume: (1.0, 1.0),
+            pitch: (1.0, 1.0),
+            handles: Vec::new(),
+        }
+    }
+}
+
+impl SoundSet {
+    /// a random variant with a random volume and speed, None for an empty set
+    pub fn pick(&self) -> Option<(Handle<AudioSource>, f32, f32)> {
+        let mut rng = rand::thread_rng();
+        let handle = self.handles.choose(&mut rng)?.clone();
+        let volume = rng.gen_range(range(self.volume));
+        let speed = rng.gen_range(range(self.pitch));
+        Some((handle, volume, speed))
+    }
+}
+
+// the ron file may have them the wrong way around
+fn range((a, b): (f32, f32)) -> RangeInclusive<f32> {
+    a.min(b)..=a.max(b)
+}
+
+fn load_sound_sets(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SoundSets(asset_server.load("sounds.sfx.ron")));
+}
+
+#[derive(Default)]
+pub struct SoundSetsAssetLoader;
+
+impl AssetLoader for SoundSetsAssetLoader {
+    type Asset = SoundSetsAsset;
+    type Settings = ();
+    type Error = CustomAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let mut asset = ron::de::from_bytes::<SoundSetsAsset>(&bytes)?;
+            for set in asset.0.values_mut() {
+                set.handles = set
+                    .variants
+                    .iter()
+                    .map(|path| load_context.load(path))
+                    .collect();
+            }
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["sfx.ron"]
+    }
+}
@@ -0,0 +1,187 @@
+use bevy::{
+    ecs::query::ReadOnlyWorldQuery, prelude::*, transform::TransformSystem, utils::HashMap,
+};
+
+use crate::{
+    player::{MonkeyTag, RobotTag},
+    sapper::StructureTag,
+    tree::TreeTrunkTag,
+    tree_spawner::TreeSpawner,
+};
+
+// meters, about the range robots and towers look around in
+const CELL_SIZE: f32 = 4.0;
+
+pub struct SpatialGridPlugin;
+
+impl Plugin for SpatialGridPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpatialGrids>().add_systems(
+            PostUpdate,
+            update_spatial_grids.after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Buckets entities into square cells on the ground, so lookups only visit the cells nearby.
+/// Distances are measured on the ground, ignoring height.
+#[derive(Clone)]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<(Entity, Vec3)>>,
+    // the cells anything was inserted into, searches don't go past these
+    min: IVec2,
+    max: IVec2,
+    len: usize,
+}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        Self::new(CELL_SIZE)
+    }
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+            min: IVec2::MAX,
+            max: IVec2::MIN,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // keeps the cells around so refilling every frame doesn't allocate
+    pub fn clear(&mut self) {
+        for entries in self.cells.values_mut() {
+            entries.clear();
+        }
+        self.min = IVec2::MAX;
+        self.max = IVec2::MIN;
+        self.len = 0;
+    }
+
+    pub fn insert(&mut self, entity: Entity, pos: Vec3) {
+        let cell = self.cell(pos);
+        self.cells.entry(cell).or_default().push((entity, pos));
+        self.min = self.min.min(cell);
+        self.max = self.max.max(cell);
+        self.len += 1;
+    }
+
+    fn cell(&self, pos: Vec3) -> IVec2 {
+        (pos.xz() / self.cell_size).floor().as_ivec2()
+    }
+
+    /// everything in the cells touching the circle, callers check the exact distance
+    pub fn within(&self, pos: Vec3, radius: f32) -> impl Iterator<Item = (Entity, Vec3)> + '_ {
+        let from = self.cell(pos - Vec3::splat(radius)).max(self.min);
+        let to = self.cell(pos + Vec3::splat(radius)).min(self.max);
+        (from.y..=to.y)
+            .flat_map(move |z| (from.x..=to.x).map(move |x| IVec2::new(x, z)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+
+    /// squared distance to, and the entity of, the closest entry
+    pub fn nearest(&self, pos: Vec3) -> Option<(f32, Entity)> {
+        if self.is_empty() {
+            return None;
+        }
+        let center = self.cell(pos);
+        // the furthest ring that still has cells in it
+        let max_ring = (center - self.min)
+            .abs()
+            .max((self.max - center).abs())
+            .max_element();
+        let mut best: Option<(f32, Entity)> = None;
+        let mut visited = 0;
+        for ring in 0..=max_ring {
+            // a few entries spread far apart, checking all of them is cheaper than more rings
+            if visited > self.len {
+                return self.nearest_of_all(pos);
+            }
+            for cell in ring_cells(center, ring) {
+                visited += 1;
+                let Some(entries) = self.cells.get(&cell) else {
+                    continue;
+                };
+                for &(entity, entry_pos) in entries {
+                    let distance = entry_pos.xz().distance_squared(pos.xz());
+                    if best.is_none_or(|(d, _)| distance < d) {
+                        best = Some((distance, entity));
+                    }
+                }
+            }
+            // anything in the next ring is at least this far away
+            let reach = ring as f32 * self.cell_size;
+            if best.is_some_and(|(d, _)| d <= reach * reach) {
+                break;
+            }
+        }
+        best
+    }
+
+    fn nearest_of_all(&self, pos: Vec3) -> Option<(f32, Entity)> {
+        self.cells
+            .values()
+            .flatten()
+            .map(|&(entity, entry_pos)| (entry_pos.xz().distance_squared(pos.xz()), entity))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+}
+
+// the cells `ring` steps away from `center`, walking around the square.
+// the top and bottom rows, then the columns between them, ring 0 is just the center
+fn ring_cells(center: IVec2, ring: i32) -> impl Iterator<Item = IVec2> {
+    let rows = (-ring..=ring).flat_map(move |x| [IVec2::new(x, -ring), IVec2::new(x, ring)]);
+    let columns = (1 - ring..ring).flat_map(move |z| [IVec2::new(-ring, z), IVec2::new(ring, z)]);
+    rows.chain(columns)
+        .take((ring as usize * 8).max(1))
+        .map(move |offset| center + offset)
+}
+
+/// Where everything the robots and towers look for was at the end of the last frame.
+#[derive(Resource, Default)]
+pub struct SpatialGrids {
+    pub monkeys: SpatialHash,
+    pub robots: SpatialHash,
+    pub trees: SpatialHash,
+    pub tree_spawners: SpatialHash,
+    pub structures: SpatialHash,
+}
+
+fn update_spatial_grids(
+    mut grids: ResMut<SpatialGrids>,
+    monkeys: Query<(Entity, &GlobalTransform), With<MonkeyTag>>,
+    robots: Query<(Entity, &GlobalTransform), With<RobotTag>>,
+    trees: Query<(Entity, &GlobalTransform), With<TreeTrunkTag>>,
+    tree_spawners: Query<(Entity, &GlobalTransform), With<TreeSpawner>>,
+    structures: Query<(Entity, &GlobalTransform), With<StructureTag>>,
+) {
+    fill(&mut grids.monkeys, &monkeys);
+    fill(&mut grids.robots, &robots);
+    fill(&mut grids.trees, &trees);
+    fill(&mut grids.tree_spawners, &tree_spawners);
+    fill(&mut grids.structures, &structures);
+}
+
+fn fill<F: ReadOnlyWorldQuery>(
+    grid: &mut SpatialHash,
+    query: &Query<(Entity, &GlobalTransform), F>,
+) {
+    grid.clear();
+    for (entity, transform) in query.iter() {
+        grid.insert(entity, transform.translation());
+    }
+}
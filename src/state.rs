@@ -1,134 +1,329 @@
-use bevy::{core::FrameCount, math::vec3, prelude::*};
-use rand::Rng;
+use bevy::{core::FrameCount, prelude::*};
 
 use crate::{
-    map::MAP_SIZE_HALF,
+    depot::Depot,
+    item_pickups::ItemPickup,
+    map::{self, MapBounds},
+    mixer::PlaySfxEvent,
     notification::NotificationEvent,
-    player::{Body, PlayerControllerTag, SpawnPlayerEvent},
-    shop::SpawnShopItemEvent,
-    tree::TreeTrunkTag,
-    waves::{WaveDescriptors, WaveDescriptorsAsset},
-    weapon::WeaponType,
+    pause::{not_paused, Pause, PauseReason},
+    perks::no_pending_drafts,
+    pickup::FlyToEntity,
+    player::{Body, EnemyScaling, Player, PlayerControllerTag, PlayerInput, SpawnPlayerEvent},
+    profile::Profile,
+    projectile::Projectile,
+    shop::{ShopCatalogue, ShopCatalogueAsset, ShopItem, SpawnShopItemEvent},
+    shop_stall::ShopStall,
+    shrine::Shrine,
+    tower::TowerTag,
+    tree::{TreeRootTag, TreeTrunkTag},
+    tree_spawner::TreeSpawner,
+    wall::WallTag,
+    wave_spawner::{Portal, WaveSpawner},
+    waves::{WaveDescriptor, WaveDescriptors, WaveDescriptorsAsset},
 };
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Resource)]
+#[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
 pub enum AppState {
-    Init,
-    Wave(usize),
+    #[default]
+    MainMenu,
+    Playing,
+    GameOver,
+}
+
+// the wave currently being played, 0 is the first one
+#[derive(Resource, Debug, Default)]
+pub struct Wave(pub usize);
+
+// how the last run ended, inserted when entering GameOver
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    Won,
     Lost,
-    Win,
 }
 
+/// Campaign runs end after the last wave, endless runs keep generating waves until lost.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    #[default]
+    Campaign,
+    Endless,
+}
+
+/// The map was cleared of robots, the next wave waits until every perk draft is picked.
+#[derive(Event)]
+pub struct WaveClearedEvent {
+    pub wave: usize,
+}
+
+/// Ends the run, the game over screen shows how.
+#[derive(Event)]
+pub struct GameOverEvent {
+    pub result: GameResult,
+}
+
+/// Throws away the current run and starts a new one without leaving `AppState::Playing`.
+#[derive(Event)]
+pub struct RestartRunEvent;
+
+// frame the current run started on, the world needs a few frames to spawn
+#[derive(Resource, Default)]
+struct RunStartFrame(u32);
+
+/// The map was cleared and the next wave hasn't been queued yet.
+#[derive(Resource, Default)]
+pub struct WaveCleared(pub bool);
+
+/// Everything that belongs to a run, despawned when it ends or a save is loaded over it.
+pub type RunEntities = Or<(
+    With<Player>,
+    With<TreeRootTag>,
+    With<TowerTag>,
+    With<TreeSpawner>,
+    With<ShopItem>,
+    With<Projectile>,
+    With<ItemPickup>,
+    With<FlyToEntity>,
+    With<Portal>,
+    With<ShopStall>,
+    With<WallTag>,
+    With<Depot>,
+    With<Shrine>,
+)>;
+
 pub struct StatePlugin;
 
 impl Plugin for StatePlugin {
     fn build(&self, app: &mut App) {
+        app.add_state::<AppState>()
+            .init_resource::<Wave>()
+            .init_resource::<GameMode>()
+            .init_resource::<RunStartFrame>()
+            .init_resource::<WaveCleared>()
+            .add_event::<RestartRunEvent>()
+            .add_event::<WaveClearedEvent>()
+            .add_event::<GameOverEvent>()
+            // the first wave spawns around the walls of the new run
+            .add_systems(
+                OnEnter(AppState::Playing),
+                start_run.after(map::reset_bounds),
+            )
+            .add_systems(OnEnter(AppState::GameOver), stop_all_input)
+            .add_systems(OnExit(AppState::GameOver), cleanup_run)
+            // PreUpdate, so the old run is gone before the new one spawns in Update
+            .add_systems(
+                PreUpdate,
+                (cleanup_run, start_run)
+                    .chain()
+                    .after(map::reset_bounds)
+                    .run_if(on_event::<RestartRunEvent>()),
+            );
         app.add_systems(
             Last,
-            handle_next_wave
+            clear_wave
                 .run_if(check_for_no_robots)
-                .run_if(|v: Res<AppState>| matches!(&*v, AppState::Wave(_)))
+                .run_if(in_state(AppState::Playing))
+                .run_if(not_paused)
                 .run_if(not(reached_max_wave))
-                .run_if(|f: Res<FrameCount>| f.0 > 3),
+                .run_if(run_started)
+                .run_if(not(wave_cleared)),
+        );
+        // a frame behind clear_wave, so the drafts for the cleared wave are queued before it checks
+        app.add_systems(
+            Last,
+            handle_next_wave
+                .run_if(wave_cleared)
+                .run_if(in_state(AppState::Playing))
+                .run_if(not_paused)
+                .run_if(no_pending_drafts)
+                .before(clear_wave),
         );
         app.add_systems(
             Last,
             handle_win
                 .run_if(check_for_no_robots)
+                .run_if(in_state(AppState::Playing))
+                .run_if(not_paused)
                 .run_if(reached_max_wave)
-                .run_if(|f: Res<FrameCount>| f.0 > 3)
-                .before(handle_next_wave),
+                .run_if(run_started)
+                .before(clear_wave),
         );
         app.add_systems(
             Last,
             handle_loss
                 .run_if(check_for_loss)
-                .run_if(|v: Res<AppState>| !(resource_equals::<AppState>(AppState::Lost))(v))
-                .run_if(|f: Res<FrameCount>| f.0 > 3),
+                .run_if(in_state(AppState::Playing))
+                .run_if(not_paused)
+                .run_if(run_started)
+                .before(handle_win),
         );
+        app.add_systems(
+            Last,
+            enter_game_over
+                .run_if(in_state(AppState::Playing))
+                .after(handle_loss),
+        );
+    }
+}
+
+fn start_run(
+    frame: Res<FrameCount>,
+    mut run_start: ResMut<RunStartFrame>,
+    mut cleared: ResMut<WaveCleared>,
+    mut wave: ResMut<Wave>,
+    mut spawner: ResMut<WaveSpawner>,
+    mut spawn_shop_item_event: EventWriter<SpawnShopItemEvent>,
+    wave_descriptors: Res<WaveDescriptors>,
+    wave_descriptor_assets: Res<Assets<WaveDescriptorsAsset>>,
+    shop_catalogue: Res<ShopCatalogue>,
+    shop_catalogue_assets: Res<Assets<ShopCatalogueAsset>>,
+    asset_server: Res<AssetServer>,
+    profile: Res<Profile>,
+    bounds: Res<MapBounds>,
+) {
+    run_start.0 = frame.0;
+    cleared.0 = false;
+    wave.0 = 0;
+    spawner.clear();
+    // later waves start in handle_next_wave once the map is cleared
+    if let Some((first, scaling)) = wave_descriptor_assets
+        .get(&wave_descriptors.0)
+        .and_then(|waves| waves.wave(0))
+    {
+        queue_wave(
+            0,
+            first,
+            scaling,
+            &mut spawner,
+            shop_catalogue_assets.get(&shop_catalogue.0),
+            &mut spawn_shop_item_event,
+            &asset_server,
+            &profile,
+            bounds.size_half,
+        );
+    }
+}
+
+fn queue_wave(
+    wave: usize,
+    wave_descriptor: WaveDescriptor,
+    scaling: EnemyScaling,
+    spawner: &mut WaveSpawner,
+    shop_catalogue: Option<&ShopCatalogueAsset>,
+    spawn_shop_item_event: &mut EventWriter<SpawnShopItemEvent>,
+    asset_server: &AssetServer,
+    profile: &Profile,
+    map_size_half: Vec2,
+) {
+    for group in wave_descriptor.groups.iter() {
+        spawner.queue_group(
+            group,
+            wave_descriptor.spawn_duration,
+            scaling,
+            asset_server,
+            map_size_half,
+        );
+    }
+    for new_item in shop_catalogue
+        .into_iter()
+        .flat_map(|c| c.unlocked_at(wave, profile))
+    {
+        spawn_shop_item_event.send(SpawnShopItemEvent {
+            item: new_item.clone(),
+        });
+    }
+}
+
+fn run_started(frame: Res<FrameCount>, run_start: Res<RunStartFrame>) -> bool {
+    frame.0 > run_start.0 + 3
+}
+
+// everything a run spawns, so the next one starts fresh
+fn cleanup_run(mut commands: Commands, query: Query<Entity, RunEntities>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
     }
 }
 
+// endless runs never run out of waves
 fn reached_max_wave(
-    state: Res<AppState>,
+    wave: Res<Wave>,
+    mode: Res<GameMode>,
     wave_descriptors: Res<WaveDescriptors>,
     wave_descriptor_assets: Res<Assets<WaveDescriptorsAsset>>,
 ) -> bool {
-    let Some(wave) = wave_descriptor_assets.get(&wave_descriptors.0) else {
+    let Some(waves) = wave_descriptor_assets.get(&wave_descriptors.0) else {
         return false;
     };
-    let max_wave = wave.0.len();
-    matches!(&*state, AppState::Wave(w) if *w == max_wave-1)
+    let max_wave = waves.waves.len();
+    *mode == GameMode::Campaign && wave.0 >= max_wave - 1
 }
 
-fn check_for_no_robots(players: Query<&Body>) -> bool {
-    players
-        .into_iter()
-        .filter(|b| {
-            matches!(b, Body::Robot) || matches!(b, Body::FastRobot) || matches!(b, Body::Boss)
-        })
-        .count()
-        == 0
+// also waits for enemies that haven't spawned yet, or were sent but aren't spawned
+fn check_for_no_robots(
+    players: Query<&Body>,
+    spawner: Res<WaveSpawner>,
+    spawn_events: Res<Events<SpawnPlayerEvent>>,
+) -> bool {
+    spawner.is_empty()
+        && spawn_events.is_empty()
+        && players
+            .into_iter()
+            .filter(|b| matches!(b, Body::Robot | Body::FastRobot | Body::Boss | Body::Sapper))
+            .count()
+            == 0
 }
 
-pub fn handle_next_wave(
-    mut commands: Commands,
+fn wave_cleared(cleared: Res<WaveCleared>) -> bool {
+    cleared.0
+}
+
+fn clear_wave(
+    wave: Res<Wave>,
+    mut cleared: ResMut<WaveCleared>,
+    mut wave_cleared_event: EventWriter<WaveClearedEvent>,
+) {
+    cleared.0 = true;
+    wave_cleared_event.send(WaveClearedEvent { wave: wave.0 });
+}
+
+fn handle_next_wave(
     asset_server: Res<AssetServer>,
-    mut app_state: ResMut<AppState>,
-    mut spawn_player_event: EventWriter<SpawnPlayerEvent>,
+    mut cleared: ResMut<WaveCleared>,
+    mut wave: ResMut<Wave>,
+    mut spawner: ResMut<WaveSpawner>,
     mut notification_event: EventWriter<NotificationEvent>,
     mut spawn_shop_item_event: EventWriter<SpawnShopItemEvent>,
     wave_descriptors: Res<WaveDescriptors>,
     wave_descriptor_assets: Res<Assets<WaveDescriptorsAsset>>,
+    shop_catalogue: Res<ShopCatalogue>,
+    shop_catalogue_assets: Res<Assets<ShopCatalogueAsset>>,
+    profile: Res<Profile>,
+    bounds: Res<MapBounds>,
 ) {
-    let AppState::Wave(wave) = app_state.as_mut() else {
-        panic!("how did we get here?");
-    };
+    cleared.0 = false;
+    let wave = &mut wave.0;
     // tree_trigger_writer.send(TriggerSpawnTrees(0.1 - *wave as f32 / 30.0));
-    let mut rng = rand::thread_rng();
-
-    commands.spawn(AudioBundle {
-        source: asset_server.load("sounds/next-level.ogg"),
-        ..default()
-    });
 
     *wave += 1;
 
-    let wave_descriptors = &wave_descriptor_assets.get(&wave_descriptors.0).unwrap().0;
-    let is_last_wave = wave_descriptors.len() - 1 == *wave;
-    let wave_descriptor = wave_descriptors[*wave].clone();
-
-    for i in 1..(1 + wave_descriptor.nb_enemies) {
-        let weapon_type = WeaponType::Axe;
-        let mut x = MAP_SIZE_HALF + rng.gen_range(6.0..26.0);
-        let mut z = MAP_SIZE_HALF + rng.gen_range(6.0..26.0);
-        x *= match rng.gen::<bool>() {
-            true => 1.0,
-            false => -1.0,
-        };
-        z *= match rng.gen::<bool>() {
-            true => 1.0,
-            false => -1.0,
-        };
-        let mut body = Body::Robot;
-        let p = i as f32 / wave_descriptor.nb_enemies as f32;
-        if p > 0.7 {
-            body = Body::FastRobot;
-        }
-        if is_last_wave && i == wave_descriptor.nb_enemies {
-            body = Body::Boss;
-        }
-        spawn_player_event.send(SpawnPlayerEvent {
-            pos: vec3(x, 4.0, z),
-            is_main: false,
-            body,
-            weapon_type,
-        });
-    }
+    let (wave_descriptor, scaling) = wave_descriptor_assets
+        .get(&wave_descriptors.0)
+        .unwrap()
+        .wave(*wave)
+        .unwrap();
 
-    for new_item in wave_descriptor.new_shop_items {
-        spawn_shop_item_event.send(SpawnShopItemEvent { item: new_item });
-    }
+    queue_wave(
+        *wave,
+        wave_descriptor,
+        scaling,
+        &mut spawner,
+        shop_catalogue_assets.get(&shop_catalogue.0),
+        &mut spawn_shop_item_event,
+        &asset_server,
+        &profile,
+        bounds.size_half,
+    );
 
     notification_event.send(NotificationEvent {
         text: format!("Wave {}!", *wave),
@@ -137,29 +332,21 @@ pub fn handle_next_wave(
     });
 }
 
+// the menu asks whether to finish or keep going in endless mode
 pub fn handle_win(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
     mut notification_event: EventWriter<NotificationEvent>,
-    mut app_state: ResMut<AppState>,
+    mut pause: ResMut<Pause>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
 ) {
-    let AppState::Wave(wave) = &mut *app_state else {
-        return;
-    };
-    *wave += 1;
-
-    commands.spawn(AudioBundle {
-        source: asset_server.load("sounds/win.ogg"),
-        ..default()
-    });
+    sfx_events.send(PlaySfxEvent::new("win"));
 
     notification_event.send(NotificationEvent {
         text: "You Win!".into(),
-        show_for: 60.0,
+        show_for: 5.0,
         color: Color::GREEN,
     });
 
-    *app_state = AppState::Win;
+    pause.pause(PauseReason::WinPrompt);
 }
 
 fn check_for_loss(
@@ -171,14 +358,11 @@ fn check_for_loss(
 }
 
 pub fn handle_loss(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
     mut notification_event: EventWriter<NotificationEvent>,
+    mut game_over_event: EventWriter<GameOverEvent>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
 ) {
-    commands.spawn(AudioBundle {
-        source: asset_server.load("sounds/lost.ogg"),
-        ..default()
-    });
+    sfx_events.send(PlaySfxEvent::new("lost"));
 
     notification_event.send(NotificationEvent {
         text: "You Lost!".into(),
@@ -186,5 +370,27 @@ pub fn handle_loss(
         color: Color::RED,
     });
 
-    commands.insert_resource(AppState::Lost);
+    game_over_event.send(GameOverEvent {
+        result: GameResult::Lost,
+    });
+}
+
+fn enter_game_over(
+    mut commands: Commands,
+    mut events: EventReader<GameOverEvent>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    // a loss in the same frame as finishing still counts as finishing
+    let Some(event) = events.read().min_by_key(|e| e.result != GameResult::Won) else {
+        return;
+    };
+    commands.insert_resource(event.result);
+    next_state.set(AppState::GameOver);
+}
+
+// the ai stops with the game over screen, nobody keeps walking or swinging
+fn stop_all_input(mut inputs: Query<&mut PlayerInput>) {
+    for mut input in inputs.iter_mut() {
+        *input = PlayerInput::default();
+    }
 }
@@ -0,0 +1,194 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    health::{ApplyHealthEvent, Health},
+    pause::not_paused,
+    player::PlayerInput,
+};
+
+// poison is the only effect that stacks, more than this refreshes the oldest stack instead
+const MAX_POISON_STACKS: usize = 5;
+// seconds between damage ticks
+const DOT_INTERVAL: f32 = 0.5;
+
+pub struct StatusEffectPlugin;
+
+impl Plugin for StatusEffectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ApplyStatusEvent>().add_systems(
+            Update,
+            (apply_status_events, tick_status_effects)
+                .chain()
+                .run_if(not_paused),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusKind {
+    // damage over time, stacks
+    Poison,
+    // damage over time, only the strongest burn counts
+    Burn,
+    // movement speed is multiplied by (1.0 - strength)
+    Slow,
+    // can't move or attack
+    Stun,
+}
+
+/// Declared on weapons, projectiles and shop items.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    // seconds
+    pub duration: f32,
+    // damage per second for poison and burn, slow fraction for slow, unused for stun
+    #[serde(default)]
+    pub strength: f32,
+}
+
+#[derive(Event)]
+pub struct ApplyStatusEvent {
+    pub effect: StatusEffect,
+    pub target_entity: Entity,
+    pub caster_entity: Entity,
+}
+
+#[derive(Clone)]
+struct ActiveStatus {
+    effect: StatusEffect,
+    time_left: f32,
+    // damage over time is dealt in whole points, the rest carries over
+    pending_damage: f32,
+}
+
+/// Every status currently on an entity, inserted the first time something is applied.
+#[derive(Component, Default)]
+pub struct StatusEffects {
+    active: Vec<ActiveStatus>,
+    tick_timer: f32,
+}
+
+impl StatusEffects {
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.active.iter().any(|s| s.effect.kind == kind)
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.has(StatusKind::Stun)
+    }
+
+    /// multiplier for movement speed, slows don't add up, the strongest one is used
+    pub fn speed_multiplier(&self) -> f32 {
+        let slow = self
+            .active
+            .iter()
+            .filter(|s| s.effect.kind == StatusKind::Slow)
+            .map(|s| s.effect.strength)
+            .fold(0.0, f32::max);
+        (1.0 - slow).clamp(0.0, 1.0)
+    }
+
+    fn add(&mut self, effect: StatusEffect) {
+        let new = ActiveStatus {
+            effect,
+            time_left: effect.duration,
+            pending_damage: 0.0,
+        };
+        let poison_stacks = self
+            .active
+            .iter()
+            .filter(|s| s.effect.kind == StatusKind::Poison)
+            .count();
+        let mut same_kind = self
+            .active
+            .iter_mut()
+            .filter(|s| s.effect.kind == effect.kind);
+        match effect.kind {
+            StatusKind::Poison if poison_stacks < MAX_POISON_STACKS => self.active.push(new),
+            StatusKind::Poison => {
+                if let Some(oldest) = same_kind.min_by(|a, b| a.time_left.total_cmp(&b.time_left)) {
+                    *oldest = new;
+                }
+            }
+            // refresh the duration, keeping whichever is stronger
+            StatusKind::Burn | StatusKind::Slow | StatusKind::Stun => match same_kind.next() {
+                Some(existing) => {
+                    existing.time_left = existing.time_left.max(effect.duration);
+                    existing.effect.strength = existing.effect.strength.max(effect.strength);
+                }
+                None => self.active.push(new),
+            },
+        }
+    }
+}
+
+fn apply_status_events(
+    mut commands: Commands,
+    mut events: EventReader<ApplyStatusEvent>,
+    mut query: Query<Option<&mut StatusEffects>, With<Health>>,
+) {
+    for event in events.read() {
+        let Ok(statuses) = query.get_mut(event.target_entity) else {
+            continue;
+        };
+        match statuses {
+            Some(mut statuses) => statuses.add(event.effect),
+            None => {
+                let mut statuses = StatusEffects::default();
+                statuses.add(event.effect);
+                commands.entity(event.target_entity).insert(statuses);
+            }
+        }
+    }
+}
+
+fn tick_status_effects(
+    mut query: Query<(Entity, &mut StatusEffects)>,
+    mut apply_health_events: EventWriter<ApplyHealthEvent>,
+    time: Res<Time>,
+) {
+    for (entity, mut statuses) in query.iter_mut() {
+        let dt = time.delta_seconds();
+        statuses.tick_timer += dt;
+        let deal_damage = statuses.tick_timer >= DOT_INTERVAL;
+        if deal_damage {
+            statuses.tick_timer -= DOT_INTERVAL;
+        }
+
+        let mut damage = 0;
+        for status in statuses.active.iter_mut() {
+            status.time_left -= dt;
+            if matches!(status.effect.kind, StatusKind::Poison | StatusKind::Burn) {
+                status.pending_damage += status.effect.strength * dt;
+                if deal_damage {
+                    damage += status.pending_damage.floor() as i32;
+                    status.pending_damage = status.pending_damage.fract();
+                }
+            }
+        }
+        statuses.active.retain(|s| s.time_left > 0.0);
+
+        if damage > 0 {
+            // caster is the target itself, so ticking damage doesn't knock anything back
+            apply_health_events.send(ApplyHealthEvent {
+                amount: -damage,
+                target_entity: entity,
+                caster_entity: entity,
+                is_crit: false,
+                is_finisher: false,
+            });
+        }
+    }
+}
+
+// runs after the controllers wrote their input, the same way staggering does
+pub fn apply_stun(mut query: Query<(&mut PlayerInput, &StatusEffects)>) {
+    for (mut player_input, statuses) in query.iter_mut() {
+        if statuses.is_stunned() {
+            player_input.movement = Vec3::ZERO;
+            player_input.attack = None;
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+
+use crate::{
+    health::{apply_health_events, DeathEvent},
+    tree::{SpawnTreeEvent, Species, TreeBlueprint, TreeRootTag, TreeTrunkTag},
+};
+
+// a stump grows back into a sapling after this long, or right away when watered
+const STUMP_REGROW_SECONDS: f32 = 60.0;
+const STUMP_RADIUS: f32 = 0.3;
+const STUMP_HEIGHT: f32 = 0.3;
+
+pub struct StumpPlugin;
+
+impl Plugin for StumpPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RegrowStumpsEvent>()
+            .add_systems(Startup, setup_stump_assets)
+            // the dead trunk is gone once the frame's commands are applied
+            .add_systems(
+                Update,
+                (leave_stumps, water_stumps, regrow_stumps)
+                    .chain()
+                    .after(apply_health_events),
+            );
+    }
+}
+
+/// What's left of a cut down tree, on the tree's root.
+#[derive(Component)]
+pub struct Stump {
+    timer: Timer,
+    // unknown if the tree species weren't loaded
+    species: Option<Species>,
+}
+
+/// Every stump grows back right away.
+#[derive(Event)]
+pub struct RegrowStumpsEvent;
+
+#[derive(Resource)]
+struct StumpAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn setup_stump_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(StumpAssets {
+        mesh: meshes.add(Mesh::from(shape::Cylinder {
+            radius: STUMP_RADIUS,
+            height: STUMP_HEIGHT,
+            resolution: 10,
+            segments: 1,
+        })),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.4, 0.26, 0.13),
+            perceptual_roughness: 1.0,
+            ..default()
+        }),
+    });
+}
+
+// the trunk is despawned when it dies, its root stays behind as the stump
+fn leave_stumps(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    trunks: Query<(&Parent, Option<&Species>), With<TreeTrunkTag>>,
+    roots: Query<(), (With<TreeRootTag>, Without<Stump>)>,
+    stump_assets: Res<StumpAssets>,
+) {
+    for event in death_events.read() {
+        let Ok((parent, species)) = trunks.get(event.entity) else {
+            continue;
+        };
+        let root = parent.get();
+        if !roots.contains(root) {
+            continue;
+        }
+        commands
+            .entity(root)
+            .insert(Stump {
+                timer: Timer::from_seconds(STUMP_REGROW_SECONDS, TimerMode::Once),
+                species: species.copied(),
+            })
+            .with_children(|parent| {
+                parent.spawn(PbrBundle {
+                    mesh: stump_assets.mesh.clone(),
+                    material: stump_assets.material.clone(),
+                    transform: Transform::from_translation(Vec3::Y * STUMP_HEIGHT / 2.0),
+                    ..default()
+                });
+            });
+    }
+}
+
+fn water_stumps(mut events: EventReader<RegrowStumpsEvent>, mut stumps: Query<&mut Stump>) {
+    if events.read().count() == 0 {
+        return;
+    }
+    for mut stump in stumps.iter_mut() {
+        let duration = stump.timer.duration();
+        stump.timer.set_elapsed(duration);
+    }
+}
+
+fn regrow_stumps(
+    mut commands: Commands,
+    mut stumps: Query<(Entity, &mut Stump, &GlobalTransform)>,
+    mut spawn_tree_event: EventWriter<SpawnTreeEvent>,
+    time: Res<Time>,
+) {
+    for (entity, mut stump, transform) in stumps.iter_mut() {
+        // watered stumps are already finished
+        if !stump.timer.tick(time.delta()).finished() {
+            continue;
+        }
+        spawn_tree_event.send(SpawnTreeEvent {
+            pos: transform.translation(),
+            blueprint: match stump.species {
+                Some(species) => TreeBlueprint::OfSpecies(species),
+                None => TreeBlueprint::Randomized,
+            },
+            play_sound: false,
+            sapling: true,
+        });
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,119 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    fixed_step::FixedStepSet,
+    health::{ApplyHealthEvent, Health},
+    pause::not_paused,
+    player::MonkeyTag,
+    robot_ai::robot_ai,
+    tower::TowerTag,
+};
+
+// threat gained for every point of damage taken
+const DAMAGE_THREAT: f32 = 10.0;
+// threat per second from a monkey standing right next to the robot
+const PROXIMITY_THREAT: f32 = 20.0;
+// fraction of threat lost every second
+const THREAT_DECAY: f32 = 0.1;
+// entries below this are dropped
+const MIN_THREAT: f32 = 0.1;
+
+pub struct ThreatPlugin;
+
+impl Plugin for ThreatPlugin {
+    fn build(&self, app: &mut App) {
+        // hits are counted every frame, fixed steps can skip the frame their events are in
+        app.add_systems(Update, damage_threat.run_if(not_paused))
+            .add_systems(
+                FixedUpdate,
+                (proximity_threat, decay_threat)
+                    .chain()
+                    .in_set(FixedStepSet::Simulate)
+                    .before(robot_ai)
+                    .run_if(not_paused),
+            );
+    }
+}
+
+/// How much each monkey or tower has annoyed this robot, robot_ai goes for the top entry.
+#[derive(Component, Default)]
+pub struct ThreatTable {
+    threat: HashMap<Entity, f32>,
+    // monkeys closer than this slowly build up threat
+    proximity_range: f32,
+}
+
+impl ThreatTable {
+    pub fn new(proximity_range: f32) -> Self {
+        Self {
+            threat: HashMap::default(),
+            proximity_range,
+        }
+    }
+
+    pub fn add(&mut self, entity: Entity, amount: f32) {
+        *self.threat.entry(entity).or_default() += amount;
+    }
+
+    /// stop caring about an entity until it builds up threat again
+    pub fn forget(&mut self, entity: Entity) {
+        self.threat.remove(&entity);
+    }
+
+    pub fn highest(&self) -> Option<(Entity, f32)> {
+        self.threat
+            .iter()
+            .map(|(e, t)| (*e, *t))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+// players, companions and towers hitting a robot
+fn damage_threat(
+    mut events: EventReader<ApplyHealthEvent>,
+    mut tables: Query<&mut ThreatTable>,
+    sources: Query<(), Or<(With<MonkeyTag>, With<TowerTag>)>>,
+) {
+    for event in events.read() {
+        if event.amount >= 0 || !sources.contains(event.caster_entity) {
+            continue;
+        }
+        if let Ok(mut table) = tables.get_mut(event.target_entity) {
+            table.add(event.caster_entity, -event.amount as f32 * DAMAGE_THREAT);
+        }
+    }
+}
+
+fn proximity_threat(
+    mut tables: Query<(&mut ThreatTable, &GlobalTransform)>,
+    monkeys: Query<(Entity, &GlobalTransform), With<MonkeyTag>>,
+    time: Res<Time>,
+) {
+    for (mut table, transform) in tables.iter_mut() {
+        for (monkey, monkey_transform) in monkeys.iter() {
+            let distance = monkey_transform
+                .translation()
+                .distance(transform.translation());
+            if distance >= table.proximity_range {
+                continue;
+            }
+            // closer monkeys are more annoying
+            let closeness = 1.0 - distance / table.proximity_range;
+            table.add(monkey, PROXIMITY_THREAT * closeness * time.delta_seconds());
+        }
+    }
+}
+
+fn decay_threat(
+    mut tables: Query<&mut ThreatTable>,
+    alive: Query<(), With<Health>>,
+    time: Res<Time>,
+) {
+    let keep = (1.0 - THREAT_DECAY * time.delta_seconds()).max(0.0);
+    for mut table in tables.iter_mut() {
+        table.threat.retain(|e, t| {
+            *t *= keep;
+            *t > MIN_THREAT && alive.contains(*e)
+        });
+    }
+}
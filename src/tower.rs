@@ -7,23 +7,51 @@ use bevy::{
 use bevy_rapier3d::prelude::{Collider, CollisionGroups, Group};
 use bevy_vector_shapes::{
     prelude::ShapePainter,
-    shapes::{DiscPainter, LinePainter},
+    shapes::{DiscPainter, LinePainter, RectPainter},
 };
 
 use crate::{
-    collision_groups::{COLLISION_CHARACTER, COLLISION_WORLD},
+    collision_groups::{COLLISION_CHARACTER, COLLISION_POINTER, COLLISION_WORLD},
+    demolish::BuildCost,
+    fixed_step::FixedStepSet,
+    health::{Health, ShowHealthBar},
+    inventory::Item,
+    knockback::KnockbackResistance,
+    mixer::{PlaySfxEvent, SfxLimit},
+    pathfinding::NavObstacle,
     player::RobotTag,
-    weapon::{TryCastWeaponEvent, WeaponCooldown, WeaponStats, WeaponType},
+    sapper::StructureTag,
+    settings::Settings,
+    spatial_grid::SpatialGrids,
+    state::AppState,
+    tower_upgrade::TowerLevel,
+    weapon::{TryCastWeaponEvent, Weapon, WeaponCooldown, WeaponStats},
+    weather::CurrentWeather,
 };
 
+// range of a freshly built tower, upgrades extend it
 const TOWER_RANGE: f32 = 8.0;
+// only sappers damage towers
+const TOWER_HEALTH: i32 = 30;
+// loading a save builds every tower in the same frame, they don't all need to be heard
+const BUILD_SFX_LIMIT: SfxLimit = SfxLimit {
+    cooldown: 0.05,
+    max_playing: 2,
+};
 
 pub struct TowerPlugin;
 impl Plugin for TowerPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SpawnTowerEvent>()
             .add_systems(Startup, setup_tower_model)
-            .add_systems(Update, (tower_spawn, tower_target, tower_shoot).chain());
+            .add_systems(Update, (tower_spawn, draw_tower_targets))
+            .add_systems(
+                FixedUpdate,
+                (tower_target, tower_shoot)
+                    .chain()
+                    .in_set(FixedStepSet::Simulate)
+                    .run_if(in_state(AppState::Playing)),
+            );
     }
 }
 
@@ -42,9 +70,49 @@ pub struct TowerTag;
 #[derive(Component)]
 pub struct TowerTarget(Entity);
 
+#[derive(Component)]
+pub struct TowerRange(pub f32);
+
+/// Which robot in range a tower shoots at.
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum TargetingMode {
+    #[default]
+    Closest,
+    // keeps shooting whatever entered range first until it leaves
+    First,
+    LowestHealth,
+    Strongest,
+    // the robot closest to reaching a tree
+    NearestToTree,
+}
+
+impl TargetingMode {
+    pub fn next(self) -> Self {
+        match self {
+            TargetingMode::Closest => TargetingMode::First,
+            TargetingMode::First => TargetingMode::LowestHealth,
+            TargetingMode::LowestHealth => TargetingMode::Strongest,
+            TargetingMode::Strongest => TargetingMode::NearestToTree,
+            TargetingMode::NearestToTree => TargetingMode::Closest,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TargetingMode::Closest => "Closest",
+            TargetingMode::First => "First",
+            TargetingMode::LowestHealth => "Lowest health",
+            TargetingMode::Strongest => "Strongest",
+            TargetingMode::NearestToTree => "Nearest to tree",
+        }
+    }
+}
+
 #[derive(Event)]
 pub struct SpawnTowerEvent {
     pub pos: Vec3,
+    // refunded in part when it's demolished
+    pub cost: Vec<(Item, u32)>,
 }
 
 fn tower_spawn(
@@ -52,19 +120,28 @@ fn tower_spawn(
     tower_model: Res<TowerModel>,
     mut ev_spawn_tower: EventReader<SpawnTowerEvent>,
     asset_server: Res<AssetServer>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
 ) {
     for ev in ev_spawn_tower.read() {
-        cmds.spawn(AudioBundle {
-            source: asset_server.load("sounds/build.ogg"),
-            settings: PlaybackSettings::DESPAWN,
-        });
+        sfx_events.send(
+            PlaySfxEvent::new("build")
+                .at(ev.pos)
+                .limited(BUILD_SFX_LIMIT),
+        );
         cmds.spawn((
             Name::new("Tower"),
-            TowerTag,
+            (TowerTag, StructureTag, BuildCost(ev.cost.clone())),
+            Health::new(TOWER_HEALTH),
+            ShowHealthBar,
             TowerTarget(Entity::PLACEHOLDER),
-            WeaponType::Bow(asset_server.load("projectiles/tower.projectile.ron")),
+            TowerRange(TOWER_RANGE),
+            TargetingMode::default(),
+            TowerLevel::default(),
+            Weapon(asset_server.load("weapons/tower.weapon.ron")),
             WeaponCooldown { time_left: 2.0 },
             WeaponStats::default(),
+            KnockbackResistance(1.0),
+            NavObstacle { radius: 1.0 },
             SceneBundle {
                 scene: tower_model.0.clone_weak(),
                 transform: Transform::from_translation(vec3(ev.pos.x, 5.0, ev.pos.z)),
@@ -80,50 +157,96 @@ fn tower_spawn(
                     Group::from_bits(COLLISION_CHARACTER).unwrap(),
                 ),
             ));
+            // lets the tower be clicked to open the upgrade panel
+            cmds.spawn((
+                SpatialBundle::from_transform(Transform::from_xyz(0.0, -2.5, 0.0)),
+                Collider::cuboid(1.0, 2.5, 1.0),
+                CollisionGroups::new(
+                    Group::from_bits(COLLISION_POINTER).unwrap(),
+                    Group::from_bits(COLLISION_POINTER).unwrap(),
+                ),
+            ));
         });
     }
 }
 
 fn tower_target(
-    mut painter: ShapePainter,
-    mut q_tower: Query<(&mut TowerTarget, &Transform)>,
-    q_enemies: Query<(Entity, &Transform), With<RobotTag>>,
+    mut q_tower: Query<(&mut TowerTarget, &TowerRange, &TargetingMode, &Transform)>,
+    q_enemies: Query<(Entity, &Transform, &Health), With<RobotTag>>,
+    grids: Res<SpatialGrids>,
+    weather: Res<CurrentWeather>,
 ) {
-    for (mut target, tower_tr) in &mut q_tower {
-        // get current targeted enemy distance
-        let mut curr_target_distance = q_enemies
-            .get(target.0)
-            .map(|(_, tr)| (tr.translation.xz() - tower_tr.translation.xz()).length())
-            .unwrap_or(10000.0);
-
-        // switch to any closer enemy
-        for (enemy_entity, enemy_tr) in &q_enemies {
-            let distance = (enemy_tr.translation.xz() - tower_tr.translation.xz()).length();
-            if distance < curr_target_distance {
-                target.0 = enemy_entity;
-                curr_target_distance = distance;
+    for (mut target, range, mode, tower_tr) in &mut q_tower {
+        // fog shortens every tower's range
+        let range = range.0 * weather.weather.tower_range_mul();
+        let in_range = |enemy_tr: &Transform| {
+            (enemy_tr.translation.xz() - tower_tr.translation.xz()).length() <= range
+        };
+        // the grid is a frame behind, so the range is checked again on the current transforms
+        let candidates = grids
+            .robots
+            .within(tower_tr.translation, range)
+            .filter_map(|(entity, _)| q_enemies.get(entity).ok())
+            .filter(|(_, tr, _)| in_range(tr));
+        // lower score is a better target
+        let score = |(_, enemy_tr, health): &(Entity, &Transform, &Health)| -> f32 {
+            match mode {
+                TargetingMode::Closest | TargetingMode::First => {
+                    (enemy_tr.translation.xz() - tower_tr.translation.xz()).length()
+                }
+                TargetingMode::LowestHealth => health.current as f32,
+                TargetingMode::Strongest => -(health.current as f32),
+                TargetingMode::NearestToTree => grids
+                    .trees
+                    .nearest(enemy_tr.translation)
+                    .map_or(f32::MAX, |(distance, _)| distance.sqrt()),
             }
-        }
+        };
 
-        if curr_target_distance > TOWER_RANGE {
-            target.0 = Entity::PLACEHOLDER;
+        let keep_current = *mode == TargetingMode::First
+            && q_enemies
+                .get(target.0)
+                .is_ok_and(|(_, enemy_tr, _)| in_range(enemy_tr));
+        if !keep_current {
+            target.0 = candidates
+                .min_by(|a, b| score(a).total_cmp(&score(b)))
+                .map(|(entity, _, _)| entity)
+                .unwrap_or(Entity::PLACEHOLDER);
         }
+    }
+}
 
-        painter.color = Color::GREEN;
+// every frame, the targets are only picked on fixed steps
+fn draw_tower_targets(
+    mut painter: ShapePainter,
+    q_tower: Query<(&TowerTarget, &TowerRange, &Transform)>,
+    q_enemies: Query<&Transform, With<RobotTag>>,
+    settings: Res<Settings>,
+    weather: Res<CurrentWeather>,
+) {
+    let accessibility = &settings.accessibility;
+    for (target, range, tower_tr) in &q_tower {
+        // fog shortens every tower's range, the drawn circle shows it
+        let range = range.0 * weather.weather.tower_range_mul();
+        painter.color = accessibility.palette.remap(Color::GREEN);
         painter.thickness = 0.03;
         painter.hollow = true;
         painter.set_rotation(Quat::from_rotation_x(TAU / 4.0));
         painter.set_translation(vec3(tower_tr.translation.x, 0.0, tower_tr.translation.z));
-        painter.circle(TOWER_RANGE);
+        painter.circle(range);
 
         // highlight targeted enemy
-        if let Ok((_, target_pos)) = q_enemies.get(target.0) {
-            painter.color = Color::RED;
+        if let Ok(target_pos) = q_enemies.get(target.0) {
+            painter.color = accessibility.palette.remap(Color::RED);
             painter.thickness = 0.01;
             painter.hollow = true;
             painter.set_rotation(Quat::from_rotation_x(TAU / 4.0));
             painter.set_translation(target_pos.translation);
-            painter.circle(1.0);
+            // squares for targets, circles for ranges
+            match accessibility.shape_coding {
+                true => painter.rect(Vec2::splat(2.0)),
+                false => painter.circle(1.0),
+            };
 
             painter.set_translation(Vec3::ZERO);
             painter.set_rotation(Quat::default());
@@ -144,6 +267,7 @@ fn tower_shoot(
                 caster_entity: tower_e,
                 target_entity: Some(tower_target.0),
                 dir,
+                charge: 0.0,
             });
         }
     }
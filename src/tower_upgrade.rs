@@ -0,0 +1,339 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+};
+use serde::Deserialize;
+
+use crate::{
+    asset_utils::CustomAssetLoaderError,
+    demolish::BuildCost,
+    depot::{DepotStock, DepotStockMut},
+    health::FlashedMaterial,
+    inventory::{Inventory, Item},
+    player::PlayerControllerTag,
+    selection::{Selection, SelectionActionsTag},
+    tower::{TargetingMode, TowerRange, TowerTag},
+    ui_util::{JustClicked, UiAssets},
+    weapon::{Weapon, WeaponStats},
+};
+
+pub struct TowerUpgradePlugin;
+
+impl Plugin for TowerUpgradePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<TowerTiersAsset>()
+            .init_asset_loader::<TowerTiersAssetLoader>()
+            .add_systems(Startup, setup_tower_tiers)
+            // the selection panel it goes in is spawned on startup
+            .add_systems(PostStartup, setup_tower_panel)
+            .add_systems(
+                Update,
+                (handle_panel_click, tint_towers, update_tower_panel).chain(),
+            );
+    }
+}
+
+/// One step on the upgrade path, the first tier is what a tower is built as.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TowerTier {
+    // items spent to reach this tier
+    pub cost: Vec<(Item, u32)>,
+    pub range: f32,
+    pub damage_add: i32,
+    pub cooldown_mul: f32,
+    // path to the weapon asset, decides which projectile is shot
+    pub weapon: String,
+    pub scale: f32,
+    pub tint: (f32, f32, f32),
+}
+
+#[derive(Debug, Deserialize, Asset, TypePath)]
+pub struct TowerTiersAsset(pub Vec<TowerTier>);
+
+#[derive(Resource)]
+pub struct TowerTiers(pub Handle<TowerTiersAsset>);
+
+/// Index into `TowerTiersAsset`.
+#[derive(Component, Default)]
+pub struct TowerLevel(pub usize);
+
+// tier the tower materials were last tinted for
+#[derive(Component)]
+struct TintedTier(usize);
+
+// shown in the selection panel while a tower is selected
+#[derive(Component)]
+struct TowerPanelTag;
+
+#[derive(Component)]
+struct TowerPanelText;
+
+#[derive(Component)]
+enum TowerPanelButton {
+    Upgrade,
+    Targeting,
+}
+
+fn setup_tower_tiers(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(TowerTiers(asset_server.load("towers.tower.ron")));
+}
+
+fn setup_tower_panel(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    actions: Query<Entity, With<SelectionActionsTag>>,
+) {
+    let text_style = TextStyle {
+        font: ui_assets.font.clone(),
+        font_size: 21.0,
+        color: Color::BLACK,
+    };
+    // labels are filled in by update_tower_panel
+    let button = |parent: &mut ChildBuilder, kind: TowerPanelButton| {
+        parent
+            .spawn((
+                kind,
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::all(Val::Px(5.0)),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section("", text_style.clone()));
+            });
+    };
+    commands
+        .spawn((
+            TowerPanelTag,
+            NodeBundle {
+                style: Style {
+                    row_gap: Val::Px(5.0),
+                    flex_direction: FlexDirection::Column,
+                    display: Display::None,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TowerPanelText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: ui_assets.font.clone(),
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                    },
+                ),
+            ));
+            button(parent, TowerPanelButton::Upgrade);
+            button(parent, TowerPanelButton::Targeting);
+        })
+        .set_parent(actions.single());
+}
+
+fn handle_panel_click(
+    buttons: Query<&TowerPanelButton, With<JustClicked>>,
+    selection: Res<Selection>,
+    mut towers: Query<(
+        &mut TowerLevel,
+        &mut TowerRange,
+        &mut WeaponStats,
+        &mut Weapon,
+        &mut Transform,
+        &mut TargetingMode,
+        &mut BuildCost,
+    )>,
+    mut player: Query<&mut Inventory, With<PlayerControllerTag>>,
+    mut depot_stock: DepotStockMut,
+    tower_tiers: Res<TowerTiers>,
+    tower_tier_assets: Res<Assets<TowerTiersAsset>>,
+    asset_server: Res<AssetServer>,
+) {
+    for button in buttons.iter() {
+        let Some(Ok((
+            mut level,
+            mut range,
+            mut stats,
+            mut weapon,
+            mut transform,
+            mut mode,
+            mut build_cost,
+        ))) = selection.0.map(|e| towers.get_mut(e))
+        else {
+            continue;
+        };
+        match button {
+            TowerPanelButton::Upgrade => {}
+            TowerPanelButton::Targeting => {
+                *mode = mode.next();
+                continue;
+            }
+        }
+        let Some(tiers) = tower_tier_assets.get(&tower_tiers.0) else {
+            continue;
+        };
+        let Some(next) = tiers.0.get(level.0 + 1) else {
+            continue;
+        };
+        let Ok(mut inventory) = player.get_single_mut() else {
+            continue;
+        };
+        if !depot_stock.spend_items(&mut inventory, next.cost.iter().copied()) {
+            continue;
+        }
+        build_cost.add(&next.cost);
+        level.0 += 1;
+        range.0 = next.range;
+        stats.damage_add = next.damage_add;
+        stats.cooldown_mul = next.cooldown_mul;
+        weapon.0 = asset_server.load(&next.weapon);
+        transform.scale = Vec3::splat(next.scale);
+    }
+}
+
+// the tower model is a scene, so every material below the tower gets its own tinted copy.
+// a flashing tower has the copy put back once the flash ends
+fn tint_towers(
+    mut commands: Commands,
+    towers: Query<(Entity, &TowerLevel, Option<&TintedTier>), With<TowerTag>>,
+    children: Query<&Children>,
+    mut material_handles: Query<(&mut Handle<StandardMaterial>, Option<&mut FlashedMaterial>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    tower_tiers: Res<TowerTiers>,
+    tower_tier_assets: Res<Assets<TowerTiersAsset>>,
+) {
+    let Some(tiers) = tower_tier_assets.get(&tower_tiers.0) else {
+        return;
+    };
+    for (entity, level, tinted) in towers.iter() {
+        // built towers keep the model's own colors
+        if tinted.map_or(level.0 == 0, |t| t.0 == level.0) {
+            continue;
+        }
+        let Some(tier) = tiers.0.get(level.0) else {
+            continue;
+        };
+        let mut tinted_any = false;
+        for child in children.iter_descendants(entity) {
+            let Ok((mut handle, flashed)) = material_handles.get_mut(child) else {
+                continue;
+            };
+            let source = flashed.as_ref().map_or(handle.as_ref(), |f| &f.0);
+            let Some(mut material) = materials.get(source).cloned() else {
+                continue;
+            };
+            let (r, g, b) = tier.tint;
+            material.base_color = Color::rgb(r, g, b);
+            let tinted = materials.add(material);
+            match flashed {
+                Some(mut flashed) => flashed.0 = tinted,
+                None => *handle = tinted,
+            }
+            tinted_any = true;
+        }
+        // the scene might not be spawned yet, try again next frame
+        if tinted_any {
+            commands.entity(entity).insert(TintedTier(level.0));
+        }
+    }
+}
+
+fn update_tower_panel(
+    selection: Res<Selection>,
+    towers: Query<(&TowerLevel, &TowerRange, &TargetingMode)>,
+    player: Query<&Inventory, With<PlayerControllerTag>>,
+    depot_stock: DepotStock,
+    tower_tiers: Res<TowerTiers>,
+    tower_tier_assets: Res<Assets<TowerTiersAsset>>,
+    mut panel: Query<&mut Style, With<TowerPanelTag>>,
+    buttons: Query<(&TowerPanelButton, &Children)>,
+    mut texts: Query<&mut Text>,
+    panel_text: Query<Entity, With<TowerPanelText>>,
+) {
+    let tower = selection.0.and_then(|e| towers.get(e).ok());
+    let Ok(mut style) = panel.get_single_mut() else {
+        return;
+    };
+    let (Some((level, range, mode)), Some(tiers)) = (tower, tower_tier_assets.get(&tower_tiers.0))
+    else {
+        style.display = Display::None;
+        return;
+    };
+    style.display = Display::Flex;
+
+    if let Some(mut text) = panel_text
+        .get_single()
+        .ok()
+        .and_then(|e| texts.get_mut(e).ok())
+    {
+        text.sections[0].value = format!(
+            "Tier {}/{}, range {:.0}",
+            level.0 + 1,
+            tiers.0.len(),
+            range.0,
+        );
+    }
+    let upgrade_label = match tiers.0.get(level.0 + 1) {
+        Some(next) => {
+            let cost = next
+                .cost
+                .iter()
+                .map(|(item, count)| format!("{count} {item}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let affordable = player.get_single().is_ok_and(|inventory| {
+                depot_stock
+                    .combined(inventory)
+                    .has_items(next.cost.iter().copied())
+            });
+            match affordable {
+                true => format!("Upgrade ({cost})"),
+                false => format!("Need {cost}"),
+            }
+        }
+        None => String::from("Max tier"),
+    };
+    for (button, children) in buttons.iter() {
+        let label = match button {
+            TowerPanelButton::Upgrade => upgrade_label.clone(),
+            TowerPanelButton::Targeting => format!("Targeting: {}", mode.name()),
+        };
+        let mut iter = texts.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TowerTiersAssetLoader;
+
+impl AssetLoader for TowerTiersAssetLoader {
+    type Asset = TowerTiersAsset;
+    type Settings = ();
+    type Error = CustomAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let asset = ron::de::from_bytes::<TowerTiersAsset>(&bytes)?;
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tower.ron"]
+    }
+}
@@ -1,37 +1,123 @@
-use bevy::{math::vec3, prelude::*};
+use std::f32::consts::TAU;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    math::vec3,
+    prelude::*,
+    utils::HashMap,
+};
 use bevy_rapier3d::{prelude::*, rapier::prelude::JointAxis};
-use rand::{thread_rng, Rng};
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use serde::Deserialize;
 
 use crate::{
+    asset_utils::CustomAssetLoaderError,
     collision_groups::{
-        COLLISION_CHARACTER, COLLISION_NO_PHYSICS, COLLISION_PROJECTILES, COLLISION_TREES,
-        COLLISION_WORLD,
+        COLLISION_CHARACTER, COLLISION_NO_PHYSICS, COLLISION_POINTER, COLLISION_PROJECTILES,
+        COLLISION_TREES, COLLISION_WORLD,
     },
+    fruit::FruitTree,
     health::{ApplyHealthEvent, DespawnOnHealth0, Health, HealthRoot},
-    inventory::Item,
-    item_pickups::{SpawnItemEvent, SpawnItemEvery},
+    item_pickups::{DropSource, SpawnDropsEvent},
+    map_gen::{Habitat, MapLayout},
+    mixer::PlaySfxEvent,
+    pathfinding::NavObstacle,
+    perks::PerkModifiers,
+    settings::Settings,
 };
 
+// randomized trees are this much more likely to be a species for each of its habitats they're in
+const HABITAT_WEIGHT: f32 = 8.0;
+
+/// Scatters trees over the map, each spot that the noise allows gets one with `chance`.
 #[derive(Event)]
-pub struct TriggerSpawnTrees(pub f32);
+pub struct TriggerSpawnTrees {
+    pub chance: f32,
+    // only the land outside of this half size, after the map was expanded
+    pub outside_of: Option<Vec2>,
+}
 
 #[derive(Event)]
 pub struct SpawnTreeEvent {
     pub pos: Vec3,
     pub blueprint: TreeBlueprint,
     pub play_sound: bool,
+    // freshly planted, starts small and grows up before it drops fruit
+    pub sapling: bool,
 }
 
 // how to style tree
 pub enum TreeBlueprint {
     Randomized,
+    // a random look of this species
+    OfSpecies(Species),
     Specific {
+        species: Species,
         y_scale: f32,
         xz_scale: f32,
         tree_model: Handle<Scene>,
     },
 }
 
+/// What kind of tree it is, the stats are in trees.species.ron.
+/// Hitting one drops from its own table in drops.drops.ron.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Species {
+    Leafy,
+    Birch,
+    Pine,
+}
+
+impl Species {
+    pub fn name(self) -> &'static str {
+        match self {
+            Species::Leafy => "Leafy tree",
+            Species::Birch => "Birch",
+            Species::Pine => "Pine",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TreeSpecies {
+    pub health: i32,
+    // 2.0 grows up twice as fast
+    pub growth_speed: f32,
+    // dropped every now and then once grown, None bears no fruit
+    pub fruit: Option<DropSource>,
+    // how often randomized trees pick it
+    pub weight: f32,
+    // where it's picked more often, see HABITAT_WEIGHT
+    #[serde(default)]
+    pub habitats: Vec<Habitat>,
+    // file names in models/trees
+    pub models: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Asset, TypePath)]
+pub struct TreeSpeciesAsset(pub HashMap<Species, TreeSpecies>);
+
+impl TreeSpeciesAsset {
+    fn get(&self, species: Species) -> Option<(Species, &TreeSpecies)> {
+        self.0.get(&species).map(|s| (species, s))
+    }
+
+    fn pick_at(&self, pos: Vec3, layout: &MapLayout) -> Option<(Species, &TreeSpecies)> {
+        let all = self.0.iter().collect::<Vec<_>>();
+        all.choose_weighted(&mut thread_rng(), |(_, s)| {
+            s.habitats
+                .iter()
+                .filter(|h| layout.is_habitat(pos, **h))
+                .fold(s.weight, |weight, _| weight * HABITAT_WEIGHT)
+        })
+        .ok()
+        .map(|(species, s)| (**species, *s))
+    }
+}
+
+#[derive(Resource)]
+pub struct TreeSpeciesTable(pub Handle<TreeSpeciesAsset>);
+
 #[derive(Component)]
 pub struct TreeRootTag;
 
@@ -42,14 +128,69 @@ pub struct TreeTrunkTag;
 #[derive(Resource)]
 pub struct TreeModels(Vec<Handle<Scene>>);
 
+// saplings are a bush until they grow into their tree model
+#[derive(Resource)]
+pub struct SaplingModel(Handle<Scene>);
+
+const SAPLING_SCALE: f32 = 1.5;
+// used until the species are loaded
+const GROWN_HEALTH: i32 = 6;
+// scale and max health, as parts of the grown tree's, of every stage after the sapling
+const GROWTH_STAGES: [(f32, f32); 2] = [(0.5, 0.6), (1.0, 1.0)];
+const SAPLING_HEALTH: i32 = 2;
+const GROWTH_STAGE_SECONDS: f32 = 20.0;
+// radians a hit tips a tree without physics, about as far as the joint lets it
+const SHAKE_ANGLE: f32 = 0.15;
+const SHAKE_SECONDS: f32 = 0.6;
+// back and forth swings per second
+const SHAKE_FREQUENCY: f32 = 5.0;
+
+/// Wobbles a tree without physics after it's hit, see GeneralSettings::tree_physics.
+#[derive(Component, Default)]
+pub struct TreeShake {
+    // tips around this, away from whoever hit it
+    axis: Vec3,
+    time_left: f32,
+}
+
+/// A planted tree that hasn't grown up yet, no fruit until it has.
+#[derive(Component)]
+pub struct Growing {
+    stage: usize,
+    timer: Timer,
+    model: Handle<Scene>,
+    scale: Vec3,
+    max_health: i32,
+    fruit: Option<DropSource>,
+}
+
+impl Growing {
+    // seconds until fully grown
+    pub fn time_left(&self) -> f32 {
+        let stages_left = GROWTH_STAGES.len() - self.stage - 1;
+        self.timer.remaining_secs() + stages_left as f32 * self.timer.duration().as_secs_f32()
+    }
+}
+
 pub struct TreePlugin;
 
 impl Plugin for TreePlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<SpawnTreeEvent>()
+        app.init_asset::<TreeSpeciesAsset>()
+            .init_asset_loader::<TreeSpeciesAssetLoader>()
+            .add_event::<SpawnTreeEvent>()
             .add_event::<TriggerSpawnTrees>()
             .add_systems(Startup, setup_tree_resources)
-            .add_systems(Update, (spawn_trees, shake_on_health, spawn_log_on_health));
+            .add_systems(
+                Update,
+                (
+                    spawn_trees,
+                    grow_trees,
+                    shake_on_health,
+                    animate_shake.after(shake_on_health),
+                    spawn_log_on_health,
+                ),
+            );
     }
 }
 
@@ -57,14 +198,12 @@ fn shake_on_health(
     mut events: EventReader<ApplyHealthEvent>,
     transforms: Query<&GlobalTransform>,
     mut trees_impulse: Query<&mut ExternalImpulse>,
+    mut tree_shakes: Query<&mut TreeShake>,
 ) {
     for event in events.read() {
         if event.amount >= 0 || event.target_entity == event.caster_entity {
             continue;
         }
-        let Ok(mut tree_impulse) = trees_impulse.get_mut(event.target_entity) else {
-            continue;
-        };
         // get dir
         let Ok(transform) = transforms.get(event.caster_entity) else {
             continue;
@@ -75,23 +214,53 @@ fn shake_on_health(
         let caster_pos = transform.translation();
         let target_pos = transform_2.translation();
         let mut dir = (caster_pos - target_pos).normalize_or_zero();
+        if let Ok(mut shake) = tree_shakes.get_mut(event.target_entity) {
+            shake.axis = Vec3::Y.cross(-dir).try_normalize().unwrap_or(Vec3::X);
+            shake.time_left = SHAKE_SECONDS;
+            continue;
+        }
+        let Ok(mut tree_impulse) = trees_impulse.get_mut(event.target_entity) else {
+            continue;
+        };
         dir.y = -0.3;
         let power = 20.0;
         tree_impulse.impulse = -dir * power;
     }
 }
 
+// swings back and forth, settling down as the shake runs out
+fn animate_shake(mut trees: Query<(&mut TreeShake, &mut Transform)>, time: Res<Time>) {
+    for (mut shake, mut transform) in trees.iter_mut() {
+        if shake.time_left <= 0.0 {
+            continue;
+        }
+        shake.time_left = (shake.time_left - time.delta_seconds()).max(0.0);
+        let elapsed = SHAKE_SECONDS - shake.time_left;
+        let swing = (elapsed * SHAKE_FREQUENCY * TAU).sin() * shake.time_left / SHAKE_SECONDS;
+        transform.rotation = Quat::from_axis_angle(shake.axis, swing * SHAKE_ANGLE);
+    }
+}
+
 fn spawn_log_on_health(
     mut events: EventReader<ApplyHealthEvent>,
     transforms: Query<&GlobalTransform>,
-    mut log_spawn_events: EventWriter<SpawnItemEvent>,
+    species: Query<&Species>,
+    mut spawn_drops_events: EventWriter<SpawnDropsEvent>,
 ) {
     for event in events.read() {
+        // heals and repairs aren't hits
+        if event.amount >= 0 {
+            continue;
+        }
         let Ok(transform) = transforms.get(event.target_entity) else {
             continue;
         };
-        log_spawn_events.send(SpawnItemEvent {
-            item: Item::Log,
+        let source = match species.get(event.target_entity) {
+            Ok(species) => DropSource::Tree(*species),
+            Err(_) => DropSource::Hit,
+        };
+        spawn_drops_events.send(SpawnDropsEvent {
+            source,
             pos: transform.translation() + Vec3::Y,
         });
     }
@@ -101,30 +270,51 @@ pub fn spawn_trees(
     mut events: EventReader<SpawnTreeEvent>,
     mut commands: Commands,
     tree_models: Res<TreeModels>,
-    time: Res<Time>,
+    sapling_model: Res<SaplingModel>,
+    species_table: Res<TreeSpeciesTable>,
+    species_assets: Res<Assets<TreeSpeciesAsset>>,
     asset_server: Res<AssetServer>,
+    perks: Res<PerkModifiers>,
+    layout: Res<MapLayout>,
+    settings: Res<Settings>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
 ) {
+    let all_species = species_assets.get(&species_table.0);
     for event in events.read() {
         if event.play_sound {
-            commands.spawn(AudioBundle {
-                source: asset_server.load("sounds/plant_tree.ogg"),
-                settings: PlaybackSettings::DESPAWN,
-            });
+            sfx_events.send(PlaySfxEvent::new("plant_tree").at(event.pos));
         }
-        let (model_handle, y_scale, xz_scale) = match &event.blueprint {
-            TreeBlueprint::Randomized => {
+        let (species, model_handle, y_scale, xz_scale) = match &event.blueprint {
+            TreeBlueprint::Randomized | TreeBlueprint::OfSpecies(_) => {
                 let mut rng = rand::thread_rng();
-                let model = tree_models.0[rng.gen_range(0..tree_models.0.len())].clone();
+                let species = match event.blueprint {
+                    TreeBlueprint::OfSpecies(species) => all_species.and_then(|s| s.get(species)),
+                    _ => all_species.and_then(|s| s.pick_at(event.pos, &layout)),
+                };
+                let model = match species.and_then(|(_, s)| s.models.choose(&mut rng)) {
+                    Some(name) => asset_server.load(format!("models/trees/{name}.gltf#Scene0")),
+                    // species aren't loaded yet
+                    None => tree_models.0[rng.gen_range(0..tree_models.0.len())].clone(),
+                };
                 let y_scale = rng.gen_range(0.4..=0.9);
                 let xz_scale = y_scale * rng.gen_range(0.5..=0.9);
-                (model, y_scale, xz_scale)
+                (species, model, y_scale, xz_scale)
             }
             TreeBlueprint::Specific {
+                species,
                 y_scale,
                 xz_scale,
                 tree_model,
-            } => (tree_model.clone(), *y_scale, *xz_scale),
+            } => {
+                let species = all_species.and_then(|s| s.get(*species));
+                (species, tree_model.clone(), *y_scale, *xz_scale)
+            }
         };
+        let (max_health, growth_speed, fruit) = match species {
+            Some((_, s)) => (s.health, s.growth_speed, s.fruit),
+            None => (GROWN_HEALTH, 1.0, Some(DropSource::BananaTree)),
+        };
+        let max_health = max_health + perks.tree_health;
 
         let joint = SphericalJointBuilder::new()
             .local_anchor1(vec3(0.0, 0.4, 0.0))
@@ -148,39 +338,30 @@ pub fn spawn_trees(
 
         let collider_height = 2.0;
         let collider_radius = 0.2;
+        let scale = vec3(xz_scale, y_scale, xz_scale);
+        let (scene, scene_scale, health) = match event.sapling {
+            true => (
+                sapling_model.0.clone(),
+                Vec3::splat(SAPLING_SCALE),
+                SAPLING_HEALTH,
+            ),
+            false => (model_handle.clone(), scale, max_health),
+        };
         let child = commands
             .spawn((
                 TreeTrunkTag,
-                DespawnOnHealth0,
-                Health::new(6),
-                SpawnItemEvery {
-                    range: 5.0..20.0,
-                    item: if rand::thread_rng().gen_bool(0.1) {
-                        Item::Apple
-                    } else {
-                        Item::Banana
-                    },
-                    next: time.elapsed_seconds_f64() + thread_rng().gen_range(5.0..120.0),
+                NavObstacle {
+                    radius: collider_radius,
                 },
+                DespawnOnHealth0,
+                Health::new(health),
                 SceneBundle {
-                    scene: model_handle,
+                    scene,
                     transform: Transform::from_translation(vec3(0.0, collider_radius + 0.2, 0.0))
-                        .with_scale(vec3(xz_scale, y_scale, xz_scale)),
+                        .with_scale(scene_scale),
                     ..default()
                 },
-                RigidBody::Dynamic,
                 Collider::capsule(Vec3::ZERO, vec3(0.0, collider_height, 0.0), collider_radius),
-                ColliderMassProperties::Mass(1.0),
-                GravityScale(-3.0),
-                ExternalImpulse {
-                    impulse: Vec3::ZERO,
-                    torque_impulse: Vec3::ZERO,
-                },
-                Damping {
-                    linear_damping: 1.0,
-                    angular_damping: 1.0,
-                },
-                ImpulseJoint::new(root, joint),
                 // EXPLANATION: see docs/physics.txt
                 CollisionGroups::new(
                     Group::from_bits(COLLISION_TREES | COLLISION_WORLD).unwrap(), // group 0: character
@@ -189,7 +370,48 @@ pub fn spawn_trees(
                 ),
             ))
             .id();
+        match settings.general.tree_physics {
+            true => {
+                commands.entity(child).insert((
+                    RigidBody::Dynamic,
+                    ColliderMassProperties::Mass(1.0),
+                    GravityScale(-3.0),
+                    ExternalImpulse {
+                        impulse: Vec3::ZERO,
+                        torque_impulse: Vec3::ZERO,
+                    },
+                    Damping {
+                        linear_damping: 1.0,
+                        angular_damping: 1.0,
+                    },
+                    ImpulseJoint::new(root, joint),
+                ));
+            }
+            false => {
+                commands
+                    .entity(child)
+                    .insert((RigidBody::Fixed, TreeShake::default()));
+            }
+        }
         commands.entity(child).set_parent(root);
+        if let Some((species, _)) = species {
+            commands.entity(child).insert(species);
+        }
+        if event.sapling {
+            commands.entity(child).insert(Growing {
+                stage: 0,
+                timer: Timer::from_seconds(GROWTH_STAGE_SECONDS / growth_speed, TimerMode::Once),
+                model: model_handle,
+                scale,
+                max_health,
+                fruit,
+            });
+        } else if let Some(source) = fruit {
+            // spread out so the whole forest doesn't fruit at once
+            commands
+                .entity(child)
+                .insert(FruitTree::new(source, thread_rng().gen_range(5.0..60.0)));
+        }
 
         // make hit box larger for projectiles
         commands.entity(child).with_children(|parent| {
@@ -209,18 +431,74 @@ pub fn spawn_trees(
             ));
         });
 
-        // anti gravity mass to make trees stand up
+        // lets the tree be clicked to select it, a sensor so robots walk through it
         commands.entity(child).with_children(|parent| {
             parent.spawn((
-                ColliderMassProperties::Mass(1.0),
-                GravityScale(-3.0),
-                TransformBundle::from_transform(Transform::from_translation(vec3(
-                    0.0,
-                    collider_height + 5.0,
-                    0.0,
-                ))),
+                Collider::capsule(
+                    Vec3::ZERO,
+                    vec3(0.0, collider_height, 0.0),
+                    collider_radius * 1.5,
+                ),
+                Sensor,
+                // EXPLANATION: see docs/physics.txt
+                CollisionGroups::new(
+                    Group::from_bits(COLLISION_POINTER).unwrap(),
+                    Group::from_bits(COLLISION_POINTER).unwrap(),
+                ),
+                ColliderMassProperties::Mass(0.0),
             ));
         });
+
+        // anti gravity mass to make trees stand up
+        if settings.general.tree_physics {
+            commands.entity(child).with_children(|parent| {
+                parent.spawn((
+                    ColliderMassProperties::Mass(1.0),
+                    GravityScale(-3.0),
+                    TransformBundle::from_transform(Transform::from_translation(vec3(
+                        0.0,
+                        collider_height + 5.0,
+                        0.0,
+                    ))),
+                ));
+            });
+        }
+    }
+}
+
+// saplings get their tree model on the first stage, and fruit once fully grown
+fn grow_trees(
+    mut commands: Commands,
+    mut trees: Query<(
+        Entity,
+        &mut Growing,
+        &mut Handle<Scene>,
+        &mut Transform,
+        &mut Health,
+    )>,
+    time: Res<Time>,
+) {
+    for (entity, mut growing, mut scene, mut transform, mut health) in trees.iter_mut() {
+        if !growing.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        let (scale, health_part) = GROWTH_STAGES[growing.stage];
+        let max_health = (growing.max_health as f32 * health_part).ceil() as i32;
+        *scene = growing.model.clone();
+        transform.scale = growing.scale * scale;
+        health.current += max_health - health.max;
+        health.max = max_health;
+        if growing.stage + 1 < GROWTH_STAGES.len() {
+            growing.stage += 1;
+            growing.timer.reset();
+            continue;
+        }
+        commands.entity(entity).remove::<Growing>();
+        if let Some(source) = growing.fruit {
+            commands
+                .entity(entity)
+                .insert(FruitTree::new(source, thread_rng().gen_range(5.0..20.0)));
+        }
     }
 }
 
@@ -233,4 +511,35 @@ fn setup_tree_resources(mut commands: Commands, asset_server: Res<AssetServer>)
     .map(|name| asset_server.load(format!("models/trees/{}.gltf#Scene0", name)))
     .collect::<Vec<_>>();
     commands.insert_resource(TreeModels(models));
+    commands.insert_resource(SaplingModel(
+        asset_server.load("models/foliage/foliage_2.gltf#Scene0"),
+    ));
+    commands.insert_resource(TreeSpeciesTable(asset_server.load("trees.species.ron")));
+}
+
+#[derive(Default)]
+pub struct TreeSpeciesAssetLoader;
+
+impl AssetLoader for TreeSpeciesAssetLoader {
+    type Asset = TreeSpeciesAsset;
+    type Settings = ();
+    type Error = CustomAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let asset = ron::de::from_bytes::<TreeSpeciesAsset>(&bytes)?;
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["species.ron"]
+    }
 }
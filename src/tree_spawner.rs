@@ -12,7 +12,15 @@ use rand::Rng;
 use crate::{
     animation_linker::AnimationEntityLink,
     collision_groups::{COLLISION_CHARACTER, COLLISION_PROJECTILES, COLLISION_WORLD},
-    health::Health,
+    demolish::BuildCost,
+    health::{Health, ShowHealthBar},
+    inventory::Item,
+    knockback::KnockbackResistance,
+    map_gen::MapLayout,
+    mixer::PlaySfxEvent,
+    pathfinding::NavObstacle,
+    sapper::StructureTag,
+    settings::Settings,
     tree::{SpawnTreeEvent, TreeBlueprint},
 };
 
@@ -45,9 +53,18 @@ pub struct TreeSpawner {
     timer: Timer,
 }
 
+impl TreeSpawner {
+    /// Seconds until the next tree is planted.
+    pub fn time_left(&self) -> f32 {
+        self.timer.remaining_secs()
+    }
+}
+
 #[derive(Event)]
 pub struct SpawnTreeSpawnerEvent {
     pub pos: Vec3,
+    // refunded in part when it's demolished
+    pub cost: Vec<(Item, u32)>,
 }
 
 fn start_animation(
@@ -56,7 +73,7 @@ fn start_animation(
     tree_model: Res<TreeSpawnerModel>,
 ) {
     for link in query.iter() {
-        let Ok(mut player) = players.get_mut(link.0) else {
+        let Ok(mut player) = players.get_mut(link.main) else {
             continue;
         };
         player.play(tree_model.0 .1.clone()).repeat();
@@ -67,19 +84,21 @@ fn tower_spawn(
     mut cmds: Commands,
     tower_model: Res<TreeSpawnerModel>,
     mut ev_spawn_tower: EventReader<SpawnTreeSpawnerEvent>,
-    asset_server: Res<AssetServer>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
 ) {
     for ev in ev_spawn_tower.read() {
-        cmds.spawn(AudioBundle {
-            source: asset_server.load("sounds/build.ogg"),
-            settings: PlaybackSettings::DESPAWN,
-        });
+        sfx_events.send(PlaySfxEvent::new("build").at(ev.pos));
         cmds.spawn((
             Name::new("Tower"),
             TreeSpawner {
                 timer: Timer::from_seconds(TREE_SPAWNER_TIME, TimerMode::Repeating),
             },
             Health::new(TREE_SPAWNER_HEALTH),
+            ShowHealthBar,
+            StructureTag,
+            BuildCost(ev.cost.clone()),
+            KnockbackResistance(1.0),
+            NavObstacle { radius: 0.5 },
             SceneBundle {
                 scene: tower_model.0 .0.clone_weak(),
                 transform: Transform::from_translation(vec3(ev.pos.x + 1.0, 0.0, ev.pos.z)),
@@ -108,9 +127,13 @@ fn tower_spawn(
     }
 }
 
-fn visualize_range(mut painter: ShapePainter, query: Query<(&TreeSpawner, &Transform)>) {
+fn visualize_range(
+    mut painter: ShapePainter,
+    query: Query<(&TreeSpawner, &Transform)>,
+    settings: Res<Settings>,
+) {
     for (_, transform) in query.iter() {
-        painter.color = Color::YELLOW;
+        painter.color = settings.accessibility.palette.remap(Color::YELLOW);
         painter.thickness = 0.05;
         painter.hollow = true;
         painter.set_rotation(Quat::from_rotation_x(TAU / 4.0));
@@ -123,6 +146,7 @@ fn tower_shoot(
     mut query: Query<(&mut TreeSpawner, &Transform)>,
     time: Res<Time>,
     mut spawn: EventWriter<SpawnTreeEvent>,
+    layout: Res<MapLayout>,
 ) {
     for (mut tower, transform) in query.iter_mut() {
         if !tower.timer.tick(time.delta()).just_finished() {
@@ -132,10 +156,14 @@ fn tower_shoot(
         let dist = rng.gen_range(1.0..TREE_SPAWNER_RANGE);
         let rot = Quat::from_rotation_y(rng.gen_range(0.0..TAU));
         let pos = transform.translation + rot * (Vec3::Z * dist);
+        if layout.is_blocked(pos) {
+            continue;
+        }
         spawn.send(SpawnTreeEvent {
             pos,
             blueprint: TreeBlueprint::Randomized,
             play_sound: true,
+            sapling: true,
         });
     }
 }
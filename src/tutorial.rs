@@ -0,0 +1,339 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::TypePath,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asset_utils::CustomAssetLoaderError,
+    camera::MainCameraTag,
+    combat_stats::CombatStats,
+    inventory::{Inventory, Item},
+    persistence,
+    player::{PlayerControllerTag, RobotTag},
+    shop::ShopScrollArea,
+    state::{AppState, Wave},
+    tower::TowerTag,
+    tree::TreeTrunkTag,
+    ui_util::UiAssets,
+};
+
+const TUTORIAL_FILE: &str = "tutorial.ron";
+const ARROW_SIZE: f32 = 32.0;
+// how far the world arrow bobs up and down, in pixels
+const ARROW_BOB: f32 = 6.0;
+
+pub struct TutorialPlugin;
+
+impl Plugin for TutorialPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<TutorialAsset>()
+            .init_asset_loader::<TutorialAssetLoader>()
+            .insert_resource(
+                persistence::load::<TutorialProgress>(TUTORIAL_FILE).unwrap_or_default(),
+            )
+            .add_systems(Startup, setup_tutorial)
+            .add_systems(
+                Update,
+                (advance_tutorial, update_hint_ui)
+                    .chain()
+                    .run_if(in_state(AppState::Playing)),
+            )
+            .add_systems(OnExit(AppState::Playing), hide_hint_ui);
+    }
+}
+
+/// Checked against the current run, see tutorial.tutorial.ron.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum HintCondition {
+    #[default]
+    Always,
+    // the player carries at least this many
+    Carrying(Item, u32),
+    // the wave being played is at least this one, 0 is the first
+    Wave(usize),
+    Towers(u32),
+    // robots killed by the player this run
+    Kills(u32),
+}
+
+/// What the hint arrow points at.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum HintTarget {
+    Shop,
+    NearestTree,
+    NearestRobot,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TutorialStep {
+    pub text: String,
+    #[serde(default)]
+    pub show_when: HintCondition,
+    pub done_when: HintCondition,
+    #[serde(default)]
+    pub target: Option<HintTarget>,
+}
+
+/// Hints shown one after another, each waits for the previous one to be done.
+#[derive(Debug, Deserialize, Asset, TypePath)]
+pub struct TutorialAsset(pub Vec<TutorialStep>);
+
+#[derive(Resource)]
+pub struct Tutorial(pub Handle<TutorialAsset>);
+
+// how far the player got, kept between runs so hints are only shown once
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct TutorialProgress {
+    step: usize,
+}
+
+#[derive(Component)]
+struct HintText;
+
+#[derive(Component)]
+struct HintArrow {
+    down: Handle<Image>,
+    right: Handle<Image>,
+}
+
+#[derive(SystemParam)]
+struct HintConditions<'w, 's> {
+    players: Query<'w, 's, &'static Inventory, With<PlayerControllerTag>>,
+    towers: Query<'w, 's, (), With<TowerTag>>,
+    wave: Res<'w, Wave>,
+    stats: Res<'w, CombatStats>,
+}
+
+impl HintConditions<'_, '_> {
+    fn holds(&self, condition: HintCondition) -> bool {
+        match condition {
+            HintCondition::Always => true,
+            HintCondition::Carrying(item, count) => self
+                .players
+                .iter()
+                .any(|inventory| inventory.has_items([(item, count)].into_iter())),
+            HintCondition::Wave(wave) => self.wave.0 >= wave,
+            HintCondition::Towers(count) => self.towers.iter().count() as u32 >= count,
+            HintCondition::Kills(count) => self.stats.player_kills >= count,
+        }
+    }
+}
+
+fn setup_tutorial(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    ui_assets: Res<UiAssets>,
+) {
+    commands.insert_resource(Tutorial(asset_server.load("tutorial.tutorial.ron")));
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                top: Val::Px(20.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                HintText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: ui_assets.font.clone(),
+                        font_size: 28.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    padding: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                })
+                .with_background_color(Color::BLACK.with_a(0.7)),
+                Visibility::Hidden,
+            ));
+        });
+
+    let down = asset_server.load("icons/hint_arrow_down.png");
+    commands.spawn((
+        HintArrow {
+            down: down.clone(),
+            right: asset_server.load("icons/hint_arrow_right.png"),
+        },
+        ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Px(ARROW_SIZE),
+                height: Val::Px(ARROW_SIZE),
+                ..default()
+            },
+            image: UiImage::new(down),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+}
+
+// steps that are already done when reached are skipped without being shown
+fn advance_tutorial(
+    tutorial: Res<Tutorial>,
+    tutorial_assets: Res<Assets<TutorialAsset>>,
+    mut progress: ResMut<TutorialProgress>,
+    conditions: HintConditions,
+) {
+    let Some(tutorial) = tutorial_assets.get(&tutorial.0) else {
+        return;
+    };
+    let start = progress.step;
+    while let Some(step) = tutorial.0.get(progress.step) {
+        if !conditions.holds(step.done_when) {
+            break;
+        }
+        progress.step += 1;
+    }
+    if progress.step != start {
+        persistence::save(TUTORIAL_FILE, &*progress);
+    }
+}
+
+fn update_hint_ui(
+    mut commands: Commands,
+    tutorial: Res<Tutorial>,
+    tutorial_assets: Res<Assets<TutorialAsset>>,
+    progress: Res<TutorialProgress>,
+    conditions: HintConditions,
+    mut texts: Query<(&mut Text, &mut Visibility), (With<HintText>, Without<HintArrow>)>,
+    mut arrows: Query<(
+        Entity,
+        &HintArrow,
+        &mut Style,
+        &mut UiImage,
+        &mut Visibility,
+        Option<&Parent>,
+    )>,
+    (shop_areas, camera, time, ui_scale): (
+        Query<Entity, With<ShopScrollArea>>,
+        Query<(&Camera, &GlobalTransform), With<MainCameraTag>>,
+        Res<Time>,
+        Res<UiScale>,
+    ),
+    (players, trees, robots): (
+        Query<&GlobalTransform, With<PlayerControllerTag>>,
+        Query<&GlobalTransform, With<TreeTrunkTag>>,
+        Query<&GlobalTransform, With<RobotTag>>,
+    ),
+) {
+    let step = tutorial_assets
+        .get(&tutorial.0)
+        .and_then(|t| t.0.get(progress.step))
+        .filter(|step| conditions.holds(step.show_when));
+
+    for (mut text, mut visibility) in texts.iter_mut() {
+        *visibility = match step {
+            Some(step) => {
+                if text.sections[0].value != step.text {
+                    text.sections[0].value = step.text.clone();
+                }
+                Visibility::Inherited
+            }
+            None => Visibility::Hidden,
+        };
+    }
+
+    let Ok((arrow_entity, arrow, mut style, mut image, mut visibility, parent)) =
+        arrows.get_single_mut()
+    else {
+        return;
+    };
+    *visibility = Visibility::Hidden;
+    let Some(target) = step.and_then(|step| step.target) else {
+        return;
+    };
+
+    // ui targets carry the arrow as a child, to the left of them
+    if let HintTarget::Shop = target {
+        let Ok(area) = shop_areas.get_single() else {
+            return;
+        };
+        if parent.map(|p| p.get()) != Some(area) {
+            commands.entity(area).add_child(arrow_entity);
+        }
+        image.texture = arrow.right.clone();
+        style.left = Val::Px(-ARROW_SIZE - 8.0);
+        style.top = Val::Px(8.0);
+        *visibility = Visibility::Inherited;
+        return;
+    }
+
+    if parent.is_some() {
+        commands.entity(arrow_entity).remove_parent();
+    }
+    let (Ok(player), Ok((camera, camera_transform))) = (players.get_single(), camera.get_single())
+    else {
+        return;
+    };
+    let candidates = match target {
+        HintTarget::NearestTree => trees.iter().collect::<Vec<_>>(),
+        _ => robots.iter().collect(),
+    };
+    let Some(pos) = candidates
+        .into_iter()
+        .map(|t| t.translation())
+        .min_by(|a, b| {
+            a.distance_squared(player.translation())
+                .total_cmp(&b.distance_squared(player.translation()))
+        })
+        .and_then(|pos| camera.world_to_viewport(camera_transform, pos + Vec3::Y * 2.0))
+        .map(|p| p / ui_scale.0 as f32)
+    else {
+        return;
+    };
+    let bob = (time.elapsed_seconds() * 4.0).sin() * ARROW_BOB;
+    image.texture = arrow.down.clone();
+    style.left = Val::Px(pos.x - ARROW_SIZE * 0.5);
+    style.top = Val::Px(pos.y - ARROW_SIZE + bob);
+    *visibility = Visibility::Inherited;
+}
+
+fn hide_hint_ui(
+    mut texts: Query<&mut Visibility, (With<HintText>, Without<HintArrow>)>,
+    mut arrows: Query<&mut Visibility, With<HintArrow>>,
+) {
+    for mut visibility in texts.iter_mut().chain(arrows.iter_mut()) {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+#[derive(Default)]
+pub struct TutorialAssetLoader;
+
+impl AssetLoader for TutorialAssetLoader {
+    type Asset = TutorialAsset;
+    type Settings = ();
+    type Error = CustomAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let asset = ron::de::from_bytes::<TutorialAsset>(&bytes)?;
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tutorial.ron"]
+    }
+}
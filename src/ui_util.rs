@@ -87,3 +87,15 @@ fn update_button_color(
         }
     }
 }
+
+/// Moves an absolutely positioned node to `pos` in pixels.
+/// Only writes what moved, touching the style makes bevy lay out the node again.
+pub fn set_position(style: &mut Mut<Style>, pos: Vec2) {
+    let (left, top) = (Val::Px(pos.x), Val::Px(pos.y));
+    if style.left != left {
+        style.left = left;
+    }
+    if style.top != top {
+        style.top = top;
+    }
+}
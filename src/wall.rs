@@ -0,0 +1,114 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::{Collider, CollisionGroups, Group, RigidBody};
+
+use crate::{
+    collision_groups::{COLLISION_CHARACTER, COLLISION_POINTER, COLLISION_WORLD},
+    demolish::BuildCost,
+    health::{Health, ShowHealthBar},
+    inventory::Item,
+    knockback::KnockbackResistance,
+    mixer::PlaySfxEvent,
+    pathfinding::NavObstacle,
+    sapper::StructureTag,
+};
+
+pub const WALL_LENGTH: f32 = 2.0;
+pub const WALL_HEIGHT: f32 = 1.2;
+pub const WALL_THICKNESS: f32 = 0.3;
+const WALL_HEALTH: i32 = 8;
+
+pub struct WallPlugin;
+
+impl Plugin for WallPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpawnWallEvent>()
+            .add_systems(Startup, setup_wall_assets)
+            .add_systems(Update, spawn_walls);
+    }
+}
+
+/// A short wall segment, robots walk around it or chew through it.
+#[derive(Component)]
+pub struct WallTag;
+
+#[derive(Resource)]
+pub struct WallAssets {
+    pub mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+#[derive(Event)]
+pub struct SpawnWallEvent {
+    // on the ground, the middle of the segment
+    pub pos: Vec3,
+    pub rotation: Quat,
+    // refunded in part when it's demolished
+    pub cost: Vec<(Item, u32)>,
+}
+
+fn setup_wall_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(WallAssets {
+        mesh: meshes.add(Mesh::from(shape::Box::new(
+            WALL_LENGTH,
+            WALL_HEIGHT,
+            WALL_THICKNESS,
+        ))),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.45, 0.3, 0.15),
+            perceptual_roughness: 1.0,
+            ..default()
+        }),
+    });
+}
+
+fn spawn_walls(
+    mut commands: Commands,
+    mut events: EventReader<SpawnWallEvent>,
+    wall_assets: Res<WallAssets>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
+) {
+    for ev in events.read() {
+        sfx_events.send(PlaySfxEvent::new("build").at(ev.pos));
+        commands
+            .spawn((
+                Name::new("Wall"),
+                (WallTag, StructureTag, BuildCost(ev.cost.clone())),
+                Health::new(WALL_HEALTH),
+                ShowHealthBar,
+                KnockbackResistance(1.0),
+                // the whole segment fits in a circle this big
+                NavObstacle {
+                    radius: WALL_LENGTH / 2.0,
+                },
+                PbrBundle {
+                    mesh: wall_assets.mesh.clone(),
+                    material: wall_assets.material.clone(),
+                    transform: Transform::from_translation(ev.pos + Vec3::Y * WALL_HEIGHT / 2.0)
+                        .with_rotation(ev.rotation),
+                    ..default()
+                },
+                RigidBody::Fixed,
+                Collider::cuboid(WALL_LENGTH / 2.0, WALL_HEIGHT / 2.0, WALL_THICKNESS / 2.0),
+                // EXPLANATION: see docs/physics.txt
+                CollisionGroups::new(
+                    Group::from_bits(COLLISION_WORLD).unwrap(),
+                    Group::from_bits(COLLISION_CHARACTER | COLLISION_WORLD).unwrap(),
+                ),
+            ))
+            .with_children(|parent| {
+                // lets the wall be clicked to select it
+                parent.spawn((
+                    TransformBundle::default(),
+                    Collider::cuboid(WALL_LENGTH / 2.0, WALL_HEIGHT / 2.0, WALL_THICKNESS / 2.0),
+                    CollisionGroups::new(
+                        Group::from_bits(COLLISION_POINTER).unwrap(),
+                        Group::from_bits(COLLISION_POINTER).unwrap(),
+                    ),
+                ));
+            });
+    }
+}
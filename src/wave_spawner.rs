@@ -0,0 +1,374 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+};
+use bevy_rapier3d::prelude::{Collider, CollisionGroups, Group, RigidBody, Sensor};
+use bevy_vector_shapes::{prelude::ShapePainter, shapes::DiscPainter};
+use rand::Rng;
+
+use crate::{
+    collision_groups::{COLLISION_CHARACTER, COLLISION_PROJECTILES},
+    cutscene::{input_unlocked, CutsceneStep, PlayCutsceneEvent},
+    fixed_step::FixedStepSet,
+    health::{DeathEvent, Health, ShowHealthBar},
+    notification::NotificationEvent,
+    pause::not_paused,
+    player::{Body, Controller, EnemyScaling, SpawnPlayerEvent},
+    settings::Settings,
+    state::{AppState, Wave},
+    waves::{SpawnEdge, SpawnGroup},
+    weapon::WeaponAsset,
+};
+
+// a marker shows up on the spawn point this long before the enemy does
+const TELEGRAPH_TIME: f32 = 1.0;
+const TELEGRAPH_RADIUS: f32 = 1.5;
+// seconds the camera stays on the incoming wave
+const SWEEP_TIME: f32 = 2.0;
+// portals open this far outside the walls
+const PORTAL_DISTANCE: f32 = 4.0;
+const PORTAL_RADIUS: f32 = 1.2;
+const PORTAL_HEALTH: i32 = 30;
+// enemies step out this far from the middle of their portal
+const PORTAL_SPREAD: f32 = 1.0;
+
+pub struct WaveSpawnerPlugin;
+
+impl Plugin for WaveSpawnerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WaveSpawner>()
+            .add_systems(Startup, setup_portal_assets)
+            .add_systems(
+                Update,
+                (
+                    wave_start_sweep,
+                    destroy_portals,
+                    open_portals,
+                    close_portals,
+                    draw_telegraphs,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::Playing))
+                    .run_if(not_paused),
+            )
+            .add_systems(
+                FixedUpdate,
+                // held while a cutscene locks input, so nothing arrives while the player can't move
+                tick_wave_spawner
+                    .in_set(FixedStepSet::Simulate)
+                    .run_if(in_state(AppState::Playing))
+                    .run_if(not_paused)
+                    .run_if(input_unlocked),
+            )
+            .add_systems(Update, pulse_portals);
+    }
+}
+
+struct QueuedSpawn {
+    time_left: f32,
+    pos: Vec3,
+    // index into WaveSpawner::portals
+    portal: usize,
+    body: Body,
+    weapon: Handle<WeaponAsset>,
+    scaling: EnemyScaling,
+}
+
+struct QueuedPortal {
+    pos: Vec3,
+    edge: SpawnEdge,
+    // None until open_portals spawns it, and again once it's closed or destroyed
+    entity: Option<Entity>,
+}
+
+/// Where the enemies of a wave come out, shooting it down cuts off the rest of its enemies.
+#[derive(Component)]
+pub struct Portal;
+
+// the swirl inside the ring
+#[derive(Component)]
+struct PortalSwirl;
+
+#[derive(Resource)]
+struct PortalAssets {
+    ring_mesh: Handle<Mesh>,
+    ring_material: Handle<StandardMaterial>,
+    swirl_mesh: Handle<Mesh>,
+    swirl_material: Handle<StandardMaterial>,
+}
+
+/// Trickles the enemies of the current wave in through portals, instead of spawning them in a single frame.
+#[derive(Resource, Default)]
+pub struct WaveSpawner {
+    queued: Vec<QueuedSpawn>,
+    portals: Vec<QueuedPortal>,
+}
+
+impl WaveSpawner {
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+
+    // open portals close on their own once nothing is queued for them
+    pub fn clear(&mut self) {
+        self.queued.clear();
+        self.portals.clear();
+    }
+
+    fn has_queued(&self, portal: usize) -> bool {
+        self.queued.iter().any(|q| q.portal == portal)
+    }
+
+    // groups coming from the same side share a portal, the corners of Any get their own
+    fn portal_for(&mut self, edge: SpawnEdge, rng: &mut impl Rng, map_size_half: Vec2) -> usize {
+        if let Some(index) = (0..self.portals.len())
+            .find(|&i| edge != SpawnEdge::Any && self.portals[i].edge == edge && self.has_queued(i))
+        {
+            return index;
+        }
+        self.portals.push(QueuedPortal {
+            pos: edge.random_pos(rng, map_size_half, PORTAL_DISTANCE),
+            edge,
+            entity: None,
+        });
+        self.portals.len() - 1
+    }
+
+    /// where the next enemy will show up
+    pub fn incoming_pos(&self) -> Option<Vec3> {
+        self.queued
+            .iter()
+            .min_by(|a, b| a.time_left.total_cmp(&b.time_left))
+            .map(|q| q.pos)
+    }
+
+    /// spreads the group evenly over `duration` seconds, starting after its delay
+    pub fn queue_group(
+        &mut self,
+        group: &SpawnGroup,
+        duration: f32,
+        scaling: EnemyScaling,
+        asset_server: &AssetServer,
+        map_size_half: Vec2,
+    ) {
+        let mut rng = rand::thread_rng();
+        let weapon = asset_server.load(&group.weapon);
+        let portal = self.portal_for(group.edge, &mut rng, map_size_half);
+        for i in 0..group.count {
+            let offset = Vec3::new(
+                rng.gen_range(-PORTAL_SPREAD..PORTAL_SPREAD),
+                1.0,
+                rng.gen_range(-PORTAL_SPREAD..PORTAL_SPREAD),
+            );
+            self.queued.push(QueuedSpawn {
+                time_left: TELEGRAPH_TIME + group.delay + duration * i as f32 / group.count as f32,
+                // picked up front so the telegraph marks the right spot
+                pos: self.portals[portal].pos + offset,
+                portal,
+                body: group.body,
+                weapon: weapon.clone(),
+                scaling,
+            });
+        }
+    }
+}
+
+// the camera pans over to where the new wave comes from and back
+fn wave_start_sweep(
+    wave: Res<Wave>,
+    spawner: Res<WaveSpawner>,
+    settings: Res<Settings>,
+    mut cutscene_events: EventWriter<PlayCutsceneEvent>,
+) {
+    if !wave.is_changed() || !settings.general.wave_sweeps {
+        return;
+    }
+    let Some(pos) = spawner.incoming_pos() else {
+        return;
+    };
+    cutscene_events.send(PlayCutsceneEvent {
+        lock_input: true,
+        steps: vec![
+            CutsceneStep::FocusPoint(pos),
+            CutsceneStep::Wait(SWEEP_TIME),
+            CutsceneStep::ReleaseCamera,
+        ],
+    });
+}
+
+fn tick_wave_spawner(
+    mut spawner: ResMut<WaveSpawner>,
+    mut spawn_player_event: EventWriter<SpawnPlayerEvent>,
+    time: Res<Time>,
+) {
+    for queued in spawner.queued.iter_mut() {
+        queued.time_left -= time.delta_seconds();
+    }
+    for queued in spawner.queued.iter().filter(|q| q.time_left <= 0.0) {
+        spawn_player_event.send(SpawnPlayerEvent {
+            pos: queued.pos,
+            controller: Controller::Robot,
+            body: queued.body,
+            weapon: queued.weapon.clone(),
+            scaling: queued.scaling,
+        });
+    }
+    spawner.queued.retain(|q| q.time_left > 0.0);
+    if spawner.queued.is_empty() {
+        spawner.portals.clear();
+    }
+}
+
+fn setup_portal_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(PortalAssets {
+        ring_mesh: meshes.add(
+            shape::Torus {
+                radius: PORTAL_RADIUS,
+                ring_radius: 0.12,
+                ..default()
+            }
+            .into(),
+        ),
+        ring_material: materials.add(StandardMaterial {
+            base_color: Color::PURPLE,
+            emissive: Color::PURPLE * 2.0,
+            ..default()
+        }),
+        swirl_mesh: meshes.add(shape::Circle::new(PORTAL_RADIUS).into()),
+        swirl_material: materials.add(StandardMaterial {
+            base_color: Color::rgba(0.5, 0.1, 0.8, 0.6),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            cull_mode: None,
+            ..default()
+        }),
+    });
+}
+
+// portals stand upright facing the middle of the map
+fn open_portals(
+    mut commands: Commands,
+    mut spawner: ResMut<WaveSpawner>,
+    portal_assets: Res<PortalAssets>,
+) {
+    for index in 0..spawner.portals.len() {
+        if spawner.portals[index].entity.is_some() || !spawner.has_queued(index) {
+            continue;
+        }
+        let pos = spawner.portals[index].pos + Vec3::Y * (PORTAL_RADIUS + 0.2);
+        let entity = commands
+            .spawn((
+                Name::new("Portal"),
+                Portal,
+                Health::new(PORTAL_HEALTH),
+                ShowHealthBar,
+                SpatialBundle::from_transform(
+                    Transform::from_translation(pos).looking_at(Vec3::Y * pos.y, Vec3::Y),
+                ),
+                // enemies walk right through it, but it still catches the player's shots
+                RigidBody::Fixed,
+                Collider::ball(PORTAL_RADIUS),
+                Sensor,
+                // EXPLANATION: see docs/physics.txt
+                CollisionGroups::new(
+                    Group::from_bits(COLLISION_CHARACTER).unwrap(),
+                    Group::from_bits(COLLISION_PROJECTILES).unwrap(),
+                ),
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    NotShadowReceiver,
+                    PbrBundle {
+                        mesh: portal_assets.ring_mesh.clone(),
+                        material: portal_assets.ring_material.clone(),
+                        transform: Transform::from_rotation(Quat::from_rotation_x(TAU / 4.0)),
+                        ..default()
+                    },
+                ));
+                parent.spawn((
+                    PortalSwirl,
+                    NotShadowCaster,
+                    NotShadowReceiver,
+                    PbrBundle {
+                        mesh: portal_assets.swirl_mesh.clone(),
+                        material: portal_assets.swirl_material.clone(),
+                        ..default()
+                    },
+                ));
+            })
+            .id();
+        spawner.portals[index].entity = Some(entity);
+    }
+}
+
+// once a portal has let all of its enemies through it goes away
+fn close_portals(
+    mut commands: Commands,
+    spawner: Res<WaveSpawner>,
+    portals: Query<Entity, With<Portal>>,
+) {
+    for entity in portals.iter() {
+        let in_use = spawner
+            .portals
+            .iter()
+            .enumerate()
+            .any(|(i, p)| p.entity == Some(entity) && spawner.has_queued(i));
+        if !in_use {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// the portal itself is despawned by the health plugin
+fn destroy_portals(
+    mut death_events: EventReader<DeathEvent>,
+    mut spawner: ResMut<WaveSpawner>,
+    mut notification_event: EventWriter<NotificationEvent>,
+) {
+    for event in death_events.read() {
+        let Some(index) = spawner
+            .portals
+            .iter()
+            .position(|p| p.entity == Some(event.entity))
+        else {
+            continue;
+        };
+        spawner.portals[index].entity = None;
+        spawner.queued.retain(|q| q.portal != index);
+        notification_event.send(NotificationEvent {
+            text: "Portal destroyed".into(),
+            show_for: 2.0,
+            color: Color::GREEN,
+        });
+    }
+}
+
+fn pulse_portals(time: Res<Time>, mut swirls: Query<&mut Transform, With<PortalSwirl>>) {
+    let scale = 0.85 + (time.elapsed_seconds() * 3.0).sin() * 0.1;
+    for mut transform in swirls.iter_mut() {
+        transform.scale = Vec3::splat(scale);
+    }
+}
+
+// circles on the ground that close in as the enemy is about to appear
+fn draw_telegraphs(mut painter: ShapePainter, spawner: Res<WaveSpawner>, settings: Res<Settings>) {
+    for queued in spawner
+        .queued
+        .iter()
+        .filter(|q| q.time_left <= TELEGRAPH_TIME)
+    {
+        let progress = 1.0 - queued.time_left / TELEGRAPH_TIME;
+        painter.color = settings.accessibility.palette.remap(Color::RED);
+        painter.thickness = 0.1;
+        painter.hollow = true;
+        painter.set_rotation(Quat::from_rotation_x(TAU / 4.0));
+        painter.set_translation(queued.pos * Vec3::new(1.0, 0.0, 1.0) + Vec3::Y * 0.05);
+        painter.circle(TELEGRAPH_RADIUS * (1.0 - progress * 0.7));
+    }
+}
@@ -1,9 +1,15 @@
-use crate::{asset_utils::CustomAssetLoaderError, shop::ShopItemData};
+use crate::{
+    asset_utils::CustomAssetLoaderError,
+    player::{Body, EnemyScaling},
+    weather::Weather,
+};
 use bevy::{
     asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    math::vec3,
     prelude::*,
     reflect::TypePath,
 };
+use rand::Rng;
 use serde::Deserialize;
 
 pub struct WavePlugin;
@@ -26,7 +32,77 @@ fn setup_wave_descriptors(mut commands: Commands, asset_server: Res<AssetServer>
 pub struct WavesAssetLoader;
 
 #[derive(Debug, Deserialize, Asset, TypePath)]
-pub struct WaveDescriptorsAsset(pub Vec<WaveDescriptor>);
+pub struct WaveDescriptorsAsset {
+    pub waves: Vec<WaveDescriptor>,
+    #[serde(default)]
+    pub endless: EndlessScaling,
+}
+
+impl WaveDescriptorsAsset {
+    /// the authored wave, or past the last one a generated endless wave
+    pub fn wave(&self, index: usize) -> Option<(WaveDescriptor, EnemyScaling)> {
+        if let Some(wave) = self.waves.get(index) {
+            return Some((wave.clone(), wave.scaling()));
+        }
+        let last = self.waves.last()?;
+        Some(self.endless.generate(last, index + 1 - self.waves.len()))
+    }
+}
+
+/// How endless mode keeps growing the last wave, each generated wave applies the growth once more.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct EndlessScaling {
+    pub count_growth: f32,
+    pub health_growth: f32,
+    pub speed_growth: f32,
+    pub max_speed_mul: f32,
+    // bosses of the last wave only come back every this many waves
+    pub boss_every: usize,
+}
+
+impl Default for EndlessScaling {
+    fn default() -> Self {
+        Self {
+            count_growth: 1.2,
+            health_growth: 1.15,
+            speed_growth: 1.03,
+            max_speed_mul: 1.6,
+            boss_every: 5,
+        }
+    }
+}
+
+impl EndlessScaling {
+    fn generate(&self, last: &WaveDescriptor, extra: usize) -> (WaveDescriptor, EnemyScaling) {
+        let growth = |per_wave: f32| per_wave.powi(extra as i32);
+        let groups = last
+            .groups
+            .iter()
+            .filter(|g| g.body != Body::Boss || extra.is_multiple_of(self.boss_every.max(1)))
+            .map(|g| SpawnGroup {
+                count: match g.body {
+                    Body::Boss => g.count,
+                    _ => (g.count as f32 * growth(self.count_growth)).round() as usize,
+                },
+                ..g.clone()
+            })
+            .collect();
+        let scaling = EnemyScaling {
+            health_mul: last.health_mul * growth(self.health_growth),
+            speed_mul: growth(self.speed_growth).min(self.max_speed_mul),
+            damage_add: last.damage_add,
+        };
+        let wave = WaveDescriptor {
+            groups,
+            health_mul: scaling.health_mul,
+            damage_add: scaling.damage_add,
+            spawn_duration: last.spawn_duration,
+            weather: None,
+        };
+        (wave, scaling)
+    }
+}
 
 impl AssetLoader for WavesAssetLoader {
     type Asset = WaveDescriptorsAsset;
@@ -54,6 +130,92 @@ impl AssetLoader for WavesAssetLoader {
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct WaveDescriptor {
-    pub nb_enemies: usize,
-    pub new_shop_items: Vec<ShopItemData>,
+    pub groups: Vec<SpawnGroup>,
+    // multiplies the health of every enemy in the wave
+    #[serde(default = "default_health_mul")]
+    pub health_mul: f32,
+    // added to the damage of every enemy in the wave
+    #[serde(default)]
+    pub damage_add: i32,
+    // seconds each group's enemies are spread over
+    #[serde(default = "default_spawn_duration")]
+    pub spawn_duration: f32,
+    // held for the whole wave, otherwise the weather changes randomly
+    #[serde(default)]
+    pub weather: Option<Weather>,
+}
+
+impl WaveDescriptor {
+    pub fn scaling(&self) -> EnemyScaling {
+        EnemyScaling {
+            health_mul: self.health_mul,
+            damage_add: self.damage_add,
+            ..default()
+        }
+    }
+}
+
+/// Enemies that enter the map together.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpawnGroup {
+    pub body: Body,
+    // path to the weapon asset
+    pub weapon: String,
+    #[serde(default = "default_count")]
+    pub count: usize,
+    // seconds after the wave started
+    #[serde(default)]
+    pub delay: f32,
+    #[serde(default)]
+    pub edge: SpawnEdge,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+fn default_health_mul() -> f32 {
+    1.0
+}
+
+fn default_spawn_duration() -> f32 {
+    4.0
+}
+
+/// Side of the map a group walks in from, north is -z.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum SpawnEdge {
+    // any of the corners
+    #[default]
+    Any,
+    North,
+    East,
+    South,
+    West,
+}
+
+impl SpawnEdge {
+    /// random spot `distance` meters outside a map of `size_half` on this edge
+    pub fn random_pos(self, rng: &mut impl Rng, size_half: Vec2, distance: f32) -> Vec3 {
+        let outside = size_half + distance;
+        let along = Vec2::new(
+            rng.gen_range(-size_half.x..size_half.x),
+            rng.gen_range(-size_half.y..size_half.y),
+        );
+        let (x, z) = match self {
+            SpawnEdge::Any => (random_sign(rng) * outside.x, random_sign(rng) * outside.y),
+            SpawnEdge::North => (along.x, -outside.y),
+            SpawnEdge::East => (outside.x, along.y),
+            SpawnEdge::South => (along.x, outside.y),
+            SpawnEdge::West => (-outside.x, along.y),
+        };
+        vec3(x, 0.0, z)
+    }
+}
+
+fn random_sign(rng: &mut impl Rng) -> f32 {
+    match rng.gen::<bool>() {
+        true => 1.0,
+        false => -1.0,
+    }
 }
@@ -1,26 +1,54 @@
-use bevy::{audio::PlaybackMode, prelude::*};
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+};
 use bevy_rapier3d::prelude::{Collider, QueryFilter, RapierContext};
 use rand::Rng;
+use serde::Deserialize;
 
 use crate::{
+    ammo::{Ammo, AmmoConfig},
+    asset_utils::{maybe_load_asset, CustomAssetLoaderError},
+    fruit::{has_ripe_fruit, Fruit, FruitTree, ShakeTreeEvent},
     health::{ApplyHealthEvent, Health},
-    player::Body,
+    mixer::{PlaySfxEvent, SfxLimit},
+    pause::not_paused,
+    player::{Body, MonkeyTag, RobotTag},
     projectile::{ProjectileAsset, SpawnProjectileEvent},
+    sapper::StructureTag,
+    state::AppState,
+    status_effect::{ApplyStatusEvent, StatusEffect},
 };
 
-pub const AXE_SFX_COOLDOWN: f32 = 0.11;
-pub const PROJ_SFX_COOLDOWN: f32 = 0.3;
-pub const SLEDGEHAMMER_SFX_COOLDOWN: f32 = 0.6;
-
-#[derive(Resource)]
-pub struct AxeSfxCooldownTimer(pub f32);
-#[derive(Resource)]
-pub struct ProjSfxCooldownTimer(pub f32);
+// shots of towers and robots wait for each other, monkey shots are always heard
+const PROJ_SFX_LIMIT: SfxLimit = SfxLimit {
+    cooldown: 0.3,
+    max_playing: 4,
+};
+// melee hits wait for the weapon's hit_sound_cooldown, crits are always heard
+const MELEE_HIT_MAX_PLAYING: usize = 4;
+// melee swings in a full combo, the last one is the finisher
+pub const COMBO_LENGTH: usize = 3;
+// radians between the projectiles of one shot
+const PROJECTILE_SPREAD: f32 = 0.15;
 
 #[derive(Component, Reflect)]
 pub struct WeaponStats {
     pub cooldown_mul: f32,
     pub damage_add: i32,
+    pub knockback_mul: f32,
+    /// seconds after the cooldown ends where the next melee swing continues the combo
+    pub combo_window: f32,
+    /// extra damage for every step into a combo, 0.5 is +50% on the second swing and +100% on the third
+    pub combo_damage_mul: f32,
+    /// knockback multiplier of the combo finisher
+    pub finisher_knockback_mul: f32,
+    /// 0.0 to 1.0 chance for an attack to be a critical hit
+    pub crit_chance: f32,
+    /// damage multiplier of critical hits
+    pub crit_multiplier: f32,
+    /// projectiles fired per shot on top of the first, fanned out around the aim
+    pub extra_projectiles: u32,
 }
 
 impl Default for WeaponStats {
@@ -28,38 +56,105 @@ impl Default for WeaponStats {
         Self {
             cooldown_mul: 1.0,
             damage_add: 0,
+            knockback_mul: 1.0,
+            combo_window: 0.4,
+            combo_damage_mul: 0.5,
+            finisher_knockback_mul: 2.5,
+            crit_chance: 0.0,
+            crit_multiplier: 2.0,
+            extra_projectiles: 0,
         }
     }
 }
 
-#[derive(Component, Debug, Clone, Reflect)]
-pub enum WeaponType {
-    Axe,
-    Bow(Handle<ProjectileAsset>),
-    SledgeHammer,
+impl WeaponStats {
+    /// Rolled once per attack, returns the damage multiplier and if it was a crit.
+    pub fn roll_crit(&self) -> (f32, bool) {
+        match rand::thread_rng().gen_bool(self.crit_chance.clamp(0.0, 1.0) as f64) {
+            true => (self.crit_multiplier, true),
+            false => (1.0, false),
+        }
+    }
 }
 
-// should maybe be fetched from asssets
-impl WeaponType {
-    pub fn sound_effect(&self) -> (String, f32) {
-        let (sound_name, volume) = match self {
-            WeaponType::Axe => ("axe", 0.5),
-            WeaponType::Bow(_) => ("bow", 0.9),
-            WeaponType::SledgeHammer => ("sledgehammer", 1.0),
-        };
-        let path = format!("sounds/{}-projectile.ogg", sound_name);
-        (path, volume)
+/// Which swing of a melee combo the caster is on.
+#[derive(Component, Default, Reflect)]
+pub struct Combo {
+    pub step: usize,
+}
+
+impl Combo {
+    pub fn is_finisher(&self) -> bool {
+        self.step == COMBO_LENGTH - 1
     }
+}
 
-    pub fn cooldown(&self) -> f32 {
-        match self {
-            WeaponType::Axe => 0.4,
-            WeaponType::Bow(_) => 0.6,
-            WeaponType::SledgeHammer => 1.4,
-        }
+/// Everything about a weapon, loaded from a `.weapon.ron` file in assets/weapons.
+#[derive(Debug, Deserialize, TypePath, Asset)]
+pub struct WeaponAsset {
+    pub name: String,
+    pub cooldown: f32,
+    // id of a sound set in sounds.sfx.ron, played when the weapon is used
+    pub sound: String,
+    pub volume: f32,
+    // swing and hit everything in front of the caster
+    #[serde(default)]
+    pub melee: Option<MeleeAttack>,
+    // path to a projectile asset, leave empty for weapons that don't shoot
+    #[serde(default)]
+    pub projectile: String,
+    // the player needs ammo to use this weapon
+    #[serde(default)]
+    pub ammo: Option<AmmoConfig>,
+    // the player holds the attack to charge it up and attacks on release
+    #[serde(default)]
+    pub charge: Option<ChargeAttack>,
+    // chops like an axe, for the lumberjack achievement
+    #[serde(default)]
+    pub axe: bool,
+    #[serde(skip)]
+    pub projectile_handle: Option<Handle<ProjectileAsset>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MeleeAttack {
+    pub damage: i32,
+    pub range: f32,
+    // dot product between aim and target direction needed to hit, 0.3 is about a 90 degree swing
+    pub cone_dot: f32,
+    pub max_hits: i32,
+    // id of a sound set in sounds.sfx.ron
+    pub hit_sound: String,
+    pub hit_volume: f32,
+    // seconds between hit sounds, so crowds don't get too loud
+    pub hit_sound_cooldown: f32,
+    // applied to everything the swing hits
+    #[serde(default)]
+    pub on_hit: Option<StatusEffect>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ChargeAttack {
+    // seconds until fully charged
+    pub max_time: f32,
+    // multipliers at full charge, no charge is 1.0
+    pub damage_mul: f32,
+    pub speed_mul: f32,
+}
+
+impl ChargeAttack {
+    pub fn damage_mul(&self, charge: f32) -> f32 {
+        1.0 + (self.damage_mul - 1.0) * charge.clamp(0.0, 1.0)
+    }
+
+    pub fn speed_mul(&self, charge: f32) -> f32 {
+        1.0 + (self.speed_mul - 1.0) * charge.clamp(0.0, 1.0)
     }
 }
 
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct Weapon(pub Handle<WeaponAsset>);
+
 #[derive(Component, Reflect)]
 pub struct WeaponCooldown {
     pub time_left: f32,
@@ -71,6 +166,8 @@ pub struct TryCastWeaponEvent {
     pub caster_entity: Entity,
     pub target_entity: Option<Entity>,
     pub dir: Vec3,
+    // 0.0 to 1.0, only used by weapons with a charge attack
+    pub charge: f32,
 }
 
 // any entity can at any point execute a "spell", regardless of cooldown using this
@@ -78,17 +175,24 @@ pub struct TryCastWeaponEvent {
 pub struct CastWeaponEvent {
     pub caster_entity: Entity,
     pub target_entity: Option<Entity>,
-    weapon_type: WeaponType,
+    weapon: Handle<WeaponAsset>,
     dir: Vec3,
+    charge: f32,
 }
 
 pub struct WeaponPlugin;
 
+#[derive(Default)]
+pub struct WeaponAssetLoader;
+
 impl Plugin for WeaponPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<WeaponCooldown>()
-            .register_type::<WeaponType>()
+            .register_type::<Weapon>()
             .register_type::<WeaponStats>()
+            .register_type::<Combo>()
+            .init_asset::<WeaponAsset>()
+            .init_asset_loader::<WeaponAssetLoader>()
             .add_event::<TryCastWeaponEvent>()
             .add_event::<CastWeaponEvent>()
             .add_systems(
@@ -96,19 +200,16 @@ impl Plugin for WeaponPlugin {
                 (
                     update_cooldown,
                     promote_try_cast,
-                    (cast_axes, cast_projectiles, cast_sledgehammer),
+                    (cast_melee, cast_projectiles),
                 )
-                    .chain(),
+                    .chain()
+                    .run_if(not_paused)
+                    .run_if(in_state(AppState::Playing)),
             );
     }
 }
 
-pub fn update_cooldown(
-    mut query: Query<Option<&mut WeaponCooldown>>,
-    time: Res<Time>,
-    mut sfx_cooldown: ResMut<ProjSfxCooldownTimer>,
-) {
-    sfx_cooldown.0 += time.delta_seconds();
+pub fn update_cooldown(mut query: Query<Option<&mut WeaponCooldown>>, time: Res<Time>) {
     for mut cooldown in query.iter_mut().flatten() {
         cooldown.time_left -= time.delta_seconds();
     }
@@ -118,11 +219,16 @@ pub fn update_cooldown(
 pub fn promote_try_cast(
     mut try_events: EventReader<TryCastWeaponEvent>,
     mut events: EventWriter<CastWeaponEvent>,
-    mut weapon_query: Query<(&mut WeaponCooldown, &WeaponType, &WeaponStats)>,
+    mut weapon_query: Query<(
+        &mut WeaponCooldown,
+        &Weapon,
+        &WeaponStats,
+        Option<&mut Ammo>,
+        Option<&mut Combo>,
+    )>,
+    weapon_assets: Res<Assets<WeaponAsset>>,
     player_query: Query<&Body>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut sfx_cooldown: ResMut<ProjSfxCooldownTimer>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
 ) {
     for event in try_events.read() {
         let cast_by_monkey = player_query
@@ -130,7 +236,8 @@ pub fn promote_try_cast(
             .map(|body| *body == Body::Monkey)
             .unwrap_or(false);
 
-        let Ok((mut cooldown, weapon_type, stats)) = weapon_query.get_mut(event.caster_entity)
+        let Ok((mut cooldown, weapon, stats, ammo, combo)) =
+            weapon_query.get_mut(event.caster_entity)
         else {
             continue;
         };
@@ -138,62 +245,86 @@ pub fn promote_try_cast(
         if cooldown.time_left > 0.0 {
             continue;
         }
+        let Some(weapon_asset) = weapon_assets.get(&weapon.0) else {
+            continue;
+        };
+        // out of ammo or reloading
+        if ammo.is_some_and(|mut ammo| !ammo.try_use()) {
+            continue;
+        }
 
-        if sfx_cooldown.0 >= PROJ_SFX_COOLDOWN || cast_by_monkey {
-            let (sound_path, volume) = weapon_type.sound_effect();
-            commands.spawn(AudioBundle {
-                source: asset_server.load(sound_path),
-                settings: PlaybackSettings {
-                    volume: bevy::audio::Volume::Relative(bevy::audio::VolumeLevel::new(volume)),
-                    speed: 1.0 + rand::thread_rng().gen::<f32>(),
-                    mode: PlaybackMode::Despawn,
-                    ..Default::default()
-                },
-            });
-            sfx_cooldown.0 = 0.0;
+        let sfx = PlaySfxEvent::new(&weapon_asset.sound).with_volume(weapon_asset.volume);
+        sfx_events.send(match cast_by_monkey {
+            true => sfx,
+            false => sfx.limited(PROJ_SFX_LIMIT),
+        });
+        // swinging again soon after the cooldown ends continues the combo
+        if let Some(mut combo) = combo.filter(|_| weapon_asset.melee.is_some()) {
+            let waited = -cooldown.time_left;
+            combo.step = match waited <= stats.combo_window {
+                true => (combo.step + 1) % COMBO_LENGTH,
+                false => 0,
+            };
         }
         // yay cast spell
-        cooldown.time_left = weapon_type.cooldown() * stats.cooldown_mul;
+        cooldown.time_left = weapon_asset.cooldown * stats.cooldown_mul;
         events.send(CastWeaponEvent {
             caster_entity: event.caster_entity,
             target_entity: event.target_entity,
-            weapon_type: weapon_type.clone(),
+            weapon: weapon.0.clone(),
             dir: event.dir.try_normalize().unwrap_or(Vec3::Z),
+            charge: event.charge,
         });
     }
 }
 
-// axe behaviour
-pub fn cast_axes(
+// axe and sledgehammer behaviour
+pub fn cast_melee(
     mut events: EventReader<CastWeaponEvent>,
-    mut query: Query<(&GlobalTransform, &WeaponStats)>,
+    mut query: Query<(
+        &GlobalTransform,
+        &WeaponStats,
+        Option<&Combo>,
+        Has<MonkeyTag>,
+        Has<RobotTag>,
+    )>,
+    weapon_assets: Res<Assets<WeaponAsset>>,
     rapier_context: Res<RapierContext>,
     mut apply_health_events: EventWriter<ApplyHealthEvent>,
+    mut apply_status_events: EventWriter<ApplyStatusEvent>,
     mut gizmos: Gizmos,
-    transforms: Query<&GlobalTransform, With<Health>>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut sfx_cooldown: ResMut<AxeSfxCooldownTimer>,
-    time: Res<Time>,
+    transforms: Query<(&GlobalTransform, Has<StructureTag>), With<Health>>,
+    mut sfx_events: EventWriter<PlaySfxEvent>,
+    (fruit_trees, fruits, mut shake_events): (
+        Query<&Children, With<FruitTree>>,
+        Query<&Fruit>,
+        EventWriter<ShakeTreeEvent>,
+    ),
 ) {
     for event in events.read() {
-        let Ok((caster_transform_g, stats)) = query.get_mut(event.caster_entity) else {
+        let Ok((caster_transform_g, stats, combo, monkey, robot)) =
+            query.get_mut(event.caster_entity)
+        else {
             continue;
         };
-        let WeaponType::Axe = &event.weapon_type else {
+        let Some(weapon) = weapon_assets.get(&event.weapon) else {
             continue;
         };
+        let Some(melee) = weapon.melee.as_ref() else {
+            continue;
+        };
+        // charged swings and later swings in a combo hit harder
+        let combo_step = combo.map_or(0, |c| c.step);
+        let is_finisher = combo.is_some_and(Combo::is_finisher);
+        let (crit_mul, is_crit) = stats.roll_crit();
+        let damage_mul = weapon.charge.map_or(1.0, |c| c.damage_mul(event.charge))
+            * (1.0 + stats.combo_damage_mul * combo_step as f32)
+            * crit_mul;
 
-        let axe_range = 2.6;
-        // 90 degree swing
-        let axe_cone_dot = 0.3;
-
-        let shape = Collider::ball(axe_range);
+        let shape = Collider::ball(melee.range);
         let shape_pos = caster_transform_g.translation();
         let filter = QueryFilter::default();
-        const AXE_DAMAGE: i32 = 1;
-        let axe_damage = stats.damage_add + AXE_DAMAGE;
-        const MAX_HIT: i32 = 2;
+        let damage = ((stats.damage_add + melee.damage) as f32 * damage_mul).round() as i32;
         let mut hits = 0;
         rapier_context.intersections_with_shape(
             shape_pos,
@@ -201,15 +332,19 @@ pub fn cast_axes(
             &shape,
             filter,
             |hit_entity| {
-                let Ok(hit_transform) = transforms.get(hit_entity) else {
+                let Ok((hit_transform, is_structure)) = transforms.get(hit_entity) else {
                     return true;
                 };
+                // the player's side doesn't damage its own buildings, robots chew through them
+                if is_structure && !robot {
+                    return true;
+                }
                 let to_target = caster_transform_g.translation() - hit_transform.translation();
                 // let to_target = hit_transform.translation() - caster_transform_g.translation();
                 let to_target_dir = to_target.normalize();
                 let caster_dir = event.dir;
                 let dot = -caster_dir.dot(to_target_dir);
-                let is_outside_of_cone = dot < axe_cone_dot;
+                let is_outside_of_cone = dot < melee.cone_dot;
                 if is_outside_of_cone {
                     return true;
                 }
@@ -219,6 +354,18 @@ pub fn cast_axes(
                     // continue intersection_with_shape
                     return true;
                 }
+                // a friendly hit, monkeys harvest a tree with ripe fruit before chopping it
+                if monkey
+                    && fruit_trees
+                        .get(hit_entity)
+                        .is_ok_and(|children| has_ripe_fruit(children, &fruits))
+                {
+                    shake_events.send(ShakeTreeEvent {
+                        tree_entity: hit_entity,
+                    });
+                    hits += 1;
+                    return hits < melee.max_hits;
+                }
                 gizmos.sphere(
                     hit_transform.translation(),
                     Quat::IDENTITY,
@@ -230,28 +377,33 @@ pub fn cast_axes(
                     hit_transform.translation() + Vec3::Y * 2.0,
                     Color::YELLOW,
                 );
-                if sfx_cooldown.0 >= AXE_SFX_COOLDOWN {
-                    commands.spawn(AudioBundle {
-                        source: asset_server.load("sounds/chop.ogg"),
-                        settings: PlaybackSettings {
-                            volume: bevy::audio::Volume::Relative(bevy::audio::VolumeLevel::new(
-                                0.6,
-                            )),
-                            speed: 1.0 + rand::thread_rng().gen::<f32>(),
-                            ..Default::default()
-                        },
-                    });
-                    sfx_cooldown.0 = 0.0;
-                } else {
-                    sfx_cooldown.0 += time.delta_seconds();
-                }
+                // crits always get heard, a bit deeper than normal hits
+                let sfx = PlaySfxEvent::new(&melee.hit_sound)
+                    .with_volume(melee.hit_volume)
+                    .at(hit_transform.translation());
+                sfx_events.send(match is_crit {
+                    true => sfx.with_exact_speed(0.6),
+                    false => sfx.limited(SfxLimit {
+                        cooldown: melee.hit_sound_cooldown,
+                        max_playing: MELEE_HIT_MAX_PLAYING,
+                    }),
+                });
                 apply_health_events.send(ApplyHealthEvent {
-                    amount: -axe_damage,
+                    amount: -damage,
                     target_entity: hit_entity,
                     caster_entity: event.caster_entity,
+                    is_crit,
+                    is_finisher,
                 });
+                if let Some(effect) = melee.on_hit {
+                    apply_status_events.send(ApplyStatusEvent {
+                        effect,
+                        target_entity: hit_entity,
+                        caster_entity: event.caster_entity,
+                    });
+                }
                 hits += 1;
-                if hits <= MAX_HIT - 1 {
+                if hits < melee.max_hits {
                     true // continute search
                 } else {
                     false // don't hit anything more
@@ -264,121 +416,63 @@ pub fn cast_axes(
 pub fn cast_projectiles(
     mut events: EventReader<CastWeaponEvent>,
     mut query: Query<(&GlobalTransform, &WeaponStats)>,
+    weapon_assets: Res<Assets<WeaponAsset>>,
     mut projectile_events: EventWriter<SpawnProjectileEvent>,
 ) {
     for event in events.read() {
         let Ok((caster_transform_g, stats)) = query.get_mut(event.caster_entity) else {
             continue;
         };
-        let WeaponType::Bow(projectile_asset) = &event.weapon_type else {
-            continue;
-        };
-
-        projectile_events.send(SpawnProjectileEvent {
-            pos: caster_transform_g.translation(),
-            dir: event.dir,
-            projectile_asset: projectile_asset.clone(),
-            additional_damage: stats.damage_add,
-            caster_entity: event.caster_entity,
-            target_entity: event.target_entity,
-        })
-    }
-}
-
-// sledgehammer behaviour (pretty much a big axe)
-pub fn cast_sledgehammer(
-    mut events: EventReader<CastWeaponEvent>,
-    mut query: Query<(&GlobalTransform, &WeaponStats)>,
-    rapier_context: Res<RapierContext>,
-    mut apply_health_events: EventWriter<ApplyHealthEvent>,
-    mut gizmos: Gizmos,
-    transforms: Query<&GlobalTransform, With<Health>>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut sfx_cooldown: ResMut<AxeSfxCooldownTimer>,
-    time: Res<Time>,
-) {
-    for event in events.read() {
-        let Ok((caster_transform_g, stats)) = query.get_mut(event.caster_entity) else {
+        let Some(weapon) = weapon_assets.get(&event.weapon) else {
             continue;
         };
-        let WeaponType::SledgeHammer = &event.weapon_type else {
+        let Some(projectile_asset) = weapon.projectile_handle.as_ref() else {
             continue;
         };
+        let (crit_mul, is_crit) = stats.roll_crit();
 
-        let axe_range = 2.6;
-        // 90 degree swing
-        let axe_cone_dot = 0.3;
+        let count = 1 + stats.extra_projectiles;
+        for i in 0..count {
+            let angle = (i as f32 - (count - 1) as f32 / 2.0) * PROJECTILE_SPREAD;
+            projectile_events.send(SpawnProjectileEvent {
+                pos: caster_transform_g.translation(),
+                dir: Quat::from_rotation_y(angle) * event.dir,
+                projectile_asset: projectile_asset.clone(),
+                additional_damage: stats.damage_add,
+                damage_mul: weapon.charge.map_or(1.0, |c| c.damage_mul(event.charge)) * crit_mul,
+                is_crit,
+                speed_mul: weapon.charge.map_or(1.0, |c| c.speed_mul(event.charge)),
+                caster_entity: event.caster_entity,
+                target_entity: event.target_entity,
+            });
+        }
+    }
+}
 
-        let shape = Collider::ball(axe_range);
-        let shape_pos = caster_transform_g.translation();
-        let filter = QueryFilter::default();
-        const SLEDGEHAMMER_DAMAGE: i32 = 6;
-        let sledgehammer_damage = stats.damage_add + SLEDGEHAMMER_DAMAGE;
-        const MAX_HIT: i32 = 2;
-        let mut hits = 0;
-        rapier_context.intersections_with_shape(
-            shape_pos,
-            Quat::IDENTITY,
-            &shape,
-            filter,
-            |hit_entity| {
-                let Ok(hit_transform) = transforms.get(hit_entity) else {
-                    return true;
-                };
-                let to_target = caster_transform_g.translation() - hit_transform.translation();
-                // let to_target = hit_transform.translation() - caster_transform_g.translation();
-                let to_target_dir = to_target.normalize();
-                let caster_dir = event.dir;
-                let dot = -caster_dir.dot(to_target_dir);
-                let is_outside_of_cone = dot < axe_cone_dot;
-                if is_outside_of_cone {
-                    return true;
-                }
+impl AssetLoader for WeaponAssetLoader {
+    type Asset = WeaponAsset;
+    type Settings = ();
+    type Error = CustomAssetLoaderError;
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let mut asset = ron::de::from_bytes::<WeaponAsset>(&bytes)?;
+            maybe_load_asset(
+                asset.projectile.clone(),
+                &mut asset.projectile_handle,
+                load_context,
+            );
+            Ok(asset)
+        })
+    }
 
-                // don't hurt self
-                if hit_entity == event.caster_entity {
-                    // continue intersection_with_shape
-                    return true;
-                }
-                gizmos.sphere(
-                    hit_transform.translation(),
-                    Quat::IDENTITY,
-                    0.9,
-                    Color::YELLOW,
-                );
-                gizmos.line(
-                    caster_transform_g.translation() + Vec3::Y * 2.0,
-                    hit_transform.translation() + Vec3::Y * 2.0,
-                    Color::YELLOW,
-                );
-                if sfx_cooldown.0 >= SLEDGEHAMMER_SFX_COOLDOWN {
-                    commands.spawn(AudioBundle {
-                        source: asset_server.load("sounds/chop.ogg"),
-                        settings: PlaybackSettings {
-                            volume: bevy::audio::Volume::Relative(bevy::audio::VolumeLevel::new(
-                                0.6,
-                            )),
-                            speed: 1.0 + rand::thread_rng().gen::<f32>(),
-                            ..Default::default()
-                        },
-                    });
-                    sfx_cooldown.0 = 0.0;
-                } else {
-                    sfx_cooldown.0 += time.delta_seconds();
-                }
-                apply_health_events.send(ApplyHealthEvent {
-                    amount: -sledgehammer_damage,
-                    target_entity: hit_entity,
-                    caster_entity: event.caster_entity,
-                });
-                hits += 1;
-                if hits <= MAX_HIT - 1 {
-                    true // continute search
-                } else {
-                    false // don't hit anything more
-                }
-            },
-        );
+    fn extensions(&self) -> &[&str] {
+        &["weapon.ron"]
     }
 }
@@ -0,0 +1,292 @@
+use bevy::{
+    math::vec3,
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+};
+use rand::{thread_rng, Rng};
+use serde::Deserialize;
+
+use crate::{
+    camera::MainCameraTag,
+    notification::NotificationEvent,
+    pause::not_paused,
+    player::PlayerControllerTag,
+    state::{AppState, RestartRunEvent, Wave},
+    waves::{WaveDescriptors, WaveDescriptorsAsset},
+};
+
+// clear skies roll for new weather this often
+const WEATHER_ROLL_SECONDS: f32 = 30.0;
+const WEATHER_CHANCE: f32 = 0.25;
+// how long random weather lasts, weather from the wave asset lasts the whole wave
+const WEATHER_SECONDS: std::ops::Range<f32> = 30.0..60.0;
+const RAIN_DROPS: usize = 400;
+// drops fall in a box this wide around the player
+const RAIN_AREA_HALF: f32 = 18.0;
+const RAIN_HEIGHT: f32 = 16.0;
+const RAIN_SPEED: f32 = 25.0;
+// fog fades in and out this much per second
+const FOG_FADE_SPEED: f32 = 0.3;
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CurrentWeather>()
+            .add_systems(Startup, setup_rain)
+            .add_systems(OnEnter(AppState::Playing), reset_weather)
+            .add_systems(
+                PreUpdate,
+                reset_weather.run_if(on_event::<RestartRunEvent>()),
+            )
+            .add_systems(
+                Update,
+                (wave_weather, roll_weather)
+                    .chain()
+                    .run_if(in_state(AppState::Playing))
+                    .run_if(not_paused),
+            )
+            .add_systems(Update, (add_camera_fog, update_rain, update_fog));
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Weather {
+    #[default]
+    Clear,
+    // trees grow fruit faster
+    Rain,
+    // towers can't see as far
+    Fog,
+}
+
+impl Weather {
+    fn announcement(self) -> &'static str {
+        match self {
+            Weather::Clear => "The weather clears up",
+            Weather::Rain => "It starts to rain",
+            Weather::Fog => "Fog rolls in",
+        }
+    }
+
+    /// how much faster fruit timers run
+    pub fn fruit_speed_mul(self) -> f32 {
+        match self {
+            Weather::Rain => 2.0,
+            _ => 1.0,
+        }
+    }
+
+    pub fn tower_range_mul(self) -> f32 {
+        match self {
+            Weather::Fog => 0.65,
+            _ => 1.0,
+        }
+    }
+}
+
+/// The weather of the current run and when it changes back.
+#[derive(Resource)]
+pub struct CurrentWeather {
+    pub weather: Weather,
+    // None while the wave asset asked for it
+    time_left: Option<f32>,
+    // counts down to the next roll while clear
+    roll_timer: Timer,
+}
+
+impl Default for CurrentWeather {
+    fn default() -> Self {
+        Self {
+            weather: Weather::Clear,
+            time_left: Some(0.0),
+            roll_timer: Timer::from_seconds(WEATHER_ROLL_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+impl CurrentWeather {
+    fn set(
+        &mut self,
+        weather: Weather,
+        time_left: Option<f32>,
+        notification_event: &mut EventWriter<NotificationEvent>,
+    ) {
+        self.time_left = time_left;
+        if self.weather == weather {
+            return;
+        }
+        self.weather = weather;
+        notification_event.send(NotificationEvent {
+            text: weather.announcement().into(),
+            show_for: 3.0,
+            color: Color::CYAN,
+        });
+    }
+}
+
+#[derive(Component)]
+struct RainDrop;
+
+fn setup_rain(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mesh = meshes.add(shape::Box::new(0.02, 0.5, 0.02).into());
+    let material = materials.add(StandardMaterial {
+        base_color: Color::rgba(0.6, 0.7, 1.0, 0.5),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..default()
+    });
+    let mut rng = thread_rng();
+    for _ in 0..RAIN_DROPS {
+        commands.spawn((
+            Name::new("Rain drop"),
+            RainDrop,
+            NotShadowCaster,
+            NotShadowReceiver,
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_xyz(
+                    rng.gen_range(-RAIN_AREA_HALF..RAIN_AREA_HALF),
+                    rng.gen_range(0.0..RAIN_HEIGHT),
+                    rng.gen_range(-RAIN_AREA_HALF..RAIN_AREA_HALF),
+                ),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ));
+    }
+}
+
+// invisible until its alpha is raised in update_fog
+fn add_camera_fog(
+    mut commands: Commands,
+    cameras: Query<Entity, (With<MainCameraTag>, Without<FogSettings>)>,
+) {
+    for camera in cameras.iter() {
+        commands.entity(camera).insert(FogSettings {
+            color: Color::rgba(0.7, 0.72, 0.75, 0.0),
+            falloff: FogFalloff::Linear {
+                start: 12.0,
+                end: 45.0,
+            },
+            ..default()
+        });
+    }
+}
+
+fn reset_weather(mut weather: ResMut<CurrentWeather>) {
+    *weather = CurrentWeather::default();
+}
+
+// waves can ask for weather in waves.wave.ron, which holds until the next wave
+fn wave_weather(
+    wave: Res<Wave>,
+    wave_descriptors: Res<WaveDescriptors>,
+    wave_descriptor_assets: Res<Assets<WaveDescriptorsAsset>>,
+    mut weather: ResMut<CurrentWeather>,
+    mut notification_event: EventWriter<NotificationEvent>,
+) {
+    if !wave.is_changed() {
+        return;
+    }
+    let wave_weather = wave_descriptor_assets
+        .get(&wave_descriptors.0)
+        .and_then(|waves| waves.wave(wave.0))
+        .and_then(|(wave, _)| wave.weather);
+    match wave_weather {
+        Some(wave_weather) => weather.set(wave_weather, None, &mut notification_event),
+        // the last wave's weather ends with it
+        None if weather.time_left.is_none() => {
+            weather.set(Weather::Clear, Some(0.0), &mut notification_event)
+        }
+        None => {}
+    }
+}
+
+fn roll_weather(
+    time: Res<Time>,
+    mut weather: ResMut<CurrentWeather>,
+    mut notification_event: EventWriter<NotificationEvent>,
+) {
+    let Some(time_left) = weather.time_left.as_mut() else {
+        return;
+    };
+    *time_left -= time.delta_seconds();
+    if *time_left > 0.0 {
+        return;
+    }
+    if weather.weather != Weather::Clear {
+        weather.set(Weather::Clear, Some(0.0), &mut notification_event);
+        return;
+    }
+    if !weather.roll_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let mut rng = thread_rng();
+    if rng.gen_range(0.0..1.0) < WEATHER_CHANCE {
+        let new_weather = match rng.gen::<bool>() {
+            true => Weather::Rain,
+            false => Weather::Fog,
+        };
+        let duration = rng.gen_range(WEATHER_SECONDS);
+        weather.set(new_weather, Some(duration), &mut notification_event);
+    }
+}
+
+// drops that reach the ground start over above a random spot around the player
+fn update_rain(
+    time: Res<Time>,
+    weather: Res<CurrentWeather>,
+    state: Res<State<AppState>>,
+    players: Query<&GlobalTransform, With<PlayerControllerTag>>,
+    mut drops: Query<(&mut Transform, &mut Visibility), With<RainDrop>>,
+) {
+    let raining = weather.weather == Weather::Rain && *state.get() == AppState::Playing;
+    let center = players
+        .get_single()
+        .map(|p| p.translation() * Vec3::new(1.0, 0.0, 1.0))
+        .unwrap_or_default();
+    let mut rng = thread_rng();
+    for (mut transform, mut visibility) in drops.iter_mut() {
+        *visibility = match raining {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+        if !raining {
+            continue;
+        }
+        transform.translation.y -= RAIN_SPEED * time.delta_seconds();
+        if transform.translation.y < 0.0 {
+            transform.translation = center
+                + vec3(
+                    rng.gen_range(-RAIN_AREA_HALF..RAIN_AREA_HALF),
+                    RAIN_HEIGHT,
+                    rng.gen_range(-RAIN_AREA_HALF..RAIN_AREA_HALF),
+                );
+        }
+    }
+}
+
+fn update_fog(
+    time: Res<Time>,
+    weather: Res<CurrentWeather>,
+    state: Res<State<AppState>>,
+    mut fogs: Query<&mut FogSettings, With<MainCameraTag>>,
+) {
+    let target = match weather.weather == Weather::Fog && *state.get() == AppState::Playing {
+        true => 1.0,
+        false => 0.0,
+    };
+    for mut fog in fogs.iter_mut() {
+        let alpha = fog.color.a();
+        let step = FOG_FADE_SPEED * time.delta_seconds();
+        if alpha != target {
+            fog.color.set_a(alpha + (target - alpha).clamp(-step, step));
+        }
+    }
+}